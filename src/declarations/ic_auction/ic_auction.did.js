@@ -16,11 +16,25 @@ export const idlFactory = ({ IDL }) => {
     'Upgrade' : UpgradeArgs,
     'Init' : InitArgs,
   });
+  const WithdrawTxInfo = IDL.Record({
+    'id' : IDL.Nat64,
+    'fee' : IDL.Nat,
+    'kind' : IDL.Nat8,
+    'txid' : IDL.Text,
+    'user' : IDL.Principal,
+    'recipient' : IDL.Text,
+    'timestamp' : IDL.Nat64,
+    'amount' : IDL.Nat,
+  });
+  const Result = IDL.Variant({
+    'Ok' : IDL.Vec(WithdrawTxInfo),
+    'Err' : IDL.Text,
+  });
   const FinalizeOutput = IDL.Record({
     'txid' : IDL.Text,
     'pool_id' : IDL.Text,
   });
-  const Result = IDL.Variant({
+  const Result_1 = IDL.Variant({
     'Ok' : IDL.Opt(FinalizeOutput),
     'Err' : IDL.Text,
   });
@@ -34,7 +48,7 @@ export const idlFactory = ({ IDL }) => {
     'total_supply' : IDL.Nat,
     'min_bid_duration' : IDL.Nat64,
   });
-  const Result_1 = IDL.Variant({ 'Ok' : IDL.Null, 'Err' : IDL.Text });
+  const Result_2 = IDL.Variant({ 'Ok' : IDL.Null, 'Err' : IDL.Text });
   const TokenInput = IDL.Record({
     'decimals' : IDL.Nat8,
     'token' : IDL.Text,
@@ -44,6 +58,14 @@ export const idlFactory = ({ IDL }) => {
     'logo_url' : IDL.Text,
     'symbol' : IDL.Text,
   });
+  const EvmFeeConfig = IDL.Record({
+    'currency_per_native' : IDL.Opt(IDL.Nat),
+    'max_priority_fee_per_gas' : IDL.Opt(IDL.Nat),
+    'reward_percentile' : IDL.Nat8,
+    'max_fee_per_gas' : IDL.Opt(IDL.Nat),
+    'min_deposit_gas_multiple' : IDL.Nat64,
+    'fee_history_blocks' : IDL.Nat64,
+  });
   const FinalizeKind = IDL.Variant({
     'CreatePool' : IDL.Text,
     'Transfer' : IDL.Null,
@@ -55,16 +77,17 @@ export const idlFactory = ({ IDL }) => {
     'detail' : IDL.Text,
     'restricted_countries' : IDL.Vec(IDL.Text),
   });
-  const WithdrawTxInfo = IDL.Record({
-    'id' : IDL.Nat64,
-    'kind' : IDL.Nat8,
-    'txid' : IDL.Text,
-    'user' : IDL.Principal,
-    'recipient' : IDL.Text,
-    'timestamp' : IDL.Nat64,
-    'amount' : IDL.Nat,
+  const SolFeeConfig = IDL.Record({
+    'min_compute_unit_price' : IDL.Nat64,
+    'max_compute_unit_price' : IDL.Nat64,
+    'priority_fee_percentile' : IDL.Nat8,
   });
-  const Result_2 = IDL.Variant({ 'Ok' : WithdrawTxInfo, 'Err' : IDL.Text });
+  const X402NetworkInput = IDL.Record({
+    'chain' : Chain,
+    'network' : IDL.Text,
+    'paying_public_keys' : IDL.Vec(IDL.Text),
+  });
+  const Result_3 = IDL.Variant({ 'Ok' : WithdrawTxInfo, 'Err' : IDL.Text });
   const AuctionInfo = IDL.Record({
     'cumulative_demand_raised' : IDL.Nat,
     'total_amount' : IDL.Nat,
@@ -90,16 +113,35 @@ export const idlFactory = ({ IDL }) => {
     'amount' : IDL.Nat,
     'refund' : IDL.Nat,
   });
-  const Result_3 = IDL.Variant({ 'Ok' : BidInfo, 'Err' : IDL.Text });
-  const Result_4 = IDL.Variant({ 'Ok' : IDL.Vec(BidInfo), 'Err' : IDL.Text });
-  const DepositInput = IDL.Record({ 'txid' : IDL.Text, 'sender' : IDL.Text });
-  const Result_5 = IDL.Variant({ 'Ok' : IDL.Nat, 'Err' : IDL.Text });
+  const Result_4 = IDL.Variant({ 'Ok' : BidInfo, 'Err' : IDL.Text });
+  const Result_5 = IDL.Variant({ 'Ok' : IDL.Vec(BidInfo), 'Err' : IDL.Text });
+  const DepositInput = IDL.Record({
+    'memo' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+    'txid' : IDL.Text,
+    'sender' : IDL.Text,
+  });
+  const Result_6 = IDL.Variant({ 'Ok' : IDL.Nat, 'Err' : IDL.Text });
+  const Eip3009Input = IDL.Record({
+    'valid_after' : IDL.Nat64,
+    'signature' : IDL.Text,
+    'valid_before' : IDL.Nat64,
+    'value' : IDL.Nat,
+    'from' : IDL.Text,
+    'salt' : IDL.Text,
+    'nonce' : IDL.Text,
+  });
+  const Result_7 = IDL.Variant({ 'Ok' : IDL.Text, 'Err' : IDL.Text });
   const AuctionSnapshot = IDL.Record({
     'c' : IDL.Nat,
     'd' : IDL.Nat,
     's' : IDL.Nat,
     't' : IDL.Nat64,
   });
+  const X402Network = IDL.Record({
+    'chain' : Chain,
+    'network' : IDL.Text,
+    'paying_public_keys' : IDL.Vec(IDL.Vec(IDL.Nat8)),
+  });
   const StateInfo = IDL.Record({
     'url' : IDL.Text,
     'token' : IDL.Text,
@@ -109,6 +151,7 @@ export const idlFactory = ({ IDL }) => {
     'total_withdrawn_token' : IDL.Nat,
     'token_logo_url' : IDL.Text,
     'token_symbol' : IDL.Text,
+    'x402_networks' : IDL.Vec(X402Network),
     'governance_canister' : IDL.Opt(IDL.Principal),
     'chain' : Chain,
     'name' : IDL.Text,
@@ -117,6 +160,7 @@ export const idlFactory = ({ IDL }) => {
     'icp_address' : IDL.Principal,
     'currency_symbol' : IDL.Text,
     'description' : IDL.Text,
+    'evm_fee_config' : EvmFeeConfig,
     'detail' : IDL.Text,
     'chain_providers' : IDL.Vec(IDL.Text),
     'currency_decimals' : IDL.Nat8,
@@ -129,6 +173,8 @@ export const idlFactory = ({ IDL }) => {
     'token_decimals' : IDL.Nat8,
     'total_deposited_currency' : IDL.Nat,
     'paying_public_keys' : IDL.Vec(IDL.Vec(IDL.Nat8)),
+    'sol_lookup_tables' : IDL.Vec(IDL.Text),
+    'sol_fee_config' : SolFeeConfig,
     'token_name' : IDL.Text,
     'finalize_output' : IDL.Opt(FinalizeOutput),
     'payment_requirements_extra' : IDL.Opt(IDL.Text),
@@ -137,7 +183,7 @@ export const idlFactory = ({ IDL }) => {
     'currency_program_id' : IDL.Opt(IDL.Text),
     'currency_logo_url' : IDL.Text,
   });
-  const Result_6 = IDL.Variant({ 'Ok' : StateInfo, 'Err' : IDL.Text });
+  const Result_8 = IDL.Variant({ 'Ok' : StateInfo, 'Err' : IDL.Text });
   const DepositTxInfo = IDL.Record({
     'txid' : IDL.Text,
     'user' : IDL.Principal,
@@ -145,23 +191,19 @@ export const idlFactory = ({ IDL }) => {
     'timestamp' : IDL.Nat64,
     'amount' : IDL.Nat,
   });
-  const Result_7 = IDL.Variant({
+  const Result_9 = IDL.Variant({
     'Ok' : IDL.Vec(DepositTxInfo),
     'Err' : IDL.Text,
   });
   const UserInfo = IDL.Record({
     'token_amount' : IDL.Nat,
+    'deposit_address' : IDL.Opt(IDL.Text),
     'currency_amount' : IDL.Nat,
     'agreed_terms' : IDL.Bool,
     'timestamp' : IDL.Nat64,
     'bound_addresses' : IDL.Vec(IDL.Text),
   });
-  const Result_8 = IDL.Variant({ 'Ok' : UserInfo, 'Err' : IDL.Text });
-  const Result_9 = IDL.Variant({
-    'Ok' : IDL.Vec(WithdrawTxInfo),
-    'Err' : IDL.Text,
-  });
-  const Result_10 = IDL.Variant({ 'Ok' : IDL.Text, 'Err' : IDL.Text });
+  const Result_10 = IDL.Variant({ 'Ok' : UserInfo, 'Err' : IDL.Text });
   const WithdrawInput = IDL.Record({ 'recipient' : IDL.Text });
   const PayingResultInput = IDL.Record({
     'result' : IDL.Vec(IDL.Nat8),
@@ -175,30 +217,46 @@ export const idlFactory = ({ IDL }) => {
   });
   const Result_11 = IDL.Variant({ 'Ok' : X402PaymentOutput, 'Err' : IDL.Text });
   return IDL.Service({
-    'admin_finalize_auction' : IDL.Func([], [Result], []),
-    'admin_set_auction' : IDL.Func([AuctionConfig], [Result_1], []),
-    'admin_set_currency' : IDL.Func([TokenInput], [Result_1], []),
-    'admin_set_finalize' : IDL.Func([FinalizeKind], [Result_1], []),
+    'admin_distribute_token' : IDL.Func([], [Result], []),
+    'admin_finalize_auction' : IDL.Func([], [Result_1], []),
+    'admin_refund_currency' : IDL.Func([], [Result], []),
+    'admin_set_auction' : IDL.Func([AuctionConfig], [Result_2], []),
+    'admin_set_currency' : IDL.Func([TokenInput], [Result_2], []),
+    'admin_set_evm_fee_config' : IDL.Func([EvmFeeConfig], [Result_2], []),
+    'admin_set_finalize' : IDL.Func([FinalizeKind], [Result_2], []),
     'admin_set_paying_public_keys' : IDL.Func(
         [IDL.Vec(IDL.Text)],
-        [Result_1],
+        [Result_2],
         [],
       ),
     'admin_set_payment_requirements_extra' : IDL.Func(
         [IDL.Text],
-        [Result_1],
+        [Result_2],
+        [],
+      ),
+    'admin_set_project' : IDL.Func([ProjectInput], [Result_2], []),
+    'admin_set_providers' : IDL.Func([IDL.Vec(IDL.Text)], [Result_2], []),
+    'admin_set_sol_fee_config' : IDL.Func([SolFeeConfig], [Result_2], []),
+    'admin_set_sol_lookup_tables' : IDL.Func(
+        [IDL.Vec(IDL.Text)],
+        [Result_2],
+        [],
+      ),
+    'admin_set_token' : IDL.Func([TokenInput], [Result_2], []),
+    'admin_set_x402_networks' : IDL.Func(
+        [IDL.Vec(X402NetworkInput)],
+        [Result_2],
         [],
       ),
-    'admin_set_project' : IDL.Func([ProjectInput], [Result_1], []),
-    'admin_set_providers' : IDL.Func([IDL.Vec(IDL.Text)], [Result_1], []),
-    'admin_set_token' : IDL.Func([TokenInput], [Result_1], []),
-    'admin_setup_auction' : IDL.Func([], [Result_1], []),
-    'admin_sweep_currency' : IDL.Func([], [Result_2], []),
-    'admin_sweep_token' : IDL.Func([], [Result_2], []),
+    'admin_setup_auction' : IDL.Func([], [Result_2], []),
+    'admin_sweep_currency' : IDL.Func([], [Result_3], []),
+    'admin_sweep_token' : IDL.Func([], [Result_3], []),
     'auction_info' : IDL.Func([], [IDL.Opt(AuctionInfo)], ['query']),
-    'claim' : IDL.Func([IDL.Nat64], [Result_3], []),
-    'claim_all' : IDL.Func([], [Result_4], []),
-    'deposit_currency' : IDL.Func([DepositInput], [Result_5], []),
+    'claim' : IDL.Func([IDL.Nat64], [Result_4], []),
+    'claim_all' : IDL.Func([], [Result_5], []),
+    'deposit_currency' : IDL.Func([DepositInput], [Result_6], []),
+    'deposit_currency_eip3009' : IDL.Func([Eip3009Input], [Result_7], []),
+    'deposit_currency_icrc2' : IDL.Func([IDL.Nat], [Result_6], []),
     'estimate_max_price' : IDL.Func([IDL.Nat], [IDL.Nat, IDL.Nat], ['query']),
     'get_grouped_bids' : IDL.Func(
         [IDL.Opt(IDL.Nat64)],
@@ -210,37 +268,59 @@ export const idlFactory = ({ IDL }) => {
         [IDL.Vec(AuctionSnapshot)],
         ['query'],
       ),
-    'info' : IDL.Func([], [Result_6], ['query']),
-    'my_bids' : IDL.Func([], [Result_4], ['query']),
-    'my_deposits' : IDL.Func([], [Result_7], ['query']),
-    'my_info' : IDL.Func([], [Result_8], ['query']),
-    'my_withdraws' : IDL.Func([], [Result_9], ['query']),
-    'submit_bid' : IDL.Func([IDL.Nat, IDL.Nat], [Result_3], []),
-    'validate_admin_set_auction' : IDL.Func([AuctionConfig], [Result_10], []),
-    'validate_admin_set_currency' : IDL.Func([TokenInput], [Result_10], []),
-    'validate_admin_set_finalize' : IDL.Func([FinalizeKind], [Result_10], []),
+    'info' : IDL.Func([], [Result_8], ['query']),
+    'my_bids' : IDL.Func([], [Result_5], ['query']),
+    'my_deposit_address' : IDL.Func([], [Result_7], ['query']),
+    'my_deposits' : IDL.Func([], [Result_9], ['query']),
+    'my_info' : IDL.Func([], [Result_10], ['query']),
+    'my_withdraws' : IDL.Func([], [Result], ['query']),
+    'submit_bid' : IDL.Func([IDL.Nat, IDL.Nat], [Result_4], []),
+    'sweep_deposit' : IDL.Func([], [Result_6], []),
+    'validate_admin_set_auction' : IDL.Func([AuctionConfig], [Result_7], []),
+    'validate_admin_set_currency' : IDL.Func([TokenInput], [Result_7], []),
+    'validate_admin_set_evm_fee_config' : IDL.Func(
+        [EvmFeeConfig],
+        [Result_7],
+        [],
+      ),
+    'validate_admin_set_finalize' : IDL.Func([FinalizeKind], [Result_7], []),
     'validate_admin_set_paying_public_keys' : IDL.Func(
         [IDL.Vec(IDL.Text)],
-        [Result_10],
+        [Result_7],
         [],
       ),
     'validate_admin_set_payment_requirements_extra' : IDL.Func(
         [IDL.Text],
-        [Result_10],
+        [Result_7],
         [],
       ),
-    'validate_admin_set_project' : IDL.Func([ProjectInput], [Result_10], []),
+    'validate_admin_set_project' : IDL.Func([ProjectInput], [Result_7], []),
     'validate_admin_set_providers' : IDL.Func(
         [IDL.Vec(IDL.Text)],
-        [Result_10],
+        [Result_7],
+        [],
+      ),
+    'validate_admin_set_sol_fee_config' : IDL.Func(
+        [SolFeeConfig],
+        [Result_7],
+        [],
+      ),
+    'validate_admin_set_sol_lookup_tables' : IDL.Func(
+        [IDL.Vec(IDL.Text)],
+        [Result_7],
+        [],
+      ),
+    'validate_admin_set_token' : IDL.Func([TokenInput], [Result_7], []),
+    'validate_admin_set_x402_networks' : IDL.Func(
+        [IDL.Vec(X402NetworkInput)],
+        [Result_7],
         [],
       ),
-    'validate_admin_set_token' : IDL.Func([TokenInput], [Result_10], []),
-    'validate_empty_input' : IDL.Func([], [Result_10], []),
-    'withdraw_currency' : IDL.Func([WithdrawInput], [Result_2], []),
-    'withdraw_token' : IDL.Func([WithdrawInput], [Result_2], []),
-    'x402_bind_address' : IDL.Func([PayingResultInput], [Result_1], []),
-    'x402_deposit_currency' : IDL.Func([PayingResultInput], [Result_5], []),
+    'validate_empty_input' : IDL.Func([], [Result_7], []),
+    'withdraw_currency' : IDL.Func([WithdrawInput], [Result_3], []),
+    'withdraw_token' : IDL.Func([WithdrawInput], [Result_3], []),
+    'x402_bind_address' : IDL.Func([PayingResultInput], [Result_2], []),
+    'x402_deposit_currency' : IDL.Func([PayingResultInput], [Result_6], []),
     'x402_payment' : IDL.Func([IDL.Nat, IDL.Bool], [Result_11], ['query']),
   });
 };
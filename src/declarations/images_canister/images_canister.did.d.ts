@@ -19,6 +19,11 @@ export interface ImageMetadata {
   'locations' : Array<string>,
 }
 export interface InitArgs { 'governance_canister' : [] | [Principal] }
+export interface PayingResultInput {
+  'result' : Uint8Array | number[],
+  'signature' : Uint8Array | number[],
+  'timestamp' : bigint,
+}
 export type Result = { 'Ok' : null } |
   { 'Err' : string };
 export type Result_1 = { 'Ok' : ImageMetadata } |
@@ -38,7 +43,13 @@ export interface _SERVICE {
   'admin_set_tokens_canister' : ActorMethod<[Principal], Result>,
   'get_image_metadata' : ActorMethod<[bigint], Result_1>,
   'info' : ActorMethod<[], Result_2>,
-  'update_image' : ActorMethod<[bigint, ImageInput], Result_3>,
+  /**
+   * Stores the image of a token, paid by size through the tokens canister.
+   */
+  'update_image' : ActorMethod<
+    [bigint, ImageInput, [] | [PayingResultInput]],
+    Result_3
+  >,
   'validate_admin_set_tokens_canister' : ActorMethod<[Principal], Result_4>,
 }
 export declare const idlFactory: IDL.InterfaceFactory;
@@ -25,13 +25,22 @@ export const idlFactory = ({ IDL }) => {
     'name' : IDL.Text,
     'type' : IDL.Text,
   });
+  const PayingResultInput = IDL.Record({
+    'result' : IDL.Vec(IDL.Nat8),
+    'signature' : IDL.Vec(IDL.Nat8),
+    'timestamp' : IDL.Nat64,
+  });
   const Result_3 = IDL.Variant({ 'Ok' : IDL.Nat64, 'Err' : IDL.Text });
   const Result_4 = IDL.Variant({ 'Ok' : IDL.Text, 'Err' : IDL.Text });
   return IDL.Service({
     'admin_set_tokens_canister' : IDL.Func([IDL.Principal], [Result], []),
     'get_image_metadata' : IDL.Func([IDL.Nat64], [Result_1], ['query']),
     'info' : IDL.Func([], [Result_2], ['query']),
-    'update_image' : IDL.Func([IDL.Nat64, ImageInput], [Result_3], []),
+    'update_image' : IDL.Func(
+        [IDL.Nat64, ImageInput, IDL.Opt(PayingResultInput)],
+        [Result_3],
+        [],
+      ),
     'validate_admin_set_tokens_canister' : IDL.Func(
         [IDL.Principal],
         [Result_4],
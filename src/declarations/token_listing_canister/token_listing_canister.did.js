@@ -47,6 +47,17 @@ export const idlFactory = ({ IDL }) => {
     'currency_logo_url' : IDL.Text,
   });
   const Result = IDL.Variant({ 'Ok' : IDL.Null, 'Err' : IDL.Text });
+  const EvmFeeConfig = IDL.Record({
+    'max_priority_fee_per_gas' : IDL.Opt(IDL.Nat),
+    'reward_percentile' : IDL.Nat8,
+    'max_fee_per_gas' : IDL.Opt(IDL.Nat),
+    'fee_history_blocks' : IDL.Nat64,
+  });
+  const SolFeeConfig = IDL.Record({
+    'min_compute_unit_price' : IDL.Nat64,
+    'max_compute_unit_price' : IDL.Nat64,
+    'priority_fee_percentile' : IDL.Nat8,
+  });
   const StateInfo = IDL.Record({
     'sol_address' : IDL.Text,
     'evm_address' : IDL.Text,
@@ -54,21 +65,25 @@ export const idlFactory = ({ IDL }) => {
     'daos' : IDL.Vec(IDL.Principal),
     'auctions' : IDL.Vec(AuctionId),
     'icp_address' : IDL.Principal,
+    'evm_fee_config' : EvmFeeConfig,
     'chain_providers' : IDL.Vec(IDL.Tuple(Chain, IDL.Vec(IDL.Text))),
     'key_name' : IDL.Text,
     'paying_public_keys' : IDL.Vec(IDL.Vec(IDL.Nat8)),
     'storages' : IDL.Vec(IDL.Principal),
+    'sol_fee_config' : SolFeeConfig,
   });
   const Result_1 = IDL.Variant({ 'Ok' : StateInfo, 'Err' : IDL.Text });
   const Result_2 = IDL.Variant({ 'Ok' : IDL.Text, 'Err' : IDL.Text });
   return IDL.Service({
     'admin_set_auction' : IDL.Func([AuctionInfo], [Result], []),
+    'admin_set_evm_fee_config' : IDL.Func([EvmFeeConfig], [Result], []),
     'admin_set_paying_public_keys' : IDL.Func(
         [IDL.Vec(IDL.Text)],
         [Result],
         [],
       ),
     'admin_set_providers' : IDL.Func([Chain, IDL.Vec(IDL.Text)], [Result], []),
+    'admin_set_sol_fee_config' : IDL.Func([SolFeeConfig], [Result], []),
     'get_auction' : IDL.Func(
         [IDL.Opt(AuctionId)],
         [IDL.Opt(AuctionInfo)],
@@ -81,6 +96,11 @@ export const idlFactory = ({ IDL }) => {
         ['query'],
       ),
     'validate_admin_set_auction' : IDL.Func([AuctionInfo], [Result_2], []),
+    'validate_admin_set_evm_fee_config' : IDL.Func(
+        [EvmFeeConfig],
+        [Result_2],
+        [],
+      ),
     'validate_admin_set_paying_public_keys' : IDL.Func(
         [IDL.Vec(IDL.Text)],
         [Result_2],
@@ -91,6 +111,11 @@ export const idlFactory = ({ IDL }) => {
         [Result_2],
         [],
       ),
+    'validate_admin_set_sol_fee_config' : IDL.Func(
+        [SolFeeConfig],
+        [Result_2],
+        [],
+      ),
     'validate_empty_input' : IDL.Func([], [Result_2], []),
   });
 };
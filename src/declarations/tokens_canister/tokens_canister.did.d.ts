@@ -12,6 +12,18 @@ export interface Announcement {
 }
 export type CanisterArgs = { 'Upgrade' : InitArgs } |
   { 'Init' : InitArgs };
+export interface CreditLog {
+  'balance' : bigint,
+  'kind' : CreditLogKind,
+  'created_at' : bigint,
+  'amount' : bigint,
+}
+export type CreditLogKind = {
+    'Debit' : { 'action' : string, 'units' : bigint }
+  } |
+  { 'X402TopUp' : string } |
+  { 'Icrc2TopUp' : string };
+export interface Credits { 'balance' : bigint, 'history' : Array<CreditLog> }
 export interface InitArgs { 'governance_canister' : [] | [Principal] }
 export interface LinkItem { 'rel' : string, 'url' : string, 'name' : string }
 export interface PayingResultInput {
@@ -23,25 +35,53 @@ export type Result = { 'Ok' : BigUint64Array | bigint[] } |
   { 'Err' : string };
 export type Result_1 = { 'Ok' : null } |
   { 'Err' : string };
+export type Result_10 = { 'Ok' : bigint } |
+  { 'Err' : string };
+export type Result_11 = { 'Ok' : string } |
+  { 'Err' : string };
+export type Result_12 = { 'Ok' : X402PaymentOutput } |
+  { 'Err' : string };
 export type Result_2 = { 'Ok' : Array<string> } |
   { 'Err' : string };
 export type Result_3 = { 'Ok' : TokenProfile } |
   { 'Err' : string };
-export type Result_4 = { 'Ok' : StateInfo } |
+export type Result_4 = { 'Ok' : X402Receipt } |
+  { 'Err' : string };
+export type Result_5 = { 'Ok' : StateInfo } |
   { 'Err' : string };
-export type Result_5 = { 'Ok' : bigint } |
+export type Result_6 = { 'Ok' : Array<RevenueEntry> } |
   { 'Err' : string };
-export type Result_6 = { 'Ok' : string } |
+export type Result_7 = { 'Ok' : Credits } |
   { 'Err' : string };
-export type Result_7 = { 'Ok' : X402PaymentOutput } |
+export type Result_8 = { 'Ok' : Array<X402Receipt> } |
   { 'Err' : string };
+export type Result_9 = { 'Ok' : bigint } |
+  { 'Err' : string };
+export interface RevenueEntry {
+  'action' : string,
+  'asset' : string,
+  'date' : string,
+  'count' : bigint,
+  'refunded_amount' : bigint,
+  'network' : string,
+  'refunded_count' : bigint,
+  'amount' : bigint,
+  'pay_to' : string,
+}
 export interface StateInfo {
+  'x402_discount_schedule' : Array<X402DiscountChange>,
+  'x402_accepts' : Array<[string, Array<X402PriceOption>]>,
   'governance_canister' : [] | [Principal],
   'x402_prices' : Array<[string, bigint]>,
   'x402_pay_to' : string,
   'total_incoming' : bigint,
+  'x402_discounts' : Array<X402Discount>,
+  'total_credits' : bigint,
+  'x402_price_schedule' : Array<X402PriceChange>,
+  'credits_ledger' : [] | [Principal],
   'total_tokens' : bigint,
   'x402_paying_public_keys' : Array<Uint8Array | number[]>,
+  'images_canister' : [] | [Principal],
 }
 export interface TokenMetadata {
   'decimals' : number,
@@ -69,15 +109,67 @@ export interface VerificationBadge {
   'is_verified' : boolean,
   'verified_at' : bigint,
 }
+export interface X402Discount {
+  'percent' : number,
+  'target' : X402DiscountTarget,
+}
+export interface X402DiscountChange {
+  'effective_at' : bigint,
+  'discounts' : Array<X402Discount>,
+}
+export type X402DiscountTarget = { 'Tag' : string } |
+  { 'Verified' : null };
 export interface X402PaymentOutput {
   'x402' : Uint8Array | number[],
   'nonce' : string,
   'timestamp' : bigint,
 }
+export interface X402PriceChange {
+  'action' : string,
+  'effective_at' : bigint,
+  'options' : Array<X402PriceOption>,
+}
+export interface X402PriceOption {
+  'asset' : string,
+  'network' : string,
+  'extra' : [] | [string],
+  'amount' : bigint,
+  'pay_to' : string,
+}
+export interface X402Receipt {
+  'updated_at' : bigint,
+  'action' : string,
+  'asset' : string,
+  'txid' : string,
+  'user' : Principal,
+  'network' : string,
+  'units' : bigint,
+  'payer' : [] | [string],
+  'amount' : bigint,
+  'outcome' : X402ReceiptOutcome,
+  'pay_to' : string,
+  'settled_at' : bigint,
+}
+export type X402ReceiptOutcome = { 'Failed' : string } |
+  { 'Refunded' : string } |
+  { 'Succeeded' : null } |
+  { 'Credited' : null };
 export interface _SERVICE {
   'admin_batch_register_tokens' : ActorMethod<[Array<TokenMetadata>], Result>,
+  'admin_cancel_x402_discounts' : ActorMethod<[bigint], Result_1>,
+  'admin_cancel_x402_price' : ActorMethod<[string, bigint], Result_1>,
+  'admin_credit_x402_receipt' : ActorMethod<[string], Result_1>,
+  'admin_refund_x402_receipt' : ActorMethod<[string, string], Result_1>,
+  'admin_schedule_x402_discounts' : ActorMethod<[X402DiscountChange], Result_1>,
+  'admin_schedule_x402_price' : ActorMethod<[X402PriceChange], Result_1>,
+  'admin_set_credits_ledger' : ActorMethod<[[] | [Principal]], Result_1>,
+  'admin_set_images_canister' : ActorMethod<[[] | [Principal]], Result_1>,
   'admin_set_paying_public_keys' : ActorMethod<[Array<string>], Result_1>,
   'admin_set_payment_requirements_extra' : ActorMethod<[string], Result_1>,
+  'admin_set_x402_accepts' : ActorMethod<
+    [string, Array<X402PriceOption>],
+    Result_1
+  >,
   'admin_set_x402_pay_to' : ActorMethod<[string], Result_1>,
   'admin_set_x402_prices' : ActorMethod<[Array<[string, bigint]>], Result_1>,
   'admin_update_token_status' : ActorMethod<[bigint, string], Result_1>,
@@ -86,39 +178,108 @@ export interface _SERVICE {
     [bigint, VerificationBadge],
     Result_1
   >,
+  'batch_update_token_metadata' : ActorMethod<
+    [Array<[bigint, TokenMetadata]>, [] | [PayingResultInput]],
+    Result_1
+  >,
+  /**
+   * Checks that `user` may store an image of `size` bytes for the token and charges the
+   * storage, metered per started KiB. Called by the images canister, the user pays with
+   * `x402_metered_payment("store_image", units, ..)` or prepaid credits.
+   */
+  'charge_image_storage' : ActorMethod<
+    [bigint, Principal, bigint, [] | [PayingResultInput]],
+    Result_2
+  >,
   'check_permission' : ActorMethod<[bigint, Principal], Result_2>,
   'get_token_profile' : ActorMethod<[bigint], Result_3>,
-  'info' : ActorMethod<[], Result_4>,
+  /**
+   * The receipt of a settled transaction, for the user who paid it and for controllers.
+   */
+  'get_x402_receipt' : ActorMethod<[string], Result_4>,
+  'info' : ActorMethod<[], Result_5>,
+  'list_revenue' : ActorMethod<[[] | [string], [] | [string]], Result_6>,
   'list_tokens' : ActorMethod<
     [bigint, [] | [bigint]],
     Array<[bigint, TokenMetadata]>
   >,
+  'my_credits' : ActorMethod<[], Result_7>,
   'my_txs' : ActorMethod<[], Result_2>,
+  'my_x402_receipts' : ActorMethod<[], Result_8>,
   'query_token' : ActorMethod<[string], Array<[bigint, TokenMetadata]>>,
-  'register_token' : ActorMethod<[TokenMetadata, PayingResultInput], Result_5>,
+  'register_token' : ActorMethod<
+    [TokenMetadata, [] | [PayingResultInput]],
+    Result_9
+  >,
   'set_announcement' : ActorMethod<
-    [bigint, Announcement, PayingResultInput],
+    [bigint, Announcement, [] | [PayingResultInput]],
     Result_1
   >,
+  'top_up_credits' : ActorMethod<[PayingResultInput], Result_10>,
+  /**
+   * Tops up prepaid credits from the caller's ICRC-2 approval on the credits ledger.
+   */
+  'top_up_credits_icrc2' : ActorMethod<[bigint], Result_10>,
   'update_token_controllers' : ActorMethod<
     [bigint, Array<Principal>],
     Result_1
   >,
-  'update_token_metadata' : ActorMethod<[bigint, TokenMetadata], Result_1>,
+  /**
+   * Updates the metadata of a token, metered as one unit of the batch update.
+   */
+  'update_token_metadata' : ActorMethod<
+    [bigint, TokenMetadata, [] | [PayingResultInput]],
+    Result_1
+  >,
+  'validate_admin_cancel_x402_discounts' : ActorMethod<[bigint], Result_11>,
+  'validate_admin_cancel_x402_price' : ActorMethod<[string, bigint], Result_11>,
+  'validate_admin_credit_x402_receipt' : ActorMethod<[string], Result_11>,
+  'validate_admin_refund_x402_receipt' : ActorMethod<
+    [string, string],
+    Result_11
+  >,
+  'validate_admin_schedule_x402_discounts' : ActorMethod<
+    [X402DiscountChange],
+    Result_11
+  >,
+  'validate_admin_schedule_x402_price' : ActorMethod<
+    [X402PriceChange],
+    Result_11
+  >,
+  'validate_admin_set_credits_ledger' : ActorMethod<
+    [[] | [Principal]],
+    Result_11
+  >,
+  'validate_admin_set_images_canister' : ActorMethod<
+    [[] | [Principal]],
+    Result_11
+  >,
   'validate_admin_set_paying_public_keys' : ActorMethod<
     [Array<string>],
-    Result_6
+    Result_11
   >,
   'validate_admin_set_payment_requirements_extra' : ActorMethod<
     [string],
-    Result_6
+    Result_11
+  >,
+  'validate_admin_set_x402_accepts' : ActorMethod<
+    [string, Array<X402PriceOption>],
+    Result_11
   >,
-  'validate_admin_set_x402_pay_to' : ActorMethod<[string], Result_6>,
+  'validate_admin_set_x402_pay_to' : ActorMethod<[string], Result_11>,
   'validate_admin_set_x402_prices' : ActorMethod<
     [Array<[string, bigint]>],
-    Result_6
+    Result_11
+  >,
+  'x402_metered_payment' : ActorMethod<
+    [string, bigint, [] | [BigUint64Array | bigint[]]],
+    Result_12
   >,
-  'x402_payment' : ActorMethod<[string], Result_7>,
+  /**
+   * The x402 payment of an action, discounted for the token the action is on.
+   */
+  'x402_payment' : ActorMethod<[string, [] | [bigint]], Result_12>,
+  'x402_top_up_payment' : ActorMethod<[bigint], Result_12>,
 }
 export declare const idlFactory: IDL.InterfaceFactory;
 export declare const init: (args: { IDL: typeof IDL }) => IDL.Type[];
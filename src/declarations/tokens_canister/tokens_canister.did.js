@@ -20,11 +20,40 @@ export const idlFactory = ({ IDL }) => {
   });
   const Result = IDL.Variant({ 'Ok' : IDL.Vec(IDL.Nat64), 'Err' : IDL.Text });
   const Result_1 = IDL.Variant({ 'Ok' : IDL.Null, 'Err' : IDL.Text });
+  const X402DiscountTarget = IDL.Variant({
+    'Tag' : IDL.Text,
+    'Verified' : IDL.Null,
+  });
+  const X402Discount = IDL.Record({
+    'percent' : IDL.Nat8,
+    'target' : X402DiscountTarget,
+  });
+  const X402DiscountChange = IDL.Record({
+    'effective_at' : IDL.Nat64,
+    'discounts' : IDL.Vec(X402Discount),
+  });
+  const X402PriceOption = IDL.Record({
+    'asset' : IDL.Text,
+    'network' : IDL.Text,
+    'extra' : IDL.Opt(IDL.Text),
+    'amount' : IDL.Nat64,
+    'pay_to' : IDL.Text,
+  });
+  const X402PriceChange = IDL.Record({
+    'action' : IDL.Text,
+    'effective_at' : IDL.Nat64,
+    'options' : IDL.Vec(X402PriceOption),
+  });
   const VerificationBadge = IDL.Record({
     'methods' : IDL.Vec(IDL.Text),
     'is_verified' : IDL.Bool,
     'verified_at' : IDL.Nat64,
   });
+  const PayingResultInput = IDL.Record({
+    'result' : IDL.Vec(IDL.Nat8),
+    'signature' : IDL.Vec(IDL.Nat8),
+    'timestamp' : IDL.Nat64,
+  });
   const Result_2 = IDL.Variant({ 'Ok' : IDL.Vec(IDL.Text), 'Err' : IDL.Text });
   const Announcement = IDL.Record({
     'id' : IDL.Nat64,
@@ -46,34 +75,117 @@ export const idlFactory = ({ IDL }) => {
     'announcements' : IDL.Vec(Announcement),
   });
   const Result_3 = IDL.Variant({ 'Ok' : TokenProfile, 'Err' : IDL.Text });
+  const X402ReceiptOutcome = IDL.Variant({
+    'Failed' : IDL.Text,
+    'Refunded' : IDL.Text,
+    'Succeeded' : IDL.Null,
+    'Credited' : IDL.Null,
+  });
+  const X402Receipt = IDL.Record({
+    'updated_at' : IDL.Nat64,
+    'action' : IDL.Text,
+    'asset' : IDL.Text,
+    'txid' : IDL.Text,
+    'user' : IDL.Principal,
+    'network' : IDL.Text,
+    'units' : IDL.Nat64,
+    'payer' : IDL.Opt(IDL.Text),
+    'amount' : IDL.Nat,
+    'outcome' : X402ReceiptOutcome,
+    'pay_to' : IDL.Text,
+    'settled_at' : IDL.Nat64,
+  });
+  const Result_4 = IDL.Variant({ 'Ok' : X402Receipt, 'Err' : IDL.Text });
   const StateInfo = IDL.Record({
+    'x402_discount_schedule' : IDL.Vec(X402DiscountChange),
+    'x402_accepts' : IDL.Vec(IDL.Tuple(IDL.Text, IDL.Vec(X402PriceOption))),
     'governance_canister' : IDL.Opt(IDL.Principal),
     'x402_prices' : IDL.Vec(IDL.Tuple(IDL.Text, IDL.Nat64)),
     'x402_pay_to' : IDL.Text,
     'total_incoming' : IDL.Nat,
+    'x402_discounts' : IDL.Vec(X402Discount),
+    'total_credits' : IDL.Nat,
+    'x402_price_schedule' : IDL.Vec(X402PriceChange),
+    'credits_ledger' : IDL.Opt(IDL.Principal),
     'total_tokens' : IDL.Nat64,
     'x402_paying_public_keys' : IDL.Vec(IDL.Vec(IDL.Nat8)),
+    'images_canister' : IDL.Opt(IDL.Principal),
   });
-  const Result_4 = IDL.Variant({ 'Ok' : StateInfo, 'Err' : IDL.Text });
-  const PayingResultInput = IDL.Record({
-    'result' : IDL.Vec(IDL.Nat8),
-    'signature' : IDL.Vec(IDL.Nat8),
-    'timestamp' : IDL.Nat64,
+  const Result_5 = IDL.Variant({ 'Ok' : StateInfo, 'Err' : IDL.Text });
+  const RevenueEntry = IDL.Record({
+    'action' : IDL.Text,
+    'asset' : IDL.Text,
+    'date' : IDL.Text,
+    'count' : IDL.Nat64,
+    'refunded_amount' : IDL.Nat,
+    'network' : IDL.Text,
+    'refunded_count' : IDL.Nat64,
+    'amount' : IDL.Nat,
+    'pay_to' : IDL.Text,
+  });
+  const Result_6 = IDL.Variant({
+    'Ok' : IDL.Vec(RevenueEntry),
+    'Err' : IDL.Text,
+  });
+  const CreditLogKind = IDL.Variant({
+    'Debit' : IDL.Record({ 'action' : IDL.Text, 'units' : IDL.Nat64 }),
+    'X402TopUp' : IDL.Text,
+    'Icrc2TopUp' : IDL.Text,
   });
-  const Result_5 = IDL.Variant({ 'Ok' : IDL.Nat64, 'Err' : IDL.Text });
-  const Result_6 = IDL.Variant({ 'Ok' : IDL.Text, 'Err' : IDL.Text });
+  const CreditLog = IDL.Record({
+    'balance' : IDL.Nat,
+    'kind' : CreditLogKind,
+    'created_at' : IDL.Nat64,
+    'amount' : IDL.Nat,
+  });
+  const Credits = IDL.Record({
+    'balance' : IDL.Nat,
+    'history' : IDL.Vec(CreditLog),
+  });
+  const Result_7 = IDL.Variant({ 'Ok' : Credits, 'Err' : IDL.Text });
+  const Result_8 = IDL.Variant({
+    'Ok' : IDL.Vec(X402Receipt),
+    'Err' : IDL.Text,
+  });
+  const Result_9 = IDL.Variant({ 'Ok' : IDL.Nat64, 'Err' : IDL.Text });
+  const Result_10 = IDL.Variant({ 'Ok' : IDL.Nat, 'Err' : IDL.Text });
+  const Result_11 = IDL.Variant({ 'Ok' : IDL.Text, 'Err' : IDL.Text });
   const X402PaymentOutput = IDL.Record({
     'x402' : IDL.Vec(IDL.Nat8),
     'nonce' : IDL.Text,
     'timestamp' : IDL.Nat64,
   });
-  const Result_7 = IDL.Variant({ 'Ok' : X402PaymentOutput, 'Err' : IDL.Text });
+  const Result_12 = IDL.Variant({ 'Ok' : X402PaymentOutput, 'Err' : IDL.Text });
   return IDL.Service({
     'admin_batch_register_tokens' : IDL.Func(
         [IDL.Vec(TokenMetadata)],
         [Result],
         [],
       ),
+    'admin_cancel_x402_discounts' : IDL.Func([IDL.Nat64], [Result_1], []),
+    'admin_cancel_x402_price' : IDL.Func([IDL.Text, IDL.Nat64], [Result_1], []),
+    'admin_credit_x402_receipt' : IDL.Func([IDL.Text], [Result_1], []),
+    'admin_refund_x402_receipt' : IDL.Func(
+        [IDL.Text, IDL.Text],
+        [Result_1],
+        [],
+      ),
+    'admin_schedule_x402_discounts' : IDL.Func(
+        [X402DiscountChange],
+        [Result_1],
+        [],
+      ),
+    'admin_schedule_x402_price' : IDL.Func([X402PriceChange], [Result_1], []),
+    'admin_set_credits_ledger' : IDL.Func(
+        [IDL.Opt(IDL.Principal)],
+        [Result_1],
+        [],
+      ),
+    'admin_set_images_canister' : IDL.Func(
+        [IDL.Opt(IDL.Principal)],
+        [Result_1],
+        [],
+      ),
     'admin_set_paying_public_keys' : IDL.Func(
         [IDL.Vec(IDL.Text)],
         [Result_1],
@@ -84,6 +196,11 @@ export const idlFactory = ({ IDL }) => {
         [Result_1],
         [],
       ),
+    'admin_set_x402_accepts' : IDL.Func(
+        [IDL.Text, IDL.Vec(X402PriceOption)],
+        [Result_1],
+        [],
+      ),
     'admin_set_x402_pay_to' : IDL.Func([IDL.Text], [Result_1], []),
     'admin_set_x402_prices' : IDL.Func(
         [IDL.Vec(IDL.Tuple(IDL.Text, IDL.Nat64))],
@@ -105,61 +222,139 @@ export const idlFactory = ({ IDL }) => {
         [Result_1],
         [],
       ),
+    'batch_update_token_metadata' : IDL.Func(
+        [
+          IDL.Vec(IDL.Tuple(IDL.Nat64, TokenMetadata)),
+          IDL.Opt(PayingResultInput),
+        ],
+        [Result_1],
+        [],
+      ),
+    'charge_image_storage' : IDL.Func(
+        [IDL.Nat64, IDL.Principal, IDL.Nat64, IDL.Opt(PayingResultInput)],
+        [Result_2],
+        [],
+      ),
     'check_permission' : IDL.Func(
         [IDL.Nat64, IDL.Principal],
         [Result_2],
         ['query'],
       ),
     'get_token_profile' : IDL.Func([IDL.Nat64], [Result_3], ['query']),
-    'info' : IDL.Func([], [Result_4], ['query']),
+    'get_x402_receipt' : IDL.Func([IDL.Text], [Result_4], ['query']),
+    'info' : IDL.Func([], [Result_5], ['query']),
+    'list_revenue' : IDL.Func(
+        [IDL.Opt(IDL.Text), IDL.Opt(IDL.Text)],
+        [Result_6],
+        ['query'],
+      ),
     'list_tokens' : IDL.Func(
         [IDL.Nat64, IDL.Opt(IDL.Nat64)],
         [IDL.Vec(IDL.Tuple(IDL.Nat64, TokenMetadata))],
         ['query'],
       ),
+    'my_credits' : IDL.Func([], [Result_7], ['query']),
     'my_txs' : IDL.Func([], [Result_2], ['query']),
+    'my_x402_receipts' : IDL.Func([], [Result_8], ['query']),
     'query_token' : IDL.Func(
         [IDL.Text],
         [IDL.Vec(IDL.Tuple(IDL.Nat64, TokenMetadata))],
         ['query'],
       ),
     'register_token' : IDL.Func(
-        [TokenMetadata, PayingResultInput],
-        [Result_5],
+        [TokenMetadata, IDL.Opt(PayingResultInput)],
+        [Result_9],
         [],
       ),
     'set_announcement' : IDL.Func(
-        [IDL.Nat64, Announcement, PayingResultInput],
+        [IDL.Nat64, Announcement, IDL.Opt(PayingResultInput)],
         [Result_1],
         [],
       ),
+    'top_up_credits' : IDL.Func([PayingResultInput], [Result_10], []),
+    'top_up_credits_icrc2' : IDL.Func([IDL.Nat64], [Result_10], []),
     'update_token_controllers' : IDL.Func(
         [IDL.Nat64, IDL.Vec(IDL.Principal)],
         [Result_1],
         [],
       ),
     'update_token_metadata' : IDL.Func(
-        [IDL.Nat64, TokenMetadata],
+        [IDL.Nat64, TokenMetadata, IDL.Opt(PayingResultInput)],
         [Result_1],
         [],
       ),
+    'validate_admin_cancel_x402_discounts' : IDL.Func(
+        [IDL.Nat64],
+        [Result_11],
+        [],
+      ),
+    'validate_admin_cancel_x402_price' : IDL.Func(
+        [IDL.Text, IDL.Nat64],
+        [Result_11],
+        [],
+      ),
+    'validate_admin_credit_x402_receipt' : IDL.Func(
+        [IDL.Text],
+        [Result_11],
+        [],
+      ),
+    'validate_admin_refund_x402_receipt' : IDL.Func(
+        [IDL.Text, IDL.Text],
+        [Result_11],
+        [],
+      ),
+    'validate_admin_schedule_x402_discounts' : IDL.Func(
+        [X402DiscountChange],
+        [Result_11],
+        [],
+      ),
+    'validate_admin_schedule_x402_price' : IDL.Func(
+        [X402PriceChange],
+        [Result_11],
+        [],
+      ),
+    'validate_admin_set_credits_ledger' : IDL.Func(
+        [IDL.Opt(IDL.Principal)],
+        [Result_11],
+        [],
+      ),
+    'validate_admin_set_images_canister' : IDL.Func(
+        [IDL.Opt(IDL.Principal)],
+        [Result_11],
+        [],
+      ),
     'validate_admin_set_paying_public_keys' : IDL.Func(
         [IDL.Vec(IDL.Text)],
-        [Result_6],
+        [Result_11],
         [],
       ),
     'validate_admin_set_payment_requirements_extra' : IDL.Func(
         [IDL.Text],
-        [Result_6],
+        [Result_11],
         [],
       ),
-    'validate_admin_set_x402_pay_to' : IDL.Func([IDL.Text], [Result_6], []),
+    'validate_admin_set_x402_accepts' : IDL.Func(
+        [IDL.Text, IDL.Vec(X402PriceOption)],
+        [Result_11],
+        [],
+      ),
+    'validate_admin_set_x402_pay_to' : IDL.Func([IDL.Text], [Result_11], []),
     'validate_admin_set_x402_prices' : IDL.Func(
         [IDL.Vec(IDL.Tuple(IDL.Text, IDL.Nat64))],
-        [Result_6],
+        [Result_11],
         [],
       ),
-    'x402_payment' : IDL.Func([IDL.Text], [Result_7], ['query']),
+    'x402_metered_payment' : IDL.Func(
+        [IDL.Text, IDL.Nat64, IDL.Opt(IDL.Vec(IDL.Nat64))],
+        [Result_12],
+        ['query'],
+      ),
+    'x402_payment' : IDL.Func(
+        [IDL.Text, IDL.Opt(IDL.Nat64)],
+        [Result_12],
+        ['query'],
+      ),
+    'x402_top_up_payment' : IDL.Func([IDL.Nat64], [Result_12], ['query']),
   });
 };
 export const init = ({ IDL }) => {
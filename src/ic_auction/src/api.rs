@@ -1,11 +1,7 @@
-use ic_auth_types::{ByteBufB64, deterministic_cbor_into_vec};
-use ic_ed25519::PublicKey;
+use candid::Principal;
 use serde_json::{Map, Value};
 
-use crate::{
-    helper::{format_error, msg_caller, sha3_256},
-    store, types,
-};
+use crate::{helper::msg_caller, store, types};
 
 #[ic_cdk::query]
 fn info() -> Result<types::StateInfo, String> {
@@ -41,6 +37,13 @@ fn my_info() -> Result<types::UserInfo, String> {
     store::state::my_info(caller)
 }
 
+// Returns the caller's own deposit address on the auction chain
+#[ic_cdk::query]
+fn my_deposit_address() -> Result<String, String> {
+    let caller = msg_caller()?;
+    store::state::deposit_address(caller)
+}
+
 #[ic_cdk::query]
 fn my_bids() -> Result<Vec<types::BidInfo>, String> {
     let caller = msg_caller()?;
@@ -69,53 +72,26 @@ fn estimate_max_price(amount: u128) -> (u128, u128) {
 fn x402_payment(amount: u128, verify_only: bool) -> Result<types::X402PaymentOutput, String> {
     let caller = msg_caller()?;
     let timestamp = ic_cdk::api::time() / 1_000_000;
+    x402_payment_output(caller, amount, verify_only, timestamp)
+}
+
+fn x402_payment_output(
+    caller: Principal,
+    amount: u128,
+    verify_only: bool,
+    timestamp: u64,
+) -> Result<types::X402PaymentOutput, String> {
     store::state::with(|s| {
         let amount = if verify_only {
             amount.min(10u128.pow(s.currency_decimals.saturating_sub(2) as u32))
         } else {
             amount
         };
-
-        let (network, pay_to) = match &s.chain {
-            types::Chain::Icp(1) => ("icp:1".to_string(), s.icp_address.to_string()),
-            types::Chain::Sol(0) => (
-                "solana:EtWTRABZaYq6iMfeYKouRu166VU2xqa1".to_string(),
-                s.sol_address.to_string(),
-            ),
-            types::Chain::Sol(1) => (
-                "solana:5eykt4UsFv8P8NJdTREpY1vzqKqZKvdp".to_string(),
-                s.sol_address.to_string(),
-            ),
-            types::Chain::Evm(8453) => ("eip155:8453".to_string(), s.evm_address.to_string()),
-            types::Chain::Evm(84532) => ("eip155:84532".to_string(), s.evm_address.to_string()),
-            other => return Err(format!("{:?} is not supported yet", other)),
-        };
-
-        let extra: Option<Map<String, Value>> = s
-            .payment_requirements_extra
-            .as_ref()
-            .map(|s| serde_json::from_str(s).unwrap());
-
-        let pr = types::PaymentRequirements {
-            scheme: "exact".to_string(),
-            network,
-            amount: amount.to_string(),
-            asset: s.currency.to_string(),
-            pay_to,
-            max_timeout_seconds: 120,
-            extra,
-        };
-        let nonce_seed =
-            deterministic_cbor_into_vec(&(verify_only, &caller, timestamp, &pr, &s.nonce_iv))?;
-        let nonce = ByteBufB64::from(sha3_256(&nonce_seed)).to_string();
-        let x402 = types::PaymentRequired {
-            x402_version: 2,
-            error: if verify_only {
-                Some(format!("Verification required for auction: {:?}", s.name))
-            } else {
-                Some(format!("Payment required for auction: {:?}", s.name))
-            },
-            resource: types::ResourceInfo {
+        x402::payment_output(
+            &x402_secret(s.nonce_iv.as_slice(), verify_only),
+            &caller,
+            x402_accepts(s, amount, verify_only)?,
+            x402::ResourceInfo {
                 url: format!("https://tokenlist.ing/_/launchpad/{}", s.icp_address),
                 description: if verify_only {
                     Some("Address verification only, no settlement will be made.".to_string())
@@ -124,15 +100,13 @@ fn x402_payment(amount: u128, verify_only: bool) -> Result<types::X402PaymentOut
                 },
                 mime_type: None,
             },
-            accepts: vec![pr],
-            extensions: None,
-        };
-
-        Ok(types::X402PaymentOutput {
-            x402: ByteBufB64::from(deterministic_cbor_into_vec(&x402)?),
-            nonce,
+            if verify_only {
+                Some(format!("Verification required for auction: {:?}", s.name))
+            } else {
+                Some(format!("Payment required for auction: {:?}", s.name))
+            },
             timestamp,
-        })
+        )
     })
 }
 
@@ -140,48 +114,75 @@ fn x402_payment(amount: u128, verify_only: bool) -> Result<types::X402PaymentOut
 fn x402_bind_address(input: types::PayingResultInput) -> Result<(), String> {
     let caller = msg_caller()?;
     let now_ms = ic_cdk::api::time() / 1_000_000;
+    x402_bind_address_with(caller, input, now_ms)
+}
 
-    store::state::with(|s| {
-        let mut verified = false;
-        for pk_bytes in &s.paying_public_keys {
-            let pk = PublicKey::deserialize_raw(&pk_bytes.0).map_err(format_error)?;
-            if pk.verify_signature(&input.result, &input.signature).is_ok() {
-                verified = true;
-                break;
-            }
-        }
-        if !verified {
-            return Err("signature verification failed".to_string());
-        }
-        let pv: types::PaymentVerifyResult =
-            ciborium::from_reader(&input.result[..]).map_err(format_error)?;
-        if input.timestamp + 600 * 1000 < now_ms {
-            return Err("payment verification result expired".to_string());
-        }
-        if !pv.verify_response.is_valid {
-            return Err("payment verification failed".to_string());
-        }
+fn x402_bind_address_with(
+    caller: Principal,
+    input: types::PayingResultInput,
+    now_ms: u64,
+) -> Result<(), String> {
+    let payer = x402_verify_payer(caller, &input, now_ms)?;
+    store::state::bind_address(caller, payer, now_ms)
+}
+
+// Deposits can be paid with the exact amount or up to it, address verifications only with
+// the exact amount
+fn x402_accepts(
+    s: &store::State,
+    amount: u128,
+    verify_only: bool,
+) -> Result<Vec<x402::PaymentRequirements>, String> {
+    let (network, _) = s.x402_network()?;
+    let pay_to = match &s.chain {
+        types::Chain::Icp(_) => s.icp_address.to_string(),
+        types::Chain::Sol(_) => s.sol_address.to_string(),
+        types::Chain::Evm(_) => s.evm_address.to_string(),
+    };
+    let extra: Option<Map<String, Value>> = s
+        .payment_requirements_extra
+        .as_ref()
+        .map(|s| serde_json::from_str(s).unwrap());
+
+    let exact = x402::PaymentRequirements::exact(
+        network.clone(),
+        s.currency.to_string(),
+        amount,
+        pay_to.clone(),
+        extra.clone(),
+    );
+    if verify_only {
+        return Ok(vec![exact]);
+    }
+    Ok(vec![
+        exact,
+        x402::PaymentRequirements::upto(network, s.currency.to_string(), amount, pay_to, extra),
+    ])
+}
 
+// Returns the payer of a verified address verification payment
+fn x402_verify_payer(
+    caller: Principal,
+    input: &types::PayingResultInput,
+    now_ms: u64,
+) -> Result<String, String> {
+    store::state::with_mut(|s| {
+        let secret = x402_secret(s.nonce_iv.as_slice(), true);
+        let (_, public_keys) = s.x402_network()?;
+        let accepts = x402_accepts(s, input.payment_requirements()?.amount()?, true)?;
+        let verifier = x402::PaymentVerifier {
+            public_keys,
+            secret: &secret,
+            accepts: &accepts,
+        };
+        let pv = verifier.verify(input, &caller, now_ms)?;
         let payer = pv
             .verify_response
             .payer
             .ok_or("missing payer in verification response")?;
-
-        let nonce_seed = deterministic_cbor_into_vec(&(
-            true,
-            &caller,
-            input.timestamp,
-            &pv.payment_requirements,
-            &s.nonce_iv,
-        ))?;
-        let nonce = ByteBufB64::from(sha3_256(&nonce_seed)).to_string();
-        if nonce != pv.nonce {
-            return Err("nonce mismatch".to_string());
-        }
-
-        store::state::bind_address(caller, payer, now_ms)?;
-
-        Ok(())
+        s.x402_replay_guard
+            .consume(&pv.nonce, input.timestamp, now_ms)?;
+        Ok::<_, String>(payer)
     })
 }
 
@@ -189,51 +190,15 @@ fn x402_bind_address(input: types::PayingResultInput) -> Result<(), String> {
 async fn x402_deposit_currency(input: types::PayingResultInput) -> Result<u128, String> {
     let caller = msg_caller()?;
     let now_ms = ic_cdk::api::time() / 1_000_000;
-    let (settle_response, amount) = store::state::with(|s| {
-        let mut verified = false;
-        for pk_bytes in &s.paying_public_keys {
-            let pk = PublicKey::deserialize_raw(&pk_bytes.0).map_err(format_error)?;
-            if pk.verify_signature(&input.result, &input.signature).is_ok() {
-                verified = true;
-                break;
-            }
-        }
-        if !verified {
-            return Err("signature verification failed".to_string());
-        }
-        let pv: types::PaymentSettleResult =
-            ciborium::from_reader(&input.result[..]).map_err(format_error)?;
-        if input.timestamp + 600 * 1000 < now_ms {
-            return Err("payment settlement result expired".to_string());
-        }
-
-        if !pv.settle_response.success {
-            return Err("payment settlement failed".to_string());
-        }
-        if pv.settle_response.payer.is_none() {
-            return Err("missing payer in settlement response".to_string());
-        }
-
-        let nonce_seed = deterministic_cbor_into_vec(&(
-            false,
-            &caller,
-            input.timestamp,
-            &pv.payment_requirements,
-            &s.nonce_iv,
-        ))?;
-        let nonce = ByteBufB64::from(sha3_256(&nonce_seed)).to_string();
-        if nonce != pv.nonce {
-            return Err("nonce mismatch".to_string());
-        }
-        let amount: u128 = pv
-            .payment_requirements
-            .amount
-            .parse()
-            .map_err(|_| "invalid amount in payment requirements".to_string())?;
-
-        Ok((pv.settle_response, amount))
-    })?;
+    x402_deposit_currency_with(caller, input, now_ms).await
+}
 
+async fn x402_deposit_currency_with(
+    caller: Principal,
+    input: types::PayingResultInput,
+    now_ms: u64,
+) -> Result<u128, String> {
+    let (settle_response, amount) = x402_settle(caller, &input, now_ms)?;
     store::state::x402_deposit_currency(
         caller,
         settle_response.payer.unwrap(),
@@ -244,6 +209,41 @@ async fn x402_deposit_currency(input: types::PayingResultInput) -> Result<u128,
     .await
 }
 
+// Returns the settlement and the amount of a deposit payment
+fn x402_settle(
+    caller: Principal,
+    input: &types::PayingResultInput,
+    now_ms: u64,
+) -> Result<(x402::SettleResponse, u128), String> {
+    store::state::with_mut(|s| {
+        let secret = x402_secret(s.nonce_iv.as_slice(), false);
+        let (_, public_keys) = s.x402_network()?;
+        let accepts = x402_accepts(s, input.payment_requirements()?.amount()?, false)?;
+        let verifier = x402::PaymentVerifier {
+            public_keys,
+            secret: &secret,
+            accepts: &accepts,
+        };
+        let ps = verifier.settle(input, &caller, now_ms)?;
+        if ps.settle_response.payer.is_none() {
+            return Err("missing payer in settlement response".to_string());
+        }
+        let amount = ps.settled_amount()?;
+        let txid = s.chain.parse_txid(&ps.settle_response.transaction)?;
+        x402::check_settled_txid(&txid, store::state::is_deposited)?;
+        s.x402_replay_guard
+            .consume(&ps.nonce, input.timestamp, now_ms)?;
+        Ok((ps.settle_response, amount))
+    })
+}
+
+// Payments for address verification and for deposits use different nonces
+fn x402_secret(nonce_iv: &[u8], verify_only: bool) -> Vec<u8> {
+    let mut secret = nonce_iv.to_vec();
+    secret.push(verify_only as u8);
+    secret
+}
+
 #[ic_cdk::update]
 fn submit_bid(amount: u128, max_price: u128) -> Result<types::BidInfo, String> {
     let caller = msg_caller()?;
@@ -271,7 +271,34 @@ fn claim_all() -> Result<Vec<types::BidInfo>, String> {
 async fn deposit_currency(input: types::DepositInput) -> Result<u128, String> {
     let caller = msg_caller()?;
     let now_ms = ic_cdk::api::time() / 1_000_000;
-    store::state::deposit_currency(caller, input.sender, input.txid, now_ms).await
+    store::state::deposit_currency(caller, input.sender, input.txid, input.memo, now_ms).await
+}
+
+// Deposit currency by pulling an ICRC-2 allowance granted to the auction contract
+// Returns the user account balance after deposit
+#[ic_cdk::update]
+async fn deposit_currency_icrc2(amount: u128) -> Result<u128, String> {
+    let caller = msg_caller()?;
+    let now_ms = ic_cdk::api::time() / 1_000_000;
+    store::state::deposit_currency_icrc2(caller, amount, now_ms).await
+}
+
+// Broadcast an EIP-3009 authorization paying the auction contract (EVM only)
+// Returns the transaction hash, the deposit is credited once it is mined
+#[ic_cdk::update]
+async fn deposit_currency_eip3009(input: types::Eip3009Input) -> Result<String, String> {
+    let caller = msg_caller()?;
+    let now_ms = ic_cdk::api::time() / 1_000_000;
+    store::state::deposit_currency_eip3009(caller, input, now_ms).await
+}
+
+// Sweep the caller's deposit address into the auction contract
+// Returns the user account balance after deposit
+#[ic_cdk::update]
+async fn sweep_deposit() -> Result<u128, String> {
+    let caller = msg_caller()?;
+    let now_ms = ic_cdk::api::time() / 1_000_000;
+    store::state::sweep_deposit(caller, now_ms).await
 }
 
 // Withdraw currency from the auction contract
@@ -289,3 +316,216 @@ async fn withdraw_token(input: types::WithdrawInput) -> Result<types::WithdrawTx
     let now_ms = ic_cdk::api::time() / 1_000_000;
     store::state::withdraw_token(caller, input.recipient, now_ms).await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use x402_facilitator::Facilitator;
+
+    const PAYER: &str = "0x0000000000000000000000000000000000000002";
+    const TX1: &str = "0x00000000000000000000000000000000000000000000000000000000000000ab";
+    const TX2: &str = "0x00000000000000000000000000000000000000000000000000000000000000ac";
+
+    fn setup(facilitator: &Facilitator) {
+        store::state::with_mut(|s| {
+            s.chain = types::Chain::Evm(1);
+            s.currency = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48".to_string();
+            s.currency_decimals = 6;
+            s.nonce_iv = [7u8; 32].into();
+            s.x402_networks = vec![types::X402Network {
+                chain: types::Chain::Evm(1),
+                network: "eip155:1".to_string(),
+                paying_public_keys: vec![facilitator.public_key()],
+            }];
+        });
+    }
+
+    #[test]
+    fn test_x402_bind_address() {
+        let facilitator = Facilitator::from_seed(b"facilitator");
+        setup(&facilitator);
+        let user = Principal::from_slice(&[1]);
+        let now_ms = 1_000_000;
+
+        let output = x402_payment_output(user, 5_000_000, true, now_ms).unwrap();
+        let pr = x402_facilitator::payment_required(&output).unwrap();
+        assert_eq!(pr.accepts[0].network, "eip155:1");
+        assert_eq!(pr.accepts[0].amount, "10000");
+
+        let input = facilitator.verify(&output, 0, PAYER).unwrap();
+        x402_bind_address_with(user, input.clone(), now_ms).unwrap();
+        assert_eq!(
+            store::state::my_info(user).unwrap().bound_addresses,
+            vec![PAYER.to_string()]
+        );
+        assert_eq!(
+            x402_bind_address_with(user, input.clone(), now_ms + 1).unwrap_err(),
+            "payment result has already been used"
+        );
+        // a verification does not deposit
+        assert!(block_on(x402_deposit_currency_with(user, input, now_ms + 1)).is_err());
+
+        // verified for another user
+        let other = Principal::from_slice(&[2]);
+        let input = facilitator.verify(&output, 0, PAYER).unwrap();
+        assert!(x402_bind_address_with(other, input, now_ms + 1).is_err());
+        // an address is bound by one user only
+        let output = x402_payment_output(other, 5_000_000, true, now_ms + 1).unwrap();
+        let input = facilitator.verify(&output, 0, PAYER).unwrap();
+        assert_eq!(
+            x402_bind_address_with(other, input, now_ms + 1).unwrap_err(),
+            "address is already bound to another user"
+        );
+    }
+
+    #[test]
+    fn test_bind_authorized_holders() {
+        let facilitator = Facilitator::from_seed(b"facilitator");
+        setup(&facilitator);
+        let user = Principal::from_slice(&[1]);
+        let now_ms = 10_000_000;
+        store::state::with_mut(|s| {
+            for (txid, valid_before) in [(TX1, 20_000), (TX2, 0)] {
+                s.pending_authorizations.insert(
+                    txid.to_string(),
+                    store::PendingAuthorization {
+                        user,
+                        from: PAYER.to_string(),
+                        valid_before,
+                    },
+                );
+            }
+        });
+        let transfer = |txid: &str| {
+            (
+                txid.to_string(),
+                types::TransferChecked {
+                    token: store::state::with(|s| s.currency.clone()),
+                    from: PAYER.to_string(),
+                    to: "0x0000000000000000000000000000000000000001".to_string(),
+                    amount: 1_000_000,
+                },
+            )
+        };
+
+        // the holder is not bound before the transfer is confirmed, the expired
+        // authorization is dropped
+        let other = "0x00000000000000000000000000000000000000000000000000000000000000ad";
+        store::state::bind_authorized_holders(&[transfer(other)], now_ms);
+        assert!(
+            store::state::my_info(user)
+                .unwrap()
+                .bound_addresses
+                .is_empty()
+        );
+        assert_eq!(
+            store::state::with(|s| s.pending_authorizations.keys().cloned().collect::<Vec<_>>()),
+            vec![TX1.to_string()]
+        );
+
+        store::state::bind_authorized_holders(&[transfer(TX1)], now_ms);
+        let info = store::state::my_info(user).unwrap();
+        assert_eq!(info.bound_addresses, vec![PAYER.to_string()]);
+        assert!(info.agreed_terms);
+        assert!(store::state::with(|s| s.pending_authorizations.is_empty()));
+    }
+
+    #[test]
+    fn test_x402_deposit_currency() {
+        let facilitator = Facilitator::from_seed(b"facilitator");
+        setup(&facilitator);
+        let user = Principal::from_slice(&[1]);
+        let now_ms = 1_000_000;
+
+        let output = x402_payment_output(user, 5_000_000, false, now_ms).unwrap();
+        let input = facilitator.settle(&output, 0, PAYER, TX1).unwrap();
+        let total = block_on(x402_deposit_currency_with(user, input.clone(), now_ms)).unwrap();
+        assert_eq!(total, 5_000_000);
+        let info = store::state::my_info(user).unwrap();
+        assert_eq!(info.bound_addresses, vec![PAYER.to_string()]);
+        assert_eq!(
+            block_on(x402_deposit_currency_with(user, input, now_ms + 1)).unwrap_err(),
+            format!("transaction {} has already been used", TX1)
+        );
+
+        // a new result for a settled transaction, in another case, also after the
+        // result of the first one expired
+        let later = now_ms + x402::TIME_EXPIRY_MS + 1;
+        let output = x402_payment_output(user, 5_000_000, false, later).unwrap();
+        let input = facilitator
+            .settle(&output, 0, PAYER, &TX1.to_uppercase().replace("0X", "0x"))
+            .unwrap();
+        assert_eq!(
+            block_on(x402_deposit_currency_with(user, input, later)).unwrap_err(),
+            format!("transaction {} has already been used", TX1)
+        );
+
+        // signed by a facilitator of another network
+        let output = x402_payment_output(user, 5_000_000, false, now_ms + 2).unwrap();
+        let input = Facilitator::from_seed(b"base")
+            .settle(&output, 0, PAYER, TX2)
+            .unwrap();
+        assert_eq!(
+            block_on(x402_deposit_currency_with(user, input, now_ms + 2)).unwrap_err(),
+            "signature verification failed"
+        );
+        let input = facilitator
+            .settle_failed(&output, 0, "insufficient funds")
+            .unwrap();
+        assert!(block_on(x402_deposit_currency_with(user, input, now_ms + 2)).is_err());
+        assert_eq!(
+            store::state::my_info(user).unwrap().currency_amount,
+            5_000_000
+        );
+
+        // no x402 network for the chain
+        store::state::with_mut(|s| s.chain = types::Chain::Evm(56));
+        assert!(x402_payment_output(user, 5_000_000, false, now_ms).is_err());
+    }
+
+    #[test]
+    fn test_x402_deposit_currency_upto() {
+        let facilitator = Facilitator::from_seed(b"facilitator");
+        setup(&facilitator);
+        let user = Principal::from_slice(&[1]);
+        let now_ms = 1_000_000;
+
+        let output = x402_payment_output(user, 5_000_000, false, now_ms).unwrap();
+        let pr = x402_facilitator::payment_required(&output).unwrap();
+        assert_eq!(pr.accepts.len(), 2);
+        assert_eq!(pr.accepts[1].scheme, "upto");
+
+        // the payer settles less than the maximum
+        let input = facilitator
+            .settle_upto(&output, 1, PAYER, TX1, 3_000_000)
+            .unwrap();
+        assert_eq!(
+            block_on(x402_deposit_currency_with(user, input, now_ms)).unwrap(),
+            3_000_000
+        );
+
+        let input = facilitator
+            .settle_upto(&output, 1, PAYER, TX2, 5_000_001)
+            .unwrap();
+        assert_eq!(
+            block_on(x402_deposit_currency_with(user, input, now_ms)).unwrap_err(),
+            "settled amount exceeds the maximum amount"
+        );
+        let input = facilitator.settle(&output, 1, PAYER, TX2).unwrap();
+        assert_eq!(
+            block_on(x402_deposit_currency_with(user, input, now_ms)).unwrap_err(),
+            "missing amount in settlement response"
+        );
+
+        // address verifications are exact only
+        let output = x402_payment_output(user, 5_000_000, true, now_ms).unwrap();
+        assert_eq!(
+            x402_facilitator::payment_required(&output)
+                .unwrap()
+                .accepts
+                .len(),
+            1
+        );
+    }
+}
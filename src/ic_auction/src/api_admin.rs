@@ -1,11 +1,12 @@
-use ic_auth_types::ByteArrayB64;
 use serde_json::{Map, Value};
 use std::str::FromStr;
 use url::Url;
 
 use crate::{
     helper::{format_error, pretty_format},
-    store, types,
+    store,
+    svm::Pubkey,
+    types,
 };
 
 #[ic_cdk::update(guard = "is_controller")]
@@ -36,7 +37,9 @@ fn validate_admin_set_project(input: types::ProjectInput) -> Result<String, Stri
 }
 
 #[ic_cdk::update(guard = "is_controller")]
-fn admin_set_token(input: types::TokenInput) -> Result<(), String> {
+async fn admin_set_token(input: types::TokenInput) -> Result<(), String> {
+    let now_ms = ic_cdk::api::time() / 1_000_000;
+    store::state::check_token(&input, now_ms).await?;
     store::state::with_mut(|s| {
         if s.auction.is_some() {
             return Err("cannot change token when an auction is ongoing".to_string());
@@ -57,8 +60,11 @@ fn admin_set_token(input: types::TokenInput) -> Result<(), String> {
     })
 }
 
-#[ic_cdk::update]
-fn validate_admin_set_token(input: types::TokenInput) -> Result<String, String> {
+// guarded, the chain checks make outcalls paid by the canister
+#[ic_cdk::update(guard = "is_controller")]
+async fn validate_admin_set_token(input: types::TokenInput) -> Result<String, String> {
+    let now_ms = ic_cdk::api::time() / 1_000_000;
+    store::state::check_token(&input, now_ms).await?;
     store::state::with(|s| {
         if s.auction.is_some() {
             return Err("cannot change token when an auction is ongoing".to_string());
@@ -74,7 +80,9 @@ fn validate_admin_set_token(input: types::TokenInput) -> Result<String, String>
 }
 
 #[ic_cdk::update(guard = "is_controller")]
-fn admin_set_currency(input: types::TokenInput) -> Result<(), String> {
+async fn admin_set_currency(input: types::TokenInput) -> Result<(), String> {
+    let now_ms = ic_cdk::api::time() / 1_000_000;
+    store::state::check_token(&input, now_ms).await?;
     store::state::with_mut(|s| {
         if s.auction.is_some() {
             return Err("cannot change currency when an auction is ongoing".to_string());
@@ -98,8 +106,11 @@ fn admin_set_currency(input: types::TokenInput) -> Result<(), String> {
     })
 }
 
-#[ic_cdk::update]
-fn validate_admin_set_currency(input: types::TokenInput) -> Result<String, String> {
+// guarded, the chain checks make outcalls paid by the canister
+#[ic_cdk::update(guard = "is_controller")]
+async fn validate_admin_set_currency(input: types::TokenInput) -> Result<String, String> {
+    let now_ms = ic_cdk::api::time() / 1_000_000;
+    store::state::check_token(&input, now_ms).await?;
     store::state::with(|s| {
         if s.auction.is_some() {
             return Err("cannot change currency when an auction is ongoing".to_string());
@@ -182,12 +193,60 @@ fn validate_admin_set_payment_requirements_extra(
 }
 
 #[ic_cdk::update(guard = "is_controller")]
-fn admin_set_paying_public_keys(public_keys: Vec<String>) -> Result<(), String> {
-    let mut paying_public_keys = Vec::new();
-    for key in public_keys {
-        paying_public_keys.push(ByteArrayB64::from_str(&key).map_err(format_error)?)
+fn admin_set_evm_fee_config(config: types::EvmFeeConfig) -> Result<(), String> {
+    config.validate()?;
+    store::state::with_mut(|s| {
+        s.evm_fee_config = config;
+        Ok(())
+    })
+}
+
+#[ic_cdk::update]
+fn validate_admin_set_evm_fee_config(config: types::EvmFeeConfig) -> Result<String, String> {
+    config.validate()?;
+    pretty_format(&(config,))
+}
+
+#[ic_cdk::update(guard = "is_controller")]
+fn admin_set_sol_fee_config(config: types::SolFeeConfig) -> Result<(), String> {
+    config.validate()?;
+    store::state::with_mut(|s| {
+        s.sol_fee_config = config;
+        Ok(())
+    })
+}
+
+#[ic_cdk::update]
+fn validate_admin_set_sol_fee_config(config: types::SolFeeConfig) -> Result<String, String> {
+    config.validate()?;
+    pretty_format(&(config,))
+}
+
+#[ic_cdk::update(guard = "is_controller")]
+fn admin_set_sol_lookup_tables(lookup_tables: Vec<String>) -> Result<(), String> {
+    let mut sol_lookup_tables = Vec::new();
+    for key in lookup_tables {
+        sol_lookup_tables.push(Pubkey::from_str(&key).map_err(format_error)?);
     }
 
+    store::state::with_mut(|s| {
+        s.sol_lookup_tables = sol_lookup_tables;
+        Ok(())
+    })
+}
+
+#[ic_cdk::update]
+fn validate_admin_set_sol_lookup_tables(lookup_tables: Vec<String>) -> Result<String, String> {
+    for key in &lookup_tables {
+        Pubkey::from_str(key).map_err(format_error)?;
+    }
+    pretty_format(&(lookup_tables,))
+}
+
+#[ic_cdk::update(guard = "is_controller")]
+fn admin_set_paying_public_keys(public_keys: Vec<String>) -> Result<(), String> {
+    let paying_public_keys = x402::parse_public_keys(public_keys)?;
+
     store::state::with_mut(|s| {
         s.paying_public_keys = paying_public_keys;
         Ok(())
@@ -196,12 +255,42 @@ fn admin_set_paying_public_keys(public_keys: Vec<String>) -> Result<(), String>
 
 #[ic_cdk::update]
 fn validate_admin_set_paying_public_keys(public_keys: Vec<String>) -> Result<String, String> {
-    for key in &public_keys {
-        ByteArrayB64::<32>::from_str(key).map_err(format_error)?;
-    }
+    x402::parse_public_keys(public_keys.clone())?;
     pretty_format(&(public_keys,))
 }
 
+#[ic_cdk::update(guard = "is_controller")]
+fn admin_set_x402_networks(networks: Vec<types::X402NetworkInput>) -> Result<(), String> {
+    let x402_networks = parse_x402_networks(networks)?;
+
+    store::state::with_mut(|s| {
+        s.x402_networks = x402_networks;
+        Ok(())
+    })
+}
+
+#[ic_cdk::update]
+fn validate_admin_set_x402_networks(
+    networks: Vec<types::X402NetworkInput>,
+) -> Result<String, String> {
+    parse_x402_networks(networks.clone())?;
+    pretty_format(&(networks,))
+}
+
+fn parse_x402_networks(
+    networks: Vec<types::X402NetworkInput>,
+) -> Result<Vec<types::X402Network>, String> {
+    let mut x402_networks: Vec<types::X402Network> = Vec::with_capacity(networks.len());
+    for input in networks {
+        let network = input.parse()?;
+        if x402_networks.iter().any(|n| n.chain == network.chain) {
+            return Err(format!("duplicate x402 network for {:?}", network.chain));
+        }
+        x402_networks.push(network);
+    }
+    Ok(x402_networks)
+}
+
 #[ic_cdk::update(guard = "is_controller")]
 async fn admin_set_auction(auction: types::AuctionConfig) -> Result<(), String> {
     let now_ms = ic_cdk::api::time() / 1_000_000;
@@ -242,6 +331,18 @@ async fn admin_sweep_token() -> Result<types::WithdrawTxInfo, String> {
     store::state::sweep_token(now_ms).await
 }
 
+#[ic_cdk::update(guard = "is_controller")]
+async fn admin_distribute_token() -> Result<Vec<types::WithdrawTxInfo>, String> {
+    let now_ms = ic_cdk::api::time() / 1_000_000;
+    store::state::distribute_sol_payouts(1, now_ms).await
+}
+
+#[ic_cdk::update(guard = "is_controller")]
+async fn admin_refund_currency() -> Result<Vec<types::WithdrawTxInfo>, String> {
+    let now_ms = ic_cdk::api::time() / 1_000_000;
+    store::state::distribute_sol_payouts(0, now_ms).await
+}
+
 #[ic_cdk::update]
 fn validate_empty_input() -> Result<String, String> {
     pretty_format(&())
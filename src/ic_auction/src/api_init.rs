@@ -36,6 +36,8 @@ fn init(args: Option<CanisterArgs>) {
 
     store::state::init_http_certified_data();
     ic_cdk_timers::set_timer(Duration::from_secs(0), store::state::init_public_key());
+    store::state::try_set_evm_nonce_timer();
+    store::state::set_deposit_scanner_timer();
 }
 
 #[ic_cdk::pre_upgrade]
@@ -63,4 +65,6 @@ fn post_upgrade(args: Option<CanisterArgs>) {
 
     store::state::init_http_certified_data();
     store::state::try_set_auction_timer();
+    store::state::try_set_evm_nonce_timer();
+    store::state::set_deposit_scanner_timer();
 }
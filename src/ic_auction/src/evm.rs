@@ -1,8 +1,10 @@
-use alloy_primitives::{U256, hex::FromHex};
-use alloy_rpc_types_eth::TransactionReceipt;
+use alloy_primitives::{B256, Bytes, Signature, U256, hex::FromHex, keccak256};
+use alloy_rpc_types_eth::{FeeHistory, Filter, Log, TransactionReceipt};
+use alloy_sol_types::{SolCall, SolStruct};
 use ic_cdk::management_canister::{HttpHeader, HttpMethod, HttpRequestArgs};
-use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use serde_json::Value;
+use std::collections::BTreeSet;
 
 use crate::{
     helper::APP_AGENT,
@@ -10,8 +12,12 @@ use crate::{
     types::{RPCRequest, RPCResponse},
 };
 
+pub mod abi;
+
 pub use alloy_primitives::{Address, TxHash};
 
+use abi::{IERC20, IERC3009};
+
 #[allow(unused)]
 pub struct EvmClient<T: HttpOutcall> {
     pub providers: Vec<String>,
@@ -44,6 +50,7 @@ impl<H: HttpOutcall> EvmClient<H> {
         hex_to_u64(&res)
     }
 
+    #[allow(unused)]
     pub async fn gas_price(&self, now_ms: u64) -> Result<u128, String> {
         let res: String = self
             .call(format!("eth_gasPrice-{}", now_ms), "eth_gasPrice", &[])
@@ -62,6 +69,132 @@ impl<H: HttpOutcall> EvmClient<H> {
         hex_to_u128(&res)
     }
 
+    /// Returns the base fees and the `reward_percentile` priority fee of the latest blocks.
+    pub async fn fee_history(
+        &self,
+        now_ms: u64,
+        block_count: u64,
+        reward_percentile: u8,
+    ) -> Result<FeeHistory, String> {
+        self.call(
+            format!("eth_feeHistory-{}", now_ms),
+            "eth_feeHistory",
+            &[
+                format!("{:#x}", block_count).into(),
+                "latest".into(),
+                serde_json::json!([reward_percentile]),
+            ],
+        )
+        .await
+    }
+
+    pub async fn estimate_gas(
+        &self,
+        now_ms: u64,
+        from: &Address,
+        to: &Address,
+        data: String,
+    ) -> Result<u64, String> {
+        let call_object = serde_json::json!({
+            "from": from.to_string(),
+            "to": to.to_string(),
+            "data": data,
+        });
+
+        let res: String = self
+            .call(
+                format!("eth_estimateGas-{}", now_ms),
+                "eth_estimateGas",
+                &[call_object],
+            )
+            .await?;
+        hex_to_u64(&res)
+    }
+
+    /// Scans `filter` (address and topics) from the cursor up to the block `confirmations`
+    /// below the head, in pages of at most `page_blocks` blocks. A page the provider
+    /// rejects as too large is retried with half the range. If the block below the cursor
+    /// was reorged out, the cursor rewinds by `confirmations` blocks first, so callers may
+    /// receive logs they have seen before and must deduplicate them, and must recheck what
+    /// they took from the rewound blocks.
+    pub async fn scan_logs(
+        &self,
+        now_ms: u64,
+        filter: &Filter,
+        cursor: &mut LogCursor,
+        scan: &LogScan,
+    ) -> Result<ScannedLogs, String> {
+        let latest = self.block_number(now_ms).await?;
+        let safe = latest.saturating_sub(scan.confirmations);
+        let mut scanned = ScannedLogs::default();
+        if cursor.next_block == 0 {
+            cursor.next_block = safe + 1;
+            cursor.block_hash = None;
+            return Ok(scanned);
+        }
+
+        if let Some(hash) = cursor.block_hash {
+            let number = cursor.next_block - 1;
+            if self.get_block_hash(now_ms, number).await? != hash {
+                cursor.next_block = cursor
+                    .next_block
+                    .saturating_sub(scan.confirmations.max(1))
+                    .max(1);
+                cursor.block_hash = None;
+                scanned.rewound_from = Some(cursor.next_block);
+            }
+        }
+
+        let start = cursor.next_block;
+        let mut page = scan.page_blocks.max(1);
+        let mut rounds = 0;
+        let logs = &mut scanned.logs;
+        while rounds < scan.max_pages && cursor.next_block <= safe {
+            rounds += 1;
+            let from = cursor.next_block;
+            let to = safe.min(from + page - 1);
+            let query = filter.clone().from_block(from).to_block(to);
+            let query = serde_json::to_value(&query).map_err(|err| err.to_string())?;
+            let res: Result<Vec<Log>, String> = self
+                .call(
+                    format!("eth_getLogs-{}-{}-{}", now_ms, from, to),
+                    "eth_getLogs",
+                    &[query],
+                )
+                .await;
+            match res {
+                Ok(page_logs) => {
+                    logs.extend(page_logs.into_iter().filter(|log| !log.removed));
+                    cursor.next_block = to + 1;
+                }
+                // too many results or range too large, try a smaller page
+                Err(err) if to > from && is_log_range_error(&err) => page = (to - from).div_ceil(2),
+                Err(err) if cursor.next_block == start => return Err(err),
+                Err(_) => break,
+            }
+        }
+
+        if cursor.next_block > start {
+            cursor.block_hash = Some(self.get_block_hash(now_ms, cursor.next_block - 1).await?);
+        }
+        Ok(scanned)
+    }
+
+    pub async fn get_block_hash(&self, now_ms: u64, number: u64) -> Result<B256, String> {
+        let res: Value = self
+            .call(
+                format!("eth_getBlockByNumber-{}-{}", now_ms, number),
+                "eth_getBlockByNumber",
+                &[format!("0x{:x}", number).into(), false.into()],
+            )
+            .await?;
+        let hash = res
+            .get("hash")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| format!("block {} not found", number))?;
+        B256::from_hex(hash).map_err(|err| err.to_string())
+    }
+
     #[allow(unused)]
     pub async fn block_number(&self, now_ms: u64) -> Result<u64, String> {
         let res: String = self
@@ -89,6 +222,22 @@ impl<H: HttpOutcall> EvmClient<H> {
         hex_to_u64(&res)
     }
 
+    /// Like `get_transaction_count`, also counting the transactions in the node's mempool.
+    pub async fn get_pending_transaction_count(
+        &self,
+        now_ms: u64,
+        address: &Address,
+    ) -> Result<u64, String> {
+        let res: String = self
+            .call(
+                format!("eth_getTransactionCount-pending-{}", now_ms),
+                "eth_getTransactionCount",
+                &[address.to_string().into(), "pending".into()],
+            )
+            .await?;
+        hex_to_u64(&res)
+    }
+
     #[allow(unused)]
     pub async fn get_balance(&self, now_ms: u64, address: &Address) -> Result<u128, String> {
         let res: String = self
@@ -149,28 +298,56 @@ impl<H: HttpOutcall> EvmClient<H> {
         <Vec<u8>>::from_hex(res).map_err(|err| err.to_string())
     }
 
-    // pub async fn erc20_name(&self, now_ms: u64, contract: &Address) -> Result<String, String> {
-    //     let res = self
-    //         .call_contract(now_ms, contract, "0x06fdde03".to_string())
-    //         .await?;
-    //     decode_abi_string(&res)
-    // }
+    /// Calls a view function of `contract` and decodes its return value.
+    pub async fn call_sol<C: SolCall>(
+        &self,
+        now_ms: u64,
+        contract: &Address,
+        call: &C,
+    ) -> Result<C::Return, String> {
+        let call_data = Bytes::from(call.abi_encode()).to_string();
+        let res = self.call_contract(now_ms, contract, call_data).await?;
+        abi::decode_return::<C>(&res)
+    }
+
+    #[allow(unused)]
+    pub async fn erc20_name(&self, now_ms: u64, contract: &Address) -> Result<String, String> {
+        self.call_sol(now_ms, contract, &IERC20::nameCall {}).await
+    }
+
+    /// Returns the EIP-712 domain separator of an EIP-3009 token.
+    pub async fn erc20_domain_separator(
+        &self,
+        now_ms: u64,
+        contract: &Address,
+    ) -> Result<B256, String> {
+        self.call_sol(now_ms, contract, &IERC3009::DOMAIN_SEPARATORCall {})
+            .await
+    }
 
     #[allow(unused)]
     pub async fn erc20_symbol(&self, now_ms: u64, contract: &Address) -> Result<String, String> {
-        let res = self
-            .call_contract(now_ms, contract, "0x95d89b41".to_string())
-            .await?;
-        decode_abi_string(&res)
+        let call_data = Bytes::from(IERC20::symbolCall {}.abi_encode()).to_string();
+        let res = self.call_contract(now_ms, contract, call_data).await?;
+        abi::decode_symbol(&res)
     }
 
     #[allow(unused)]
     pub async fn erc20_decimals(&self, now_ms: u64, contract: &Address) -> Result<u8, String> {
-        let res = self
-            .call_contract(now_ms, contract, "0x313ce567".to_string())
+        self.call_sol(now_ms, contract, &IERC20::decimalsCall {})
+            .await
+    }
+
+    #[allow(unused)]
+    pub async fn erc20_total_supply(
+        &self,
+        now_ms: u64,
+        contract: &Address,
+    ) -> Result<u128, String> {
+        let v = self
+            .call_sol(now_ms, contract, &IERC20::totalSupplyCall {})
             .await?;
-        let v = decode_abi_uint(&res)?;
-        u8::try_from(v).map_err(|_| "decimals overflow u8".to_string())
+        u128::try_from(v).map_err(|_| "total supply overflow u128".to_string())
     }
 
     pub async fn erc20_balance(
@@ -179,16 +356,37 @@ impl<H: HttpOutcall> EvmClient<H> {
         contract: &Address,
         address: &Address,
     ) -> Result<u128, String> {
-        let addr_hex = address.to_string();
-        let addr_hex = addr_hex.strip_prefix("0x").unwrap_or(&addr_hex);
-        // balanceOf(address): 70a08231
-        let call_data = format!("0x70a08231000000000000000000000000{}", addr_hex);
-
-        let res = self.call_contract(now_ms, contract, call_data).await?;
-        let v = decode_abi_uint(&res)?;
+        let v = self
+            .call_sol(
+                now_ms,
+                contract,
+                &IERC20::balanceOfCall { account: *address },
+            )
+            .await?;
         u128::try_from(v).map_err(|_| "balance overflow u128".to_string())
     }
 
+    #[allow(unused)]
+    pub async fn erc20_allowance(
+        &self,
+        now_ms: u64,
+        contract: &Address,
+        owner: &Address,
+        spender: &Address,
+    ) -> Result<u128, String> {
+        let v = self
+            .call_sol(
+                now_ms,
+                contract,
+                &IERC20::allowanceCall {
+                    owner: *owner,
+                    spender: *spender,
+                },
+            )
+            .await?;
+        u128::try_from(v).map_err(|_| "allowance overflow u128".to_string())
+    }
+
     pub async fn call<T: DeserializeOwned>(
         &self,
         idempotency_key: String,
@@ -285,67 +483,366 @@ impl<H: HttpOutcall> EvmClient<H> {
     }
 }
 
-pub fn encode_erc20_transfer(to: &Address, value: u128) -> Vec<u8> {
-    const TRANSFER_SELECTOR: [u8; 4] = [0xa9, 0x05, 0x9c, 0xbb]; // keccak256("transfer(address,uint256)")[:4]
+/// Kinds of outgoing transactions, each with its own gas limit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EvmTxKind {
+    Transfer,
+    Erc20Transfer,
+    // EIP-3009 transferWithAuthorization
+    Erc20TransferWithAuthorization,
+    #[allow(unused)]
+    Erc20Approve,
+    #[allow(unused)]
+    CreatePool,
+}
 
-    let mut call_data = Vec::with_capacity(4 + 32 + 32);
-    call_data.extend_from_slice(&TRANSFER_SELECTOR);
+impl EvmTxKind {
+    /// Fallback gas limit when `eth_estimateGas` is unavailable.
+    pub fn default_gas_limit(&self) -> u64 {
+        match self {
+            EvmTxKind::Transfer => 21_000,
+            EvmTxKind::Erc20Transfer => 84_000, // sample: ~53,696
+            EvmTxKind::Erc20TransferWithAuthorization => 120_000, // sample: ~80,000
+            EvmTxKind::Erc20Approve => 72_000,  // sample: ~46,000
+            EvmTxKind::CreatePool => 6_000_000,
+        }
+    }
 
-    let mut padded_to = [0u8; 32];
-    padded_to[12..].copy_from_slice(to.as_slice());
-    call_data.extend_from_slice(&padded_to);
+    /// Whether a failed `eth_estimateGas` rejects the transaction. The estimation proves
+    /// that authorizations and pool creations succeed, plain transfers fall back to the
+    /// default gas limit.
+    pub fn requires_estimation(&self) -> bool {
+        !matches!(
+            self,
+            EvmTxKind::Transfer | EvmTxKind::Erc20Transfer | EvmTxKind::Erc20Approve
+        )
+    }
 
-    let value_bytes = U256::from(value).to_be_bytes::<32>();
-    call_data.extend_from_slice(&value_bytes);
+    /// Gas limit with a 20% margin over the estimation, never more than twice the fallback.
+    pub fn gas_limit(&self, estimated: Option<u64>) -> u64 {
+        match (self, estimated) {
+            (EvmTxKind::Transfer, _) | (_, None) => self.default_gas_limit(),
+            (_, Some(gas)) => (gas + gas / 5)
+                .max(21_000)
+                .min(self.default_gas_limit() * 2),
+        }
+    }
+}
 
-    call_data
+/// Returns the median of the sampled priority fees in `history`, ignoring empty blocks.
+pub fn priority_fee_from_history(history: &FeeHistory) -> Option<u128> {
+    let mut fees: Vec<u128> = history
+        .reward
+        .as_ref()?
+        .iter()
+        .filter_map(|r| r.first().copied())
+        .filter(|fee| *fee > 0)
+        .collect();
+    if fees.is_empty() {
+        return None;
+    }
+    fees.sort_unstable();
+    Some(fees[fees.len() / 2])
 }
 
-fn hex_to_u64(s: &str) -> Result<u64, String> {
-    let s = s.strip_prefix("0x").unwrap_or(s);
-    u64::from_str_radix(s, 16).map_err(|err| err.to_string())
+/// Returns (max_fee_per_gas, max_priority_fee_per_gas) for the next block, bounded by the caps.
+pub fn eip1559_fees(
+    base_fee_per_gas: u128,
+    priority_fee_per_gas: u128,
+    max_priority_fee_per_gas: Option<u128>,
+    max_fee_per_gas: Option<u128>,
+) -> Result<(u128, u128), String> {
+    // 20% tip margin to get included quickly
+    let mut priority_fee = priority_fee_per_gas + priority_fee_per_gas / 5;
+    if let Some(cap) = max_priority_fee_per_gas {
+        priority_fee = priority_fee.min(cap);
+    }
+    // base fee can double in ~6 full blocks
+    let mut max_fee = base_fee_per_gas * 2 + priority_fee;
+    if let Some(cap) = max_fee_per_gas {
+        if base_fee_per_gas + priority_fee > cap {
+            return Err(format!(
+                "base fee {base_fee_per_gas} plus priority fee {priority_fee} exceeds the max fee per gas {cap}"
+            ));
+        }
+        max_fee = max_fee.min(cap);
+    }
+    Ok((max_fee, priority_fee))
 }
 
-fn hex_to_u128(s: &str) -> Result<u128, String> {
-    let s = s.strip_prefix("0x").unwrap_or(s);
-    u128::from_str_radix(s, 16).map_err(|err| err.to_string())
+/// Position of an `eth_getLogs` scan. The hash of the last scanned block is kept so
+/// that a reorg below the cursor can be detected and the affected blocks rescanned.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogCursor {
+    // Next block number to scan, 0 starts from the current safe block
+    #[serde(rename = "n")]
+    pub next_block: u64,
+    // Hash of block `next_block - 1`
+    #[serde(rename = "h")]
+    pub block_hash: Option<B256>,
+}
+
+/// Logs of one `scan_logs` round.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ScannedLogs {
+    pub logs: Vec<Log>,
+    // First block rescanned after a reorg below the cursor
+    pub rewound_from: Option<u64>,
+}
+
+// Providers reject eth_getLogs requests over too many blocks or results with various
+// codes and messages, other errors are not retried with a smaller range.
+fn is_log_range_error(err: &str) -> bool {
+    let err = err.to_ascii_lowercase();
+    [
+        "-32005",
+        "block range",
+        "range too large",
+        "range is too large",
+        "more than",
+        "too many",
+        "response size",
+        "exceed",
+    ]
+    .iter()
+    .any(|pat| err.contains(pat))
+}
+
+/// Bounds of one `scan_logs` round.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogScan {
+    // Blocks below the head that are not scanned yet
+    pub confirmations: u64,
+    // Max blocks per eth_getLogs request
+    pub page_blocks: u64,
+    // Max eth_getLogs requests per round
+    pub max_pages: u32,
+}
+
+// Reconcile the local nonces with the chain at most every 10 minutes
+pub const NONCE_SYNC_INTERVAL_MS: u64 = 10 * 60 * 1000;
+// A larger distance from the chain nonce means the local state is unusable
+const MAX_NONCE_DISTANCE: u64 = 1024;
+
+/// Allocates transaction nonces locally for one sender address, so that concurrent
+/// outgoing transactions never receive the same nonce.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NonceManager {
+    // The lowest nonce that has never been allocated
+    #[serde(rename = "n")]
+    pub next_nonce: u64,
+    // Allocated nonces that are not yet confirmed on-chain
+    #[serde(rename = "p")]
+    pub pending: BTreeSet<u64>,
+    // Nonces that were never used on-chain, they are reused before `next_nonce`
+    #[serde(rename = "r")]
+    pub released: BTreeSet<u64>,
+    // Last reconciliation with the chain, in milliseconds
+    #[serde(rename = "s")]
+    pub synced_at: u64,
+    // Pending nonces whose broadcast failed with an unknown outcome
+    #[serde(rename = "u", default)]
+    pub unknown: BTreeSet<u64>,
 }
 
-#[allow(dead_code)]
-fn decode_abi_string(bytes: &[u8]) -> Result<String, String> {
-    if bytes.len() < 64 {
-        return Err("abi string result too short".to_string());
+impl NonceManager {
+    pub fn needs_sync(&self, now_ms: u64) -> bool {
+        self.synced_at + NONCE_SYNC_INTERVAL_MS < now_ms
     }
 
-    let offset = U256::try_from_be_slice(&bytes[0..32]).unwrap();
-    let offset = usize::try_from(offset).unwrap_or(isize::MAX as usize);
-    if bytes.len() < offset + 32 {
-        return Err("abi string length out of bounds".to_string());
+    pub fn is_idle(&self) -> bool {
+        self.pending.is_empty() && self.released.is_empty()
     }
 
-    let len = U256::try_from_be_slice(&bytes[offset..offset + 32]).unwrap();
-    let len = usize::try_from(len).unwrap_or(isize::MAX as usize);
-    if bytes.len() < offset + 32 + len {
-        return Err("abi string data out of bounds".to_string());
+    /// Reconciles with `chain_nonce`, the transaction count of the address on-chain.
+    /// Nonces below it are done; unallocated holes above it become reusable gaps.
+    pub fn reconcile(&mut self, chain_nonce: u64, now_ms: u64) {
+        self.synced_at = now_ms;
+        if self.next_nonce <= chain_nonce || self.next_nonce > chain_nonce + MAX_NONCE_DISTANCE {
+            self.next_nonce = chain_nonce;
+            self.pending.clear();
+            self.released.clear();
+            self.unknown.clear();
+            return;
+        }
+
+        self.pending.retain(|n| *n >= chain_nonce);
+        self.unknown.retain(|n| *n >= chain_nonce);
+        self.released.clear();
+        for n in chain_nonce..self.next_nonce {
+            if !self.pending.contains(&n) {
+                self.released.insert(n);
+            }
+        }
+        self.trim();
     }
 
-    let data = &bytes[offset + 32..offset + 32 + len];
-    String::from_utf8(data.to_vec()).map_err(|err| err.to_string())
+    /// Allocates the lowest reusable nonce, or a new one.
+    pub fn allocate(&mut self) -> u64 {
+        let nonce = match self.released.pop_first() {
+            Some(n) => n,
+            None => {
+                let n = self.next_nonce;
+                self.next_nonce += 1;
+                n
+            }
+        };
+        self.pending.insert(nonce);
+        nonce
+    }
+
+    /// Gives back a nonce whose transaction was never broadcast.
+    pub fn release(&mut self, nonce: u64) {
+        self.unknown.remove(&nonce);
+        if self.pending.remove(&nonce) {
+            self.released.insert(nonce);
+            self.trim();
+        }
+    }
+
+    /// Keeps `nonce` reserved after a broadcast with an unknown outcome, e.g. a timed out
+    /// outcall, until `resolve_unknown` learns whether the node received the transaction.
+    pub fn mark_unknown(&mut self, nonce: u64) {
+        if self.pending.contains(&nonce) {
+            self.unknown.insert(nonce);
+        }
+    }
+
+    /// Resolves the unknown broadcasts with `pending_nonce`, the transaction count of the
+    /// address including the node's mempool. Nonces below it were received, the others
+    /// are released, to be reused or filled.
+    pub fn resolve_unknown(&mut self, pending_nonce: u64) {
+        let unknown = std::mem::take(&mut self.unknown);
+        for nonce in unknown.into_iter().filter(|n| *n >= pending_nonce) {
+            self.release(nonce);
+        }
+    }
+
+    /// Takes the released nonces that block pending transactions with higher nonces.
+    /// They must be filled by the caller, e.g. with a zero-value self transfer.
+    pub fn take_gaps(&mut self) -> Vec<u64> {
+        let max_pending = match self.pending.last() {
+            Some(n) => *n,
+            None => return vec![],
+        };
+        let gaps: Vec<u64> = self
+            .released
+            .iter()
+            .take_while(|n| **n < max_pending)
+            .copied()
+            .collect();
+        for n in &gaps {
+            self.released.remove(n);
+            self.pending.insert(*n);
+        }
+        gaps
+    }
+
+    // Released nonces at the top are not gaps, hand them back to `next_nonce`
+    fn trim(&mut self) {
+        while let Some(last) = self.released.last().copied() {
+            if last + 1 != self.next_nonce {
+                break;
+            }
+            self.released.pop_last();
+            self.next_nonce = last;
+        }
+    }
+}
+
+// Errors of eth_sendRawTransaction after which the transaction, or another one with
+// its nonce, may be in the mempool
+const KNOWN_TX_ERRORS: [&str; 5] = [
+    "already known",
+    "known transaction",
+    "already imported",
+    "nonce too low",
+    "replacement transaction underpriced",
+];
+
+/// Whether an `eth_sendRawTransaction` error is the node's rejection of the transaction,
+/// so that its nonce can be reused. Failed outcalls and errors about a known transaction
+/// or nonce leave the outcome unknown.
+pub fn is_tx_rejection(err: &str) -> bool {
+    let is_rpc_error = serde_json::from_str::<Value>(err)
+        .is_ok_and(|error| error.get("code").is_some() && error.get("message").is_some());
+    let err = err.to_ascii_lowercase();
+    is_rpc_error && !KNOWN_TX_ERRORS.iter().any(|pat| err.contains(pat))
+}
+
+pub fn encode_erc20_transfer(to: &Address, value: u128) -> Vec<u8> {
+    IERC20::transferCall {
+        to: *to,
+        value: U256::from(value),
+    }
+    .abi_encode()
+}
+
+#[allow(unused)]
+pub fn encode_erc20_approve(spender: &Address, value: u128) -> Vec<u8> {
+    IERC20::approveCall {
+        spender: *spender,
+        value: U256::from(value),
+    }
+    .abi_encode()
 }
 
-fn decode_abi_uint(bytes: &[u8]) -> Result<U256, String> {
-    if bytes.len() != 32 {
-        return Err("abi uint result must be 32 bytes".to_string());
+/// Encodes an EIP-3009 `transferWithAuthorization` call with the holder's 65-byte
+/// signature (r || s || v).
+pub fn encode_erc20_transfer_with_authorization(
+    from: &Address,
+    to: &Address,
+    value: u128,
+    valid_after: u64,
+    valid_before: u64,
+    nonce: &[u8; 32],
+    signature: &[u8; 65],
+) -> Vec<u8> {
+    // v is 27 or 28, some wallets return 0 or 1
+    let v = if signature[64] < 27 {
+        signature[64] + 27
+    } else {
+        signature[64]
+    };
+    IERC3009::transferWithAuthorizationCall {
+        from: *from,
+        to: *to,
+        value: U256::from(value),
+        validAfter: U256::from(valid_after),
+        validBefore: U256::from(valid_before),
+        nonce: (*nonce).into(),
+        v,
+        r: <[u8; 32]>::try_from(&signature[0..32]).unwrap().into(),
+        s: <[u8; 32]>::try_from(&signature[32..64]).unwrap().into(),
     }
-    Ok(U256::from_be_slice(bytes))
+    .abi_encode()
+}
+
+/// Recovers the signer of an EIP-3009 transfer authorization, the holder's 65-byte
+/// signature (r || s || v) over its EIP-712 digest under the token's `domain_separator`.
+pub fn recover_transfer_authorization_signer(
+    domain_separator: &B256,
+    authorization: &abi::TransferWithAuthorization,
+    signature: &[u8; 65],
+) -> Result<Address, String> {
+    let mut data = Vec::with_capacity(66);
+    data.extend_from_slice(&[0x19, 0x01]);
+    data.extend_from_slice(domain_separator.as_slice());
+    data.extend_from_slice(authorization.eip712_hash_struct().as_slice());
+    Signature::from_raw_array(signature)
+        .and_then(|sig| sig.recover_address_from_prehash(&keccak256(&data)))
+        .map_err(|err| format!("invalid authorization signature: {err}"))
 }
 
-// fn decode_abi_address(bytes: &[u8]) -> Result<Address, String> {
-//     if bytes.len() != 32 {
-//         return Err("abi address result must be 32 bytes".to_string());
-//     }
-//     Address::try_from(&bytes[12..32]).map_err(|err| err.to_string())
-// }
+fn hex_to_u64(s: &str) -> Result<u64, String> {
+    let s = s.strip_prefix("0x").unwrap_or(s);
+    u64::from_str_radix(s, 16).map_err(|err| err.to_string())
+}
+
+fn hex_to_u128(s: &str) -> Result<u128, String> {
+    let s = s.strip_prefix("0x").unwrap_or(s);
+    u128::from_str_radix(s, 16).map_err(|err| err.to_string())
+}
 
 #[cfg(test)]
 mod tests {
@@ -371,37 +868,266 @@ mod tests {
     }
 
     #[test]
-    fn test_hex_to_u64_and_u128() {
-        assert_eq!(hex_to_u64("0x2a").unwrap(), 42);
-        assert_eq!(hex_to_u128("0xff").unwrap(), 255);
-        assert!(hex_to_u64("g1").is_err());
-        assert!(hex_to_u128("xyz").is_err());
+    fn test_encode_erc20_transfer_with_authorization() {
+        use sha3::{Digest, Keccak256};
+
+        let selector = Keccak256::digest(
+            b"transferWithAuthorization(address,address,uint256,uint256,uint256,bytes32,uint8,bytes32,bytes32)",
+        );
+        let from = Address::repeat_byte(0x11);
+        let to = Address::repeat_byte(0x22);
+        let mut signature = [0x33u8; 65];
+        signature[64] = 1;
+        let encoded = encode_erc20_transfer_with_authorization(
+            &from,
+            &to,
+            1_000_000,
+            0,
+            1_700_000_000,
+            &[0x44u8; 32],
+            &signature,
+        );
+
+        assert_eq!(encoded.len(), 4 + 32 * 9);
+        assert_eq!(&encoded[0..4], &selector[0..4]);
+        assert_eq!(&encoded[4..36], from.into_word().as_slice());
+        assert_eq!(&encoded[36..68], to.into_word().as_slice());
+        assert_eq!(
+            U256::from_be_slice(&encoded[68..100]),
+            U256::from(1_000_000u64)
+        );
+        assert_eq!(&encoded[164..196], &[0x44u8; 32]);
+        assert_eq!(U256::from_be_slice(&encoded[196..228]), U256::from(28u8));
+        assert_eq!(&encoded[228..292], &[0x33u8; 64]);
+    }
+
+    #[test]
+    fn test_recover_transfer_authorization_signer() {
+        use alloy_primitives::b256;
+        use alloy_signer::k256::ecdsa::SigningKey;
+
+        let key = SigningKey::from_slice(&[0x42u8; 32]).unwrap();
+        let holder = Address::from_private_key(&key);
+        let domain_separator = B256::repeat_byte(0x55);
+        let mut authorization = abi::TransferWithAuthorization {
+            from: holder,
+            to: Address::repeat_byte(0x22),
+            value: U256::from(1_000_000u64),
+            validAfter: U256::ZERO,
+            validBefore: U256::from(1_700_000_000u64),
+            nonce: B256::repeat_byte(0x44),
+        };
+        assert_eq!(
+            authorization.eip712_type_hash(),
+            b256!("7c7c6cdb67a18743f49ec6fa9b35f50d52ed05cbed4cc592e13b44501c1a2267")
+        );
+
+        let mut data = vec![0x19, 0x01];
+        data.extend_from_slice(domain_separator.as_slice());
+        data.extend_from_slice(authorization.eip712_hash_struct().as_slice());
+        let (sig, recid) = key
+            .sign_prehash_recoverable(keccak256(&data).as_slice())
+            .unwrap();
+        let mut signature = [0u8; 65];
+        signature[..64].copy_from_slice(&sig.to_bytes());
+        signature[64] = 27 + recid.to_byte();
+        assert_eq!(
+            recover_transfer_authorization_signer(&domain_separator, &authorization, &signature)
+                .unwrap(),
+            holder
+        );
+
+        // another message or domain recovers another address
+        authorization.value = U256::from(2_000_000u64);
+        assert_ne!(
+            recover_transfer_authorization_signer(&domain_separator, &authorization, &signature)
+                .unwrap(),
+            holder
+        );
+        signature[64] = 5;
+        assert!(
+            recover_transfer_authorization_signer(&domain_separator, &authorization, &signature)
+                .is_err()
+        );
     }
 
     #[test]
-    fn test_decode_abi_string() {
-        let mut payload = Vec::new();
-        payload.extend(U256::from(32u8).to_be_bytes::<32>());
-        payload.extend(U256::from(11u8).to_be_bytes::<32>());
-        payload.extend_from_slice(b"hello world");
-        payload.extend(vec![0u8; 21]);
+    fn test_nonce_manager_allocate_and_release() {
+        let mut m = NonceManager::default();
+        assert!(m.needs_sync(NONCE_SYNC_INTERVAL_MS + 1));
+        m.reconcile(5, 1_000);
+        assert!(!m.needs_sync(1_000 + NONCE_SYNC_INTERVAL_MS));
+        assert!(m.is_idle());
+
+        assert_eq!(m.allocate(), 5);
+        assert_eq!(m.allocate(), 6);
+        assert_eq!(m.allocate(), 7);
+        assert_eq!(m.next_nonce, 8);
+
+        // the released nonce is reused before a new one
+        m.release(6);
+        assert_eq!(m.released, BTreeSet::from([6]));
+        assert_eq!(m.allocate(), 6);
+        assert_eq!(m.allocate(), 8);
+
+        // releasing the highest nonce hands it back
+        m.release(8);
+        assert!(m.released.is_empty());
+        assert_eq!(m.next_nonce, 8);
+
+        // unknown nonces are ignored
+        m.release(100);
+        assert!(m.released.is_empty());
+        assert_eq!(m.pending, BTreeSet::from([5, 6, 7]));
+    }
 
-        assert_eq!(decode_abi_string(&payload).unwrap(), "hello world");
+    #[test]
+    fn test_nonce_manager_reconcile() {
+        let mut m = NonceManager::default();
+        m.reconcile(10, 1_000);
+        for _ in 0..5 {
+            m.allocate();
+        }
+        assert_eq!(m.pending, BTreeSet::from([10, 11, 12, 13, 14]));
+
+        // 10 and 11 are confirmed, 12 was dropped before broadcasting
+        m.pending.remove(&12);
+        m.reconcile(12, 2_000);
+        assert_eq!(m.pending, BTreeSet::from([13, 14]));
+        assert_eq!(m.released, BTreeSet::from([12]));
+        assert_eq!(m.next_nonce, 15);
+        assert_eq!(m.synced_at, 2_000);
+
+        // the gap blocks 13 and 14, it must be filled
+        assert_eq!(m.take_gaps(), vec![12]);
+        assert!(m.released.is_empty());
+        assert_eq!(m.pending, BTreeSet::from([12, 13, 14]));
+        assert!(m.take_gaps().is_empty());
+
+        // the address was used outside of the canister
+        m.reconcile(20, 3_000);
+        assert!(m.is_idle());
+        assert_eq!(m.next_nonce, 20);
+
+        // trailing released nonces are not gaps
+        m.allocate();
+        m.allocate();
+        m.release(20);
+        m.release(21);
+        assert!(m.take_gaps().is_empty());
+        assert_eq!(m.next_nonce, 20);
+
+        // local state is too far ahead of the chain
+        m.next_nonce = 20 + MAX_NONCE_DISTANCE + 1;
+        m.reconcile(20, 4_000);
+        assert_eq!(m.next_nonce, 20);
+        assert!(m.is_idle());
+    }
+
+    #[test]
+    fn test_nonce_manager_unknown_broadcast() {
+        let mut m = NonceManager::default();
+        m.reconcile(10, 1_000);
+        for _ in 0..4 {
+            m.allocate();
+        }
 
-        assert!(decode_abi_string(&payload[..60]).is_err());
+        // the broadcasts of 10, 11 and 12 timed out, 20 was never allocated
+        m.mark_unknown(10);
+        m.mark_unknown(11);
+        m.mark_unknown(12);
+        m.mark_unknown(20);
+        assert_eq!(m.unknown, BTreeSet::from([10, 11, 12]));
+        assert_eq!(m.allocate(), 14);
+
+        // the node received 10 and 11, 12 is released and blocks 13 and 14
+        m.resolve_unknown(12);
+        assert!(m.unknown.is_empty());
+        assert_eq!(m.pending, BTreeSet::from([10, 11, 13, 14]));
+        assert_eq!(m.take_gaps(), vec![12]);
+
+        // confirmed nonces are no longer unknown
+        m.mark_unknown(13);
+        m.reconcile(14, 2_000);
+        assert!(m.unknown.is_empty());
+        assert_eq!(m.pending, BTreeSet::from([14]));
+
+        // a trailing unknown nonce is handed back to the next allocation
+        m.mark_unknown(14);
+        m.resolve_unknown(14);
+        assert!(m.is_idle());
+        assert_eq!(m.allocate(), 14);
     }
 
     #[test]
-    fn test_decode_abi_uint() {
-        let value = U256::from(999u64).to_be_bytes::<32>();
-        assert_eq!(decode_abi_uint(&value).unwrap(), U256::from(999u64));
-        assert!(decode_abi_uint(&value[..31]).is_err());
+    fn test_is_tx_rejection() {
+        assert!(is_tx_rejection(
+            r#"{"code":-32000,"message":"insufficient funds for gas * price + value"}"#
+        ));
+        assert!(is_tx_rejection(
+            r#"{"code":-32000,"message":"max fee per gas less than block base fee"}"#
+        ));
+        assert!(!is_tx_rejection(
+            r#"{"code":-32000,"message":"already known"}"#
+        ));
+        assert!(!is_tx_rejection(
+            r#"{"code":-32000,"message":"nonce too low: next nonce 12, tx nonce 11"}"#
+        ));
+        assert!(!is_tx_rejection("No provider succeeded"));
+        assert!(!is_tx_rejection("SysTransient: timeout"));
+    }
+
+    #[test]
+    fn test_evm_tx_kind_gas_limit() {
+        assert_eq!(EvmTxKind::Transfer.gas_limit(Some(30_000)), 21_000);
+        assert_eq!(EvmTxKind::Erc20Transfer.gas_limit(None), 84_000);
+        assert_eq!(EvmTxKind::Erc20Transfer.gas_limit(Some(50_000)), 60_000);
+        assert_eq!(EvmTxKind::Erc20Transfer.gas_limit(Some(10_000)), 21_000);
+        assert_eq!(EvmTxKind::Erc20Transfer.gas_limit(Some(1_000_000)), 168_000);
+        assert_eq!(EvmTxKind::CreatePool.gas_limit(Some(4_000_000)), 4_800_000);
+
+        assert!(!EvmTxKind::Transfer.requires_estimation());
+        assert!(!EvmTxKind::Erc20Transfer.requires_estimation());
+        assert!(EvmTxKind::Erc20TransferWithAuthorization.requires_estimation());
+        assert!(EvmTxKind::CreatePool.requires_estimation());
+    }
+
+    #[test]
+    fn test_priority_fee_from_history() {
+        let mut history = FeeHistory::default();
+        assert_eq!(priority_fee_from_history(&history), None);
+
+        history.reward = Some(vec![vec![0], vec![]]);
+        assert_eq!(priority_fee_from_history(&history), None);
+
+        history.reward = Some(vec![vec![300], vec![0], vec![100], vec![200], vec![5_000]]);
+        assert_eq!(priority_fee_from_history(&history), Some(300));
+    }
+
+    #[test]
+    fn test_eip1559_fees() {
+        assert_eq!(eip1559_fees(100, 10, None, None).unwrap(), (212, 12));
+        // priority fee cap
+        assert_eq!(eip1559_fees(100, 10, Some(5), None).unwrap(), (205, 5));
+        // max fee cap
+        assert_eq!(eip1559_fees(100, 10, None, Some(150)).unwrap(), (150, 12));
+        assert_eq!(eip1559_fees(100, 10, None, Some(112)).unwrap(), (112, 12));
+        assert!(eip1559_fees(100, 10, None, Some(111)).is_err());
+    }
+
+    #[test]
+    fn test_hex_to_u64_and_u128() {
+        assert_eq!(hex_to_u64("0x2a").unwrap(), 42);
+        assert_eq!(hex_to_u128("0xff").unwrap(), 255);
+        assert!(hex_to_u64("g1").is_err());
+        assert!(hex_to_u128("xyz").is_err());
     }
 
     #[derive(Clone, Default)]
     struct MockHttpOutcall {
         responses: Arc<Mutex<VecDeque<Result<HttpRequestResult, String>>>>,
         urls: Arc<Mutex<Vec<String>>>,
+        bodies: Arc<Mutex<Vec<Value>>>,
     }
 
     impl MockHttpOutcall {
@@ -409,12 +1135,17 @@ mod tests {
             Self {
                 responses: Arc::new(Mutex::new(responses.into_iter().collect())),
                 urls: Arc::new(Mutex::new(Vec::new())),
+                bodies: Arc::new(Mutex::new(Vec::new())),
             }
         }
 
         fn urls(&self) -> Vec<String> {
             self.urls.lock().unwrap().clone()
         }
+
+        fn bodies(&self) -> Vec<Value> {
+            self.bodies.lock().unwrap().clone()
+        }
     }
 
     impl HttpOutcall for MockHttpOutcall {
@@ -423,6 +1154,12 @@ mod tests {
             args: &ic_cdk::management_canister::HttpRequestArgs,
         ) -> Result<HttpRequestResult, String> {
             self.urls.lock().unwrap().push(args.url.clone());
+            if let Some(body) = &args.body {
+                self.bodies
+                    .lock()
+                    .unwrap()
+                    .push(serde_json::from_slice(body).unwrap_or_default());
+            }
             self.responses
                 .lock()
                 .unwrap()
@@ -500,6 +1237,237 @@ mod tests {
         assert!(result.unwrap_err().contains("execution reverted"));
     }
 
+    #[test]
+    fn test_fee_history() {
+        let mock = MockHttpOutcall::new(vec![success_response(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": {
+                "oldestBlock": "0x10",
+                "baseFeePerGas": ["0x3b9aca00", "0x3b9aca01", "0x3b9aca02"],
+                "gasUsedRatio": [0.5, 0.6],
+                "reward": [["0x5f5e100"], ["0x0"]]
+            }
+        }))]);
+        let client = EvmClient::new(vec!["https://rpc.test".to_string()], 0, None, mock.clone());
+
+        let history = futures::executor::block_on(client.fee_history(1, 2, 50)).unwrap();
+        assert_eq!(history.oldest_block, 16);
+        assert_eq!(history.next_block_base_fee(), Some(1_000_000_002));
+        assert_eq!(priority_fee_from_history(&history), Some(100_000_000));
+
+        let body = &mock.bodies()[0];
+        assert_eq!(body["method"], "eth_feeHistory");
+        assert_eq!(body["params"], serde_json::json!(["0x2", "latest", [50]]));
+    }
+
+    #[test]
+    fn test_estimate_gas() {
+        let mock = MockHttpOutcall::new(vec![success_response(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": "0xd1c0"
+        }))]);
+        let client = EvmClient::new(vec!["https://rpc.test".to_string()], 0, None, mock.clone());
+
+        let from = Address::repeat_byte(0x11);
+        let to = Address::repeat_byte(0x22);
+        let gas = futures::executor::block_on(client.estimate_gas(
+            1,
+            &from,
+            &to,
+            "0xa9059cbb".to_string(),
+        ))
+        .unwrap();
+        assert_eq!(gas, 53_696);
+
+        let body = &mock.bodies()[0];
+        assert_eq!(body["method"], "eth_estimateGas");
+        assert_eq!(body["params"][0]["data"], "0xa9059cbb");
+    }
+
+    fn rpc_result(result: serde_json::Value) -> Result<HttpRequestResult, String> {
+        success_response(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": result
+        }))
+    }
+
+    #[test]
+    fn test_scan_logs_pages() {
+        use alloy_sol_types::SolEvent;
+
+        let scan = LogScan {
+            confirmations: 6,
+            page_blocks: 10,
+            max_pages: 3,
+        };
+
+        // the first round only places the cursor at the safe block
+        let mock = MockHttpOutcall::new(vec![rpc_result(serde_json::json!("0x82"))]);
+        let client = EvmClient::new(vec!["https://rpc.test".to_string()], 0, None, mock);
+        let mut cursor = LogCursor::default();
+        let scanned =
+            futures::executor::block_on(client.scan_logs(1, &Filter::new(), &mut cursor, &scan))
+                .unwrap();
+        assert_eq!(scanned, ScannedLogs::default());
+        assert_eq!(cursor.next_block, 125);
+        assert_eq!(cursor.block_hash, None);
+
+        let mock = MockHttpOutcall::new(vec![
+            rpc_result(serde_json::json!("0x82")),
+            success_response(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "error": {"code": -32005, "message": "query returned more than 10000 results"}
+            })),
+            rpc_result(serde_json::json!([{
+                "address": "0x2222222222222222222222222222222222222222",
+                "topics": [
+                    "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef",
+                    "0x0000000000000000000000001111111111111111111111111111111111111111",
+                    "0x0000000000000000000000003333333333333333333333333333333333333333"
+                ],
+                "data": "0x00000000000000000000000000000000000000000000000000000000000003e8",
+                "blockNumber": "0x66",
+                "transactionHash": "0x0101010101010101010101010101010101010101010101010101010101010101",
+                "transactionIndex": "0x0",
+                "blockHash": "0x0202020202020202020202020202020202020202020202020202020202020202",
+                "logIndex": "0x1",
+                "removed": false
+            }])),
+            rpc_result(serde_json::json!([])),
+            rpc_result(serde_json::json!({
+                "number": "0x6d",
+                "hash": "0x0303030303030303030303030303030303030303030303030303030303030303"
+            })),
+        ]);
+        let client = EvmClient::new(vec!["https://rpc.test".to_string()], 0, None, mock.clone());
+        let filter = Filter::new()
+            .address(Address::repeat_byte(0x22))
+            .event_signature(abi::IERC20::Transfer::SIGNATURE_HASH)
+            .topic2(Address::repeat_byte(0x33).into_word());
+        let mut cursor = LogCursor {
+            next_block: 100,
+            block_hash: None,
+        };
+        let scanned =
+            futures::executor::block_on(client.scan_logs(1, &filter, &mut cursor, &scan)).unwrap();
+        assert_eq!(scanned.rewound_from, None);
+        let logs = scanned.logs;
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].block_number, Some(102));
+        assert_eq!(cursor.next_block, 110);
+        assert_eq!(cursor.block_hash, Some(B256::repeat_byte(0x03)));
+
+        let bodies = mock.bodies();
+        assert_eq!(bodies.len(), 5);
+        assert_eq!(bodies[1]["params"][0]["fromBlock"], "0x64");
+        assert_eq!(bodies[1]["params"][0]["toBlock"], "0x6d");
+        assert_eq!(bodies[2]["params"][0]["fromBlock"], "0x64");
+        assert_eq!(bodies[2]["params"][0]["toBlock"], "0x68");
+        assert_eq!(
+            bodies[2]["params"][0]["topics"][2],
+            "0x0000000000000000000000003333333333333333333333333333333333333333"
+        );
+        assert_eq!(bodies[3]["params"][0]["fromBlock"], "0x69");
+        assert_eq!(bodies[3]["params"][0]["toBlock"], "0x6d");
+        assert_eq!(bodies[4]["method"], "eth_getBlockByNumber");
+        assert_eq!(bodies[4]["params"], serde_json::json!(["0x6d", false]));
+    }
+
+    #[test]
+    fn test_scan_logs_reorg() {
+        let scan = LogScan {
+            confirmations: 6,
+            page_blocks: 100,
+            max_pages: 2,
+        };
+        let mock = MockHttpOutcall::new(vec![
+            rpc_result(serde_json::json!("0x82")),
+            rpc_result(serde_json::json!({
+                "number": "0x6d",
+                "hash": "0x0404040404040404040404040404040404040404040404040404040404040404"
+            })),
+            rpc_result(serde_json::json!([])),
+            rpc_result(serde_json::json!({
+                "number": "0x7c",
+                "hash": "0x0505050505050505050505050505050505050505050505050505050505050505"
+            })),
+        ]);
+        let client = EvmClient::new(vec!["https://rpc.test".to_string()], 0, None, mock.clone());
+        let mut cursor = LogCursor {
+            next_block: 110,
+            block_hash: Some(B256::repeat_byte(0x03)),
+        };
+        let scanned =
+            futures::executor::block_on(client.scan_logs(1, &Filter::new(), &mut cursor, &scan))
+                .unwrap();
+        assert!(scanned.logs.is_empty());
+        assert_eq!(scanned.rewound_from, Some(104));
+        assert_eq!(cursor.next_block, 125);
+        assert_eq!(cursor.block_hash, Some(B256::repeat_byte(0x05)));
+
+        // rescans from 6 blocks below the reorged cursor
+        let bodies = mock.bodies();
+        assert_eq!(bodies[1]["params"], serde_json::json!(["0x6d", false]));
+        assert_eq!(bodies[2]["params"][0]["fromBlock"], "0x68");
+        assert_eq!(bodies[2]["params"][0]["toBlock"], "0x7c");
+
+        // a failing single block page is an error
+        let mock = MockHttpOutcall::new(vec![
+            rpc_result(serde_json::json!("0x82")),
+            Err("provider down".to_string()),
+        ]);
+        let client = EvmClient::new(vec!["https://rpc.test".to_string()], 0, None, mock);
+        let mut cursor = LogCursor {
+            next_block: 124,
+            block_hash: None,
+        };
+        let res =
+            futures::executor::block_on(client.scan_logs(1, &Filter::new(), &mut cursor, &scan));
+        assert!(res.is_err());
+        assert_eq!(cursor.next_block, 124);
+
+        // other errors are not retried with a smaller range
+        let mock = MockHttpOutcall::new(vec![
+            rpc_result(serde_json::json!("0x82")),
+            success_response(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "error": {"code": -32000, "message": "header not found"}
+            })),
+        ]);
+        let client = EvmClient::new(vec!["https://rpc.test".to_string()], 0, None, mock.clone());
+        let mut cursor = LogCursor {
+            next_block: 100,
+            block_hash: None,
+        };
+        let res =
+            futures::executor::block_on(client.scan_logs(1, &Filter::new(), &mut cursor, &scan));
+        assert!(res.unwrap_err().contains("header not found"));
+        assert_eq!(mock.bodies().len(), 2);
+        assert_eq!(cursor.next_block, 100);
+    }
+
+    #[test]
+    fn test_is_log_range_error() {
+        assert!(is_log_range_error(
+            r#"{"code":-32005,"message":"query returned more than 10000 results"}"#
+        ));
+        assert!(is_log_range_error(
+            r#"{"code":-32602,"message":"Log response size exceeded."}"#
+        ));
+        assert!(is_log_range_error(
+            r#"{"code":-32000,"message":"block range is too large"}"#
+        ));
+        assert!(!is_log_range_error(
+            r#"{"code":-32000,"message":"header not found"}"#
+        ));
+        assert!(!is_log_range_error("provider down"));
+    }
+
     #[test]
     fn test_get_transaction_receipt() {
         let body = serde_json::json!({
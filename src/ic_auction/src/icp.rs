@@ -1,14 +1,22 @@
 use candid::{Nat, Principal};
 use icrc_ledger_types::{
-    icrc::generic_value::{ICRC3Value, Value},
-    icrc1::{account::Account, transfer::TransferArg},
+    icrc::{
+        generic_metadata_value::MetadataValue,
+        generic_value::{ICRC3Value, Value},
+    },
+    icrc1::{
+        account::{Account, Subaccount},
+        transfer::TransferArg,
+    },
     icrc2::approve::{ApproveArgs, ApproveError},
-    icrc2::transfer_from::TransferFromError,
+    icrc2::transfer_from::{TransferFromArgs, TransferFromError},
     icrc3::blocks::{GetBlocksRequest, GetBlocksResult},
 };
 use num_traits::ToPrimitive;
+use std::collections::BTreeSet;
 
 pub mod kong;
+pub mod ledger;
 
 use crate::{helper::call, types::TransferChecked};
 
@@ -17,6 +25,42 @@ pub async fn balance_of(ledger: Principal, account: Account) -> Result<u128, Str
     res.0.to_u128().ok_or("Balance too large".to_string())
 }
 
+pub async fn fee(ledger: Principal) -> Result<u128, String> {
+    let res: Nat = call(ledger, "icrc1_fee", (), 0).await?;
+    res.0.to_u128().ok_or("Fee too large".to_string())
+}
+
+/// Returns (symbol, decimals) from the ledger's `icrc1_metadata`.
+pub async fn token_metadata(ledger: Principal) -> Result<(String, u8), String> {
+    let res: Vec<(String, MetadataValue)> = call(ledger, "icrc1_metadata", (), 0).await?;
+    parse_token_metadata(&res)
+}
+
+fn parse_token_metadata(metadata: &[(String, MetadataValue)]) -> Result<(String, u8), String> {
+    let mut symbol = None;
+    let mut decimals = None;
+    for (key, value) in metadata {
+        match (key.as_str(), value) {
+            ("icrc1:symbol", MetadataValue::Text(v)) => symbol = Some(v.clone()),
+            ("icrc1:decimals", MetadataValue::Nat(v)) => decimals = v.0.to_u8(),
+            _ => {}
+        }
+    }
+    Ok((
+        symbol.ok_or("icrc1:symbol not found in metadata".to_string())?,
+        decimals.ok_or("icrc1:decimals not found in metadata".to_string())?,
+    ))
+}
+
+/// Deterministic deposit subaccount for a user: the principal bytes prefixed with their length.
+pub fn deposit_subaccount(user: &Principal) -> Subaccount {
+    let bytes = user.as_slice();
+    let mut subaccount = [0u8; 32];
+    subaccount[0] = bytes.len() as u8;
+    subaccount[1..1 + bytes.len()].copy_from_slice(bytes);
+    subaccount
+}
+
 // return block index
 pub async fn transfer(ledger: Principal, to: Account, amount: Nat) -> Result<String, String> {
     let res: Result<Nat, TransferFromError> = call(
@@ -37,6 +81,57 @@ pub async fn transfer(ledger: Principal, to: Account, amount: Nat) -> Result<Str
     Ok(res.0.to_string())
 }
 
+// return block index
+pub async fn sweep(
+    ledger: Principal,
+    from_subaccount: Subaccount,
+    to: Account,
+    amount: Nat,
+) -> Result<String, String> {
+    let res: Result<Nat, TransferFromError> = call(
+        ledger,
+        "icrc1_transfer",
+        (TransferArg {
+            from_subaccount: Some(from_subaccount),
+            to,
+            fee: None,
+            created_at_time: None,
+            memo: None,
+            amount,
+        },),
+        0,
+    )
+    .await?;
+    let res = res.map_err(|err| format!("ICP: failed to sweep deposit, error: {:?}", err))?;
+    Ok(res.0.to_string())
+}
+
+// return block index
+pub async fn transfer_from(
+    ledger: Principal,
+    from: Account,
+    to: Account,
+    amount: Nat,
+) -> Result<String, String> {
+    let res: Result<Nat, TransferFromError> = call(
+        ledger,
+        "icrc2_transfer_from",
+        (TransferFromArgs {
+            spender_subaccount: None,
+            from,
+            to,
+            amount,
+            fee: None,
+            memo: None,
+            created_at_time: None,
+        },),
+        0,
+    )
+    .await?;
+    let res = res.map_err(|err| format!("ICP: failed to transfer token from, error: {:?}", err))?;
+    Ok(res.0.to_string())
+}
+
 pub async fn approve(ledger: Principal, spender: Account, amount: Nat) -> Result<String, String> {
     let res: Result<Nat, ApproveError> = call(
         ledger,
@@ -58,37 +153,129 @@ pub async fn approve(ledger: Principal, spender: Account, amount: Nat) -> Result
     Ok(res.0.to_string())
 }
 
+/// Returns true if a block account (ICRC-1 text or ICP AccountIdentifier hex) refers to `account`.
+pub fn account_matches(block_account: &str, account: &Account) -> bool {
+    block_account == account.to_string()
+        || block_account == ledger::hex(&ledger::account_identifier(account))
+}
+
 pub async fn verify_transfer_token(
     ledger: Principal,
     block_index: u64,
+    memo: Option<&[u8]>,
 ) -> Result<TransferChecked, String> {
+    if ledger == ledger::canister() {
+        return ledger::verify_transfer_token(ledger, block_index, memo).await;
+    }
+
     let req = GetBlocksRequest {
         start: block_index.into(),
         length: 1u64.into(),
     };
 
     let res: GetBlocksResult = call(ledger, "icrc3_get_blocks", (vec![req],), 0).await?;
+    let index = Nat::from(block_index);
+    let block = match res.blocks.into_iter().find(|b| b.id == index) {
+        Some(block) => block,
+        None => {
+            // the block has been moved to an archive canister
+            let archive = res
+                .archived_blocks
+                .into_iter()
+                .find(|a| {
+                    a.args
+                        .iter()
+                        .any(|r| r.start <= index && index < r.start.clone() + r.length.clone())
+                })
+                .ok_or("Block not found")?;
+            let res: GetBlocksResult = call(
+                archive.callback.canister_id,
+                &archive.callback.method,
+                (archive.args,),
+                0,
+            )
+            .await?;
+            res.blocks
+                .into_iter()
+                .find(|b| b.id == index)
+                .ok_or("Block not found")?
+        }
+    };
 
-    let block = res.blocks.first().ok_or("Block not found")?;
+    check_block(ledger, &block.block, memo)
+}
 
-    // Check fee
-    let fee = match &block.block {
-        ICRC3Value::Map(map) => match map.get("fee") {
-            Some(ICRC3Value::Nat(n)) => n.0.to_u128().ok_or("Fee too large")?,
-            Some(ICRC3Value::Int(i)) => i.0.to_u128().ok_or("Fee too large")?,
-            _ => return Err("Block missing fee field or invalid format".to_string()),
-        },
-        _ => return Err("Invalid block format".to_string()),
+/// Returns the ledger's log length, the index after the blocks returned from `start`
+/// and the transfers found in them, including the archived ones. Ledgers may return
+/// fewer than `length` blocks.
+pub async fn get_transfers(
+    ledger: Principal,
+    start: u64,
+    length: u64,
+) -> Result<(u64, u64, Vec<(u64, TransferChecked)>), String> {
+    if ledger == ledger::canister() {
+        return ledger::get_transfers(ledger, start, length).await;
+    }
+
+    let req = GetBlocksRequest {
+        start: start.into(),
+        length: length.into(),
     };
+    let res: GetBlocksResult = call(ledger, "icrc3_get_blocks", (vec![req],), 0).await?;
+    let log_length = res.log_length.0.to_u64().ok_or("Log length too large")?;
+    let mut blocks = Vec::new();
+    for archive in res.archived_blocks {
+        let archived: GetBlocksResult = call(
+            archive.callback.canister_id,
+            &archive.callback.method,
+            (archive.args,),
+            0,
+        )
+        .await?;
+        blocks.extend(archived.blocks);
+    }
+    blocks.extend(res.blocks);
+
+    let mut ids = Vec::with_capacity(blocks.len());
+    let mut transfers = Vec::with_capacity(blocks.len());
+    for block in blocks {
+        let id = block.id.0.to_u64().ok_or("Block index too large")?;
+        ids.push(id);
+        // only transfers are of interest
+        if let Ok(transfer) = check_block(ledger, &block.block, None) {
+            transfers.push((id, transfer));
+        }
+    }
+    transfers.sort_by_key(|(id, _)| *id);
+    let next = next_block_index(start, ids.into_iter());
+    Ok((log_length, next, transfers))
+}
 
-    let tx_map = match &block.block {
-        ICRC3Value::Map(map) => match map.get("tx") {
-            Some(ICRC3Value::Map(tx)) => tx,
-            _ => return Err("Block missing tx field or invalid format".to_string()),
-        },
+// Returns the index after the contiguous blocks from `start`.
+fn next_block_index(start: u64, ids: impl Iterator<Item = u64>) -> u64 {
+    let ids: BTreeSet<u64> = ids.collect();
+    let mut next = start;
+    while ids.contains(&next) {
+        next += 1;
+    }
+    next
+}
+
+fn check_block(
+    ledger: Principal,
+    block: &ICRC3Value,
+    memo: Option<&[u8]>,
+) -> Result<TransferChecked, String> {
+    let block_map = match block {
+        ICRC3Value::Map(map) => map,
         _ => return Err("Invalid block format".to_string()),
     };
 
+    let tx_map = match block_map.get("tx") {
+        Some(ICRC3Value::Map(tx)) => tx,
+        _ => return Err("Block missing tx field or invalid format".to_string()),
+    };
+
     // Check op
     match tx_map.get("op") {
         Some(ICRC3Value::Text(op)) if op == "xfer" => {}
@@ -96,6 +283,22 @@ pub async fn verify_transfer_token(
         _ => return Err("Missing or invalid op field".to_string()),
     }
 
+    // Check fee: `tx.fee` if set by the caller, otherwise the effective top-level fee
+    let fee = match tx_map.get("fee").or_else(|| block_map.get("fee")) {
+        Some(ICRC3Value::Nat(n)) => n.0.to_u128().ok_or("Fee too large")?,
+        Some(ICRC3Value::Int(i)) => i.0.to_u128().ok_or("Fee too large")?,
+        Some(_) => return Err("Invalid fee format".to_string()),
+        None => 0,
+    };
+
+    // Check memo
+    if let Some(memo) = memo {
+        match tx_map.get("memo") {
+            Some(ICRC3Value::Blob(m)) if m.as_slice() == memo => {}
+            _ => return Err("transaction memo does not match".to_string()),
+        }
+    }
+
     // Check from
     let from_val = tx_map.get("from").ok_or("Missing from field")?;
     let from_acc = Account::try_from(Value::from(from_val.to_owned()))?;
@@ -118,3 +321,92 @@ pub async fn verify_transfer_token(
         amount: amount.saturating_sub(fee),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_bytes::ByteBuf;
+    use std::collections::BTreeMap;
+
+    fn xfer_block(tx_fee: Option<u64>, fee: Option<u64>, memo: Option<&[u8]>) -> ICRC3Value {
+        let account =
+            |p: Principal| ICRC3Value::Array(vec![ICRC3Value::Blob(ByteBuf::from(p.as_slice()))]);
+        let mut tx = BTreeMap::from([
+            ("op".to_string(), ICRC3Value::Text("xfer".to_string())),
+            ("from".to_string(), account(Principal::anonymous())),
+            ("to".to_string(), account(Principal::management_canister())),
+            ("amt".to_string(), ICRC3Value::Nat(1_000u64.into())),
+        ]);
+        if let Some(f) = tx_fee {
+            tx.insert("fee".to_string(), ICRC3Value::Nat(f.into()));
+        }
+        if let Some(m) = memo {
+            tx.insert("memo".to_string(), ICRC3Value::Blob(ByteBuf::from(m)));
+        }
+        let mut block = BTreeMap::from([("tx".to_string(), ICRC3Value::Map(tx))]);
+        if let Some(f) = fee {
+            block.insert("fee".to_string(), ICRC3Value::Nat(f.into()));
+        }
+        ICRC3Value::Map(block)
+    }
+
+    #[test]
+    fn test_check_block() {
+        let ledger = Principal::management_canister();
+        let rt = check_block(ledger, &xfer_block(None, Some(10), None), None).unwrap();
+        assert_eq!(rt.from, Principal::anonymous().to_string());
+        assert_eq!(rt.to, Principal::management_canister().to_string());
+        assert_eq!(rt.amount, 990);
+
+        let rt = check_block(ledger, &xfer_block(Some(20), None, None), None).unwrap();
+        assert_eq!(rt.amount, 980);
+
+        let rt = check_block(ledger, &xfer_block(None, None, None), None).unwrap();
+        assert_eq!(rt.amount, 1_000);
+
+        let block = xfer_block(None, Some(10), Some(b"order-1"));
+        assert!(check_block(ledger, &block, Some(b"order-1")).is_ok());
+        assert!(check_block(ledger, &block, Some(b"order-2")).is_err());
+        assert!(check_block(ledger, &xfer_block(None, Some(10), None), Some(b"order-1")).is_err());
+    }
+
+    #[test]
+    fn test_account_matches() {
+        let account = Account::from(Principal::anonymous());
+        assert!(account_matches(&account.to_string(), &account));
+        assert!(account_matches(
+            "1c7a48ba6a562aa9eaa2481a9049cdf0433b9738c992d698c31d8abf89cadc79",
+            &account
+        ));
+        assert!(!account_matches(
+            &Principal::management_canister().to_string(),
+            &account
+        ));
+    }
+
+    #[test]
+    fn test_parse_token_metadata() {
+        let metadata = vec![
+            MetadataValue::entry("icrc1:name", "Internet Computer"),
+            MetadataValue::entry("icrc1:symbol", "ICP"),
+            MetadataValue::entry("icrc1:decimals", 8u64),
+            MetadataValue::entry("icrc1:fee", 10_000u64),
+        ];
+        assert_eq!(
+            parse_token_metadata(&metadata).unwrap(),
+            ("ICP".to_string(), 8)
+        );
+        assert!(parse_token_metadata(&metadata[0..2]).is_err());
+        assert!(parse_token_metadata(&[MetadataValue::entry("icrc1:decimals", 256u64)]).is_err());
+    }
+
+    #[test]
+    fn test_next_block_index() {
+        assert_eq!(next_block_index(10, [].into_iter()), 10);
+        assert_eq!(next_block_index(10, [10, 11, 12].into_iter()), 13);
+        assert_eq!(next_block_index(10, [12, 10, 11].into_iter()), 13);
+        // a gap stops at the missing block
+        assert_eq!(next_block_index(10, [10, 12].into_iter()), 11);
+        assert_eq!(next_block_index(10, [11, 12].into_iter()), 10);
+    }
+}
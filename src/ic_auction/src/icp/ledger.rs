@@ -0,0 +1,305 @@
+use candid::{CandidType, Principal, types::reference::Func};
+use icrc_ledger_types::icrc1::account::Account;
+use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+use sha2::{Digest, Sha224};
+
+use crate::{helper::call, types::TransferChecked};
+
+// https://github.com/dfinity/ic/blob/master/rs/ledger_suite/icp/ledger.did
+
+pub fn canister() -> Principal {
+    Principal::from_text("ryjl3-tyaaa-aaaaa-aaaba-cai").unwrap()
+}
+
+/// ICP ledger AccountIdentifier: crc32 checksum followed by
+/// sha224("\x0Aaccount-id" || owner || subaccount).
+pub fn account_identifier(account: &Account) -> [u8; 32] {
+    let mut hasher = Sha224::new();
+    hasher.update(b"\x0Aaccount-id");
+    hasher.update(account.owner.as_slice());
+    hasher.update(account.effective_subaccount());
+    let hash: [u8; 28] = hasher.finalize().into();
+
+    let mut id = [0u8; 32];
+    id[0..4].copy_from_slice(&crc32fast::hash(&hash).to_be_bytes());
+    id[4..].copy_from_slice(&hash);
+    id
+}
+
+pub async fn verify_transfer_token(
+    ledger: Principal,
+    block_index: u64,
+    memo: Option<&[u8]>,
+) -> Result<TransferChecked, String> {
+    let args = GetBlocksArgs {
+        start: block_index,
+        length: 1,
+    };
+    let res: QueryBlocksResponse = call(ledger, "query_blocks", (args.clone(),), 0).await?;
+
+    let block = if let Some(block) = res.blocks.into_iter().next() {
+        block
+    } else {
+        let archive = res
+            .archived_blocks
+            .into_iter()
+            .find(|r| r.start <= block_index && block_index < r.start + r.length)
+            .ok_or("Block not found")?;
+        let res: Result<BlockRange, GetBlocksError> = call(
+            archive.callback.principal,
+            &archive.callback.method,
+            (args,),
+            0,
+        )
+        .await?;
+        let res = res.map_err(|err| format!("ICP: failed to query archive, error: {:?}", err))?;
+        res.blocks.into_iter().next().ok_or("Block not found")?
+    };
+
+    check_block(ledger, block, memo)
+}
+
+/// Returns the ledger's chain length and the transfers found in the blocks
+/// [start, start + length), including the archived ones.
+pub async fn get_transfers(
+    ledger: Principal,
+    start: u64,
+    length: u64,
+) -> Result<(u64, u64, Vec<(u64, TransferChecked)>), String> {
+    let res: QueryBlocksResponse = call(
+        ledger,
+        "query_blocks",
+        (GetBlocksArgs { start, length },),
+        0,
+    )
+    .await?;
+
+    let mut blocks: Vec<(u64, CandidBlock)> = Vec::new();
+    for archive in res.archived_blocks {
+        let args = GetBlocksArgs {
+            start: archive.start,
+            length: archive.length,
+        };
+        let range: Result<BlockRange, GetBlocksError> = call(
+            archive.callback.principal,
+            &archive.callback.method,
+            (args,),
+            0,
+        )
+        .await?;
+        let range =
+            range.map_err(|err| format!("ICP: failed to query archive, error: {:?}", err))?;
+        blocks.extend(
+            range
+                .blocks
+                .into_iter()
+                .enumerate()
+                .map(|(i, b)| (archive.start + i as u64, b)),
+        );
+    }
+    blocks.extend(
+        res.blocks
+            .into_iter()
+            .enumerate()
+            .map(|(i, b)| (res.first_block_index + i as u64, b)),
+    );
+
+    let next = super::next_block_index(start, blocks.iter().map(|(id, _)| *id));
+    let transfers = blocks
+        .into_iter()
+        .filter_map(|(id, block)| check_block(ledger, block, None).ok().map(|t| (id, t)))
+        .collect();
+    Ok((res.chain_length, next, transfers))
+}
+
+fn check_block(
+    ledger: Principal,
+    block: CandidBlock,
+    memo: Option<&[u8]>,
+) -> Result<TransferChecked, String> {
+    if let Some(memo) = memo {
+        let matched = match &block.transaction.icrc1_memo {
+            Some(m) => m.as_slice() == memo,
+            None => memo.len() == 8 && block.transaction.memo.to_be_bytes() == memo,
+        };
+        if !matched {
+            return Err("transaction memo does not match".to_string());
+        }
+    }
+
+    let (from, to, amount, fee) = match block.transaction.operation {
+        Some(Operation::Transfer {
+            from,
+            to,
+            amount,
+            fee,
+        })
+        | Some(Operation::TransferFrom {
+            from,
+            to,
+            amount,
+            fee,
+        }) => (from, to, amount, fee),
+        Some(op) => return Err(format!("Invalid operation: {:?}", op)),
+        None => return Err("Missing operation".to_string()),
+    };
+
+    Ok(TransferChecked {
+        token: ledger.to_string(),
+        from: hex(&from),
+        to: hex(&to),
+        amount: (amount.e8s as u128).saturating_sub(fee.e8s as u128),
+    })
+}
+
+pub fn hex(data: &[u8]) -> String {
+    alloy_primitives::hex::encode(data)
+}
+
+#[derive(CandidType, Debug, Clone, Serialize, Deserialize)]
+pub struct GetBlocksArgs {
+    pub start: u64,
+    pub length: u64,
+}
+
+#[derive(CandidType, Debug, Clone, Deserialize)]
+pub struct QueryBlocksResponse {
+    pub chain_length: u64,
+    pub first_block_index: u64,
+    pub blocks: Vec<CandidBlock>,
+    pub archived_blocks: Vec<ArchivedBlocksRange>,
+}
+
+#[derive(CandidType, Debug, Clone, Deserialize)]
+pub struct ArchivedBlocksRange {
+    pub start: u64,
+    pub length: u64,
+    pub callback: Func,
+}
+
+#[derive(CandidType, Debug, Clone, Serialize, Deserialize)]
+pub struct BlockRange {
+    pub blocks: Vec<CandidBlock>,
+}
+
+#[derive(CandidType, Debug, Clone, Serialize, Deserialize)]
+pub enum GetBlocksError {
+    BadFirstBlockIndex {
+        requested_index: u64,
+        first_valid_index: u64,
+    },
+    Other {
+        error_code: u64,
+        error_message: String,
+    },
+}
+
+#[derive(CandidType, Debug, Clone, Serialize, Deserialize)]
+pub struct CandidBlock {
+    pub transaction: Transaction,
+    pub timestamp: TimeStamp,
+}
+
+#[derive(CandidType, Debug, Clone, Serialize, Deserialize)]
+pub struct Transaction {
+    pub memo: u64,
+    pub icrc1_memo: Option<ByteBuf>,
+    pub operation: Option<Operation>,
+    pub created_at_time: TimeStamp,
+}
+
+#[derive(CandidType, Debug, Clone, Serialize, Deserialize)]
+pub struct TimeStamp {
+    pub timestamp_nanos: u64,
+}
+
+#[derive(CandidType, Debug, Clone, Serialize, Deserialize)]
+pub struct Tokens {
+    pub e8s: u64,
+}
+
+// Only the fields needed for deposit verification are decoded.
+#[derive(CandidType, Debug, Clone, Serialize, Deserialize)]
+pub enum Operation {
+    Mint {
+        to: ByteBuf,
+    },
+    Burn {
+        from: ByteBuf,
+    },
+    Approve {
+        from: ByteBuf,
+    },
+    Transfer {
+        from: ByteBuf,
+        to: ByteBuf,
+        amount: Tokens,
+        fee: Tokens,
+    },
+    TransferFrom {
+        from: ByteBuf,
+        to: ByteBuf,
+        amount: Tokens,
+        fee: Tokens,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfer_block(memo: u64, icrc1_memo: Option<Vec<u8>>) -> CandidBlock {
+        CandidBlock {
+            transaction: Transaction {
+                memo,
+                icrc1_memo: icrc1_memo.map(ByteBuf::from),
+                operation: Some(Operation::Transfer {
+                    from: ByteBuf::from(vec![1u8; 32]),
+                    to: ByteBuf::from(vec![2u8; 32]),
+                    amount: Tokens { e8s: 100_010_000 },
+                    fee: Tokens { e8s: 10_000 },
+                }),
+                created_at_time: TimeStamp { timestamp_nanos: 0 },
+            },
+            timestamp: TimeStamp { timestamp_nanos: 0 },
+        }
+    }
+
+    #[test]
+    fn test_account_identifier() {
+        let id = account_identifier(&Account::from(Principal::anonymous()));
+        assert_eq!(
+            hex(&id),
+            "1c7a48ba6a562aa9eaa2481a9049cdf0433b9738c992d698c31d8abf89cadc79"
+        );
+    }
+
+    #[test]
+    fn test_check_block() {
+        let ledger = canister();
+        let rt = check_block(ledger, transfer_block(42, None), None).unwrap();
+        assert_eq!(rt.from, hex(&[1u8; 32]));
+        assert_eq!(rt.to, hex(&[2u8; 32]));
+        assert_eq!(rt.amount, 100_000_000);
+
+        assert!(check_block(ledger, transfer_block(42, None), Some(&42u64.to_be_bytes())).is_ok());
+        assert!(check_block(ledger, transfer_block(42, None), Some(&43u64.to_be_bytes())).is_err());
+        assert!(
+            check_block(
+                ledger,
+                transfer_block(0, Some(b"abc".to_vec())),
+                Some(b"abc")
+            )
+            .is_ok()
+        );
+        assert!(
+            check_block(
+                ledger,
+                transfer_block(0, Some(b"abc".to_vec())),
+                Some(b"abd")
+            )
+            .is_err()
+        );
+    }
+}
@@ -1,6 +1,8 @@
 use alloy_consensus::{SignableTransaction, Signed, TxEip1559};
 use alloy_eips::eip2718::Encodable2718;
-use alloy_primitives::{Address, Bytes, Signature, TxHash, U256, hex};
+use alloy_primitives::{Address, B256, Bytes, Signature, TxHash, U256, hex};
+use alloy_rpc_types_eth::Filter;
+use alloy_sol_types::SolEvent;
 use candid::{CandidType, Principal};
 use ciborium::{from_reader, into_writer};
 use ic_auth_types::ByteArrayB64;
@@ -15,6 +17,7 @@ use ic_stable_structures::{
 };
 use icrc_ledger_types::icrc1::account::Account;
 use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
 use std::{
     borrow::Cow,
     cell::RefCell,
@@ -25,19 +28,30 @@ use std::{
 use crate::{
     cca,
     ecdsa::{derive_public_key, ecdsa_public_key, sign_with_ecdsa},
-    evm::{EvmClient, encode_erc20_transfer},
+    evm::{
+        EvmClient, EvmTxKind, LogCursor, LogScan, NONCE_SYNC_INTERVAL_MS, NonceManager,
+        abi::{self, IERC20},
+        eip1559_fees, encode_erc20_transfer, encode_erc20_transfer_with_authorization,
+        is_tx_rejection, priority_fee_from_history, recover_transfer_authorization_signer,
+    },
     helper::format_error,
     icp,
     outcall::DefaultHttpOutcall,
     schnorr::{derive_schnorr_public_key, schnorr_public_key, sign_with_schnorr},
     svm::{
-        Message, Pubkey, SvmClient, Transaction, create_associated_token_account_idempotent,
-        get_associated_token_address, get_transfer_checked, instruction, raydium,
-        transfer_checked_instruction,
+        Instruction, MAX_COMPUTE_UNIT_LIMIT, Pubkey, SvmClient, VersionedTransaction,
+        add_transfer_hook_accounts, batch_transfer_checked_instructions, compile_message,
+        compute_unit_limit, compute_unit_price,
+        constants::{spl_token, spl_token_2022},
+        create_associated_token_account_idempotent, get_associated_token_address,
+        get_extra_account_metas_address, get_transfer_checked, instruction, raydium,
+        signed_transaction, transfer_checked_instruction, unsigned_transaction,
+        with_compute_budget, writable_accounts,
     },
     types::{
-        AuctionConfig, AuctionInfo, AuctionSnapshot, BidInfo, Chain, DepositTxInfo, FinalizeKind,
-        FinalizeOutput, PublicKeyOutput, StateInfo, TransferChecked, UserInfo, WithdrawTxInfo,
+        AuctionConfig, AuctionInfo, AuctionSnapshot, BidInfo, Chain, DepositTxInfo, Eip3009Input,
+        EvmFeeConfig, FinalizeKind, FinalizeOutput, PublicKeyOutput, SolFeeConfig, StateInfo,
+        TokenInput, TransferChecked, UserInfo, WithdrawTxInfo, X402Network,
     },
 };
 
@@ -90,13 +104,40 @@ pub struct State {
     pub total_deposited_currency: u128,
     pub total_withdrawn_currency: u128,
     pub total_withdrawn_token: u128,
-    // (gas_updated_at, gas_price, max_priority_fee_per_gas)
-    pub evm_latest_gas: (u64, u128, u128),
     pub auction_config: Option<AuctionConfig>,
     pub auction: Option<cca::Auction>,
     pub finalize_output: Option<FinalizeOutput>,
     #[serde(default)]
     pub payment_requirements_extra: Option<String>,
+    #[serde(default)]
+    pub evm_nonce: NonceManager,
+    // (fees_updated_at, base_fee_per_gas, priority_fee_per_gas), replaces evm_latest_gas of
+    // earlier versions, which held the gas price and is ignored when decoding
+    #[serde(default)]
+    pub evm_latest_fees: (u64, u128, u128),
+    #[serde(default)]
+    pub evm_fee_config: EvmFeeConfig,
+    #[serde(default)]
+    pub sol_fee_config: SolFeeConfig,
+    // Address lookup tables for v0 transactions
+    #[serde(default)]
+    pub sol_lookup_tables: Vec<Pubkey>,
+    // Unconfirmed sweeps from the users' derived deposit addresses (EVM and Solana)
+    #[serde(default)]
+    pub pending_sweeps: HashMap<Principal, PendingSweep>,
+    // EIP-3009 deposits by txid, the holder is bound to the user once the transfer is
+    // confirmed on-chain
+    #[serde(default)]
+    pub pending_authorizations: HashMap<String, PendingAuthorization>,
+    // Deposit addresses owned by the canister, the scanner never credits transfers from them
+    #[serde(default)]
+    pub swept_deposit_addresses: BTreeSet<String>,
+    // Nonces of the accepted x402 results, until they expire
+    #[serde(default)]
+    pub x402_replay_guard: x402::ReplayGuard,
+    // Admin configured x402 networks, overriding the chains' default networks
+    #[serde(default)]
+    pub x402_networks: Vec<X402Network>,
 }
 
 impl From<&State> for StateInfo {
@@ -137,12 +178,16 @@ impl From<&State> for StateInfo {
             finalize_output: s.finalize_output.clone(),
             auction_config: s.auction_config.clone(),
             payment_requirements_extra: s.payment_requirements_extra.clone(),
+            evm_fee_config: s.evm_fee_config.clone(),
+            sol_fee_config: s.sol_fee_config.clone(),
+            sol_lookup_tables: s.sol_lookup_tables.iter().map(|k| k.to_string()).collect(),
+            x402_networks: s.x402_networks.clone(),
         }
     }
 }
 
 impl State {
-    fn new() -> Self {
+    fn new(canister: Principal) -> Self {
         Self {
             chain: Chain::Icp(0),
             name: "".to_string(),
@@ -167,7 +212,7 @@ impl State {
             finalize_kind: FinalizeKind::Transfer,
             governance_canister: None,
             key_name: "dfx_test_key".to_string(),
-            icp_address: ic_cdk::api::canister_self(),
+            icp_address: canister,
             evm_address: Address::default(),
             sol_address: Pubkey::default(), // 11111111111111111111111111111111
             chain_providers: Vec::new(),
@@ -176,15 +221,39 @@ impl State {
             nonce_iv: ByteArrayB64::default(),
             paying_public_keys: Vec::new(),
             pending_deposits: HashMap::new(),
+            pending_sweeps: HashMap::new(),
+            pending_authorizations: HashMap::new(),
+            swept_deposit_addresses: BTreeSet::new(),
+            x402_replay_guard: x402::ReplayGuard::default(),
+            x402_networks: Vec::new(),
             snapshots: Vec::new(),
             total_deposited_currency: 0,
             total_withdrawn_currency: 0,
             total_withdrawn_token: 0,
-            evm_latest_gas: (0, 0, 0),
             finalize_output: None,
             auction_config: None,
             auction: None,
             payment_requirements_extra: None,
+            evm_nonce: NonceManager::default(),
+            evm_latest_fees: (0, 0, 0),
+            evm_fee_config: EvmFeeConfig::default(),
+            sol_fee_config: SolFeeConfig::default(),
+            sol_lookup_tables: Vec::new(),
+        }
+    }
+
+    /// Returns the x402 network of the auction's chain and the facilitator keys for it.
+    pub fn x402_network(&self) -> Result<(String, &[ByteArrayB64<32>]), String> {
+        match self.x402_networks.iter().find(|n| n.chain == self.chain) {
+            Some(n) if n.paying_public_keys.is_empty() => {
+                Ok((n.network.clone(), &self.paying_public_keys))
+            }
+            Some(n) => Ok((n.network.clone(), &n.paying_public_keys)),
+            None => self
+                .chain
+                .default_x402_network()
+                .map(|network| (network, self.paying_public_keys.as_slice()))
+                .ok_or_else(|| format!("{:?} is not supported yet", self.chain)),
         }
     }
 }
@@ -275,6 +344,9 @@ pub struct WithdrawTx {
     pub txid: String,
     #[serde(rename = "t")]
     pub timestamp: u64,
+    // Token-2022 transfer fee withheld from `amount`
+    #[serde(default, rename = "f")]
+    pub fee: u128,
 }
 
 // todo
@@ -286,6 +358,7 @@ impl WithdrawTx {
             user: self.user,
             recipient: self.recipient,
             amount: self.amount,
+            fee: self.fee,
             txid: self.txid,
             timestamp: self.timestamp,
         }
@@ -318,9 +391,14 @@ const DEPOSITS_MEMORY_ID: MemoryId = MemoryId::new(2);
 const BIDS_MEMORY_ID: MemoryId = MemoryId::new(3);
 const WITHDRAWS_INDEX_MEMORY_ID: MemoryId = MemoryId::new(4);
 const WITHDRAWS_DATA_MEMORY_ID: MemoryId = MemoryId::new(5);
+const DEPOSIT_CURSOR_MEMORY_ID: MemoryId = MemoryId::new(6);
 
 thread_local! {
-    static STATE: RefCell<State> = RefCell::new(State::new());
+    #[cfg(not(test))]
+    static STATE: RefCell<State> = RefCell::new(State::new(ic_cdk::api::canister_self()));
+    // native tests can not call the system API
+    #[cfg(test)]
+    static STATE: RefCell<State> = RefCell::new(State::new(Principal::anonymous()));
     static HTTP_TREE: RefCell<HttpCertificationTree> = RefCell::new(HttpCertificationTree::default());
 
     static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
@@ -357,6 +435,13 @@ thread_local! {
             MEMORY_MANAGER.with_borrow(|m| m.get(WITHDRAWS_DATA_MEMORY_ID)),
         )
     );
+
+    static DEPOSIT_CURSOR: RefCell<StableCell<Vec<u8>, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with_borrow(|m| m.get(DEPOSIT_CURSOR_MEMORY_ID)),
+            Vec::new()
+        )
+    );
 }
 
 struct BidStorage;
@@ -377,6 +462,113 @@ static BS: BidStorage = BidStorage;
 static SOL_ADDRESS: &str = "So11111111111111111111111111111111111111111";
 // Wrapping SOL: So11111111111111111111111111111111111111112
 
+// An unconfirmed sweep can be retried after this timeout
+const SWEEP_TIMEOUT_MS: u64 = 10 * 60 * 1000;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PendingSweep {
+    // The sweep transaction and its replacements
+    #[serde(rename = "t")]
+    pub txids: Vec<String>,
+    #[serde(rename = "a")]
+    pub amount: u128,
+    // Nonce of the sweep transaction (EVM only)
+    #[serde(rename = "n")]
+    pub nonce: u64,
+    // Whether it is a gas top-up of the deposit address (EVM only)
+    #[serde(rename = "g")]
+    pub gas: bool,
+    #[serde(rename = "ts")]
+    pub timestamp: u64,
+}
+
+// A deposit mined before its authorization expired is scanned within this time
+const AUTHORIZATION_SCAN_MS: u64 = 60 * 60 * 1000;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PendingAuthorization {
+    #[serde(rename = "u")]
+    pub user: Principal,
+    // The holder that signed the authorization
+    #[serde(rename = "f")]
+    pub from: String,
+    // In seconds
+    #[serde(rename = "vb")]
+    pub valid_before: u64,
+}
+
+#[derive(Clone, Copy)]
+enum EvmSender {
+    // The canister address, with an optional nonce
+    Canister(Option<u64>),
+    // The user's derived deposit address, with its nonce
+    Deposit(Principal, u64),
+}
+
+const DEPOSIT_SCAN_INTERVAL_MS: u64 = 60 * 1000;
+// Max ICRC ledger blocks per scan
+const ICP_SCAN_BLOCKS: u64 = 100;
+// Max EVM blocks per eth_getLogs page and pages per scan, and blocks to wait for
+// before scanning
+const EVM_SCAN_BLOCKS: u64 = 500;
+const EVM_SCAN_PAGES: u32 = 4;
+const EVM_SCAN_CONFIRMATIONS: u64 = 6;
+// Max Solana signatures per scan
+const SOL_SCAN_TXS: usize = 20;
+// Max transactions of a Solana payout round, and recipients considered for them
+const PAYOUT_BATCHES: usize = 4;
+const PAYOUT_RECIPIENTS: usize = 200;
+
+/// Position of the deposit scanner on the auction chain, persisted in its own stable cell.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct DepositCursor {
+    // Next ICRC ledger block index or EVM block number to scan
+    #[serde(rename = "n")]
+    pub next: Option<u64>,
+    // Hash of the EVM block before `next`, to detect reorgs
+    #[serde(rename = "h")]
+    pub block_hash: Option<B256>,
+    // Newest scanned Solana signature
+    #[serde(rename = "s")]
+    pub signature: Option<String>,
+    // Oldest Solana signature of the pass in progress, which scans back from the newest
+    // signature at its start (`pass_top`) to `signature`
+    #[serde(default, rename = "b")]
+    pub before: Option<String>,
+    #[serde(default, rename = "p")]
+    pub pass_top: Option<String>,
+    // EVM transfers in the blocks a reorg may rewind: (block number, txid)
+    #[serde(default, rename = "r")]
+    pub evm_recent: Vec<(u64, String)>,
+    #[serde(rename = "t")]
+    pub updated_at: u64,
+}
+
+impl DepositCursor {
+    fn load() -> Self {
+        DEPOSIT_CURSOR.with_borrow(|r| {
+            let bytes = r.get();
+            if bytes.is_empty() {
+                return Self::default();
+            }
+            from_reader(&bytes[..]).expect("failed to decode DEPOSIT_CURSOR data")
+        })
+    }
+
+    fn save(&self) {
+        DEPOSIT_CURSOR.with_borrow_mut(|r| {
+            let mut buf = vec![];
+            into_writer(self, &mut buf).expect("failed to encode DEPOSIT_CURSOR data");
+            r.set(buf);
+        });
+    }
+}
+
+// Derivation path of a user's deposit address on EVM and Solana.
+fn deposit_derivation_path(icp_address: &Principal, user: &Principal) -> Vec<Vec<u8>> {
+    vec![icp_address.as_slice().to_vec(), user.as_slice().to_vec()]
+}
+
 pub mod state {
 
     use super::*;
@@ -511,6 +703,99 @@ pub mod state {
         }
     }
 
+    pub fn try_set_evm_nonce_timer() {
+        if !STATE.with_borrow(|s| matches!(s.chain, Chain::Evm(_))) {
+            return;
+        }
+
+        ic_cdk_timers::set_timer_interval_serial(
+            std::time::Duration::from_millis(NONCE_SYNC_INTERVAL_MS),
+            async || {
+                let now_ms = ic_cdk::api::time() / 1_000_000;
+                if let Err(err) = reconcile_evm_nonce(now_ms).await {
+                    ic_cdk::api::debug_print(format!("failed to reconcile EVM nonce: {err}"));
+                }
+            },
+        );
+    }
+
+    pub fn set_deposit_scanner_timer() {
+        ic_cdk_timers::set_timer_interval_serial(
+            std::time::Duration::from_millis(DEPOSIT_SCAN_INTERVAL_MS),
+            async || {
+                let now_ms = ic_cdk::api::time() / 1_000_000;
+                if let Err(err) = scan_deposits(now_ms).await {
+                    ic_cdk::api::debug_print(format!("failed to scan deposits: {err}"));
+                }
+            },
+        );
+    }
+
+    /// Checks the decimals, symbol and program ID of a token or currency against the
+    /// chain, and refuses Token-2022 mints with unsupported extensions.
+    pub async fn check_token(input: &TokenInput, now_ms: u64) -> Result<(), String> {
+        let chain = STATE.with_borrow(|s| s.chain.clone());
+        let (symbol, decimals) = match chain {
+            Chain::Icp(_) => {
+                let ledger = Principal::from_text(&input.token)
+                    .map_err(|_| format!("Invalid token principal: {}", input.token))?;
+                let (symbol, decimals) = icp::token_metadata(ledger).await?;
+                (Some(symbol), decimals)
+            }
+            Chain::Evm(_) => {
+                let token = Address::from_str(&input.token)
+                    .map_err(|_| "Invalid token contract address".to_string())?;
+                let client = evm_client();
+                let (symbol, decimals) = futures::future::try_join(
+                    client.erc20_symbol(now_ms, &token),
+                    client.erc20_decimals(now_ms, &token),
+                )
+                .await?;
+                (Some(symbol), decimals)
+            }
+            Chain::Sol(_) if input.token == SOL_ADDRESS => (None, 9),
+            Chain::Sol(_) => {
+                let program_id = input
+                    .program_id
+                    .as_deref()
+                    .ok_or("program_id is required for Solana tokens".to_string())?;
+                let program_id = Pubkey::from_str(program_id)
+                    .map_err(|_| "Invalid token program ID".to_string())?;
+                if program_id != spl_token::ID && program_id != spl_token_2022::ID {
+                    return Err(format!("unsupported token program: {program_id}"));
+                }
+                let token = Pubkey::from_str(&input.token)
+                    .map_err(|_| "Invalid token address".to_string())?;
+                let mint = sol_client().get_mint_info(now_ms, &token).await?;
+                if mint.program_id != program_id {
+                    return Err(format!(
+                        "token is owned by {}, not {program_id}",
+                        mint.program_id
+                    ));
+                }
+                mint.check_supported()?;
+                // SPL symbols usually live in Metaplex metadata, only Token-2022 metadata is checked
+                (mint.symbol, mint.decimals)
+            }
+        };
+
+        if decimals != input.decimals {
+            return Err(format!(
+                "decimals mismatch: expected {decimals}, got {}",
+                input.decimals
+            ));
+        }
+        if let Some(symbol) = symbol
+            && symbol != input.symbol
+        {
+            return Err(format!(
+                "symbol mismatch: expected {symbol}, got {}",
+                input.symbol
+            ));
+        }
+        Ok(())
+    }
+
     pub async fn set_auction(cfg: AuctionConfig) -> Result<(), String> {
         STATE.with_borrow_mut(|s| {
             if s.auction.is_some() {
@@ -776,10 +1061,38 @@ pub mod state {
                 bound_addresses: user.bound_addresses.iter().cloned().collect(),
                 agreed_terms: user.agreed_terms,
                 timestamp: user.timestamp,
+                deposit_address: deposit_address(caller).ok(),
             })
         })
     }
 
+    pub fn deposit_address(caller: Principal) -> Result<String, String> {
+        STATE.with_borrow(|s| match s.chain {
+            Chain::Icp(_) => Ok(Account {
+                owner: s.icp_address,
+                subaccount: Some(icp::deposit_subaccount(&caller)),
+            }
+            .to_string()),
+            Chain::Evm(_) => {
+                let pk = derive_public_key(
+                    &s.ecdsa_public_key,
+                    deposit_derivation_path(&s.icp_address, &caller),
+                )
+                .map_err(|_e| "derive_public_key failed".to_string())?;
+                Ok(pk.to_evm_adress()?.to_string())
+            }
+            Chain::Sol(_) => {
+                let pk = derive_schnorr_public_key(
+                    &s.ed25519_public_key,
+                    deposit_derivation_path(&s.icp_address, &caller),
+                    None,
+                )
+                .map_err(|_e| "derive_schnorr_public_key failed".to_string())?;
+                Ok(pk.to_sol_pubkey()?.to_string())
+            }
+        })
+    }
+
     pub fn my_bids(caller: Principal) -> Result<Vec<BidInfo>, String> {
         USERS.with_borrow(|u| {
             let user = u.get(&caller).unwrap_or_default();
@@ -835,22 +1148,25 @@ pub mod state {
     }
 
     pub fn bind_address(caller: Principal, address: String, now_ms: u64) -> Result<(), String> {
-        STATE.with_borrow(|s| {
-            s.chain.parse_address(&address)?;
-            USERS.with_borrow_mut(|u| {
-                let mut info = u.get(&caller).unwrap_or_default();
-                if info.bound_addresses.insert(address) {
-                    if !info.agreed_terms {
-                        info.timestamp = now_ms;
-                        info.agreed_terms = true;
-                    }
-                    u.insert(caller, info);
+        STATE.with_borrow(|s| s.chain.parse_address(&address))?;
+        check_address_unbound(caller, &address)?;
+        USERS.with_borrow_mut(|u| {
+            let mut info = u.get(&caller).unwrap_or_default();
+            if info.bound_addresses.insert(address) {
+                if !info.agreed_terms {
+                    info.timestamp = now_ms;
+                    info.agreed_terms = true;
                 }
-                Ok(())
-            })
+                u.insert(caller, info);
+            }
+            Ok(())
         })
     }
 
+    pub fn is_deposited(txid: &str) -> bool {
+        DEPOSITS.with_borrow(|d| d.contains_key(&txid.to_string()))
+    }
+
     pub async fn x402_deposit_currency(
         caller: Principal,
         sender: String,
@@ -858,6 +1174,7 @@ pub mod state {
         amount: u128,
         now_ms: u64,
     ) -> Result<u128, String> {
+        let txid = STATE.with_borrow(|s| s.chain.parse_txid(&txid))?;
         DEPOSITS.with_borrow_mut(|d| {
             if d.contains_key(&txid) {
                 return Err("transaction already processed".to_string());
@@ -877,13 +1194,17 @@ pub mod state {
             s.total_deposited_currency += amount;
         });
 
+        // the payment is credited even if another user has bound the payer
+        let bind = check_address_unbound(caller, &sender).is_ok();
         USERS.with_borrow_mut(|u| {
             let mut user = u.get(&caller).unwrap_or_default();
             if !user.agreed_terms {
                 user.timestamp = now_ms;
                 user.agreed_terms = true;
             }
-            user.bound_addresses.insert(sender.clone());
+            if bind {
+                user.bound_addresses.insert(sender.clone());
+            }
             user.currency_amount += amount;
             user.deposits.push(txid.clone());
             let total_amount = user.currency_amount;
@@ -892,10 +1213,217 @@ pub mod state {
         })
     }
 
+    /// Pulls `amount` of currency from the caller's default ICRC account via
+    /// `icrc2_transfer_from`. The caller must approve the auction canister first.
+    pub async fn deposit_currency_icrc2(
+        caller: Principal,
+        amount: u128,
+        now_ms: u64,
+    ) -> Result<u128, String> {
+        if amount == 0 {
+            return Err("deposit amount must be greater than zero".to_string());
+        }
+
+        let (ledger_id, to) = STATE.with_borrow(|s| {
+            if !matches!(s.chain, Chain::Icp(_)) {
+                return Err("ICRC-2 deposit is only supported on ICP".to_string());
+            }
+            let ledger_id = Principal::from_text(&s.currency)
+                .map_err(|_| format!("Invalid currency principal: {}", s.currency))?;
+            Ok((ledger_id, Account::from(s.icp_address)))
+        })?;
+
+        let from = Account {
+            owner: caller,
+            subaccount: None,
+        };
+        let sender = from.to_string();
+        let block_index = icp::transfer_from(ledger_id, from, to, amount.into()).await?;
+
+        if check_address_unbound(caller, &sender).is_ok() {
+            USERS.with_borrow_mut(|u| {
+                let mut user = u.get(&caller).unwrap_or_default();
+                user.bound_addresses.insert(sender.clone());
+                u.insert(caller, user);
+            });
+        }
+        credit_deposit(caller, sender, block_index, amount, now_ms)
+    }
+
+    /// Broadcasts the caller's EIP-3009 authorization from the canister address and
+    /// binds the holder to the caller, so the deposit scanner credits it once mined.
+    pub async fn deposit_currency_eip3009(
+        caller: Principal,
+        input: Eip3009Input,
+        now_ms: u64,
+    ) -> Result<String, String> {
+        use alloy_primitives::hex::FromHex;
+
+        let (chain_id, evm_address, token) = STATE.with_borrow(|s| match s.chain {
+            Chain::Evm(chain_id) => {
+                let token = Address::from_str(&s.currency)
+                    .map_err(|_| "Invalid currency contract address".to_string())?;
+                Ok((chain_id, s.evm_address, token))
+            }
+            _ => Err("EIP-3009 deposit is only supported on EVM".to_string()),
+        })?;
+
+        let from = Address::from_str(&input.from).map_err(|_| "Invalid from address")?;
+        let nonce = input.caller_nonce(&caller)?;
+        let signature = <[u8; 65]>::from_hex(&input.signature).map_err(|_| "Invalid signature")?;
+        if input.value == 0 {
+            return Err("deposit amount must be greater than zero".to_string());
+        }
+        let now_secs = now_ms / 1000;
+        if input.valid_after > now_secs {
+            return Err("authorization is not yet valid".to_string());
+        }
+        // leave time for the transaction to be mined
+        if input.valid_before < now_secs + 60 {
+            return Err("authorization expired or expires too soon".to_string());
+        }
+
+        check_address_unbound(caller, &from.to_string())?;
+
+        let domain_separator = evm_client().erc20_domain_separator(now_ms, &token).await?;
+        let authorization = abi::TransferWithAuthorization {
+            from,
+            to: evm_address,
+            value: U256::from(input.value),
+            validAfter: U256::from(input.valid_after),
+            validBefore: U256::from(input.valid_before),
+            nonce: nonce.into(),
+        };
+        if recover_transfer_authorization_signer(&domain_separator, &authorization, &signature)?
+            != from
+        {
+            return Err("authorization is not signed by the from address".to_string());
+        }
+
+        let data = encode_erc20_transfer_with_authorization(
+            &from,
+            &evm_address,
+            input.value,
+            input.valid_after,
+            input.valid_before,
+            &nonce,
+            &signature,
+        );
+        let sender = EvmSender::Canister(None);
+        let (client, tx) = unsigned_evm_tx(
+            EvmTxKind::Erc20TransferWithAuthorization,
+            sender,
+            token,
+            0,
+            data.into(),
+            chain_id,
+            now_ms,
+        )
+        .await?;
+        // the canister pays the gas
+        let gas_cost = tx.gas_limit as u128 * tx.max_fee_per_gas;
+        let min_amount = STATE.with_borrow(|s| s.evm_fee_config.min_deposit_amount(gas_cost));
+        let min_amount = match min_amount {
+            Some(min_amount) if input.value >= min_amount => Ok(()),
+            Some(min_amount) => Err(format!(
+                "deposit {} is below the minimum amount {min_amount}",
+                input.value
+            )),
+            None => Err("currency_per_native is not configured".to_string()),
+        };
+        if let Err(err) = min_amount {
+            STATE.with_borrow_mut(|s| s.evm_nonce.release(tx.nonce));
+            return Err(err);
+        }
+
+        let signed_tx = sign_evm_tx(sender, tx).await?;
+        let txid = send_evm_tx(&client, signed_tx, now_ms).await?;
+        STATE.with_borrow_mut(|s| {
+            s.pending_authorizations.insert(
+                txid.clone(),
+                PendingAuthorization {
+                    user: caller,
+                    from: from.to_string(),
+                    valid_before: input.valid_before,
+                },
+            )
+        });
+        Ok(txid)
+    }
+
+    /// Binds the holders of the confirmed EIP-3009 deposits among `transfers` to their
+    /// users, the authorization nonce proves that the holder authorized the deposit for
+    /// the user. Authorizations that can no longer be confirmed are dropped.
+    pub fn bind_authorized_holders(transfers: &[(String, TransferChecked)], now_ms: u64) {
+        let confirmed: Vec<PendingAuthorization> = STATE.with_borrow_mut(|s| {
+            let confirmed = transfers
+                .iter()
+                .filter_map(|(txid, transfer)| {
+                    s.pending_authorizations
+                        .remove(txid)
+                        .filter(|p| p.from == transfer.from)
+                })
+                .collect();
+            s.pending_authorizations.retain(|_, p| {
+                p.valid_before
+                    .saturating_mul(1000)
+                    .saturating_add(AUTHORIZATION_SCAN_MS)
+                    >= now_ms
+            });
+            confirmed
+        });
+
+        for p in confirmed {
+            if check_address_unbound(p.user, &p.from).is_err() {
+                continue;
+            }
+            USERS.with_borrow_mut(|u| {
+                let mut user = u.get(&p.user).unwrap_or_default();
+                if !user.agreed_terms {
+                    user.timestamp = now_ms;
+                    user.agreed_terms = true;
+                }
+                user.bound_addresses.insert(p.from);
+                u.insert(p.user, user);
+            });
+        }
+    }
+
+    /// Sweeps the caller's deposit address into the auction address and credits the
+    /// swept amount to the user. On ICP the subaccount is swept at once (net of the
+    /// ledger fee); on EVM and Solana the sweep is credited once it is confirmed, so
+    /// it needs to be called again after the sweep transaction is submitted.
+    pub async fn sweep_deposit(caller: Principal, now_ms: u64) -> Result<u128, String> {
+        let chain = STATE.with_borrow_mut(|s| {
+            if let Some(ts) = s.pending_deposits.get(&caller)
+                && *ts + 20 * 1000 >= now_ms
+            {
+                return Err(
+                    "pending deposit already exists, please wait before trying again".to_string(),
+                );
+            };
+
+            s.pending_deposits.insert(caller, now_ms);
+            Ok(s.chain.clone())
+        })?;
+
+        let res = match chain {
+            Chain::Icp(_) => sweep_icp_deposit(caller).await,
+            Chain::Evm(chain_id) => sweep_evm_deposit(caller, chain_id, now_ms).await,
+            Chain::Sol(_) => sweep_sol_deposit(caller, now_ms).await,
+        };
+        STATE.with_borrow_mut(|s| {
+            s.pending_deposits.remove(&caller);
+        });
+        let (sender, txid, amount) = res?;
+        credit_deposit(caller, sender, txid, amount, now_ms)
+    }
+
     pub async fn deposit_currency(
         caller: Principal,
         sender: String,
         txid: String,
+        memo: Option<ByteBuf>,
         now_ms: u64,
     ) -> Result<u128, String> {
         USERS.with_borrow(|u| {
@@ -907,8 +1435,9 @@ pub mod state {
             Ok(())
         })?;
 
-        let chain = STATE.with_borrow_mut(|s| {
+        let (chain, txid) = STATE.with_borrow_mut(|s| {
             s.chain.parse_address(&sender)?;
+            let txid = s.chain.parse_txid(&txid)?;
             if let Some(ts) = s.pending_deposits.get(&caller)
                 && *ts + 20 * 1000 >= now_ms
             {
@@ -919,7 +1448,7 @@ pub mod state {
 
             // prevent DDoS attacks by limiting pending deposits
             s.pending_deposits.insert(caller, now_ms);
-            Ok(s.chain.clone())
+            Ok((s.chain.clone(), txid))
         })?;
 
         let mut tx = DEPOSITS.with_borrow_mut(|d| {
@@ -938,7 +1467,7 @@ pub mod state {
 
         let tx_status = match chain {
             Chain::Sol(_) => check_sol_deposit_currency(caller, sender, txid.clone(), now_ms).await,
-            Chain::Icp(_) => check_icp_deposit_currency(caller, sender, txid.clone()).await,
+            Chain::Icp(_) => check_icp_deposit_currency(caller, sender, txid.clone(), memo).await,
             Chain::Evm(_) => check_evm_deposit_currency(caller, sender, txid.clone(), now_ms).await,
         };
 
@@ -1008,14 +1537,18 @@ pub mod state {
             Chain::Sol(_) => {
                 withdraw_sol_token(&token, program_id, decimals, &recipient, amount, now_ms).await
             }
-            Chain::Icp(_) => withdraw_icp_token(&token, &recipient, amount).await,
+            Chain::Icp(_) => withdraw_icp_token(&token, &recipient, amount)
+                .await
+                .map(|txid| (txid, 0)),
             Chain::Evm(chain_id) => {
-                withdraw_evm_token(&token, &recipient, chain_id, amount, now_ms).await
+                withdraw_evm_token(&token, &recipient, chain_id, amount, now_ms)
+                    .await
+                    .map(|txid| (txid, 0))
             }
         };
 
         match tx_status {
-            Ok(txid) => {
+            Ok((txid, fee)) => {
                 let tx = WithdrawTx {
                     kind: 0,
                     user: caller,
@@ -1023,6 +1556,7 @@ pub mod state {
                     amount,
                     txid,
                     timestamp: now_ms,
+                    fee,
                 };
                 let id = WITHDRAWS
                     .with_borrow_mut(|w| w.append(&tx))
@@ -1096,14 +1630,18 @@ pub mod state {
             Chain::Sol(_) => {
                 withdraw_sol_token(&token, program_id, decimals, &recipient, amount, now_ms).await
             }
-            Chain::Icp(_) => withdraw_icp_token(&token, &recipient, amount).await,
+            Chain::Icp(_) => withdraw_icp_token(&token, &recipient, amount)
+                .await
+                .map(|txid| (txid, 0)),
             Chain::Evm(chain_id) => {
-                withdraw_evm_token(&token, &recipient, chain_id, amount, now_ms).await
+                withdraw_evm_token(&token, &recipient, chain_id, amount, now_ms)
+                    .await
+                    .map(|txid| (txid, 0))
             }
         };
 
         match tx_status {
-            Ok(txid) => {
+            Ok((txid, fee)) => {
                 let tx = WithdrawTx {
                     kind: 1,
                     user: caller,
@@ -1111,6 +1649,7 @@ pub mod state {
                     amount,
                     txid,
                     timestamp: now_ms,
+                    fee,
                 };
                 let id = WITHDRAWS
                     .with_borrow_mut(|w| w.append(&tx))
@@ -1137,6 +1676,198 @@ pub mod state {
         }
     }
 
+    /// Pays out the tokens (`kind` 1) or refunds the currency (`kind` 0) of the users to
+    /// their bound Solana addresses once the auction has ended, packing many recipients
+    /// per transaction. A round sends at most `PAYOUT_BATCHES` transactions and returns
+    /// their withdrawals, it is repeated until none are left. Native SOL and users without
+    /// a bound Solana address are left to `withdraw_currency` and `withdraw_token`.
+    pub async fn distribute_sol_payouts(
+        kind: u8,
+        now_ms: u64,
+    ) -> Result<Vec<WithdrawTxInfo>, String> {
+        let (payer, token, decimals, program_id, lookup_tables) = STATE.with_borrow(|s| {
+            if !matches!(s.chain, Chain::Sol(_)) {
+                return Err("batched payouts are only supported on Solana".to_string());
+            }
+            match &s.auction {
+                Some(auction) if auction.is_ended(now_ms) => {}
+                Some(_) => {
+                    return Err("auction is not ended yet, payouts are not allowed".to_string());
+                }
+                None => return Err("auction is not ready".to_string()),
+            }
+            let (token, decimals, program_id) = match kind {
+                0 => (&s.currency, s.currency_decimals, &s.currency_program_id),
+                _ => (&s.token, s.token_decimals, &s.token_program_id),
+            };
+            Ok((
+                s.sol_address,
+                token.clone(),
+                decimals,
+                program_id.clone(),
+                s.sol_lookup_tables.clone(),
+            ))
+        })?;
+        if token == SOL_ADDRESS {
+            return Err("native SOL is withdrawn by each user".to_string());
+        }
+        let token = Pubkey::from_str(&token).map_err(|_| "Invalid token address".to_string())?;
+        let program_id =
+            Pubkey::from_str(&program_id.ok_or("Token program ID is required".to_string())?)
+                .map_err(|_| "Invalid token program ID".to_string())?;
+
+        let client = sol_client();
+        let (mint, lookup_tables) = futures::future::try_join(
+            async {
+                if program_id == spl_token_2022::ID {
+                    client.get_mint_info(now_ms, &token).await.map(Some)
+                } else {
+                    Ok(None)
+                }
+            },
+            futures::future::try_join_all(
+                lookup_tables
+                    .iter()
+                    .map(|key| client.get_address_lookup_table(now_ms, key)),
+            ),
+        )
+        .await?;
+        let (mut epoch, mut hook) = (0, None);
+        if let Some(mint) = &mint {
+            mint.check_supported()?;
+            if mint.transfer_fee.is_some() {
+                epoch = client.get_epoch(now_ms).await?;
+            }
+            if let Some(program) = mint.transfer_hook {
+                let validation = get_extra_account_metas_address(&token, &program);
+                hook = Some((program, client.get_account_data(now_ms, &validation).await?));
+            }
+        }
+
+        // the balances are taken in the same call as they are read
+        let (payouts, batches) = USERS.with_borrow_mut(|u| {
+            let mut payouts: Vec<(Principal, Pubkey, u64)> = Vec::new();
+            for entry in u.iter() {
+                if payouts.len() >= PAYOUT_RECIPIENTS {
+                    break;
+                }
+                let (user, info) = entry.into_pair();
+                let amount = if kind == 0 {
+                    info.currency_amount
+                } else {
+                    info.token_amount
+                };
+                let recipient = info
+                    .bound_addresses
+                    .iter()
+                    .find_map(|addr| Pubkey::from_str(addr).ok().filter(|pk| pk != &payer));
+                if let (Ok(amount), Some(recipient)) = (u64::try_from(amount), recipient)
+                    && amount > 0
+                {
+                    payouts.push((user, recipient, amount));
+                }
+            }
+
+            let mut batches = batch_transfer_checked_instructions(
+                &payer,
+                &token,
+                &program_id,
+                decimals,
+                &payouts
+                    .iter()
+                    .map(|(_, recipient, amount)| (*recipient, *amount))
+                    .collect::<Vec<_>>(),
+                hook.as_ref()
+                    .map(|(program, data)| (program, data.as_deref())),
+                &lookup_tables,
+            )?;
+            batches.truncate(PAYOUT_BATCHES);
+            payouts.truncate(batches.iter().map(|b| b.len() / 2).sum());
+            for (user, _, _) in &payouts {
+                let mut info = u.get(user).unwrap_or_default();
+                if kind == 0 {
+                    info.currency_amount = 0;
+                } else {
+                    info.token_amount = 0;
+                }
+                u.insert(*user, info);
+            }
+            Ok::<_, String>((payouts, batches))
+        })?;
+
+        let mut infos = Vec::with_capacity(payouts.len());
+        let mut rest = payouts.as_slice();
+        for batch in batches {
+            let (paid, next) = rest.split_at(batch.len() / 2);
+            let sent = async {
+                let (client, signed_tx) = build_sol_tx(&payer, batch, &[], now_ms).await?;
+                let txid = signed_tx.signatures[0].to_string();
+                let data = bincode::serialize(&signed_tx).map_err(|err| format!("SOL: {err}"))?;
+                client
+                    .send_transaction(now_ms, data.into(), true)
+                    .await
+                    .map_err(|err| format!("SOL: {err}"))?;
+                Ok::<_, String>(txid)
+            }
+            .await;
+
+            let txid = match sent {
+                Ok(txid) => txid,
+                Err(err) => {
+                    // this batch and the following ones are not paid
+                    USERS.with_borrow_mut(|u| {
+                        for (user, _, amount) in rest {
+                            let mut info = u.get(user).unwrap_or_default();
+                            if kind == 0 {
+                                info.currency_amount += *amount as u128;
+                            } else {
+                                info.token_amount += *amount as u128;
+                            }
+                            u.insert(*user, info);
+                        }
+                    });
+                    if infos.is_empty() {
+                        return Err(err);
+                    }
+                    break;
+                }
+            };
+
+            for (user, recipient, amount) in paid {
+                let tx = WithdrawTx {
+                    kind,
+                    user: *user,
+                    recipient: recipient.to_string(),
+                    amount: *amount as u128,
+                    txid: txid.clone(),
+                    timestamp: now_ms,
+                    fee: mint
+                        .as_ref()
+                        .map_or(0, |mint| mint.transfer_fee(epoch, *amount))
+                        as u128,
+                };
+                let id = WITHDRAWS
+                    .with_borrow_mut(|w| w.append(&tx))
+                    .expect("append WithdrawTx failed");
+                STATE.with_borrow_mut(|s| {
+                    if kind == 0 {
+                        s.total_withdrawn_currency += tx.amount;
+                    } else {
+                        s.total_withdrawn_token += tx.amount;
+                    }
+                });
+                USERS.with_borrow_mut(|u| {
+                    let mut info = u.get(user).unwrap_or_default();
+                    info.withdraws.push(id);
+                    u.insert(*user, info);
+                });
+                infos.push(tx.into_info(id));
+            }
+            rest = next;
+        }
+        Ok(infos)
+    }
+
     pub async fn sweep_token(now_ms: u64) -> Result<WithdrawTxInfo, String> {
         let (
             chain,
@@ -1179,7 +1910,7 @@ pub mod state {
             ))
         })?;
 
-        let (amount, txid) = match chain {
+        let (amount, txid, fee) = match chain {
             Chain::Sol(_) => {
                 let token_addr = Pubkey::from_str(&token)
                     .map_err(|_| "invalid Solana token address".to_string())?;
@@ -1193,7 +1924,7 @@ pub mod state {
                     return Err("no tokens to sweep".to_string());
                 }
 
-                let txid = withdraw_sol_token(
+                let (txid, fee) = withdraw_sol_token(
                     &token,
                     token_program_id,
                     decimals,
@@ -1202,7 +1933,7 @@ pub mod state {
                     now_ms,
                 )
                 .await?;
-                (amount, txid)
+                (amount, txid, fee)
             }
             Chain::Icp(_) => {
                 let ledger = Principal::from_text(&token)
@@ -1214,7 +1945,7 @@ pub mod state {
                     return Err("no tokens to sweep".to_string());
                 }
                 let txid = icp::transfer(ledger, to, amount.into()).await?;
-                (amount, txid)
+                (amount, txid, 0)
             }
             Chain::Evm(chain_id) => {
                 let token_addr = Address::from_str(&token)
@@ -1225,7 +1956,7 @@ pub mod state {
                 }
 
                 let txid = withdraw_evm_token(&token, &recipient, chain_id, amount, now_ms).await?;
-                (amount, txid)
+                (amount, txid, 0)
             }
         };
 
@@ -1236,6 +1967,7 @@ pub mod state {
             amount,
             txid,
             timestamp: now_ms,
+            fee,
         };
         let id = WITHDRAWS
             .with_borrow_mut(|w| w.append(&tx))
@@ -1300,7 +2032,7 @@ pub mod state {
             ))
         })?;
 
-        let (amount, txid) = match chain {
+        let (amount, txid, fee) = match chain {
             Chain::Sol(_) => {
                 let currency_addr = Pubkey::from_str(&currency)
                     .map_err(|_| "invalid Solana token address".to_string())?;
@@ -1322,7 +2054,7 @@ pub mod state {
                     return Err("no tokens to sweep".to_string());
                 }
 
-                let txid = withdraw_sol_token(
+                let (txid, fee) = withdraw_sol_token(
                     &currency,
                     currency_program_id,
                     decimals,
@@ -1331,7 +2063,7 @@ pub mod state {
                     now_ms,
                 )
                 .await?;
-                (amount, txid)
+                (amount, txid, fee)
             }
             Chain::Icp(_) => {
                 let ledger = Principal::from_text(&currency)
@@ -1349,7 +2081,7 @@ pub mod state {
                     return Err("no tokens to sweep".to_string());
                 }
                 let txid = icp::transfer(ledger, to, amount.into()).await?;
-                (amount, txid)
+                (amount, txid, 0)
             }
             Chain::Evm(chain_id) => {
                 let token_addr = Address::from_str(&currency)
@@ -1365,7 +2097,7 @@ pub mod state {
 
                 let txid =
                     withdraw_evm_token(&currency, &recipient, chain_id, amount, now_ms).await?;
-                (amount, txid)
+                (amount, txid, 0)
             }
         };
 
@@ -1376,6 +2108,7 @@ pub mod state {
             amount,
             txid,
             timestamp: now_ms,
+            fee,
         };
         let id = WITHDRAWS
             .with_borrow_mut(|w| w.append(&tx))
@@ -1436,28 +2169,705 @@ pub mod state {
         })
     }
 
-    async fn check_icp_deposit_currency(
+    // Credits a confirmed deposit to the user once, keyed by its canonical txid.
+    fn credit_deposit(
         caller: Principal,
         sender: String,
-        txid: String, // u64: ICRC Ledger block index
-    ) -> Result<TransferChecked, String> {
-        let block_index = txid
-            .parse::<u64>()
-            .map_err(|_| "Invalid block index".to_string())?;
-
-        let ledger_id = STATE.with_borrow(|s| {
-            Principal::from_text(&s.currency)
-                .map_err(|_| format!("Invalid currency principal: {}", s.currency))
-        })?;
-
-        let tx_status = icp::verify_transfer_token(ledger_id, block_index).await?;
-        STATE.with_borrow_mut(|s| {
+        txid: String,
+        amount: u128,
+        now_ms: u64,
+    ) -> Result<u128, String> {
+        let txid = STATE.with_borrow(|s| s.chain.parse_txid(&txid))?;
+        DEPOSITS.with_borrow_mut(|d| {
+            if d.contains_key(&txid) {
+                return Err("transaction already processed".to_string());
+            }
+            d.insert(
+                txid.clone(),
+                DepositTx {
+                    user: caller,
+                    sender,
+                    amount,
+                    timestamp: now_ms,
+                },
+            );
+            Ok(())
+        })?;
+
+        STATE.with_borrow_mut(|s| {
+            s.total_deposited_currency += amount;
+        });
+
+        Ok(USERS.with_borrow_mut(|u| {
+            let mut user = u.get(&caller).unwrap_or_default();
+            if !user.agreed_terms {
+                user.timestamp = now_ms;
+                user.agreed_terms = true;
+            }
+            user.currency_amount += amount;
+            user.deposits.push(txid);
+            let total_amount = user.currency_amount;
+            u.insert(caller, user);
+            total_amount
+        }))
+    }
+
+    fn mark_swept_deposit_address(address: String) {
+        STATE.with_borrow_mut(|s| {
+            s.swept_deposit_addresses.insert(address);
+        });
+    }
+
+    /// Lists the incoming currency transfers to the auction address since the cursor,
+    /// and credits those sent from an address bound by a user.
+    async fn scan_deposits(now_ms: u64) -> Result<(), String> {
+        let chain = STATE.with_borrow(|s| {
+            if s.currency.is_empty() {
+                None
+            } else {
+                Some(s.chain.clone())
+            }
+        });
+        let Some(chain) = chain else {
+            return Ok(());
+        };
+
+        let mut cursor = DepositCursor::load();
+        let transfers = match chain {
+            Chain::Icp(_) => scan_icp_deposits(&mut cursor).await?,
+            Chain::Evm(_) => scan_evm_deposits(&mut cursor, now_ms).await?,
+            Chain::Sol(_) => scan_sol_deposits(&mut cursor, now_ms).await?,
+        };
+
+        if !transfers.is_empty() {
+            bind_authorized_holders(&transfers, now_ms);
+            let senders = bound_senders();
+            for (txid, transfer) in transfers {
+                if let Some(user) = senders.get(&transfer.from) {
+                    let _ = credit_deposit(*user, transfer.from, txid, transfer.amount, now_ms);
+                }
+            }
+        }
+
+        cursor.updated_at = now_ms;
+        cursor.save();
+        Ok(())
+    }
+
+    // Keys of a bound address in `bound_senders`. ICP accounts are also keyed by their
+    // AccountIdentifier.
+    fn sender_keys(chain: &Chain, addr: &str) -> Vec<String> {
+        match chain {
+            Chain::Icp(_) => {
+                let mut keys = vec![addr.to_string()];
+                if let Ok(account) = Account::from_str(addr) {
+                    let id = icp::ledger::account_identifier(&account);
+                    keys.push(icp::ledger::hex(&id));
+                }
+                keys
+            }
+            Chain::Evm(_) => Address::from_str(addr)
+                .map(|addr| vec![addr.to_string()])
+                .unwrap_or_default(),
+            Chain::Sol(_) => vec![addr.to_string()],
+        }
+    }
+
+    // An address can only be bound by one user, so that its transfers are credited to them.
+    fn check_address_unbound(caller: Principal, address: &str) -> Result<(), String> {
+        let chain = STATE.with_borrow(|s| s.chain.clone());
+        let keys = sender_keys(&chain, address);
+        USERS.with_borrow(|u| {
+            for entry in u.iter() {
+                let (user, state) = entry.into_pair();
+                if user != caller
+                    && state
+                        .bound_addresses
+                        .iter()
+                        .any(|addr| sender_keys(&chain, addr).iter().any(|k| keys.contains(k)))
+                {
+                    return Err("address is already bound to another user".to_string());
+                }
+            }
+            Ok(())
+        })
+    }
+
+    // Maps the bound sender addresses to their users, leaving out the addresses
+    // owned by the canister and those bound by more than one user.
+    fn bound_senders() -> HashMap<String, Principal> {
+        let (chain, own) = STATE.with_borrow(|s| {
+            let mut own = s.swept_deposit_addresses.clone();
+            own.insert(Account::from(s.icp_address).to_string());
+            own.insert(icp::ledger::hex(&icp::ledger::account_identifier(
+                &Account::from(s.icp_address),
+            )));
+            own.insert(s.evm_address.to_string());
+            own.insert(s.sol_address.to_string());
+            (s.chain.clone(), own)
+        });
+
+        let mut senders = HashMap::new();
+        let mut conflicts = BTreeSet::new();
+        USERS.with_borrow(|u| {
+            for entry in u.iter() {
+                let (user, state) = entry.into_pair();
+                for addr in state.bound_addresses {
+                    for key in sender_keys(&chain, &addr) {
+                        if let Some(prev) = senders.insert(key.clone(), user)
+                            && prev != user
+                        {
+                            conflicts.insert(key);
+                        }
+                    }
+                }
+            }
+        });
+        senders.retain(|addr, _| !own.contains(addr) && !conflicts.contains(addr));
+        senders
+    }
+
+    // Returns the transfers keyed by block index. The first scan starts from the ledger tip.
+    async fn scan_icp_deposits(
+        cursor: &mut DepositCursor,
+    ) -> Result<Vec<(String, TransferChecked)>, String> {
+        let (ledger_id, icp_address) = STATE.with_borrow(|s| {
+            let ledger_id = Principal::from_text(&s.currency)
+                .map_err(|_| format!("Invalid currency principal: {}", s.currency))?;
+            Ok::<_, String>((ledger_id, Account::from(s.icp_address)))
+        })?;
+
+        let start = cursor.next.unwrap_or(0);
+        let length = if cursor.next.is_some() {
+            ICP_SCAN_BLOCKS
+        } else {
+            0
+        };
+        let (log_length, next, transfers) = icp::get_transfers(ledger_id, start, length).await?;
+        // the ledger may return fewer blocks than requested
+        cursor.next = match cursor.next {
+            Some(_) => Some(next),
+            None => Some(log_length),
+        };
+
+        Ok(transfers
+            .into_iter()
+            .filter(|(_, t)| icp::account_matches(&t.to, &icp_address))
+            .map(|(id, t)| (id.to_string(), t))
+            .collect())
+    }
+
+    // Returns the ERC20 transfers keyed by transaction hash. The first scan starts
+    // from the latest confirmed block.
+    async fn scan_evm_deposits(
+        cursor: &mut DepositCursor,
+        now_ms: u64,
+    ) -> Result<Vec<(String, TransferChecked)>, String> {
+        let (evm_address, token) = STATE.with_borrow(|s| {
+            let token = Address::from_str(&s.currency)
+                .map_err(|_| "Invalid currency contract address".to_string())?;
+            Ok::<_, String>((s.evm_address, token))
+        })?;
+        if evm_address == Address::ZERO {
+            return Ok(vec![]);
+        }
+
+        let client = evm_client();
+        let filter = Filter::new()
+            .address(token)
+            .event_signature(IERC20::Transfer::SIGNATURE_HASH)
+            .topic2(evm_address.into_word());
+        let mut log_cursor = LogCursor {
+            next_block: cursor.next.unwrap_or_default(),
+            block_hash: cursor.block_hash,
+        };
+        let scanned = client
+            .scan_logs(
+                now_ms,
+                &filter,
+                &mut log_cursor,
+                &LogScan {
+                    confirmations: EVM_SCAN_CONFIRMATIONS,
+                    page_blocks: EVM_SCAN_BLOCKS,
+                    max_pages: EVM_SCAN_PAGES,
+                },
+            )
+            .await?;
+
+        // deposits of the rewound blocks are reversed if their transactions were
+        // reorged out, those still mined are found again by the rescan
+        if let Some(from) = scanned.rewound_from {
+            let (rewound, kept) = std::mem::take(&mut cursor.evm_recent)
+                .into_iter()
+                .partition(|(block, _)| *block >= from);
+            cursor.evm_recent = kept;
+            for (_, txid) in rewound {
+                let hash = TxHash::from_str(&txid).map_err(format_error)?;
+                match client.get_transaction_receipt(now_ms, &hash).await? {
+                    Some(receipt) if receipt.status() => {}
+                    _ => reverse_deposit(&txid),
+                }
+            }
+        }
+        cursor.next = Some(log_cursor.next_block);
+        cursor.block_hash = log_cursor.block_hash;
+
+        // logs of rescanned blocks after a reorg are deduplicated by `credit_deposit`
+        let mut transfers = Vec::with_capacity(scanned.logs.len());
+        for log in scanned.logs {
+            let (Some(tx_hash), Some(block)) = (log.transaction_hash, log.block_number) else {
+                continue;
+            };
+            let txid = tx_hash.to_string();
+            if !cursor.evm_recent.iter().any(|(_, id)| id == &txid) {
+                cursor.evm_recent.push((block, txid.clone()));
+            }
+            let Ok(transfer) = abi::decode_log::<IERC20::Transfer>(log.data()) else {
+                continue;
+            };
+            let Ok(amount) = u128::try_from(transfer.value) else {
+                continue;
+            };
+            transfers.push((
+                txid,
+                TransferChecked {
+                    token: token.to_string(),
+                    from: transfer.from.to_string(),
+                    to: evm_address.to_string(),
+                    amount,
+                },
+            ));
+        }
+        let next = log_cursor.next_block;
+        cursor
+            .evm_recent
+            .retain(|(block, _)| block + EVM_SCAN_CONFIRMATIONS >= next);
+        Ok(transfers)
+    }
+
+    // Reverses a credited deposit whose transaction was reorged out, as far as the
+    // user's balance allows.
+    fn reverse_deposit(txid: &str) {
+        let Some(tx) = DEPOSITS.with_borrow_mut(|d| d.remove(&txid.to_string())) else {
+            return;
+        };
+        STATE.with_borrow_mut(|s| {
+            s.total_deposited_currency = s.total_deposited_currency.saturating_sub(tx.amount);
+        });
+        USERS.with_borrow_mut(|u| {
+            if let Some(mut user) = u.get(&tx.user) {
+                user.currency_amount = user.currency_amount.saturating_sub(tx.amount);
+                user.deposits.retain(|id| id != txid);
+                u.insert(tx.user, user);
+            }
+        });
+    }
+
+    // Returns the transfers keyed by signature. The first scan starts from the newest
+    // signature of the receiving account; later scans page back from the newest one to
+    // the cursor, at most SOL_SCAN_TXS signatures at a time.
+    async fn scan_sol_deposits(
+        cursor: &mut DepositCursor,
+        now_ms: u64,
+    ) -> Result<Vec<(String, TransferChecked)>, String> {
+        let (sol_address, currency, program_id) = STATE.with_borrow(|s| {
+            (
+                s.sol_address,
+                s.currency.clone(),
+                s.currency_program_id.clone(),
+            )
+        });
+        if sol_address == Pubkey::default() {
+            return Ok(vec![]);
+        }
+
+        // SPL tokens are received by the associated token account
+        let receiver = if currency == SOL_ADDRESS {
+            sol_address
+        } else {
+            let token = Pubkey::from_str(&currency).map_err(|_| "Invalid token address")?;
+            let token_program_id = program_id
+                .as_deref()
+                .map(Pubkey::from_str)
+                .ok_or("Token program ID is required".to_string())?
+                .map_err(|_| "Invalid token program ID".to_string())?;
+            get_associated_token_address(&sol_address, &token, &token_program_id)
+        };
+
+        let client = sol_client();
+        if cursor.signature.is_none() {
+            let sigs = client
+                .get_signatures_for_address(now_ms, receiver.to_string(), None, None, 1)
+                .await?;
+            cursor.signature = sigs.into_iter().next().map(|s| s.signature);
+            return Ok(vec![]);
+        }
+
+        // the next page of new signatures, newest first
+        let sigs = client
+            .get_signatures_for_address(
+                now_ms,
+                receiver.to_string(),
+                cursor.before.clone(),
+                cursor.signature.clone(),
+                SOL_SCAN_TXS,
+            )
+            .await?;
+        let full = sigs.len() == SOL_SCAN_TXS;
+        let oldest = sigs.last().map(|s| s.signature.clone());
+        if cursor.before.is_none() {
+            cursor.pass_top = sigs.first().map(|s| s.signature.clone());
+        }
+
+        let mut transfers = Vec::new();
+        for sig in sigs {
+            if sig.err.is_some() {
+                continue;
+            }
+            let Some(tx) = client
+                .get_transaction(now_ms, sig.signature.clone(), Some("base64"))
+                .await?
+            else {
+                continue;
+            };
+            if let Ok(transfer) = get_transfer_checked(tx, &currency)
+                && transfer.to == sol_address.to_string()
+            {
+                transfers.push((sig.signature, transfer));
+            }
+        }
+
+        if full {
+            cursor.before = oldest;
+        } else {
+            // the pass reached the cursor
+            if let Some(top) = cursor.pass_top.take() {
+                cursor.signature = Some(top);
+            }
+            cursor.before = None;
+        }
+        Ok(transfers)
+    }
+
+    // returns (deposit account, block index, swept amount)
+    async fn sweep_icp_deposit(caller: Principal) -> Result<(String, String, u128), String> {
+        let (ledger_id, to) = STATE.with_borrow(|s| {
+            let ledger_id = Principal::from_text(&s.currency)
+                .map_err(|_| format!("Invalid currency principal: {}", s.currency))?;
+            Ok::<_, String>((ledger_id, Account::from(s.icp_address)))
+        })?;
+        let subaccount = icp::deposit_subaccount(&caller);
+        let from = Account {
+            owner: to.owner,
+            subaccount: Some(subaccount),
+        };
+        let (balance, fee) =
+            futures::future::try_join(icp::balance_of(ledger_id, from), icp::fee(ledger_id))
+                .await?;
+        if balance <= fee {
+            return Err("no deposit found in deposit account".to_string());
+        }
+
+        let amount = balance - fee;
+        mark_swept_deposit_address(from.to_string());
+        mark_swept_deposit_address(icp::ledger::hex(&icp::ledger::account_identifier(&from)));
+        let block_index = icp::sweep(ledger_id, subaccount, to, amount.into()).await?;
+        Ok((from.to_string(), block_index, amount))
+    }
+
+    // Returns (deposit address, txid, swept amount) once a sweep is confirmed.
+    // The deposit address is funded for gas by the auction address when needed.
+    async fn sweep_evm_deposit(
+        caller: Principal,
+        chain_id: u64,
+        now_ms: u64,
+    ) -> Result<(String, String, u128), String> {
+        let (evm_address, deposit, token, pending) = STATE.with_borrow(|s| {
+            let pk = derive_public_key(
+                &s.ecdsa_public_key,
+                deposit_derivation_path(&s.icp_address, &caller),
+            )
+            .map_err(|_e| "derive_public_key failed".to_string())?;
+            let token = Address::from_str(&s.currency)
+                .map_err(|_| "Invalid currency contract address".to_string())?;
+            Ok::<_, String>((
+                s.evm_address,
+                pk.to_evm_adress()?,
+                token,
+                s.pending_sweeps.get(&caller).cloned(),
+            ))
+        })?;
+
+        let client = evm_client();
+        let mut replacing: Option<PendingSweep> = None;
+        if let Some(pending) = pending {
+            let mut settled = None;
+            for txid in &pending.txids {
+                let hash = TxHash::from_str(txid).map_err(format_error)?;
+                if let Some(receipt) = client.get_transaction_receipt(now_ms, &hash).await? {
+                    settled = Some((txid.clone(), receipt.status()));
+                    break;
+                }
+            }
+
+            match settled {
+                Some((txid, ok)) => {
+                    STATE.with_borrow_mut(|s| s.pending_sweeps.remove(&caller));
+                    if ok && !pending.gas {
+                        return Ok((deposit.to_string(), txid, pending.amount));
+                    }
+                }
+                None if pending.timestamp + SWEEP_TIMEOUT_MS > now_ms => {
+                    return Err("deposit sweep is pending, please try again later".to_string());
+                }
+                None if pending.gas => {
+                    STATE.with_borrow_mut(|s| s.pending_sweeps.remove(&caller));
+                }
+                // replace the stuck sweep with the same nonce and amount
+                None => replacing = Some(pending),
+            }
+        }
+
+        let (amount, nonce) = match &replacing {
+            Some(pending) => (pending.amount, pending.nonce),
+            None => {
+                let (balance, nonce) = futures::future::try_join(
+                    erc20_balance_of(&deposit, &token, now_ms),
+                    client.get_transaction_count(now_ms, &deposit),
+                )
+                .await?;
+                if balance == 0 {
+                    return Err("no deposit found in deposit address".to_string());
+                }
+                (balance, nonce)
+            }
+        };
+
+        let sender = EvmSender::Deposit(caller, nonce);
+        let input = encode_erc20_transfer(&evm_address, amount);
+        let (client, tx) = unsigned_evm_tx(
+            EvmTxKind::Erc20Transfer,
+            sender,
+            token,
+            0,
+            input.into(),
+            chain_id,
+            now_ms,
+        )
+        .await?;
+
+        if replacing.is_none() {
+            let gas_cost = tx.gas_limit as u128 * tx.max_fee_per_gas;
+            let min_amount = STATE.with_borrow(|s| s.evm_fee_config.min_deposit_amount(gas_cost));
+            if let Some(min_amount) = min_amount
+                && amount < min_amount
+            {
+                return Err(format!(
+                    "deposit {amount} is below the minimum amount {min_amount}"
+                ));
+            }
+
+            let gas_balance = client.get_balance(now_ms, &deposit).await?;
+            if gas_balance < gas_cost {
+                if min_amount.is_none() {
+                    return Err(
+                        "currency_per_native is not configured, deposit address cannot be funded for gas"
+                            .to_string(),
+                    );
+                }
+                mark_swept_deposit_address(deposit.to_string());
+                let (client, top_up) = build_evm_tx(
+                    EvmTxKind::Transfer,
+                    EvmSender::Canister(None),
+                    deposit,
+                    gas_cost * 2 - gas_balance,
+                    Bytes::new(),
+                    chain_id,
+                    now_ms,
+                )
+                .await?;
+                let txid = send_evm_tx(&client, top_up, now_ms).await?;
+                STATE.with_borrow_mut(|s| {
+                    s.pending_sweeps.insert(
+                        caller,
+                        PendingSweep {
+                            txids: vec![txid.clone()],
+                            amount: 0,
+                            nonce: 0,
+                            gas: true,
+                            timestamp: now_ms,
+                        },
+                    )
+                });
+                return Err(format!(
+                    "deposit address is being funded for gas by {txid}, please try again later"
+                ));
+            }
+        }
+
+        // sign only once the deposit address can pay for the gas
+        let signed_tx = sign_evm_tx(sender, tx).await?;
+        let txid = signed_tx.hash().to_string();
+        mark_swept_deposit_address(deposit.to_string());
+        client
+            .send_raw_transaction(now_ms, Bytes::from(signed_tx.encoded_2718()).to_string())
+            .await
+            .map_err(format_error)?;
+
+        let pending = match replacing {
+            Some(mut pending) => {
+                pending.txids.push(txid.clone());
+                pending.timestamp = now_ms;
+                pending
+            }
+            None => PendingSweep {
+                txids: vec![txid.clone()],
+                amount,
+                nonce,
+                gas: false,
+                timestamp: now_ms,
+            },
+        };
+        STATE.with_borrow_mut(|s| s.pending_sweeps.insert(caller, pending));
+        Err(format!(
+            "deposit sweep {txid} submitted, please try again later to credit it"
+        ))
+    }
+
+    // Returns (deposit address, txid, swept amount) once a sweep is finalized.
+    // The auction address pays the transaction fee.
+    async fn sweep_sol_deposit(
+        caller: Principal,
+        now_ms: u64,
+    ) -> Result<(String, String, u128), String> {
+        let (sol_address, deposit, path, token, program_id, decimals, pending) = STATE
+            .with_borrow(|s| {
+                let path = deposit_derivation_path(&s.icp_address, &caller);
+                let pk = derive_schnorr_public_key(&s.ed25519_public_key, path.clone(), None)
+                    .map_err(|_e| "derive_schnorr_public_key failed".to_string())?;
+                Ok::<_, String>((
+                    s.sol_address,
+                    pk.to_sol_pubkey()?,
+                    path,
+                    s.currency.clone(),
+                    s.currency_program_id.clone(),
+                    s.currency_decimals,
+                    s.pending_sweeps.get(&caller).cloned(),
+                ))
+            })?;
+
+        let client = sol_client();
+        if let Some(pending) = pending {
+            let txid = pending.txids.last().cloned().unwrap_or_default();
+            match client.get_signature_statuses(now_ms, txid.clone()).await? {
+                Some(status) if status.is_error() => {}
+                Some(status) if status.is_finalized() => {
+                    STATE.with_borrow_mut(|s| s.pending_sweeps.remove(&caller));
+                    return Ok((deposit.to_string(), txid, pending.amount));
+                }
+                // the blockhash of an unconfirmed sweep has expired after the timeout
+                _ if pending.timestamp + SWEEP_TIMEOUT_MS > now_ms => {
+                    return Err("deposit sweep is pending, please try again later".to_string());
+                }
+                _ => {}
+            }
+            STATE.with_borrow_mut(|s| s.pending_sweeps.remove(&caller));
+        }
+
+        let (amount, ixs) = if token == SOL_ADDRESS {
+            let balance = sol_balance_of(&deposit, now_ms).await.unwrap_or(0);
+            let ix = instruction::transfer(&deposit, &sol_address, balance as u64);
+            (balance, vec![ix])
+        } else {
+            let token = Pubkey::from_str(&token).map_err(|_| "Invalid token address")?;
+            let token_program_id = program_id
+                .as_deref()
+                .map(Pubkey::from_str)
+                .ok_or("Token program ID is required".to_string())?
+                .map_err(|_| "Invalid token program ID".to_string())?;
+            let balance = spl_balance_of(&deposit, &token, &token_program_id, now_ms)
+                .await
+                .unwrap_or(0);
+            let amount: u64 = balance
+                .try_into()
+                .map_err(|_| format!("amount is too large: {}", balance))?;
+            let ix0 = create_associated_token_account_idempotent(
+                &sol_address,
+                &sol_address,
+                &token,
+                &token_program_id,
+            );
+            let (ix, fee) = spl_transfer_checked(
+                &token_program_id,
+                &deposit,
+                &token,
+                &sol_address,
+                amount,
+                decimals,
+                now_ms,
+            )
+            .await?;
+            // credit what the auction address receives
+            (balance - fee as u128, vec![ix0, ix])
+        };
+        if amount == 0 {
+            return Err("no deposit found in deposit address".to_string());
+        }
+
+        mark_swept_deposit_address(deposit.to_string());
+        let (client, transaction) =
+            build_sol_tx(&sol_address, ixs, &[(deposit, path)], now_ms).await?;
+        let txid = transaction.signatures[0].to_string();
+        let data = bincode::serialize(&transaction).map_err(|err| format!("SOL: {err}"))?;
+        client
+            .send_transaction(now_ms, data.into(), true)
+            .await
+            .map_err(|err| format!("SOL: {err}"))?;
+
+        STATE.with_borrow_mut(|s| {
+            s.pending_sweeps.insert(
+                caller,
+                PendingSweep {
+                    txids: vec![txid.clone()],
+                    amount,
+                    nonce: 0,
+                    gas: false,
+                    timestamp: now_ms,
+                },
+            )
+        });
+        Err(format!(
+            "deposit sweep {txid} submitted, please try again later to credit it"
+        ))
+    }
+
+    async fn check_icp_deposit_currency(
+        caller: Principal,
+        sender: String,
+        txid: String, // u64: ICRC Ledger block index
+        memo: Option<ByteBuf>,
+    ) -> Result<TransferChecked, String> {
+        let block_index = txid
+            .parse::<u64>()
+            .map_err(|_| "Invalid block index".to_string())?;
+
+        let ledger_id = STATE.with_borrow(|s| {
+            Principal::from_text(&s.currency)
+                .map_err(|_| format!("Invalid currency principal: {}", s.currency))
+        })?;
+
+        let sender_acc =
+            Account::from_str(&sender).map_err(|_| "Invalid sender address".to_string())?;
+        let tx_status =
+            icp::verify_transfer_token(ledger_id, block_index, memo.as_ref().map(|m| m.as_slice()))
+                .await?;
+        STATE.with_borrow_mut(|s| {
             s.pending_deposits.remove(&caller);
 
-            if tx_status.from != sender {
+            if !icp::account_matches(&tx_status.from, &sender_acc) {
                 return Err("transaction sender does not match sender".to_string());
             }
-            if tx_status.to != s.icp_address.to_string() {
+            if !icp::account_matches(&tx_status.to, &Account::from(s.icp_address)) {
                 return Err("transaction recipient does not match auction contract".to_string());
             }
             Ok(tx_status)
@@ -1501,34 +2911,19 @@ pub mod state {
                 .map_err(|_| "Invalid currency contract address".to_string())?;
 
             // Find the Transfer event log
-            // Topic0 for Transfer(address,address,uint256) is keccak256("Transfer(address,address,uint256)")
-            let transfer_topic = alloy_primitives::B256::from_hex(
-                "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef",
-            )
-            .unwrap();
 
-            let log = receipt
+            let transfer = receipt
                 .inner
                 .logs()
                 .iter()
-                .find(|l| {
-                    l.address() == currency_contract
-                        && l.topics().first() == Some(&transfer_topic)
-                        && l.topics().len() == 3
-                })
+                .filter(|l| l.address() == currency_contract)
+                .find_map(|l| abi::decode_log::<IERC20::Transfer>(l.data()).ok())
                 .ok_or("Transfer event not found in transaction receipt".to_string())?;
 
-            // Parse from address (topic 1)
-            let from_topic = log.topics()[1];
-            let from_addr = Address::from_word(from_topic);
-
-            // Parse to address (topic 2)
-            let to_topic = log.topics()[2];
-            let to_addr = Address::from_word(to_topic);
-
-            // Parse amount (data)
-            let amount = alloy_primitives::U256::from_be_slice(&log.data().data);
-            let amount_u128 = u128::try_from(amount).map_err(|_| "Amount too large".to_string())?;
+            let from_addr = transfer.from;
+            let to_addr = transfer.to;
+            let amount_u128 =
+                u128::try_from(transfer.value).map_err(|_| "Amount too large".to_string())?;
 
             if from_addr.to_string() != sender {
                 return Err("transaction sender does not match sender".to_string());
@@ -1553,11 +2948,13 @@ pub mod state {
         recipient: &str,
         amount: u128,
         now_ms: u64,
-    ) -> Result<String, String> {
+    ) -> Result<(String, u128), String> {
         let to_addr =
             Pubkey::from_str(recipient).map_err(|_| "Invalid recipient address".to_string())?;
-        let (client, signed_tx) = if token == SOL_ADDRESS {
-            build_sol_transfer_tx(&to_addr, amount as u64, now_ms).await?
+        let (client, signed_tx, fee) = if token == SOL_ADDRESS {
+            let (client, signed_tx) =
+                build_sol_transfer_tx(&to_addr, amount as u64, now_ms).await?;
+            (client, signed_tx, 0)
         } else {
             let token_program_id =
                 token_program_id.ok_or("Token program ID is required".to_string())?;
@@ -1579,7 +2976,7 @@ pub mod state {
             .send_transaction(now_ms, data.into(), true)
             .await
             .map_err(|err| format!("SOL: {err}"))?;
-        Ok(txid)
+        Ok((txid, fee as u128))
     }
 
     async fn withdraw_icp_token(
@@ -1612,13 +3009,82 @@ pub mod state {
         )
         .await?;
 
+        send_evm_tx(&client, signed_tx, now_ms).await
+    }
+
+    async fn send_evm_tx(
+        client: &EvmClient<DefaultHttpOutcall>,
+        signed_tx: Signed<TxEip1559>,
+        now_ms: u64,
+    ) -> Result<String, String> {
         let txid = signed_tx.hash().to_string();
+        let nonce = signed_tx.tx().nonce;
         let data = signed_tx.encoded_2718();
-        let _ = client
+        let err = match client
             .send_raw_transaction(now_ms, Bytes::from(data).to_string())
             .await
-            .map_err(format_error)?;
-        Ok(txid)
+        {
+            Ok(_) => return Ok(txid),
+            Err(err) => err,
+        };
+        if is_tx_rejection(&err) {
+            STATE.with_borrow_mut(|s| s.evm_nonce.release(nonce));
+            return Err(format_error(err));
+        }
+
+        // the transaction may have been broadcast, its nonce stays reserved until the
+        // node's pending transaction count shows whether it was received
+        let evm_address = STATE.with_borrow(|s| s.evm_address);
+        match client
+            .get_pending_transaction_count(now_ms, &evm_address)
+            .await
+        {
+            Ok(pending_nonce) if pending_nonce > nonce => Ok(txid),
+            _ => {
+                STATE.with_borrow_mut(|s| s.evm_nonce.mark_unknown(nonce));
+                Err(format_error(err))
+            }
+        }
+    }
+
+    /// Syncs the local nonces with the chain, releases the nonces of unknown broadcasts the
+    /// node never received, and fills the gaps left by dropped transactions with zero-value
+    /// self transfers so later transactions can be mined.
+    async fn reconcile_evm_nonce(now_ms: u64) -> Result<(), String> {
+        let (chain_id, evm_address, idle) = STATE.with_borrow(|s| match s.chain {
+            Chain::Evm(chain_id) => Ok((chain_id, s.evm_address, s.evm_nonce.is_idle())),
+            _ => Err("chain is not EVM".to_string()),
+        })?;
+        if idle || evm_address == Address::ZERO {
+            return Ok(());
+        }
+
+        let client = evm_client();
+        let (chain_nonce, pending_nonce) = futures::future::try_join(
+            client.get_transaction_count(now_ms, &evm_address),
+            client.get_pending_transaction_count(now_ms, &evm_address),
+        )
+        .await?;
+        let gaps = STATE.with_borrow_mut(|s| {
+            s.evm_nonce.reconcile(chain_nonce, now_ms);
+            s.evm_nonce.resolve_unknown(pending_nonce);
+            s.evm_nonce.take_gaps()
+        });
+
+        for nonce in gaps {
+            let (client, signed_tx) = build_evm_tx(
+                EvmTxKind::Transfer,
+                EvmSender::Canister(Some(nonce)),
+                evm_address,
+                0,
+                Bytes::new(),
+                chain_id,
+                now_ms,
+            )
+            .await?;
+            send_evm_tx(&client, signed_tx, now_ms).await?;
+        }
+        Ok(())
     }
 
     async fn spl_balance_of(
@@ -1641,6 +3107,48 @@ pub mod state {
         Ok(balance)
     }
 
+    // Returns the transfer_checked instruction between the owners' token accounts, with the
+    // accounts of the mint's transfer hook, and the Token-2022 fee withheld from `amount`.
+    async fn spl_transfer_checked(
+        token_program_id: &Pubkey,
+        from_addr: &Pubkey,
+        token: &Pubkey,
+        to_addr: &Pubkey,
+        amount: u64,
+        decimals: u8,
+        now_ms: u64,
+    ) -> Result<(Instruction, u64), String> {
+        let mut ix = transfer_checked_instruction(
+            token_program_id,
+            &get_associated_token_address(from_addr, token, token_program_id),
+            token,
+            &get_associated_token_address(to_addr, token, token_program_id),
+            from_addr,
+            &[],
+            amount,
+            decimals,
+        );
+        if token_program_id != &spl_token_2022::ID {
+            return Ok((ix, 0));
+        }
+
+        let client = sol_client();
+        let mint = client.get_mint_info(now_ms, token).await?;
+        mint.check_supported()?;
+        let fee = if mint.transfer_fee.is_some() {
+            let epoch = client.get_epoch(now_ms).await?;
+            mint.transfer_fee(epoch, amount)
+        } else {
+            0
+        };
+        if let Some(hook) = mint.transfer_hook {
+            let validation = get_extra_account_metas_address(token, &hook);
+            let data = client.get_account_data(now_ms, &validation).await?;
+            add_transfer_hook_accounts(&mut ix, &hook, data.as_deref())?;
+        }
+        Ok((ix, fee))
+    }
+
     async fn sol_balance_of(addr: &Pubkey, now_ms: u64) -> Result<u128, String> {
         let client = sol_client();
         let account_data = client.get_account_info(now_ms, addr.to_string()).await?;
@@ -1659,7 +3167,7 @@ pub mod state {
     }
 
     async fn create_sol_raydium_pool(now_ms: u64) -> Result<(Pubkey, String), String> {
-        let (key_name, icp_address, sol_address, pool_id, ixs) = STATE.with_borrow(|s| {
+        let (sol_address, pool_id, ixs) = STATE.with_borrow(|s| {
             let currency_pk = Pubkey::from_str(&s.currency).map_err(|_| "Invalid currency mint")?;
             let currency_program = Pubkey::from_str(s.currency_program_id.as_ref().unwrap())
                 .map_err(|_| "Invalid currency program ID")?;
@@ -1732,31 +3240,10 @@ pub mod state {
                 None,
             );
 
-            Ok::<_, String>((
-                s.key_name.clone(),
-                s.icp_address,
-                s.sol_address,
-                ids.pool_id,
-                vec![ix0, ix1],
-            ))
+            Ok::<_, String>((s.sol_address, ids.pool_id, vec![ix0, ix1]))
         })?;
 
-        let client = sol_client();
-        let block = client
-            .get_latest_blockhash(now_ms)
-            .await
-            .map_err(|err| format!("SOL: failed to get latest blockhash, error: {}", err))?;
-
-        let message = Message::new_with_blockhash(&ixs, Some(&sol_address), &block);
-        let msg = bincode::serialize(&message).map_err(|err| format!("SOL: {err}"))?;
-        let sig =
-            sign_with_schnorr(key_name, vec![icp_address.as_slice().to_vec()], msg, None).await?;
-        let signature: [u8; 64] = sig.try_into().map_err(|_| "invalid signature length")?;
-        let transaction = Transaction {
-            message,
-            signatures: vec![signature.into()],
-        };
-
+        let (client, transaction) = build_sol_tx(&sol_address, ixs, &[], now_ms).await?;
         let txid = transaction.signatures[0].to_string();
         let data = bincode::serialize(&transaction).map_err(|err| format!("SOL: {err}"))?;
 
@@ -1818,11 +3305,11 @@ pub mod state {
         decimals: u8,
         amount: u128,
         now_ms: u64,
-    ) -> Result<(SvmClient<DefaultHttpOutcall>, Transaction), String> {
-        let (key_name, from, from_addr, ixs) = STATE.with_borrow(|s| {
-            let amount: u64 = amount
-                .try_into()
-                .map_err(|_| format!("amount is too large: {}", amount))?;
+    ) -> Result<(SvmClient<DefaultHttpOutcall>, VersionedTransaction, u64), String> {
+        let amount: u64 = amount
+            .try_into()
+            .map_err(|_| format!("amount is too large: {}", amount))?;
+        let (from_addr, ix0) = STATE.with_borrow(|s| {
             let from_pk = derive_schnorr_public_key(
                 &s.ed25519_public_key,
                 vec![s.icp_address.as_slice().to_vec()],
@@ -1834,52 +3321,36 @@ pub mod state {
                 return Err("from and to cannot be the same".to_string());
             }
 
-            let from_pubkey = get_associated_token_address(&from_addr, token, token_program_id);
-            let to_pubkey = get_associated_token_address(to_addr, token, token_program_id);
             let ix0 = create_associated_token_account_idempotent(
                 &from_addr,
                 to_addr,
                 token,
                 token_program_id,
             );
-            let ix = transfer_checked_instruction(
-                token_program_id,
-                &from_pubkey,
-                token,
-                &to_pubkey,
-                &from_addr,
-                &[],
-                amount,
-                decimals,
-            );
-
-            Ok::<_, String>((s.key_name.clone(), s.icp_address, from_addr, vec![ix0, ix]))
+            Ok::<_, String>((from_addr, ix0))
         })?;
 
-        let client = sol_client();
-        let block = client
-            .get_latest_blockhash(now_ms)
-            .await
-            .map_err(|err| format!("SOL: failed to get latest blockhash, error: {}", err))?;
-
-        let message = Message::new_with_blockhash(&ixs, Some(&from_addr), &block);
-        let msg = bincode::serialize(&message).map_err(|err| format!("SOL: {err}"))?;
-        let sig = sign_with_schnorr(key_name, vec![from.as_slice().to_vec()], msg, None).await?;
-        let signature: [u8; 64] = sig.try_into().map_err(|_| "invalid signature length")?;
-        let transaction = Transaction {
-            message,
-            signatures: vec![signature.into()],
-        };
-
-        Ok((client, transaction))
+        // the recipient receives `amount - fee`, the fee is recorded with the withdrawal
+        let (ix, fee) = spl_transfer_checked(
+            token_program_id,
+            &from_addr,
+            token,
+            to_addr,
+            amount,
+            decimals,
+            now_ms,
+        )
+        .await?;
+        let (client, signed_tx) = build_sol_tx(&from_addr, vec![ix0, ix], &[], now_ms).await?;
+        Ok((client, signed_tx, fee))
     }
 
     async fn build_sol_transfer_tx(
         to_addr: &Pubkey,
         amount: u64,
         now_ms: u64,
-    ) -> Result<(SvmClient<DefaultHttpOutcall>, Transaction), String> {
-        let (key_name, from, from_addr, ixs) = STATE.with_borrow(|s| {
+    ) -> Result<(SvmClient<DefaultHttpOutcall>, VersionedTransaction), String> {
+        let (from_addr, ixs) = STATE.with_borrow(|s| {
             let from_pk = derive_schnorr_public_key(
                 &s.ed25519_public_key,
                 vec![s.icp_address.as_slice().to_vec()],
@@ -1892,23 +3363,101 @@ pub mod state {
             }
 
             let ix = instruction::transfer(&from_addr, to_addr, amount);
-            Ok::<_, String>((s.key_name.clone(), s.icp_address, from_addr, vec![ix]))
+            Ok::<_, String>((from_addr, vec![ix]))
         })?;
 
+        build_sol_tx(&from_addr, ixs, &[], now_ms).await
+    }
+
+    // Simulates the transaction to size its compute unit limit, prices it from the recent
+    // prioritization fees, then signs it with the canister's Solana address and the
+    // additional `signers` (address, derivation path).
+    // It is a v0 transaction when address lookup tables are configured.
+    async fn build_sol_tx(
+        payer: &Pubkey,
+        ixs: Vec<Instruction>,
+        signers: &[(Pubkey, Vec<Vec<u8>>)],
+        now_ms: u64,
+    ) -> Result<(SvmClient<DefaultHttpOutcall>, VersionedTransaction), String> {
+        let (key_name, from, cfg, lookup_tables) = STATE.with_borrow(|s| {
+            (
+                s.key_name.clone(),
+                s.icp_address,
+                s.sol_fee_config.clone(),
+                s.sol_lookup_tables.clone(),
+            )
+        });
+
         let client = sol_client();
-        let block = client
-            .get_latest_blockhash(now_ms)
+        let (block, fees, lookup_tables) = futures::future::try_join3(
+            async {
+                client
+                    .get_latest_blockhash(now_ms)
+                    .await
+                    .map_err(|err| format!("SOL: failed to get latest blockhash, error: {}", err))
+            },
+            client.get_recent_prioritization_fees(now_ms, writable_accounts(&ixs)),
+            futures::future::try_join_all(
+                lookup_tables
+                    .iter()
+                    .map(|key| client.get_address_lookup_table(now_ms, key)),
+            ),
+        )
+        .await?;
+        let unit_price = compute_unit_price(
+            &fees,
+            cfg.priority_fee_percentile,
+            cfg.min_compute_unit_price,
+            cfg.max_compute_unit_price,
+        );
+
+        let message = compile_message(
+            payer,
+            &with_compute_budget(&ixs, MAX_COMPUTE_UNIT_LIMIT, unit_price),
+            &lookup_tables,
+            &block,
+        )?;
+        let data = bincode::serialize(&unsigned_transaction(message))
+            .map_err(|err| format!("SOL: {err}"))?;
+        let simulated = client
+            .simulate_transaction(now_ms, data.into(), false)
             .await
-            .map_err(|err| format!("failed to get latest blockhash, error: {}", err))?;
-
-        let message = Message::new_with_blockhash(&ixs, Some(&from_addr), &block);
-        let msg = bincode::serialize(&message).map_err(|err| format!("SOL: {err}"))?;
-        let sig = sign_with_schnorr(key_name, vec![from.as_slice().to_vec()], msg, None).await?;
-        let signature: [u8; 64] = sig.try_into().map_err(|_| "invalid signature length")?;
-        let transaction = Transaction {
-            message,
-            signatures: vec![signature.into()],
-        };
+            .map_err(|err| format!("SOL: failed to simulate transaction, error: {err}"))?;
+        if let Some(err) = simulated.err {
+            return Err(format!(
+                "SOL: transaction simulation failed, error: {err}, logs: {:?}",
+                simulated.logs.unwrap_or_default()
+            ));
+        }
+        let unit_limit = compute_unit_limit(
+            simulated
+                .units_consumed
+                .unwrap_or(MAX_COMPUTE_UNIT_LIMIT as u64),
+        );
+
+        let message = compile_message(
+            payer,
+            &with_compute_budget(&ixs, unit_limit, unit_price),
+            &lookup_tables,
+            &block,
+        )?;
+        let data = message.serialize();
+        let num_signers = message.header().num_required_signatures as usize;
+        let mut signatures: Vec<[u8; 64]> = Vec::with_capacity(num_signers);
+        for key in message.static_account_keys().iter().take(num_signers) {
+            let path = if key == payer {
+                vec![from.as_slice().to_vec()]
+            } else {
+                signers
+                    .iter()
+                    .find(|(k, _)| k == key)
+                    .map(|(_, path)| path.clone())
+                    .ok_or_else(|| format!("SOL: missing signer {key}"))?
+            };
+            let sig = sign_with_schnorr(key_name.clone(), path, data.clone(), None).await?;
+            signatures.push(sig.try_into().map_err(|_| "invalid signature length")?);
+        }
+        let transaction = signed_transaction(message, signatures)?;
 
         Ok((client, transaction))
     }
@@ -1920,67 +3469,183 @@ pub mod state {
         amount: u128,
         now_ms: u64,
     ) -> Result<(EvmClient<DefaultHttpOutcall>, Signed<TxEip1559>), String> {
-        let (key_name, from, from_pk, mut tx, gas_updated_at) = STATE.with_borrow(|s| {
-            let from_pk =
-                derive_public_key(&s.ecdsa_public_key, vec![s.icp_address.as_slice().to_vec()])
-                    .map_err(|_e| "derive_public_key failed".to_string())?;
-
-            let input = encode_erc20_transfer(to_addr, amount);
-            let (gas_updated_at, gas_price, max_priority_fee_per_gas) = s.evm_latest_gas;
-            let max_priority_fee_per_gas = max_priority_fee_per_gas + max_priority_fee_per_gas / 5;
+        if &STATE.with_borrow(|s| s.evm_address) == to_addr {
+            return Err("from and to cannot be the same".to_string());
+        }
+
+        let input = encode_erc20_transfer(to_addr, amount);
+        build_evm_tx(
+            EvmTxKind::Erc20Transfer,
+            EvmSender::Canister(None),
+            token,
+            0,
+            input.into(),
+            chain_id,
+            now_ms,
+        )
+        .await
+    }
+
+    // For the canister address, a new nonce is allocated when `nonce` is None and
+    // it is released if signing fails.
+    async fn build_evm_tx(
+        kind: EvmTxKind,
+        sender: EvmSender,
+        to: Address,
+        value: u128,
+        input: Bytes,
+        chain_id: u64,
+        now_ms: u64,
+    ) -> Result<(EvmClient<DefaultHttpOutcall>, Signed<TxEip1559>), String> {
+        let (client, tx) =
+            unsigned_evm_tx(kind, sender, to, value, input, chain_id, now_ms).await?;
+        let signed_tx = sign_evm_tx(sender, tx).await?;
+        Ok((client, signed_tx))
+    }
+
+    // Prices the transaction and assigns its nonce, see `build_evm_tx`.
+    async fn unsigned_evm_tx(
+        kind: EvmTxKind,
+        sender: EvmSender,
+        to: Address,
+        value: u128,
+        input: Bytes,
+        chain_id: u64,
+        now_ms: u64,
+    ) -> Result<(EvmClient<DefaultHttpOutcall>, TxEip1559), String> {
+        let nonce = match sender {
+            EvmSender::Canister(nonce) => nonce,
+            EvmSender::Deposit(_, nonce) => Some(nonce),
+        };
+        let (from_addr, cfg, latest_fees, nonce_synced) = STATE.with_borrow(|s| {
+            let from_pk = derive_public_key(&s.ecdsa_public_key, evm_sender_path(s, sender))
+                .map_err(|_e| "derive_public_key failed".to_string())?;
             Ok::<_, String>((
-                s.key_name.clone(),
-                s.icp_address,
-                from_pk,
-                TxEip1559 {
-                    chain_id,
-                    nonce: 0u64,
-                    gas_limit: 84_000u64, // sample: ~53,696
-                    max_fee_per_gas: gas_price * 2 + max_priority_fee_per_gas,
-                    max_priority_fee_per_gas,
-                    to: token.into(),
-                    input: input.into(),
-                    ..Default::default()
-                },
-                gas_updated_at,
+                from_pk.to_evm_adress()?,
+                s.evm_fee_config.clone(),
+                s.evm_latest_fees,
+                nonce.is_some() || !s.evm_nonce.needs_sync(now_ms),
             ))
         })?;
 
-        let from_addr = from_pk.to_evm_adress()?;
-        if &from_addr == to_addr {
-            return Err("from and to cannot be the same".to_string());
-        }
-
         let client = evm_client();
-        if gas_updated_at + 120_000 >= now_ms {
-            let nonce = client.get_transaction_count(now_ms, &from_addr).await?;
-            tx.nonce = nonce;
-        } else {
-            let (nonce, gas_price, max_priority_fee_per_gas) = futures::future::try_join3(
-                client.get_transaction_count(now_ms, &from_addr),
-                client.gas_price(now_ms),
-                client.max_priority_fee_per_gas(now_ms),
-            )
-            .await?;
-            tx.nonce = nonce;
-            tx.max_priority_fee_per_gas = max_priority_fee_per_gas + max_priority_fee_per_gas / 5;
-            tx.max_fee_per_gas = gas_price * 2 + tx.max_priority_fee_per_gas;
+        let (fees_updated_at, mut base_fee, mut priority_fee) = latest_fees;
+        let (chain_nonce, fee_history, estimated_gas) = futures::future::try_join3(
+            async {
+                if nonce_synced {
+                    Ok(None)
+                } else {
+                    client
+                        .get_transaction_count(now_ms, &from_addr)
+                        .await
+                        .map(Some)
+                }
+            },
+            async {
+                if fees_updated_at + 120_000 >= now_ms {
+                    Ok(None)
+                } else {
+                    client
+                        .fee_history(now_ms, cfg.fee_history_blocks, cfg.reward_percentile)
+                        .await
+                        .map(Some)
+                }
+            },
+            async {
+                if kind == EvmTxKind::Transfer {
+                    Ok(None)
+                } else {
+                    let res = client
+                        .estimate_gas(now_ms, &from_addr, &to, input.to_string())
+                        .await;
+                    match res {
+                        Ok(gas) => Ok(Some(gas)),
+                        Err(err) if kind.requires_estimation() => {
+                            Err(format!("gas estimation failed: {}", err))
+                        }
+                        // the kind's default gas limit is used when the estimation fails
+                        Err(_) => Ok(None),
+                    }
+                }
+            },
+        )
+        .await?;
+
+        if let Some(history) = fee_history {
+            base_fee = history
+                .next_block_base_fee()
+                .ok_or_else(|| "missing base fee in fee history".to_string())?;
+            priority_fee = match priority_fee_from_history(&history) {
+                Some(fee) => fee,
+                None => client.max_priority_fee_per_gas(now_ms).await?,
+            };
             STATE.with_borrow_mut(|s| {
-                s.evm_latest_gas = (now_ms, gas_price, max_priority_fee_per_gas);
-            })
+                s.evm_latest_fees = (now_ms, base_fee, priority_fee);
+            });
         }
 
+        let (max_fee_per_gas, max_priority_fee_per_gas) = eip1559_fees(
+            base_fee,
+            priority_fee,
+            cfg.max_priority_fee_per_gas,
+            cfg.max_fee_per_gas,
+        )?;
+        let mut tx = TxEip1559 {
+            chain_id,
+            nonce: 0u64,
+            gas_limit: kind.gas_limit(estimated_gas),
+            max_fee_per_gas,
+            max_priority_fee_per_gas,
+            to: to.into(),
+            value: U256::from(value),
+            input,
+            ..Default::default()
+        };
+
+        tx.nonce = match nonce {
+            Some(nonce) => nonce,
+            None => STATE.with_borrow_mut(|s| {
+                if let Some(chain_nonce) = chain_nonce {
+                    s.evm_nonce.reconcile(chain_nonce, now_ms);
+                }
+                s.evm_nonce.allocate()
+            }),
+        };
+        Ok((client, tx))
+    }
+
+    // Signs the transaction from `unsigned_evm_tx`, releasing the allocated nonce on failure.
+    async fn sign_evm_tx(sender: EvmSender, tx: TxEip1559) -> Result<Signed<TxEip1559>, String> {
+        let managed = matches!(sender, EvmSender::Canister(_));
+        let (key_name, path, from_pk) = STATE.with_borrow(|s| {
+            let path = evm_sender_path(s, sender);
+            let from_pk = derive_public_key(&s.ecdsa_public_key, path.clone())
+                .map_err(|_e| "derive_public_key failed".to_string())?;
+            Ok::<_, String>((s.key_name.clone(), path, from_pk))
+        })?;
+
         let msg_hash = tx.signature_hash();
-        let sig =
-            sign_with_ecdsa(key_name, vec![from.as_slice().to_vec()], msg_hash.to_vec()).await?;
+        let sig = sign_with_ecdsa(key_name, path, msg_hash.to_vec())
+            .await
+            .inspect_err(|_| {
+                if managed {
+                    STATE.with_borrow_mut(|s| s.evm_nonce.release(tx.nonce));
+                }
+            })?;
         let signature = Signature::new(
             U256::from_be_slice(&sig[0..32]),  // r
             U256::from_be_slice(&sig[32..64]), // s
             y_parity(msg_hash.as_slice(), &sig, from_pk.public_key.as_slice())?,
         );
 
-        let signed_tx = tx.into_signed(signature);
-        Ok((client, signed_tx))
+        Ok(tx.into_signed(signature))
+    }
+
+    fn evm_sender_path(s: &State, sender: EvmSender) -> Vec<Vec<u8>> {
+        match sender {
+            EvmSender::Canister(_) => vec![s.icp_address.as_slice().to_vec()],
+            EvmSender::Deposit(owner, _) => deposit_derivation_path(&s.icp_address, &owner),
+        }
     }
 }
 
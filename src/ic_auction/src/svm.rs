@@ -1,12 +1,16 @@
+mod compute_budget;
 mod rpc;
 mod spl;
 mod types;
+mod versioned;
 
 pub mod constants;
 pub mod raydium;
 
+pub use compute_budget::*;
 pub use rpc::*;
 pub use spl::*;
 pub use types::*;
+pub use versioned::*;
 
 pub use solana_system_interface::instruction;
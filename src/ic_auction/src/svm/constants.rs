@@ -2,6 +2,10 @@ pub mod spl_token {
     solana_program::declare_id!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
 }
 
+pub mod spl_token_2022 {
+    solana_program::declare_id!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+}
+
 pub mod spl_associated_token_account {
     solana_program::declare_id!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
 }
@@ -9,3 +13,7 @@ pub mod spl_associated_token_account {
 pub mod system_program {
     solana_program::declare_id!("11111111111111111111111111111111");
 }
+
+pub mod compute_budget {
+    solana_program::declare_id!("ComputeBudget111111111111111111111111111111");
+}
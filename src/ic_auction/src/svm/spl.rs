@@ -1,6 +1,10 @@
 use solana_instruction::{AccountMeta, Instruction};
 
-use super::types::Pubkey;
+use super::{
+    compute_budget::{MAX_COMPUTE_UNIT_LIMIT, with_compute_budget},
+    types::{AddressLookupTableAccount, Hash, Pubkey},
+    versioned::compile_message,
+};
 
 pub use spl_associated_token_account_interface::{
     address::get_associated_token_address_with_program_id as get_associated_token_address,
@@ -40,3 +44,474 @@ pub fn transfer_checked_instruction(
         data,
     }
 }
+
+const EXTRA_ACCOUNT_METAS_SEED: &[u8] = b"extra-account-metas";
+// sha256("spl-transfer-hook-interface:execute")[..8], also the TLV type of the
+// extra account metas list
+const EXECUTE_DISCRIMINATOR: [u8; 8] = [105, 37, 101, 197, 75, 251, 102, 26];
+// discriminator (1) + address config (32) + is_signer (1) + is_writable (1)
+const EXTRA_ACCOUNT_META_SIZE: usize = 35;
+
+/// Returns the account holding the extra account metas of a transfer hook program for `mint`.
+pub fn get_extra_account_metas_address(mint: &Pubkey, hook_program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[EXTRA_ACCOUNT_METAS_SEED, mint.as_ref()], hook_program_id).0
+}
+
+/// Appends the accounts required by the mint's transfer hook to a `transfer_checked`
+/// instruction: the extra accounts resolved from `validation_data` (the data of the
+/// extra account metas account, None if it does not exist), the hook program and the
+/// extra account metas account.
+/// Seeds from account data are not supported, as they need more RPC calls.
+pub fn add_transfer_hook_accounts(
+    ix: &mut Instruction,
+    hook_program_id: &Pubkey,
+    validation_data: Option<&[u8]>,
+) -> Result<(), String> {
+    if ix.accounts.len() < 4 || ix.data.len() < 9 {
+        return Err("invalid transfer_checked instruction".to_string());
+    }
+
+    let validation = get_extra_account_metas_address(&ix.accounts[1].pubkey, hook_program_id);
+    // The accounts and data of the hook's Execute instruction, which seeds refer to
+    let mut data = EXECUTE_DISCRIMINATOR.to_vec();
+    data.extend_from_slice(&ix.data[1..9]);
+    let mut accounts = ix.accounts[0..4].to_vec();
+    accounts.push(AccountMeta::new_readonly(validation, false));
+
+    let metas = match validation_data {
+        Some(data) => extra_account_metas(data)?,
+        None => vec![],
+    };
+    for meta in metas {
+        let address_config: [u8; 32] = meta[1..33].try_into().unwrap();
+        let pubkey = match meta[0] {
+            0 => Pubkey::new_from_array(address_config),
+            1 => {
+                let seeds = unpack_seeds(&address_config, &data, &accounts)?;
+                find_program_address(&seeds, hook_program_id)
+            }
+            d if d >= 128 => {
+                let program_id = accounts
+                    .get((d - 128) as usize)
+                    .map(|a| a.pubkey)
+                    .ok_or("invalid extra account program index".to_string())?;
+                let seeds = unpack_seeds(&address_config, &data, &accounts)?;
+                find_program_address(&seeds, &program_id)
+            }
+            d => return Err(format!("unsupported extra account meta: {d}")),
+        };
+        if meta[33] != 0 {
+            return Err(format!("transfer hook requires signer {pubkey}"));
+        }
+        accounts.push(if meta[34] != 0 {
+            AccountMeta::new(pubkey, false)
+        } else {
+            AccountMeta::new_readonly(pubkey, false)
+        });
+    }
+
+    ix.accounts.extend_from_slice(&accounts[5..]);
+    ix.accounts
+        .push(AccountMeta::new_readonly(*hook_program_id, false));
+    ix.accounts
+        .push(AccountMeta::new_readonly(validation, false));
+    Ok(())
+}
+
+// Returns the packed extra account metas of the Execute instruction from TLV data.
+fn extra_account_metas(data: &[u8]) -> Result<Vec<&[u8]>, String> {
+    let mut offset = 0;
+    while offset + 12 <= data.len() {
+        let tlv_type = &data[offset..offset + 8];
+        let len = u32::from_le_bytes(data[offset + 8..offset + 12].try_into().unwrap()) as usize;
+        let value = data
+            .get(offset + 12..offset + 12 + len)
+            .ok_or("extra account metas out of bounds".to_string())?;
+        if tlv_type == EXECUTE_DISCRIMINATOR {
+            if value.len() < 4 {
+                return Err("invalid extra account metas".to_string());
+            }
+            let count = u32::from_le_bytes(value[0..4].try_into().unwrap()) as usize;
+            let metas = value[4..]
+                .get(..count * EXTRA_ACCOUNT_META_SIZE)
+                .ok_or("extra account metas out of bounds".to_string())?;
+            return Ok(metas.chunks_exact(EXTRA_ACCOUNT_META_SIZE).collect());
+        }
+        if tlv_type == [0u8; 8] {
+            break;
+        }
+        offset += 12 + len;
+    }
+    Ok(vec![])
+}
+
+fn unpack_seeds(
+    config: &[u8; 32],
+    data: &[u8],
+    accounts: &[AccountMeta],
+) -> Result<Vec<Vec<u8>>, String> {
+    let byte = |i: usize| {
+        config
+            .get(i)
+            .map(|v| *v as usize)
+            .ok_or("invalid seed config".to_string())
+    };
+    let mut seeds = Vec::new();
+    let mut i = 0;
+    while i < config.len() {
+        match config[i] {
+            0 => break,
+            // literal: length, bytes
+            1 => {
+                let len = byte(i + 1)?;
+                let seed = config
+                    .get(i + 2..i + 2 + len)
+                    .ok_or("invalid literal seed".to_string())?;
+                seeds.push(seed.to_vec());
+                i += 2 + len;
+            }
+            // instruction data: index, length
+            2 => {
+                let (index, len) = (byte(i + 1)?, byte(i + 2)?);
+                let seed = data
+                    .get(index..index + len)
+                    .ok_or("invalid instruction data seed".to_string())?;
+                seeds.push(seed.to_vec());
+                i += 3;
+            }
+            // account key: index
+            3 => {
+                let account = accounts
+                    .get(byte(i + 1)?)
+                    .ok_or("invalid account key seed".to_string())?;
+                seeds.push(account.pubkey.to_bytes().to_vec());
+                i += 2;
+            }
+            t => return Err(format!("unsupported seed type: {t}")),
+        }
+    }
+    Ok(seeds)
+}
+
+fn find_program_address(seeds: &[Vec<u8>], program_id: &Pubkey) -> Pubkey {
+    let seeds: Vec<&[u8]> = seeds.iter().map(|s| s.as_slice()).collect();
+    Pubkey::find_program_address(&seeds, program_id).0
+}
+
+/// The max size of a serialized transaction.
+pub const PACKET_DATA_SIZE: usize = 1232;
+// Upper bound of the compute units for one recipient, including the creation of its token account
+const PAYOUT_COMPUTE_UNITS: u32 = 30_000;
+
+/// Packs SPL payouts from `payer` into as few transactions as possible.
+/// Each batch holds `create_associated_token_account_idempotent` + `transfer_checked`
+/// instructions for its recipients, and fits in one transaction with the compute budget
+/// instructions. The transfers of a mint with a transfer `hook` (program id and validation
+/// account data) carry the hook's accounts, and are sent one per transaction since the
+/// hook may use any compute units.
+pub fn batch_transfer_checked_instructions(
+    payer: &Pubkey,
+    mint: &Pubkey,
+    token_program_id: &Pubkey,
+    decimals: u8,
+    payouts: &[(Pubkey, u64)],
+    hook: Option<(&Pubkey, Option<&[u8]>)>,
+    lookup_tables: &[AddressLookupTableAccount],
+) -> Result<Vec<Vec<Instruction>>, String> {
+    let source = get_associated_token_address(payer, mint, token_program_id);
+    let payout_units = match hook {
+        Some(_) => MAX_COMPUTE_UNIT_LIMIT,
+        None => PAYOUT_COMPUTE_UNITS,
+    };
+    let mut batches: Vec<Vec<Instruction>> = Vec::new();
+    let mut batch: Vec<Instruction> = Vec::new();
+    for (recipient, amount) in payouts {
+        if recipient == payer {
+            return Err("from and to cannot be the same".to_string());
+        }
+
+        let mut transfer = transfer_checked_instruction(
+            token_program_id,
+            &source,
+            mint,
+            &get_associated_token_address(recipient, mint, token_program_id),
+            payer,
+            &[],
+            *amount,
+            decimals,
+        );
+        if let Some((hook_program_id, validation_data)) = hook {
+            add_transfer_hook_accounts(&mut transfer, hook_program_id, validation_data)?;
+        }
+        let ixs = [
+            create_associated_token_account_idempotent(payer, recipient, mint, token_program_id),
+            transfer,
+        ];
+
+        batch.extend_from_slice(&ixs);
+        if !fits_in_transaction(payer, &batch, payout_units, lookup_tables)? {
+            batch.truncate(batch.len() - ixs.len());
+            if batch.is_empty() {
+                return Err(format!(
+                    "payout to {recipient} does not fit in a transaction"
+                ));
+            }
+            batches.push(std::mem::take(&mut batch));
+            batch.extend_from_slice(&ixs);
+        }
+    }
+
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+    Ok(batches)
+}
+
+fn fits_in_transaction(
+    payer: &Pubkey,
+    ixs: &[Instruction],
+    payout_units: u32,
+    lookup_tables: &[AddressLookupTableAccount],
+) -> Result<bool, String> {
+    let recipients = ixs.len() as u32 / 2;
+    if recipients.saturating_mul(payout_units) > MAX_COMPUTE_UNIT_LIMIT {
+        return Ok(false);
+    }
+
+    let message = compile_message(
+        payer,
+        &with_compute_budget(ixs, MAX_COMPUTE_UNIT_LIMIT, u64::MAX),
+        lookup_tables,
+        &Hash::default(),
+    )?;
+    // compact-u16 length prefix + signatures + message
+    let size =
+        1 + 64 * message.header().num_required_signatures as usize + message.serialize().len();
+    Ok(size <= PACKET_DATA_SIZE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::svm::{Signature, VersionedMessage, VersionedTransaction, constants};
+
+    #[test]
+    fn test_add_transfer_hook_accounts() {
+        use sha2::{Digest, Sha256};
+
+        let digest = Sha256::digest(b"spl-transfer-hook-interface:execute");
+        assert_eq!(digest[..8], EXECUTE_DISCRIMINATOR);
+
+        let hook = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let (source, destination, owner) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let token_program_id = constants::spl_token_2022::ID;
+        let ix = transfer_checked_instruction(
+            &token_program_id,
+            &source,
+            &mint,
+            &destination,
+            &owner,
+            &[],
+            500,
+            6,
+        );
+
+        let literal = Pubkey::new_unique();
+        let mut metas = Vec::new();
+        // a literal writable account
+        metas.push(0u8);
+        metas.extend_from_slice(literal.as_ref());
+        metas.extend_from_slice(&[0, 1]);
+        // a PDA of the hook from b"counter", the destination and the amount
+        let mut config = vec![1u8, 7];
+        config.extend_from_slice(b"counter");
+        config.extend_from_slice(&[3, 2, 2, 8, 8]);
+        config.resize(32, 0);
+        metas.push(1);
+        metas.extend_from_slice(&config);
+        metas.extend_from_slice(&[0, 0]);
+        // a PDA of the literal account (index 5) from the mint
+        let mut config = vec![3u8, 1];
+        config.resize(32, 0);
+        metas.push(128 + 5);
+        metas.extend_from_slice(&config);
+        metas.extend_from_slice(&[0, 1]);
+
+        let mut value = 3u32.to_le_bytes().to_vec();
+        value.extend_from_slice(&metas);
+        let mut data = EXECUTE_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        data.extend_from_slice(&value);
+
+        let mut with_hook = ix.clone();
+        add_transfer_hook_accounts(&mut with_hook, &hook, Some(&data)).unwrap();
+        let validation = get_extra_account_metas_address(&mint, &hook);
+        let counter = Pubkey::find_program_address(
+            &[b"counter", destination.as_ref(), &500u64.to_le_bytes()],
+            &hook,
+        )
+        .0;
+        let literal_pda = Pubkey::find_program_address(&[mint.as_ref()], &literal).0;
+        assert_eq!(
+            with_hook.accounts[4..],
+            [
+                AccountMeta::new(literal, false),
+                AccountMeta::new_readonly(counter, false),
+                AccountMeta::new(literal_pda, false),
+                AccountMeta::new_readonly(hook, false),
+                AccountMeta::new_readonly(validation, false),
+            ]
+        );
+        assert_eq!(with_hook.data, ix.data);
+
+        let mut without_metas = ix.clone();
+        add_transfer_hook_accounts(&mut without_metas, &hook, None).unwrap();
+        assert_eq!(without_metas.accounts.len(), 6);
+
+        // account data seeds are refused
+        let mut data = data.clone();
+        data[12 + 4 + 36] = 4;
+        let mut unsupported = ix.clone();
+        assert!(add_transfer_hook_accounts(&mut unsupported, &hook, Some(&data)).is_err());
+    }
+
+    #[test]
+    fn test_batch_transfer_checked_instructions() {
+        let payer = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let token_program_id = constants::spl_token::ID;
+        let payouts: Vec<(Pubkey, u64)> =
+            (0..50).map(|i| (Pubkey::new_unique(), 1_000 + i)).collect();
+
+        let batches = batch_transfer_checked_instructions(
+            &payer,
+            &mint,
+            &token_program_id,
+            6,
+            &payouts,
+            None,
+            &[],
+        )
+        .unwrap();
+        assert!(batches.len() > 1);
+        assert!(batches.len() < payouts.len());
+        assert_eq!(
+            batches.iter().map(|b| b.len()).sum::<usize>(),
+            payouts.len() * 2
+        );
+
+        // every batch fits in one transaction, the next payout would not
+        for (i, batch) in batches.iter().enumerate() {
+            let message = compile_message(
+                &payer,
+                &with_compute_budget(batch, MAX_COMPUTE_UNIT_LIMIT, u64::MAX),
+                &[],
+                &Hash::default(),
+            )
+            .unwrap();
+            assert!(matches!(message, VersionedMessage::Legacy(_)));
+            let tx = VersionedTransaction {
+                signatures: vec![Signature::default()],
+                message,
+            };
+            assert!(bincode::serialize(&tx).unwrap().len() <= PACKET_DATA_SIZE);
+            assert!((batch.len() as u32 / 2) * PAYOUT_COMPUTE_UNITS <= MAX_COMPUTE_UNIT_LIMIT);
+
+            if let Some(next) = batches.get(i + 1) {
+                let mut grown = batch.clone();
+                grown.extend_from_slice(&next[..2]);
+                assert!(!fits_in_transaction(&payer, &grown, PAYOUT_COMPUTE_UNITS, &[]).unwrap());
+            }
+        }
+
+        // payouts keep their order
+        let last = batches.last().unwrap();
+        let ix = last.last().unwrap();
+        assert_eq!(ix.data[1..9], 1_049u64.to_le_bytes());
+        assert_eq!(
+            ix.accounts[2].pubkey,
+            get_associated_token_address(&payouts[49].0, &mint, &token_program_id)
+        );
+
+        // lookup tables pack more recipients per transaction, up to the compute limit
+        let table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: payouts
+                .iter()
+                .flat_map(|(recipient, _)| {
+                    [
+                        *recipient,
+                        get_associated_token_address(recipient, &mint, &token_program_id),
+                    ]
+                })
+                .collect(),
+        };
+        let batches_with_table = batch_transfer_checked_instructions(
+            &payer,
+            &mint,
+            &token_program_id,
+            6,
+            &payouts,
+            None,
+            &[table],
+        )
+        .unwrap();
+        assert!(batches_with_table.len() < batches.len());
+
+        // the compute limit caps the recipients of a batch that fits in size
+        let units = MAX_COMPUTE_UNIT_LIMIT / 3;
+        assert!(fits_in_transaction(&payer, &batches[0][..6], units, &[]).unwrap());
+        assert!(!fits_in_transaction(&payer, &batches[0][..8], units, &[]).unwrap());
+
+        // the transfers of a mint with a transfer hook are sent one per transaction
+        let hook = Pubkey::new_unique();
+        let token_program_id = constants::spl_token_2022::ID;
+        let batches = batch_transfer_checked_instructions(
+            &payer,
+            &mint,
+            &token_program_id,
+            6,
+            &payouts[..3],
+            Some((&hook, None)),
+            &[],
+        )
+        .unwrap();
+        assert_eq!(batches.len(), 3);
+        assert_eq!(batches[0][1].accounts.len(), 6);
+        assert_eq!(
+            batches[0][1].accounts[4],
+            AccountMeta::new_readonly(hook, false)
+        );
+
+        assert!(
+            batch_transfer_checked_instructions(
+                &payer,
+                &mint,
+                &token_program_id,
+                6,
+                &[],
+                None,
+                &[]
+            )
+            .unwrap()
+            .is_empty()
+        );
+        assert!(
+            batch_transfer_checked_instructions(
+                &payer,
+                &mint,
+                &token_program_id,
+                6,
+                &[(payer, 1)],
+                None,
+                &[]
+            )
+            .is_err()
+        );
+    }
+}
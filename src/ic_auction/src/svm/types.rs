@@ -6,12 +6,31 @@ pub use solana_account_decoder_client_types::{
     UiAccount, UiAccountData,
     token::{TokenAccountType, UiTokenAmount},
 };
+pub use solana_instruction::Instruction;
+pub use solana_message::{AddressLookupTableAccount, VersionedMessage};
 pub use solana_program::{hash::Hash, pubkey::Pubkey};
-pub use solana_transaction::{Message, Transaction};
+pub use solana_transaction::{Message, Signature, versioned::VersionedTransaction};
 pub use solana_transaction_status_client_types::EncodedTransactionWithStatusMeta;
 
+use super::constants::spl_token_2022;
 use crate::types::TransferChecked;
 
+// Token-2022 mint extensions that plain `transfer_checked` transfers handle correctly
+const SUPPORTED_MINT_EXTENSIONS: &[&str] = &[
+    "transferFeeConfig",
+    "transferHook",
+    "mintCloseAuthority",
+    "metadataPointer",
+    "tokenMetadata",
+    "groupPointer",
+    "groupMemberPointer",
+    "tokenGroup",
+    "tokenGroupMember",
+    "interestBearingConfig",
+    "scaledUiAmountConfig",
+    "defaultAccountState",
+];
+
 #[derive(Debug, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct LatestBlockhash {
@@ -25,6 +44,31 @@ impl LatestBlockhash {
     }
 }
 
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PrioritizationFee {
+    pub slot: u64,
+    // micro-lamports per compute unit
+    pub prioritization_fee: u64,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulatedTransaction {
+    pub err: Option<Value>,
+    pub logs: Option<Vec<String>>,
+    pub units_consumed: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfirmedSignature {
+    pub signature: String,
+    pub slot: u64,
+    pub err: Option<Value>,
+    pub block_time: Option<i64>,
+}
+
 #[derive(Debug, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct SignatureStatus {
@@ -50,6 +94,126 @@ impl SignatureStatus {
     }
 }
 
+/// A Token-2022 transfer fee, effective from `epoch`.
+#[derive(Debug, Deserialize, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferFee {
+    pub epoch: u64,
+    pub maximum_fee: u64,
+    pub transfer_fee_basis_points: u16,
+}
+
+impl TransferFee {
+    /// Returns the fee withheld from a transfer of `amount`, rounded up as the
+    /// Token-2022 program does.
+    pub fn calculate(&self, amount: u64) -> u64 {
+        if self.transfer_fee_basis_points == 0 || amount == 0 {
+            return 0;
+        }
+        let fee = (amount as u128 * self.transfer_fee_basis_points as u128).div_ceil(10_000);
+        (fee as u64).min(self.maximum_fee)
+    }
+}
+
+/// An SPL mint with the Token-2022 extensions that affect transfers.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MintInfo {
+    pub program_id: Pubkey,
+    pub decimals: u8,
+    pub extensions: Vec<String>,
+    // (older, newer) transfer fees
+    pub transfer_fee: Option<(TransferFee, TransferFee)>,
+    // Program invoked on every transfer
+    pub transfer_hook: Option<Pubkey>,
+    // New token accounts start frozen
+    pub default_frozen: bool,
+    // Symbol of the tokenMetadata extension
+    pub symbol: Option<String>,
+}
+
+impl MintInfo {
+    /// Refuses mints whose extensions would make deposits or payouts fail or unsafe,
+    /// e.g. non-transferable mints or a permanent delegate that can move our balance.
+    pub fn check_supported(&self) -> Result<(), String> {
+        for ext in &self.extensions {
+            if !SUPPORTED_MINT_EXTENSIONS.contains(&ext.as_str()) {
+                return Err(format!("unsupported Token-2022 extension: {ext}"));
+            }
+        }
+        if self.default_frozen {
+            return Err("unsupported Token-2022 extension: defaultAccountState frozen".to_string());
+        }
+        Ok(())
+    }
+
+    /// Returns the fee withheld from a transfer of `amount` in `epoch`.
+    pub fn transfer_fee(&self, epoch: u64, amount: u64) -> u64 {
+        match &self.transfer_fee {
+            Some((_, newer)) if epoch >= newer.epoch => newer.calculate(amount),
+            Some((older, _)) => older.calculate(amount),
+            None => 0,
+        }
+    }
+}
+
+pub fn get_mint_info(val: UiAccount) -> Result<MintInfo, String> {
+    let program_id =
+        Pubkey::from_str(&val.owner).map_err(|_| format!("Invalid mint owner: {}", val.owner))?;
+    let parsed = match val.data {
+        UiAccountData::Json(parsed_account) => parsed_account.parsed,
+        _ => return Err("UiAccount data is not in JSON format".to_string()),
+    };
+    if parsed["type"] != "mint" {
+        return Err("account is not a mint".to_string());
+    }
+
+    let info = &parsed["info"];
+    let decimals = info["decimals"]
+        .as_u64()
+        .and_then(|v| u8::try_from(v).ok())
+        .ok_or("Invalid mint decimals".to_string())?;
+    let mut mint = MintInfo {
+        program_id,
+        decimals,
+        ..Default::default()
+    };
+    if program_id != spl_token_2022::ID {
+        return Ok(mint);
+    }
+
+    for ext in info["extensions"].as_array().into_iter().flatten() {
+        let name = ext["extension"]
+            .as_str()
+            .ok_or("Invalid mint extension".to_string())?;
+        let state = &ext["state"];
+        match name {
+            "transferFeeConfig" => {
+                let older: TransferFee = serde_json::from_value(state["olderTransferFee"].clone())
+                    .map_err(|err| format!("failed to parse olderTransferFee: {err}"))?;
+                let newer: TransferFee = serde_json::from_value(state["newerTransferFee"].clone())
+                    .map_err(|err| format!("failed to parse newerTransferFee: {err}"))?;
+                mint.transfer_fee = Some((older, newer));
+            }
+            "transferHook" => {
+                // a hook without program is disabled
+                mint.transfer_hook = state["programId"]
+                    .as_str()
+                    .map(|v| Pubkey::from_str(v).map_err(|_| format!("Invalid transfer hook: {v}")))
+                    .transpose()?;
+            }
+            "defaultAccountState" => {
+                mint.default_frozen = state["accountState"] == "frozen";
+            }
+            "tokenMetadata" => {
+                mint.symbol = state["symbol"].as_str().map(|v| v.to_string());
+            }
+            _ => {}
+        }
+        mint.extensions.push(name.to_string());
+    }
+    Ok(mint)
+}
+
 #[allow(unused)]
 pub fn get_token_account(val: UiAccount) -> Result<TokenAccountType, String> {
     match val.data {
@@ -62,6 +226,31 @@ pub fn get_token_account(val: UiAccount) -> Result<TokenAccountType, String> {
     }
 }
 
+pub fn get_lookup_table_account(
+    key: Pubkey,
+    val: UiAccount,
+) -> Result<AddressLookupTableAccount, String> {
+    match val.data {
+        UiAccountData::Json(parsed_account) => {
+            let addresses = parsed_account.parsed["info"]["addresses"]
+                .as_array()
+                .ok_or("No addresses found in the lookup table".to_string())?
+                .iter()
+                .map(|v| {
+                    v.as_str()
+                        .and_then(|v| Pubkey::from_str(v).ok())
+                        .ok_or(format!("Invalid address in the lookup table: {}", v))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(AddressLookupTableAccount { key, addresses })
+        }
+        _ => Err("UiAccount data is not in JSON format".to_string()),
+    }
+}
+
+/// Returns the transfer of `token` in the transaction from the token balance changes.
+/// The received amount is net of any Token-2022 transfer fee, which is withheld in
+/// the recipient's token account and not part of its balance.
 pub fn get_transfer_checked(
     data: EncodedTransactionWithStatusMeta,
     token: &str,
@@ -1,12 +1,18 @@
+use alloy_primitives::{B256, keccak256};
 use candid::{CandidType, Principal};
-use ic_auth_types::{ByteArrayB64, ByteBufB64};
+use ic_auth_types::ByteArrayB64;
 use icrc_ledger_types::icrc1::account::Account;
 use serde::{Deserialize, Serialize};
 use serde_bytes::ByteBuf;
-use serde_json::{Map, Value};
+use serde_json::Value;
 use std::str::FromStr;
 
-use crate::{evm::Address, svm::Pubkey};
+use crate::{
+    evm::Address,
+    svm::{Pubkey, Signature},
+};
+
+pub use x402::{PayingResultInput, X402PaymentOutput};
 
 pub const MAX_TOTAL_SUPPLY: u128 = 1_000_000_000_000_000_000_000_000_000_000; // Maximum total supply (1e30)
 
@@ -87,6 +93,10 @@ pub struct StateInfo {
     pub auction_config: Option<AuctionConfig>,
     pub finalize_output: Option<FinalizeOutput>,
     pub payment_requirements_extra: Option<String>,
+    pub evm_fee_config: EvmFeeConfig,
+    pub sol_fee_config: SolFeeConfig,
+    pub sol_lookup_tables: Vec<String>,
+    pub x402_networks: Vec<X402Network>,
 }
 
 #[derive(CandidType, Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -96,6 +106,59 @@ pub enum Chain {
     Evm(u64),
 }
 
+/// The CAIP-2 network of a chain for x402 payments, with its facilitators' keys.
+#[derive(CandidType, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct X402Network {
+    pub chain: Chain,
+    // CAIP-2 network identifier, e.g. "eip155:1"
+    pub network: String,
+    // Facilitator keys for the network, the paying public keys when empty
+    pub paying_public_keys: Vec<ByteArrayB64<32>>,
+}
+
+#[derive(CandidType, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct X402NetworkInput {
+    pub chain: Chain,
+    pub network: String,
+    pub paying_public_keys: Vec<String>,
+}
+
+impl X402NetworkInput {
+    pub fn parse(self) -> Result<X402Network, String> {
+        let (namespace, reference) = x402::parse_network(&self.network)?;
+        let valid = match self.chain {
+            Chain::Icp(_) => namespace == "icp",
+            Chain::Sol(_) => namespace == "solana",
+            Chain::Evm(chain_id) => namespace == "eip155" && reference == chain_id.to_string(),
+        };
+        if !valid {
+            return Err(format!(
+                "network {} does not match chain {:?}",
+                self.network, self.chain
+            ));
+        }
+        Ok(X402Network {
+            chain: self.chain,
+            network: self.network,
+            paying_public_keys: x402::parse_public_keys(self.paying_public_keys)?,
+        })
+    }
+}
+
+impl Chain {
+    /// The CAIP-2 network of the chains supported by default.
+    pub fn default_x402_network(&self) -> Option<String> {
+        match self {
+            Chain::Icp(1) => Some("icp:1".to_string()),
+            Chain::Sol(0) => Some("solana:EtWTRABZaYq6iMfeYKouRu166VU2xqa1".to_string()),
+            Chain::Sol(1) => Some("solana:5eykt4UsFv8P8NJdTREpY1vzqKqZKvdp".to_string()),
+            Chain::Evm(8453) => Some("eip155:8453".to_string()),
+            Chain::Evm(84532) => Some("eip155:84532".to_string()),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum ChainAddress {
     Icp(Account), // ICP Account
@@ -117,6 +180,23 @@ impl Chain {
                 .map_err(|_| format!("Invalid EVM address: {address}")),
         }
     }
+
+    /// Returns the canonical form of a transaction id: the decimal block index on ICP,
+    /// the lowercase 0x-hex hash on EVM and the base58 signature on Solana.
+    pub fn parse_txid(&self, txid: &str) -> Result<String, String> {
+        match self {
+            Chain::Icp(_) => txid
+                .parse::<u64>()
+                .map(|id| id.to_string())
+                .map_err(|_| format!("Invalid block index: {txid}")),
+            Chain::Sol(_) => Signature::from_str(txid)
+                .map(|sig| sig.to_string())
+                .map_err(|_| format!("Invalid SOL signature: {txid}")),
+            Chain::Evm(_) => B256::from_str(txid)
+                .map(|hash| hash.to_string())
+                .map_err(|_| format!("Invalid EVM transaction hash: {txid}")),
+        }
+    }
 }
 
 impl std::fmt::Display for ChainAddress {
@@ -153,6 +233,36 @@ pub struct TokenInput {
 pub struct DepositInput {
     pub sender: String,
     pub txid: String,
+    // ICP only: the transfer must carry this memo when set
+    pub memo: Option<ByteBuf>,
+}
+
+/// EIP-3009 `transferWithAuthorization` signed by the holder, paying the auction address.
+/// The nonce commits to the depositing user: keccak256(caller principal bytes || salt).
+#[derive(CandidType, Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Eip3009Input {
+    pub from: String,
+    pub value: u128,
+    pub valid_after: u64,  // in seconds
+    pub valid_before: u64, // in seconds
+    pub nonce: String,     // 32 bytes in hex
+    pub salt: String,      // 32 bytes in hex
+    pub signature: String, // 65 bytes in hex: r || s || v
+}
+
+impl Eip3009Input {
+    /// Returns the nonce if it was derived for the caller, so that an authorization
+    /// seen on its way to the canister cannot be claimed by another user.
+    pub fn caller_nonce(&self, caller: &Principal) -> Result<[u8; 32], String> {
+        let nonce = B256::from_str(&self.nonce).map_err(|_| "Invalid nonce")?;
+        let salt = B256::from_str(&self.salt).map_err(|_| "Invalid salt")?;
+        let mut data = caller.as_slice().to_vec();
+        data.extend_from_slice(salt.as_slice());
+        if keccak256(&data) != nonce {
+            return Err("nonce is not derived from the caller and salt".to_string());
+        }
+        Ok(nonce.0)
+    }
 }
 
 #[derive(CandidType, Debug, Clone, PartialEq, Deserialize, Serialize)]
@@ -296,6 +406,8 @@ pub struct UserInfo {
     pub bound_addresses: Vec<String>,
     pub agreed_terms: bool,
     pub timestamp: u64,
+    // The user's own deposit address on the auction chain
+    pub deposit_address: Option<String>,
 }
 
 /// Bid Information
@@ -336,6 +448,8 @@ pub struct WithdrawTxInfo {
     pub user: Principal,
     pub recipient: String,
     pub amount: u128,
+    // Token-2022 transfer fee withheld from `amount`, the recipient receives the rest
+    pub fee: u128,
     pub txid: String,
     pub timestamp: u64,
 }
@@ -347,122 +461,179 @@ pub enum FinalizeKind {
     Transfer,
 }
 
-#[derive(CandidType, Clone, Serialize, Deserialize)]
-pub struct FinalizeOutput {
-    pub pool_id: String,
-    pub txid: String,
+#[derive(CandidType, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EvmFeeConfig {
+    // Number of latest blocks sampled by eth_feeHistory
+    pub fee_history_blocks: u64,
+    // Percentile of the priority fees paid in each sampled block, 1 ~ 99
+    pub reward_percentile: u8,
+    // Caps in wei, the canister never pays more than them
+    pub max_priority_fee_per_gas: Option<u128>,
+    pub max_fee_per_gas: Option<u128>,
+    // Raw currency units worth 1 native coin (10^18 wei), the canister only funds deposit
+    // addresses for gas when it is set
+    pub currency_per_native: Option<u128>,
+    // Deposits the canister pays gas for (sweeps and EIP-3009 authorizations) worth less
+    // than this multiple of the gas cost are refused
+    pub min_deposit_gas_multiple: u64,
+}
+
+impl Default for EvmFeeConfig {
+    fn default() -> Self {
+        Self {
+            fee_history_blocks: 10,
+            reward_percentile: 50,
+            max_priority_fee_per_gas: None,
+            max_fee_per_gas: None,
+            currency_per_native: None,
+            min_deposit_gas_multiple: 10,
+        }
+    }
 }
 
-#[derive(CandidType, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct X402PaymentOutput {
-    pub x402: ByteBufB64, // PaymentRequirementsResponse in CBOR
-    pub nonce: String,
-    pub timestamp: u64,
+impl EvmFeeConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.fee_history_blocks == 0 || self.fee_history_blocks > 1024 {
+            return Err("fee_history_blocks must be in 1 ~ 1024".to_string());
+        }
+        if self.reward_percentile == 0 || self.reward_percentile > 99 {
+            return Err("reward_percentile must be in 1 ~ 99".to_string());
+        }
+        if let (Some(priority), Some(max)) = (self.max_priority_fee_per_gas, self.max_fee_per_gas)
+            && priority > max
+        {
+            return Err("max_priority_fee_per_gas cannot exceed max_fee_per_gas".to_string());
+        }
+        if self.currency_per_native == Some(0) {
+            return Err("currency_per_native must be positive".to_string());
+        }
+        if self.min_deposit_gas_multiple == 0 {
+            return Err("min_deposit_gas_multiple must be positive".to_string());
+        }
+        Ok(())
+    }
+
+    /// The minimum currency amount worth depositing for the gas cost in wei, None if the
+    /// currency price of the native coin is unknown.
+    pub fn min_deposit_amount(&self, gas_cost: u128) -> Option<u128> {
+        let price = self.currency_per_native?;
+        Some(
+            gas_cost
+                .saturating_mul(self.min_deposit_gas_multiple as u128)
+                .saturating_mul(price)
+                / 1_000_000_000_000_000_000,
+        )
+    }
 }
 
-#[derive(CandidType, Clone, Serialize, Deserialize)]
-pub struct PayingResultInput {
-    pub result: ByteBufB64, // PaymentVerifyResult / PaymentSettleResult in CBOR,
-    pub signature: ByteBufB64, // Signature over (result || nonce || timestamp)
-    pub timestamp: u64,     // the timestamp from X402PaymentOutput
+#[derive(CandidType, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SolFeeConfig {
+    // Percentile of the recent prioritization fees paid for the written accounts, 0 ~ 100
+    pub priority_fee_percentile: u8,
+    // Bounds of the compute unit price in micro-lamports
+    pub min_compute_unit_price: u64,
+    pub max_compute_unit_price: u64,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct PaymentVerifyResult {
-    pub payment_requirements: PaymentRequirements,
-    pub verify_response: VerifyResponse,
-    pub nonce: String, // the nonce from X402PaymentOutput
+impl Default for SolFeeConfig {
+    fn default() -> Self {
+        Self {
+            priority_fee_percentile: 75,
+            min_compute_unit_price: 0,
+            max_compute_unit_price: 1_000_000,
+        }
+    }
 }
 
-#[derive(Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct PaymentSettleResult {
-    pub payment_requirements: PaymentRequirements,
-    pub settle_response: SettleResponse,
-    pub nonce: String, // the nonce from X402PaymentOutput
+impl SolFeeConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.priority_fee_percentile > 100 {
+            return Err("priority_fee_percentile must be in 0 ~ 100".to_string());
+        }
+        if self.min_compute_unit_price > self.max_compute_unit_price {
+            return Err("min_compute_unit_price cannot exceed max_compute_unit_price".to_string());
+        }
+        Ok(())
+    }
 }
 
 #[derive(CandidType, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct VerifyResponse {
-    pub is_valid: bool,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub payer: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub invalid_reason: Option<String>,
+pub struct FinalizeOutput {
+    pub pool_id: String,
+    pub txid: String,
 }
 
-#[derive(CandidType, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct SettleResponse {
-    pub success: bool,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error_reason: Option<String>,
-    pub transaction: String,
-    pub network: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub payer: Option<String>,
-}
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct PaymentRequired {
-    /// Protocol version identifier
-    pub x402_version: u8,
-    /// Human-readable error message explaining why payment is required
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error: Option<String>,
-    /// ResourceInfo object describing the protected resource
-    pub resource: ResourceInfo,
-    /// Array of payment requirement objects defining acceptable payment methods
-    pub accepts: Vec<PaymentRequirements>,
-    /// Protocol extensions data
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub extensions: Option<Extensions>,
-}
-
-/// Payment requirements set by the payment-gated endpoint for an acceptable payment.
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct PaymentRequirements {
-    /// Payment scheme identifier (e.g., "exact")
-    pub scheme: String,
-    /// Blockchain network identifier (e.g., "icp")
-    pub network: String,
-    /// Required payment amount in atomic token units
-    pub amount: String,
-    /// Token ledger canister address
-    pub asset: String,
-    /// Recipient wallet address for the payment
-    pub pay_to: String,
-    /// Maximum time allowed for payment completion in seconds
-    pub max_timeout_seconds: u64,
-    /// Scheme-specific additional information.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub extra: Option<Map<String, Value>>,
-}
-
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct ResourceInfo {
-    /// the protected resource, e.g., URL of the resource endpoint
-    pub url: String,
-    /// Human-readable description of the resource
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub description: Option<String>,
-    /// MIME type of the expected response
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub mime_type: Option<String>,
-}
-
-/// Describes additional extension data for x402 payment.
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Extensions {
-    /// Extension-specific data provided by the server
-    pub info: Map<String, Value>,
-    /// JSON Schema defining the expected structure of `info`
-    pub schema: Map<String, Value>,
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_x402_network_input() {
+        let input = |chain: Chain, network: &str| X402NetworkInput {
+            chain,
+            network: network.to_string(),
+            paying_public_keys: vec![],
+        };
+        assert!(input(Chain::Evm(1), "eip155:1").parse().is_ok());
+        assert!(input(Chain::Evm(56), "eip155:56").parse().is_ok());
+        assert!(input(Chain::Icp(0), "icp:testnet").parse().is_ok());
+        assert!(input(Chain::Evm(1), "eip155:56").parse().is_err());
+        assert!(input(Chain::Sol(1), "eip155:1").parse().is_err());
+        assert!(input(Chain::Icp(1), "icp").parse().is_err());
+
+        let mut bad = input(Chain::Evm(1), "eip155:1");
+        bad.paying_public_keys = vec!["invalid".to_string()];
+        assert!(bad.parse().is_err());
+
+        assert_eq!(
+            Chain::Evm(8453).default_x402_network().as_deref(),
+            Some("eip155:8453")
+        );
+        assert_eq!(Chain::Evm(1).default_x402_network(), None);
+    }
+
+    #[test]
+    fn test_eip3009_caller_nonce() {
+        let caller = Principal::from_slice(&[1]);
+        let salt = B256::repeat_byte(7);
+        let mut data = caller.as_slice().to_vec();
+        data.extend_from_slice(salt.as_slice());
+        let mut input = Eip3009Input {
+            from: "0x0000000000000000000000000000000000000002".to_string(),
+            value: 1_000_000,
+            valid_after: 0,
+            valid_before: u64::MAX,
+            nonce: keccak256(&data).to_string(),
+            salt: salt.to_string(),
+            signature: String::new(),
+        };
+        assert_eq!(input.caller_nonce(&caller).unwrap(), keccak256(&data).0);
+        assert!(input.caller_nonce(&Principal::from_slice(&[2])).is_err());
+
+        input.salt = B256::repeat_byte(8).to_string();
+        assert!(input.caller_nonce(&caller).is_err());
+        input.salt = "0x01".to_string();
+        assert_eq!(input.caller_nonce(&caller).unwrap_err(), "Invalid salt");
+    }
+
+    #[test]
+    fn test_min_deposit_amount() {
+        let mut cfg = EvmFeeConfig::default();
+        // 60_000 gas at 10 gwei
+        let gas_cost = 60_000u128 * 10_000_000_000;
+        assert_eq!(cfg.min_deposit_amount(gas_cost), None);
+
+        // 1 ETH = 3000 USDC
+        cfg.currency_per_native = Some(3_000_000_000);
+        assert_eq!(cfg.min_deposit_amount(gas_cost), Some(18_000_000));
+        cfg.min_deposit_gas_multiple = 1;
+        assert_eq!(cfg.min_deposit_amount(gas_cost), Some(1_800_000));
+        assert!(cfg.validate().is_ok());
+
+        cfg.min_deposit_gas_multiple = 0;
+        assert!(cfg.validate().is_err());
+        cfg.min_deposit_gas_multiple = 1;
+        cfg.currency_per_native = Some(0);
+        assert!(cfg.validate().is_err());
+    }
 }
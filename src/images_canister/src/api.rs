@@ -13,8 +13,13 @@ fn get_image_metadata(id: u64) -> Result<types::ImageMetadata, String> {
     store::state::get_image_metadata(id).ok_or_else(|| format!("Image with id {} not found", id))
 }
 
+/// Stores the image of a token, paid by size through the tokens canister.
 #[ic_cdk::update]
-async fn update_image(token_id: u64, input: types::ImageInput) -> Result<u64, String> {
+async fn update_image(
+    token_id: u64,
+    input: types::ImageInput,
+    payment: Option<x402::PayingResultInput>,
+) -> Result<u64, String> {
     let caller = helper::msg_caller()?;
     let now_ms = ic_cdk::api::time() / 1_000_000;
     if input.body.len() > 64 * 1024 {
@@ -22,12 +27,14 @@ async fn update_image(token_id: u64, input: types::ImageInput) -> Result<u64, St
     }
     input.validate()?;
     let canister = store::state::with(|s| s.tokens_canister);
-    let rt: Result<Vec<String>, String> =
-        helper::call(canister, "check_permission", (token_id, caller), 0).await?;
+    let rt: Result<Vec<String>, String> = helper::call(
+        canister,
+        "charge_image_storage",
+        (token_id, caller, input.body.len() as u64, payment),
+        0,
+    )
+    .await?;
     let locations = rt?;
-    if locations.is_empty() {
-        return Err("No location on token for storing images".to_string());
-    }
 
     let image = store::ImageMetadataState {
         name: input.name,
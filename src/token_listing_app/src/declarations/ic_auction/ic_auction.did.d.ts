@@ -48,7 +48,11 @@ export type CanisterArgs = { 'Upgrade' : UpgradeArgs } |
 export type Chain = { 'Evm' : bigint } |
   { 'Icp' : bigint } |
   { 'Sol' : bigint };
-export interface DepositInput { 'txid' : string, 'sender' : string }
+export interface DepositInput {
+  'memo' : [] | [Uint8Array | number[]],
+  'txid' : string,
+  'sender' : string,
+}
 export interface DepositTxInfo {
   'txid' : string,
   'user' : Principal,
@@ -56,6 +60,23 @@ export interface DepositTxInfo {
   'timestamp' : bigint,
   'amount' : bigint,
 }
+export interface Eip3009Input {
+  'valid_after' : bigint,
+  'signature' : string,
+  'valid_before' : bigint,
+  'value' : bigint,
+  'from' : string,
+  'salt' : string,
+  'nonce' : string,
+}
+export interface EvmFeeConfig {
+  'currency_per_native' : [] | [bigint],
+  'max_priority_fee_per_gas' : [] | [bigint],
+  'reward_percentile' : number,
+  'max_fee_per_gas' : [] | [bigint],
+  'min_deposit_gas_multiple' : bigint,
+  'fee_history_blocks' : bigint,
+}
 export type FinalizeKind = { 'CreatePool' : string } |
   { 'Transfer' : null };
 export interface FinalizeOutput { 'txid' : string, 'pool_id' : string }
@@ -76,30 +97,35 @@ export interface ProjectInput {
   'detail' : string,
   'restricted_countries' : Array<string>,
 }
-export type Result = { 'Ok' : [] | [FinalizeOutput] } |
+export type Result = { 'Ok' : Array<WithdrawTxInfo> } |
   { 'Err' : string };
-export type Result_1 = { 'Ok' : null } |
+export type Result_1 = { 'Ok' : [] | [FinalizeOutput] } |
   { 'Err' : string };
-export type Result_10 = { 'Ok' : string } |
+export type Result_10 = { 'Ok' : UserInfo } |
   { 'Err' : string };
 export type Result_11 = { 'Ok' : X402PaymentOutput } |
   { 'Err' : string };
-export type Result_2 = { 'Ok' : WithdrawTxInfo } |
+export type Result_2 = { 'Ok' : null } |
   { 'Err' : string };
-export type Result_3 = { 'Ok' : BidInfo } |
+export type Result_3 = { 'Ok' : WithdrawTxInfo } |
   { 'Err' : string };
-export type Result_4 = { 'Ok' : Array<BidInfo> } |
+export type Result_4 = { 'Ok' : BidInfo } |
   { 'Err' : string };
-export type Result_5 = { 'Ok' : bigint } |
+export type Result_5 = { 'Ok' : Array<BidInfo> } |
   { 'Err' : string };
-export type Result_6 = { 'Ok' : StateInfo } |
+export type Result_6 = { 'Ok' : bigint } |
   { 'Err' : string };
-export type Result_7 = { 'Ok' : Array<DepositTxInfo> } |
+export type Result_7 = { 'Ok' : string } |
   { 'Err' : string };
-export type Result_8 = { 'Ok' : UserInfo } |
+export type Result_8 = { 'Ok' : StateInfo } |
   { 'Err' : string };
-export type Result_9 = { 'Ok' : Array<WithdrawTxInfo> } |
+export type Result_9 = { 'Ok' : Array<DepositTxInfo> } |
   { 'Err' : string };
+export interface SolFeeConfig {
+  'min_compute_unit_price' : bigint,
+  'max_compute_unit_price' : bigint,
+  'priority_fee_percentile' : number,
+}
 export interface StateInfo {
   'url' : string,
   'token' : string,
@@ -109,6 +135,7 @@ export interface StateInfo {
   'total_withdrawn_token' : bigint,
   'token_logo_url' : string,
   'token_symbol' : string,
+  'x402_networks' : Array<X402Network>,
   'governance_canister' : [] | [Principal],
   'chain' : Chain,
   'name' : string,
@@ -117,6 +144,7 @@ export interface StateInfo {
   'icp_address' : Principal,
   'currency_symbol' : string,
   'description' : string,
+  'evm_fee_config' : EvmFeeConfig,
   'detail' : string,
   'chain_providers' : Array<string>,
   'currency_decimals' : number,
@@ -129,6 +157,8 @@ export interface StateInfo {
   'token_decimals' : number,
   'total_deposited_currency' : bigint,
   'paying_public_keys' : Array<Uint8Array | number[]>,
+  'sol_lookup_tables' : Array<string>,
+  'sol_fee_config' : SolFeeConfig,
   'token_name' : string,
   'finalize_output' : [] | [FinalizeOutput],
   'payment_requirements_extra' : [] | [string],
@@ -149,6 +179,7 @@ export interface TokenInput {
 export interface UpgradeArgs { 'governance_canister' : [] | [Principal] }
 export interface UserInfo {
   'token_amount' : bigint,
+  'deposit_address' : [] | [string],
   'currency_amount' : bigint,
   'agreed_terms' : boolean,
   'timestamp' : bigint,
@@ -157,6 +188,7 @@ export interface UserInfo {
 export interface WithdrawInput { 'recipient' : string }
 export interface WithdrawTxInfo {
   'id' : bigint,
+  'fee' : bigint,
   'kind' : number,
   'txid' : string,
   'user' : Principal,
@@ -164,56 +196,86 @@ export interface WithdrawTxInfo {
   'timestamp' : bigint,
   'amount' : bigint,
 }
+export interface X402Network {
+  'chain' : Chain,
+  'network' : string,
+  'paying_public_keys' : Array<Uint8Array | number[]>,
+}
+export interface X402NetworkInput {
+  'chain' : Chain,
+  'network' : string,
+  'paying_public_keys' : Array<string>,
+}
 export interface X402PaymentOutput {
   'x402' : Uint8Array | number[],
   'nonce' : string,
   'timestamp' : bigint,
 }
 export interface _SERVICE {
-  'admin_finalize_auction' : ActorMethod<[], Result>,
-  'admin_set_auction' : ActorMethod<[AuctionConfig], Result_1>,
-  'admin_set_currency' : ActorMethod<[TokenInput], Result_1>,
-  'admin_set_finalize' : ActorMethod<[FinalizeKind], Result_1>,
-  'admin_set_paying_public_keys' : ActorMethod<[Array<string>], Result_1>,
-  'admin_set_payment_requirements_extra' : ActorMethod<[string], Result_1>,
-  'admin_set_project' : ActorMethod<[ProjectInput], Result_1>,
-  'admin_set_providers' : ActorMethod<[Array<string>], Result_1>,
-  'admin_set_token' : ActorMethod<[TokenInput], Result_1>,
-  'admin_setup_auction' : ActorMethod<[], Result_1>,
-  'admin_sweep_currency' : ActorMethod<[], Result_2>,
-  'admin_sweep_token' : ActorMethod<[], Result_2>,
+  'admin_distribute_token' : ActorMethod<[], Result>,
+  'admin_finalize_auction' : ActorMethod<[], Result_1>,
+  'admin_refund_currency' : ActorMethod<[], Result>,
+  'admin_set_auction' : ActorMethod<[AuctionConfig], Result_2>,
+  'admin_set_currency' : ActorMethod<[TokenInput], Result_2>,
+  'admin_set_evm_fee_config' : ActorMethod<[EvmFeeConfig], Result_2>,
+  'admin_set_finalize' : ActorMethod<[FinalizeKind], Result_2>,
+  'admin_set_paying_public_keys' : ActorMethod<[Array<string>], Result_2>,
+  'admin_set_payment_requirements_extra' : ActorMethod<[string], Result_2>,
+  'admin_set_project' : ActorMethod<[ProjectInput], Result_2>,
+  'admin_set_providers' : ActorMethod<[Array<string>], Result_2>,
+  'admin_set_sol_fee_config' : ActorMethod<[SolFeeConfig], Result_2>,
+  'admin_set_sol_lookup_tables' : ActorMethod<[Array<string>], Result_2>,
+  'admin_set_token' : ActorMethod<[TokenInput], Result_2>,
+  'admin_set_x402_networks' : ActorMethod<[Array<X402NetworkInput>], Result_2>,
+  'admin_setup_auction' : ActorMethod<[], Result_2>,
+  'admin_sweep_currency' : ActorMethod<[], Result_3>,
+  'admin_sweep_token' : ActorMethod<[], Result_3>,
   'auction_info' : ActorMethod<[], [] | [AuctionInfo]>,
-  'claim' : ActorMethod<[bigint], Result_3>,
-  'claim_all' : ActorMethod<[], Result_4>,
-  'deposit_currency' : ActorMethod<[DepositInput], Result_5>,
+  'claim' : ActorMethod<[bigint], Result_4>,
+  'claim_all' : ActorMethod<[], Result_5>,
+  'deposit_currency' : ActorMethod<[DepositInput], Result_6>,
+  'deposit_currency_eip3009' : ActorMethod<[Eip3009Input], Result_7>,
+  'deposit_currency_icrc2' : ActorMethod<[bigint], Result_6>,
   'estimate_max_price' : ActorMethod<[bigint], [bigint, bigint]>,
   'get_grouped_bids' : ActorMethod<[[] | [bigint]], Array<[bigint, bigint]>>,
   'get_snapshots' : ActorMethod<[bigint, bigint], Array<AuctionSnapshot>>,
-  'info' : ActorMethod<[], Result_6>,
-  'my_bids' : ActorMethod<[], Result_4>,
-  'my_deposits' : ActorMethod<[], Result_7>,
-  'my_info' : ActorMethod<[], Result_8>,
-  'my_withdraws' : ActorMethod<[], Result_9>,
-  'submit_bid' : ActorMethod<[bigint, bigint], Result_3>,
-  'validate_admin_set_auction' : ActorMethod<[AuctionConfig], Result_10>,
-  'validate_admin_set_currency' : ActorMethod<[TokenInput], Result_10>,
-  'validate_admin_set_finalize' : ActorMethod<[FinalizeKind], Result_10>,
+  'info' : ActorMethod<[], Result_8>,
+  'my_bids' : ActorMethod<[], Result_5>,
+  'my_deposit_address' : ActorMethod<[], Result_7>,
+  'my_deposits' : ActorMethod<[], Result_9>,
+  'my_info' : ActorMethod<[], Result_10>,
+  'my_withdraws' : ActorMethod<[], Result>,
+  'submit_bid' : ActorMethod<[bigint, bigint], Result_4>,
+  'sweep_deposit' : ActorMethod<[], Result_6>,
+  'validate_admin_set_auction' : ActorMethod<[AuctionConfig], Result_7>,
+  'validate_admin_set_currency' : ActorMethod<[TokenInput], Result_7>,
+  'validate_admin_set_evm_fee_config' : ActorMethod<[EvmFeeConfig], Result_7>,
+  'validate_admin_set_finalize' : ActorMethod<[FinalizeKind], Result_7>,
   'validate_admin_set_paying_public_keys' : ActorMethod<
     [Array<string>],
-    Result_10
+    Result_7
   >,
   'validate_admin_set_payment_requirements_extra' : ActorMethod<
     [string],
-    Result_10
+    Result_7
+  >,
+  'validate_admin_set_project' : ActorMethod<[ProjectInput], Result_7>,
+  'validate_admin_set_providers' : ActorMethod<[Array<string>], Result_7>,
+  'validate_admin_set_sol_fee_config' : ActorMethod<[SolFeeConfig], Result_7>,
+  'validate_admin_set_sol_lookup_tables' : ActorMethod<
+    [Array<string>],
+    Result_7
+  >,
+  'validate_admin_set_token' : ActorMethod<[TokenInput], Result_7>,
+  'validate_admin_set_x402_networks' : ActorMethod<
+    [Array<X402NetworkInput>],
+    Result_7
   >,
-  'validate_admin_set_project' : ActorMethod<[ProjectInput], Result_10>,
-  'validate_admin_set_providers' : ActorMethod<[Array<string>], Result_10>,
-  'validate_admin_set_token' : ActorMethod<[TokenInput], Result_10>,
-  'validate_empty_input' : ActorMethod<[], Result_10>,
-  'withdraw_currency' : ActorMethod<[WithdrawInput], Result_2>,
-  'withdraw_token' : ActorMethod<[WithdrawInput], Result_2>,
-  'x402_bind_address' : ActorMethod<[PayingResultInput], Result_1>,
-  'x402_deposit_currency' : ActorMethod<[PayingResultInput], Result_5>,
+  'validate_empty_input' : ActorMethod<[], Result_7>,
+  'withdraw_currency' : ActorMethod<[WithdrawInput], Result_3>,
+  'withdraw_token' : ActorMethod<[WithdrawInput], Result_3>,
+  'x402_bind_address' : ActorMethod<[PayingResultInput], Result_2>,
+  'x402_deposit_currency' : ActorMethod<[PayingResultInput], Result_6>,
   'x402_payment' : ActorMethod<[bigint, boolean], Result_11>,
 }
 export declare const idlFactory: IDL.InterfaceFactory;
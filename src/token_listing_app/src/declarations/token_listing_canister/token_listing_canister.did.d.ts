@@ -36,6 +36,12 @@ export type CanisterArgs = { 'Upgrade' : UpgradeArgs } |
 export type Chain = { 'Evm' : bigint } |
   { 'Icp' : bigint } |
   { 'Sol' : bigint };
+export interface EvmFeeConfig {
+  'max_priority_fee_per_gas' : [] | [bigint],
+  'reward_percentile' : number,
+  'max_fee_per_gas' : [] | [bigint],
+  'fee_history_blocks' : bigint,
+}
 export interface InitArgs {
   'governance_canister' : [] | [Principal],
   'key_name' : string,
@@ -46,6 +52,11 @@ export type Result_1 = { 'Ok' : StateInfo } |
   { 'Err' : string };
 export type Result_2 = { 'Ok' : string } |
   { 'Err' : string };
+export interface SolFeeConfig {
+  'min_compute_unit_price' : bigint,
+  'max_compute_unit_price' : bigint,
+  'priority_fee_percentile' : number,
+}
 export interface StateInfo {
   'sol_address' : string,
   'evm_address' : string,
@@ -53,20 +64,25 @@ export interface StateInfo {
   'daos' : Array<Principal>,
   'auctions' : Array<AuctionId>,
   'icp_address' : Principal,
+  'evm_fee_config' : EvmFeeConfig,
   'chain_providers' : Array<[Chain, Array<string>]>,
   'key_name' : string,
   'paying_public_keys' : Array<Uint8Array | number[]>,
   'storages' : Array<Principal>,
+  'sol_fee_config' : SolFeeConfig,
 }
 export interface UpgradeArgs { 'governance_canister' : [] | [Principal] }
 export interface _SERVICE {
   'admin_set_auction' : ActorMethod<[AuctionInfo], Result>,
+  'admin_set_evm_fee_config' : ActorMethod<[EvmFeeConfig], Result>,
   'admin_set_paying_public_keys' : ActorMethod<[Array<string>], Result>,
   'admin_set_providers' : ActorMethod<[Chain, Array<string>], Result>,
+  'admin_set_sol_fee_config' : ActorMethod<[SolFeeConfig], Result>,
   'get_auction' : ActorMethod<[[] | [AuctionId]], [] | [AuctionInfo]>,
   'info' : ActorMethod<[], Result_1>,
   'list_auctions' : ActorMethod<[bigint, [] | [AuctionId]], Array<AuctionInfo>>,
   'validate_admin_set_auction' : ActorMethod<[AuctionInfo], Result_2>,
+  'validate_admin_set_evm_fee_config' : ActorMethod<[EvmFeeConfig], Result_2>,
   'validate_admin_set_paying_public_keys' : ActorMethod<
     [Array<string>],
     Result_2
@@ -75,6 +91,7 @@ export interface _SERVICE {
     [Chain, Array<string>],
     Result_2
   >,
+  'validate_admin_set_sol_fee_config' : ActorMethod<[SolFeeConfig], Result_2>,
   'validate_empty_input' : ActorMethod<[], Result_2>,
 }
 export declare const idlFactory: IDL.InterfaceFactory;
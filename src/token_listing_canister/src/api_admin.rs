@@ -1,12 +1,6 @@
-use std::str::FromStr;
-
-use ic_auth_types::ByteArrayB64;
 use url::Url;
 
-use crate::{
-    helper::{format_error, pretty_format},
-    store, types,
-};
+use crate::{helper::pretty_format, store, types};
 
 #[ic_cdk::update(guard = "is_controller")]
 fn admin_set_providers(chain: types::Chain, providers: Vec<String>) -> Result<(), String> {
@@ -39,10 +33,7 @@ fn validate_admin_set_providers(
 
 #[ic_cdk::update(guard = "is_controller")]
 fn admin_set_paying_public_keys(public_keys: Vec<String>) -> Result<(), String> {
-    let mut paying_public_keys = Vec::new();
-    for key in public_keys {
-        paying_public_keys.push(ByteArrayB64::from_str(&key).map_err(format_error)?)
-    }
+    let paying_public_keys = x402::parse_public_keys(public_keys)?;
 
     store::state::with_mut(|s| {
         s.paying_public_keys = paying_public_keys;
@@ -52,9 +43,7 @@ fn admin_set_paying_public_keys(public_keys: Vec<String>) -> Result<(), String>
 
 #[ic_cdk::update]
 fn validate_admin_set_paying_public_keys(public_keys: Vec<String>) -> Result<String, String> {
-    for key in &public_keys {
-        ByteArrayB64::<32>::from_str(key).map_err(format_error)?;
-    }
+    x402::parse_public_keys(public_keys.clone())?;
     pretty_format(&(public_keys,))
 }
 
@@ -74,6 +63,36 @@ fn validate_empty_input() -> Result<String, String> {
     pretty_format(&())
 }
 
+#[ic_cdk::update(guard = "is_controller")]
+fn admin_set_evm_fee_config(config: types::EvmFeeConfig) -> Result<(), String> {
+    config.validate()?;
+    store::state::with_mut(|s| {
+        s.evm_fee_config = config;
+        Ok(())
+    })
+}
+
+#[ic_cdk::update]
+fn validate_admin_set_evm_fee_config(config: types::EvmFeeConfig) -> Result<String, String> {
+    config.validate()?;
+    pretty_format(&(config,))
+}
+
+#[ic_cdk::update(guard = "is_controller")]
+fn admin_set_sol_fee_config(config: types::SolFeeConfig) -> Result<(), String> {
+    config.validate()?;
+    store::state::with_mut(|s| {
+        s.sol_fee_config = config;
+        Ok(())
+    })
+}
+
+#[ic_cdk::update]
+fn validate_admin_set_sol_fee_config(config: types::SolFeeConfig) -> Result<String, String> {
+    config.validate()?;
+    pretty_format(&(config,))
+}
+
 fn is_controller() -> Result<(), String> {
     let caller = ic_cdk::api::msg_caller();
     if ic_cdk::api::is_controller(&caller)
@@ -34,6 +34,7 @@ fn init(args: Option<CanisterArgs>) {
 
     store::state::init_http_certified_data();
     ic_cdk_timers::set_timer(Duration::from_secs(0), store::state::init_public_key());
+    store::state::set_evm_nonce_timer();
 }
 
 #[ic_cdk::pre_upgrade]
@@ -60,4 +61,5 @@ fn post_upgrade(args: Option<CanisterArgs>) {
     }
 
     store::state::init_http_certified_data();
+    store::state::set_evm_nonce_timer();
 }
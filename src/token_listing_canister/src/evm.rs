@@ -1,8 +1,10 @@
-use alloy_primitives::{U256, hex::FromHex};
-use alloy_rpc_types_eth::TransactionReceipt;
+use alloy_primitives::{Bytes, U256, hex::FromHex};
+use alloy_rpc_types_eth::FeeHistory;
+use alloy_sol_types::SolCall;
 use ic_cdk::management_canister::{HttpHeader, HttpMethod, HttpRequestArgs};
-use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use serde_json::Value;
+use std::collections::BTreeSet;
 
 use crate::{
     helper::APP_AGENT,
@@ -10,7 +12,11 @@ use crate::{
     types::{RPCRequest, RPCResponse},
 };
 
-pub use alloy_primitives::{Address, TxHash};
+pub mod abi;
+
+pub use alloy_primitives::Address;
+
+use abi::IERC20;
 
 #[allow(unused)]
 pub struct EvmClient<T: HttpOutcall> {
@@ -44,6 +50,7 @@ impl<H: HttpOutcall> EvmClient<H> {
         hex_to_u64(&res)
     }
 
+    #[allow(unused)]
     pub async fn gas_price(&self, now_ms: u64) -> Result<u128, String> {
         let res: String = self
             .call(format!("eth_gasPrice-{}", now_ms), "eth_gasPrice", &[])
@@ -62,6 +69,48 @@ impl<H: HttpOutcall> EvmClient<H> {
         hex_to_u128(&res)
     }
 
+    /// Returns the base fees and the `reward_percentile` priority fee of the latest blocks.
+    pub async fn fee_history(
+        &self,
+        now_ms: u64,
+        block_count: u64,
+        reward_percentile: u8,
+    ) -> Result<FeeHistory, String> {
+        self.call(
+            format!("eth_feeHistory-{}", now_ms),
+            "eth_feeHistory",
+            &[
+                format!("{:#x}", block_count).into(),
+                "latest".into(),
+                serde_json::json!([reward_percentile]),
+            ],
+        )
+        .await
+    }
+
+    pub async fn estimate_gas(
+        &self,
+        now_ms: u64,
+        from: &Address,
+        to: &Address,
+        data: String,
+    ) -> Result<u64, String> {
+        let call_object = serde_json::json!({
+            "from": from.to_string(),
+            "to": to.to_string(),
+            "data": data,
+        });
+
+        let res: String = self
+            .call(
+                format!("eth_estimateGas-{}", now_ms),
+                "eth_estimateGas",
+                &[call_object],
+            )
+            .await?;
+        hex_to_u64(&res)
+    }
+
     #[allow(unused)]
     pub async fn block_number(&self, now_ms: u64) -> Result<u64, String> {
         let res: String = self
@@ -101,19 +150,6 @@ impl<H: HttpOutcall> EvmClient<H> {
         hex_to_u128(&res)
     }
 
-    pub async fn get_transaction_receipt(
-        &self,
-        now_ms: u64,
-        tx_hash: &TxHash,
-    ) -> Result<Option<TransactionReceipt>, String> {
-        self.call(
-            format!("eth_getTransactionReceipt-{}", now_ms),
-            "eth_getTransactionReceipt",
-            &[tx_hash.to_string().into()],
-        )
-        .await
-    }
-
     pub async fn send_raw_transaction(
         &self,
         now_ms: u64,
@@ -149,44 +185,67 @@ impl<H: HttpOutcall> EvmClient<H> {
         <Vec<u8>>::from_hex(res).map_err(|err| err.to_string())
     }
 
-    // pub async fn erc20_name(&self, now_ms: u64, contract: &Address) -> Result<String, String> {
-    //     let res = self
-    //         .call_contract(now_ms, contract, "0x06fdde03".to_string())
-    //         .await?;
-    //     decode_abi_string(&res)
-    // }
+    /// Calls a view function of `contract` and decodes its return value.
+    pub async fn call_sol<C: SolCall>(
+        &self,
+        now_ms: u64,
+        contract: &Address,
+        call: &C,
+    ) -> Result<C::Return, String> {
+        let call_data = Bytes::from(call.abi_encode()).to_string();
+        let res = self.call_contract(now_ms, contract, call_data).await?;
+        abi::decode_return::<C>(&res)
+    }
+
+    #[allow(unused)]
+    pub async fn erc20_name(&self, now_ms: u64, contract: &Address) -> Result<String, String> {
+        self.call_sol(now_ms, contract, &IERC20::nameCall {}).await
+    }
 
     #[allow(unused)]
     pub async fn erc20_symbol(&self, now_ms: u64, contract: &Address) -> Result<String, String> {
-        let res = self
-            .call_contract(now_ms, contract, "0x95d89b41".to_string())
-            .await?;
-        decode_abi_string(&res)
+        let call_data = Bytes::from(IERC20::symbolCall {}.abi_encode()).to_string();
+        let res = self.call_contract(now_ms, contract, call_data).await?;
+        abi::decode_symbol(&res)
     }
 
     #[allow(unused)]
     pub async fn erc20_decimals(&self, now_ms: u64, contract: &Address) -> Result<u8, String> {
-        let res = self
-            .call_contract(now_ms, contract, "0x313ce567".to_string())
-            .await?;
-        let v = decode_abi_uint(&res)?;
-        u8::try_from(v).map_err(|_| "decimals overflow u8".to_string())
+        self.call_sol(now_ms, contract, &IERC20::decimalsCall {})
+            .await
     }
 
-    pub async fn erc20_balance(
+    #[allow(unused)]
+    pub async fn erc20_total_supply(
         &self,
         now_ms: u64,
         contract: &Address,
-        address: &Address,
     ) -> Result<u128, String> {
-        let addr_hex = address.to_string();
-        let addr_hex = addr_hex.strip_prefix("0x").unwrap_or(&addr_hex);
-        // balanceOf(address): 70a08231
-        let call_data = format!("0x70a08231000000000000000000000000{}", addr_hex);
+        let v = self
+            .call_sol(now_ms, contract, &IERC20::totalSupplyCall {})
+            .await?;
+        u128::try_from(v).map_err(|_| "total supply overflow u128".to_string())
+    }
 
-        let res = self.call_contract(now_ms, contract, call_data).await?;
-        let v = decode_abi_uint(&res)?;
-        u128::try_from(v).map_err(|_| "balance overflow u128".to_string())
+    #[allow(unused)]
+    pub async fn erc20_allowance(
+        &self,
+        now_ms: u64,
+        contract: &Address,
+        owner: &Address,
+        spender: &Address,
+    ) -> Result<u128, String> {
+        let v = self
+            .call_sol(
+                now_ms,
+                contract,
+                &IERC20::allowanceCall {
+                    owner: *owner,
+                    spender: *spender,
+                },
+            )
+            .await?;
+        u128::try_from(v).map_err(|_| "allowance overflow u128".to_string())
     }
 
     pub async fn call<T: DeserializeOwned>(
@@ -285,67 +344,223 @@ impl<H: HttpOutcall> EvmClient<H> {
     }
 }
 
-pub fn encode_erc20_transfer(to: &Address, value: u128) -> Vec<u8> {
-    const TRANSFER_SELECTOR: [u8; 4] = [0xa9, 0x05, 0x9c, 0xbb]; // keccak256("transfer(address,uint256)")[:4]
+/// Kinds of outgoing transactions, each with its own gas limit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EvmTxKind {
+    Transfer,
+    Erc20Transfer,
+    #[allow(unused)]
+    Erc20Approve,
+    #[allow(unused)]
+    CreatePool,
+}
 
-    let mut call_data = Vec::with_capacity(4 + 32 + 32);
-    call_data.extend_from_slice(&TRANSFER_SELECTOR);
+impl EvmTxKind {
+    /// Fallback gas limit when `eth_estimateGas` is unavailable.
+    pub fn default_gas_limit(&self) -> u64 {
+        match self {
+            EvmTxKind::Transfer => 21_000,
+            EvmTxKind::Erc20Transfer => 84_000, // sample: ~53,696
+            EvmTxKind::Erc20Approve => 72_000,  // sample: ~46,000
+            EvmTxKind::CreatePool => 6_000_000,
+        }
+    }
 
-    let mut padded_to = [0u8; 32];
-    padded_to[12..].copy_from_slice(to.as_slice());
-    call_data.extend_from_slice(&padded_to);
+    /// Whether a failed `eth_estimateGas` rejects the transaction. The estimation proves
+    /// that authorizations and pool creations succeed, plain transfers fall back to the
+    /// default gas limit.
+    pub fn requires_estimation(&self) -> bool {
+        !matches!(
+            self,
+            EvmTxKind::Transfer | EvmTxKind::Erc20Transfer | EvmTxKind::Erc20Approve
+        )
+    }
 
-    let value_bytes = U256::from(value).to_be_bytes::<32>();
-    call_data.extend_from_slice(&value_bytes);
+    /// Gas limit with a 20% margin over the estimation, never more than twice the fallback.
+    pub fn gas_limit(&self, estimated: Option<u64>) -> u64 {
+        match (self, estimated) {
+            (EvmTxKind::Transfer, _) | (_, None) => self.default_gas_limit(),
+            (_, Some(gas)) => (gas + gas / 5)
+                .max(21_000)
+                .min(self.default_gas_limit() * 2),
+        }
+    }
+}
 
-    call_data
+/// Returns the median of the sampled priority fees in `history`, ignoring empty blocks.
+pub fn priority_fee_from_history(history: &FeeHistory) -> Option<u128> {
+    let mut fees: Vec<u128> = history
+        .reward
+        .as_ref()?
+        .iter()
+        .filter_map(|r| r.first().copied())
+        .filter(|fee| *fee > 0)
+        .collect();
+    if fees.is_empty() {
+        return None;
+    }
+    fees.sort_unstable();
+    Some(fees[fees.len() / 2])
 }
 
-fn hex_to_u64(s: &str) -> Result<u64, String> {
-    let s = s.strip_prefix("0x").unwrap_or(s);
-    u64::from_str_radix(s, 16).map_err(|err| err.to_string())
+/// Returns (max_fee_per_gas, max_priority_fee_per_gas) for the next block, bounded by the caps.
+pub fn eip1559_fees(
+    base_fee_per_gas: u128,
+    priority_fee_per_gas: u128,
+    max_priority_fee_per_gas: Option<u128>,
+    max_fee_per_gas: Option<u128>,
+) -> Result<(u128, u128), String> {
+    // 20% tip margin to get included quickly
+    let mut priority_fee = priority_fee_per_gas + priority_fee_per_gas / 5;
+    if let Some(cap) = max_priority_fee_per_gas {
+        priority_fee = priority_fee.min(cap);
+    }
+    // base fee can double in ~6 full blocks
+    let mut max_fee = base_fee_per_gas * 2 + priority_fee;
+    if let Some(cap) = max_fee_per_gas {
+        if base_fee_per_gas + priority_fee > cap {
+            return Err(format!(
+                "base fee {base_fee_per_gas} plus priority fee {priority_fee} exceeds the max fee per gas {cap}"
+            ));
+        }
+        max_fee = max_fee.min(cap);
+    }
+    Ok((max_fee, priority_fee))
 }
 
-fn hex_to_u128(s: &str) -> Result<u128, String> {
-    let s = s.strip_prefix("0x").unwrap_or(s);
-    u128::from_str_radix(s, 16).map_err(|err| err.to_string())
+// Reconcile the local nonces with the chain at most every 10 minutes
+pub const NONCE_SYNC_INTERVAL_MS: u64 = 10 * 60 * 1000;
+// A larger distance from the chain nonce means the local state is unusable
+const MAX_NONCE_DISTANCE: u64 = 1024;
+
+/// Allocates transaction nonces locally for one sender address, so that concurrent
+/// outgoing transactions never receive the same nonce.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NonceManager {
+    // The lowest nonce that has never been allocated
+    #[serde(rename = "n")]
+    pub next_nonce: u64,
+    // Allocated nonces that are not yet confirmed on-chain
+    #[serde(rename = "p")]
+    pub pending: BTreeSet<u64>,
+    // Nonces that were never used on-chain, they are reused before `next_nonce`
+    #[serde(rename = "r")]
+    pub released: BTreeSet<u64>,
+    // Last reconciliation with the chain, in milliseconds
+    #[serde(rename = "s")]
+    pub synced_at: u64,
 }
 
-#[allow(dead_code)]
-fn decode_abi_string(bytes: &[u8]) -> Result<String, String> {
-    if bytes.len() < 64 {
-        return Err("abi string result too short".to_string());
+impl NonceManager {
+    pub fn needs_sync(&self, now_ms: u64) -> bool {
+        self.synced_at + NONCE_SYNC_INTERVAL_MS < now_ms
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.pending.is_empty() && self.released.is_empty()
     }
 
-    let offset = U256::try_from_be_slice(&bytes[0..32]).unwrap();
-    let offset = usize::try_from(offset).unwrap_or(isize::MAX as usize);
-    if bytes.len() < offset + 32 {
-        return Err("abi string length out of bounds".to_string());
+    /// Reconciles with `chain_nonce`, the transaction count of the address on-chain.
+    /// Nonces below it are done; unallocated holes above it become reusable gaps.
+    pub fn reconcile(&mut self, chain_nonce: u64, now_ms: u64) {
+        self.synced_at = now_ms;
+        if self.next_nonce <= chain_nonce || self.next_nonce > chain_nonce + MAX_NONCE_DISTANCE {
+            self.next_nonce = chain_nonce;
+            self.pending.clear();
+            self.released.clear();
+            return;
+        }
+
+        self.pending.retain(|n| *n >= chain_nonce);
+        self.released.clear();
+        for n in chain_nonce..self.next_nonce {
+            if !self.pending.contains(&n) {
+                self.released.insert(n);
+            }
+        }
+        self.trim();
     }
 
-    let len = U256::try_from_be_slice(&bytes[offset..offset + 32]).unwrap();
-    let len = usize::try_from(len).unwrap_or(isize::MAX as usize);
-    if bytes.len() < offset + 32 + len {
-        return Err("abi string data out of bounds".to_string());
+    /// Allocates the lowest reusable nonce, or a new one.
+    pub fn allocate(&mut self) -> u64 {
+        let nonce = match self.released.pop_first() {
+            Some(n) => n,
+            None => {
+                let n = self.next_nonce;
+                self.next_nonce += 1;
+                n
+            }
+        };
+        self.pending.insert(nonce);
+        nonce
     }
 
-    let data = &bytes[offset + 32..offset + 32 + len];
-    String::from_utf8(data.to_vec()).map_err(|err| err.to_string())
+    /// Gives back a nonce whose transaction was never broadcast.
+    pub fn release(&mut self, nonce: u64) {
+        if self.pending.remove(&nonce) {
+            self.released.insert(nonce);
+            self.trim();
+        }
+    }
+
+    /// Takes the released nonces that block pending transactions with higher nonces.
+    /// They must be filled by the caller, e.g. with a zero-value self transfer.
+    pub fn take_gaps(&mut self) -> Vec<u64> {
+        let max_pending = match self.pending.last() {
+            Some(n) => *n,
+            None => return vec![],
+        };
+        let gaps: Vec<u64> = self
+            .released
+            .iter()
+            .take_while(|n| **n < max_pending)
+            .copied()
+            .collect();
+        for n in &gaps {
+            self.released.remove(n);
+            self.pending.insert(*n);
+        }
+        gaps
+    }
+
+    // Released nonces at the top are not gaps, hand them back to `next_nonce`
+    fn trim(&mut self) {
+        while let Some(last) = self.released.last().copied() {
+            if last + 1 != self.next_nonce {
+                break;
+            }
+            self.released.pop_last();
+            self.next_nonce = last;
+        }
+    }
 }
 
-fn decode_abi_uint(bytes: &[u8]) -> Result<U256, String> {
-    if bytes.len() != 32 {
-        return Err("abi uint result must be 32 bytes".to_string());
+pub fn encode_erc20_transfer(to: &Address, value: u128) -> Vec<u8> {
+    IERC20::transferCall {
+        to: *to,
+        value: U256::from(value),
+    }
+    .abi_encode()
+}
+
+#[allow(unused)]
+pub fn encode_erc20_approve(spender: &Address, value: u128) -> Vec<u8> {
+    IERC20::approveCall {
+        spender: *spender,
+        value: U256::from(value),
     }
-    Ok(U256::from_be_slice(bytes))
+    .abi_encode()
+}
+
+fn hex_to_u64(s: &str) -> Result<u64, String> {
+    let s = s.strip_prefix("0x").unwrap_or(s);
+    u64::from_str_radix(s, 16).map_err(|err| err.to_string())
 }
 
-// fn decode_abi_address(bytes: &[u8]) -> Result<Address, String> {
-//     if bytes.len() != 32 {
-//         return Err("abi address result must be 32 bytes".to_string());
-//     }
-//     Address::try_from(&bytes[12..32]).map_err(|err| err.to_string())
-// }
+fn hex_to_u128(s: &str) -> Result<u128, String> {
+    let s = s.strip_prefix("0x").unwrap_or(s);
+    u128::from_str_radix(s, 16).map_err(|err| err.to_string())
+}
 
 #[cfg(test)]
 mod tests {
@@ -371,37 +586,128 @@ mod tests {
     }
 
     #[test]
-    fn test_hex_to_u64_and_u128() {
-        assert_eq!(hex_to_u64("0x2a").unwrap(), 42);
-        assert_eq!(hex_to_u128("0xff").unwrap(), 255);
-        assert!(hex_to_u64("g1").is_err());
-        assert!(hex_to_u128("xyz").is_err());
+    fn test_nonce_manager_allocate_and_release() {
+        let mut m = NonceManager::default();
+        assert!(m.needs_sync(NONCE_SYNC_INTERVAL_MS + 1));
+        m.reconcile(5, 1_000);
+        assert!(!m.needs_sync(1_000 + NONCE_SYNC_INTERVAL_MS));
+        assert!(m.is_idle());
+
+        assert_eq!(m.allocate(), 5);
+        assert_eq!(m.allocate(), 6);
+        assert_eq!(m.allocate(), 7);
+        assert_eq!(m.next_nonce, 8);
+
+        // the released nonce is reused before a new one
+        m.release(6);
+        assert_eq!(m.released, BTreeSet::from([6]));
+        assert_eq!(m.allocate(), 6);
+        assert_eq!(m.allocate(), 8);
+
+        // releasing the highest nonce hands it back
+        m.release(8);
+        assert!(m.released.is_empty());
+        assert_eq!(m.next_nonce, 8);
+
+        // unknown nonces are ignored
+        m.release(100);
+        assert!(m.released.is_empty());
+        assert_eq!(m.pending, BTreeSet::from([5, 6, 7]));
+    }
+
+    #[test]
+    fn test_nonce_manager_reconcile() {
+        let mut m = NonceManager::default();
+        m.reconcile(10, 1_000);
+        for _ in 0..5 {
+            m.allocate();
+        }
+        assert_eq!(m.pending, BTreeSet::from([10, 11, 12, 13, 14]));
+
+        // 10 and 11 are confirmed, 12 was dropped before broadcasting
+        m.pending.remove(&12);
+        m.reconcile(12, 2_000);
+        assert_eq!(m.pending, BTreeSet::from([13, 14]));
+        assert_eq!(m.released, BTreeSet::from([12]));
+        assert_eq!(m.next_nonce, 15);
+        assert_eq!(m.synced_at, 2_000);
+
+        // the gap blocks 13 and 14, it must be filled
+        assert_eq!(m.take_gaps(), vec![12]);
+        assert!(m.released.is_empty());
+        assert_eq!(m.pending, BTreeSet::from([12, 13, 14]));
+        assert!(m.take_gaps().is_empty());
+
+        // the address was used outside of the canister
+        m.reconcile(20, 3_000);
+        assert!(m.is_idle());
+        assert_eq!(m.next_nonce, 20);
+
+        // trailing released nonces are not gaps
+        m.allocate();
+        m.allocate();
+        m.release(20);
+        m.release(21);
+        assert!(m.take_gaps().is_empty());
+        assert_eq!(m.next_nonce, 20);
+
+        // local state is too far ahead of the chain
+        m.next_nonce = 20 + MAX_NONCE_DISTANCE + 1;
+        m.reconcile(20, 4_000);
+        assert_eq!(m.next_nonce, 20);
+        assert!(m.is_idle());
     }
 
     #[test]
-    fn test_decode_abi_string() {
-        let mut payload = Vec::new();
-        payload.extend(U256::from(32u8).to_be_bytes::<32>());
-        payload.extend(U256::from(11u8).to_be_bytes::<32>());
-        payload.extend_from_slice(b"hello world");
-        payload.extend(vec![0u8; 21]);
+    fn test_evm_tx_kind_gas_limit() {
+        assert_eq!(EvmTxKind::Transfer.gas_limit(Some(30_000)), 21_000);
+        assert_eq!(EvmTxKind::Erc20Transfer.gas_limit(None), 84_000);
+        assert_eq!(EvmTxKind::Erc20Transfer.gas_limit(Some(50_000)), 60_000);
+        assert_eq!(EvmTxKind::Erc20Transfer.gas_limit(Some(10_000)), 21_000);
+        assert_eq!(EvmTxKind::Erc20Transfer.gas_limit(Some(1_000_000)), 168_000);
+        assert_eq!(EvmTxKind::CreatePool.gas_limit(Some(4_000_000)), 4_800_000);
+
+        assert!(!EvmTxKind::Transfer.requires_estimation());
+        assert!(!EvmTxKind::Erc20Transfer.requires_estimation());
+        assert!(EvmTxKind::CreatePool.requires_estimation());
+    }
+
+    #[test]
+    fn test_priority_fee_from_history() {
+        let mut history = FeeHistory::default();
+        assert_eq!(priority_fee_from_history(&history), None);
+
+        history.reward = Some(vec![vec![0], vec![]]);
+        assert_eq!(priority_fee_from_history(&history), None);
 
-        assert_eq!(decode_abi_string(&payload).unwrap(), "hello world");
+        history.reward = Some(vec![vec![300], vec![0], vec![100], vec![200], vec![5_000]]);
+        assert_eq!(priority_fee_from_history(&history), Some(300));
+    }
 
-        assert!(decode_abi_string(&payload[..60]).is_err());
+    #[test]
+    fn test_eip1559_fees() {
+        assert_eq!(eip1559_fees(100, 10, None, None).unwrap(), (212, 12));
+        // priority fee cap
+        assert_eq!(eip1559_fees(100, 10, Some(5), None).unwrap(), (205, 5));
+        // max fee cap
+        assert_eq!(eip1559_fees(100, 10, None, Some(150)).unwrap(), (150, 12));
+        assert_eq!(eip1559_fees(100, 10, None, Some(112)).unwrap(), (112, 12));
+        assert!(eip1559_fees(100, 10, None, Some(111)).is_err());
     }
 
     #[test]
-    fn test_decode_abi_uint() {
-        let value = U256::from(999u64).to_be_bytes::<32>();
-        assert_eq!(decode_abi_uint(&value).unwrap(), U256::from(999u64));
-        assert!(decode_abi_uint(&value[..31]).is_err());
+    fn test_hex_to_u64_and_u128() {
+        assert_eq!(hex_to_u64("0x2a").unwrap(), 42);
+        assert_eq!(hex_to_u128("0xff").unwrap(), 255);
+        assert!(hex_to_u64("g1").is_err());
+        assert!(hex_to_u128("xyz").is_err());
     }
 
     #[derive(Clone, Default)]
     struct MockHttpOutcall {
         responses: Arc<Mutex<VecDeque<Result<HttpRequestResult, String>>>>,
         urls: Arc<Mutex<Vec<String>>>,
+        bodies: Arc<Mutex<Vec<Value>>>,
     }
 
     impl MockHttpOutcall {
@@ -409,12 +715,17 @@ mod tests {
             Self {
                 responses: Arc::new(Mutex::new(responses.into_iter().collect())),
                 urls: Arc::new(Mutex::new(Vec::new())),
+                bodies: Arc::new(Mutex::new(Vec::new())),
             }
         }
 
         fn urls(&self) -> Vec<String> {
             self.urls.lock().unwrap().clone()
         }
+
+        fn bodies(&self) -> Vec<Value> {
+            self.bodies.lock().unwrap().clone()
+        }
     }
 
     impl HttpOutcall for MockHttpOutcall {
@@ -423,6 +734,12 @@ mod tests {
             args: &ic_cdk::management_canister::HttpRequestArgs,
         ) -> Result<HttpRequestResult, String> {
             self.urls.lock().unwrap().push(args.url.clone());
+            if let Some(body) = &args.body {
+                self.bodies
+                    .lock()
+                    .unwrap()
+                    .push(serde_json::from_slice(body).unwrap_or_default());
+            }
             self.responses
                 .lock()
                 .unwrap()
@@ -501,64 +818,51 @@ mod tests {
     }
 
     #[test]
-    fn test_get_transaction_receipt() {
-        let body = serde_json::json!({
+    fn test_fee_history() {
+        let mock = MockHttpOutcall::new(vec![success_response(serde_json::json!({
             "jsonrpc": "2.0",
             "id": 1,
-            "result": null
-        });
-        let mock = MockHttpOutcall::new(vec![success_response(body)]);
-        let client = EvmClient::new(vec!["https://rpc".to_string()], 5, None, mock);
+            "result": {
+                "oldestBlock": "0x10",
+                "baseFeePerGas": ["0x3b9aca00", "0x3b9aca01", "0x3b9aca02"],
+                "gasUsedRatio": [0.5, 0.6],
+                "reward": [["0x5f5e100"], ["0x0"]]
+            }
+        }))]);
+        let client = EvmClient::new(vec!["https://rpc.test".to_string()], 0, None, mock.clone());
 
-        let tx_hash = TxHash::from([0u8; 32]);
-        let result = futures::executor::block_on(client.get_transaction_receipt(1000, &tx_hash));
-        assert!(result.unwrap().is_none());
+        let history = futures::executor::block_on(client.fee_history(1, 2, 50)).unwrap();
+        assert_eq!(history.oldest_block, 16);
+        assert_eq!(history.next_block_base_fee(), Some(1_000_000_002));
+        assert_eq!(priority_fee_from_history(&history), Some(100_000_000));
+
+        let body = &mock.bodies()[0];
+        assert_eq!(body["method"], "eth_feeHistory");
+        assert_eq!(body["params"], serde_json::json!(["0x2", "latest", [50]]));
+    }
 
-        let body = serde_json::json!({
+    #[test]
+    fn test_estimate_gas() {
+        let mock = MockHttpOutcall::new(vec![success_response(serde_json::json!({
             "jsonrpc": "2.0",
             "id": 1,
-            "result": {
-                "type": "0x2",
-                "from": "0x9ac6b9ffbb4269fc51cf0ef7bcd322cefb3e5e14",
-                "to": "0xe74583edaff618d88463554b84bc675196b36990",
-                "status": "0x1",
-                "cumulativeGasUsed": "0x3e45f",
-                "logsBloom": "0x0000000000008000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000c000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000210800000000000000000000000000000000000000000000000000100000000000000000000004000000000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000800000000000000000000000000020000000000000000000000000000000000000000800000000000000",
-                "logs": [
-                    {
-                        "address": "0xe74583edaff618d88463554b84bc675196b36990",
-                        "topics": [
-                            "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef",
-                            "0x0000000000000000000000009ac6b9ffbb4269fc51cf0ef7bcd322cefb3e5e14",
-                            "0x0000000000000000000000009792cc010fe26155c676d0cc0057a3c66564fbcd"
-                        ],
-                        "data": "0x0000000000000000000000000000000000000000000000000de0b6b3a7640000",
-                        "blockNumber": "0x418f472",
-                        "transactionHash": "0xbbded599a5f088cb82d9b439043ff691857ebff4f480225d5d563aed4ef11aaa",
-                        "transactionIndex": "0x3",
-                        "blockHash": "0xc2259f320a755bb1f21ab3cd3590f6838a48c8167268088dcf648acee2362b15",
-                        "logIndex": "0x5",
-                        "removed": false
-                    }
-                ],
-                "transactionHash": "0xbbded599a5f088cb82d9b439043ff691857ebff4f480225d5d563aed4ef11aaa",
-                "contractAddress": null,
-                "gasUsed": "0xcbdb",
-                "blockHash": "0xc2259f320a755bb1f21ab3cd3590f6838a48c8167268088dcf648acee2362b15",
-                "blockNumber": "0x418f472",
-                "transactionIndex": "0x3",
-                "effectiveGasPrice": "0x7270e00"
-            }
-        });
-        let mock = MockHttpOutcall::new(vec![success_response(body)]);
-        let client = EvmClient::new(vec!["https://rpc".to_string()], 5, None, mock);
-
-        let tx_hash =
-            TxHash::from_hex("0xbbded599a5f088cb82d9b439043ff691857ebff4f480225d5d563aed4ef11aaa")
-                .unwrap();
-        let result =
-            futures::executor::block_on(client.get_transaction_receipt(1000, &tx_hash)).unwrap();
-        assert!(result.is_some());
-        println!("{:?}", result);
+            "result": "0xd1c0"
+        }))]);
+        let client = EvmClient::new(vec!["https://rpc.test".to_string()], 0, None, mock.clone());
+
+        let from = Address::repeat_byte(0x11);
+        let to = Address::repeat_byte(0x22);
+        let gas = futures::executor::block_on(client.estimate_gas(
+            1,
+            &from,
+            &to,
+            "0xa9059cbb".to_string(),
+        ))
+        .unwrap();
+        assert_eq!(gas, 53_696);
+
+        let body = &mock.bodies()[0];
+        assert_eq!(body["method"], "eth_estimateGas");
+        assert_eq!(body["params"][0]["data"], "0xa9059cbb");
     }
 }
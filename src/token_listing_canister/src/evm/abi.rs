@@ -0,0 +1,158 @@
+//! Typed contract calls and events, encoded and decoded with alloy's `sol!` macro.
+
+use alloy_sol_types::{SolCall, sol};
+
+sol! {
+    interface IERC20 {
+        event Transfer(address indexed from, address indexed to, uint256 value);
+        event Approval(address indexed owner, address indexed spender, uint256 value);
+
+        function name() external view returns (string);
+        function symbol() external view returns (string);
+        function decimals() external view returns (uint8);
+        function totalSupply() external view returns (uint256);
+        function balanceOf(address account) external view returns (uint256);
+        function allowance(address owner, address spender) external view returns (uint256);
+        function transfer(address to, uint256 value) external returns (bool);
+        function approve(address spender, uint256 value) external returns (bool);
+        function transferFrom(address from, address to, uint256 value) external returns (bool);
+    }
+
+    // https://github.com/Uniswap/v3-periphery/blob/main/contracts/NonfungiblePositionManager.sol
+    interface INonfungiblePositionManager {
+        struct MintParams {
+            address token0;
+            address token1;
+            uint24 fee;
+            int24 tickLower;
+            int24 tickUpper;
+            uint256 amount0Desired;
+            uint256 amount1Desired;
+            uint256 amount0Min;
+            uint256 amount1Min;
+            address recipient;
+            uint256 deadline;
+        }
+
+        event IncreaseLiquidity(uint256 indexed tokenId, uint128 liquidity, uint256 amount0, uint256 amount1);
+
+        function createAndInitializePoolIfNecessary(
+            address token0,
+            address token1,
+            uint24 fee,
+            uint160 sqrtPriceX96
+        ) external payable returns (address pool);
+
+        function mint(MintParams calldata params)
+            external
+            payable
+            returns (uint256 tokenId, uint128 liquidity, uint256 amount0, uint256 amount1);
+    }
+
+    // https://github.com/Uniswap/swap-router-contracts/blob/main/contracts/interfaces/IV3SwapRouter.sol
+    interface ISwapRouter02 {
+        struct ExactInputSingleParams {
+            address tokenIn;
+            address tokenOut;
+            uint24 fee;
+            address recipient;
+            uint256 amountIn;
+            uint256 amountOutMinimum;
+            uint160 sqrtPriceLimitX96;
+        }
+
+        function exactInputSingle(ExactInputSingleParams calldata params)
+            external
+            payable
+            returns (uint256 amountOut);
+    }
+}
+
+/// Decodes the return data of an `eth_call` to `C`.
+pub fn decode_return<C: SolCall>(data: &[u8]) -> Result<C::Return, String> {
+    C::abi_decode_returns(data).map_err(|err| format!("failed to decode {}: {err}", C::SIGNATURE))
+}
+
+/// Decodes a `symbol()` return value, falling back to `bytes32` for tokens like MKR.
+pub fn decode_symbol(data: &[u8]) -> Result<String, String> {
+    if let Ok(symbol) = IERC20::symbolCall::abi_decode_returns(data) {
+        return Ok(symbol);
+    }
+    match data {
+        word if word.len() == 32 => {
+            let end = word.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+            String::from_utf8(word[..end].to_vec())
+                .map_err(|err| format!("failed to decode bytes32 symbol: {err}"))
+        }
+        _ => Err(format!(
+            "failed to decode {}: unexpected length {}",
+            IERC20::symbolCall::SIGNATURE,
+            data.len()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{Address, U256};
+
+    #[test]
+    fn test_erc20_calls() {
+        let owner = Address::repeat_byte(0x11);
+        let spender = Address::repeat_byte(0x22);
+        let data = IERC20::allowanceCall { owner, spender }.abi_encode();
+        assert_eq!(&data[0..4], &[0xdd, 0x62, 0xed, 0x3e]);
+        assert_eq!(&data[4..36], owner.into_word().as_slice());
+        assert_eq!(&data[36..68], spender.into_word().as_slice());
+
+        let data = IERC20::approveCall {
+            spender,
+            value: U256::from(7u8),
+        }
+        .abi_encode();
+        assert_eq!(&data[0..4], &[0x09, 0x5e, 0xa7, 0xb3]);
+        assert_eq!(IERC20::nameCall::SELECTOR, [0x06, 0xfd, 0xde, 0x03]);
+        assert_eq!(IERC20::totalSupplyCall::SELECTOR, [0x18, 0x16, 0x0d, 0xdd]);
+
+        let mut payload = Vec::new();
+        payload.extend(U256::from(32u8).to_be_bytes::<32>());
+        payload.extend(U256::from(11u8).to_be_bytes::<32>());
+        payload.extend_from_slice(b"hello world");
+        payload.extend(vec![0u8; 21]);
+        assert_eq!(
+            decode_return::<IERC20::symbolCall>(&payload).unwrap(),
+            "hello world"
+        );
+        assert!(decode_return::<IERC20::symbolCall>(&payload[..60]).is_err());
+        assert_eq!(decode_symbol(&payload).unwrap(), "hello world");
+
+        let mut mkr = [0u8; 32];
+        mkr[..3].copy_from_slice(b"MKR");
+        assert_eq!(decode_symbol(&mkr).unwrap(), "MKR");
+        assert!(decode_symbol(&mkr[..31]).is_err());
+
+        let value = U256::from(999u64).to_be_bytes::<32>();
+        assert_eq!(
+            decode_return::<IERC20::totalSupplyCall>(&value).unwrap(),
+            U256::from(999u64)
+        );
+        assert!(decode_return::<IERC20::totalSupplyCall>(&value[..31]).is_err());
+    }
+
+    #[test]
+    fn test_uniswap_calls() {
+        assert_eq!(
+            INonfungiblePositionManager::createAndInitializePoolIfNecessaryCall::SELECTOR,
+            [0x13, 0xea, 0xd5, 0x62]
+        );
+        assert_eq!(
+            INonfungiblePositionManager::mintCall::SELECTOR,
+            [0x88, 0x31, 0x64, 0x56]
+        );
+        assert_eq!(
+            ISwapRouter02::exactInputSingleCall::SELECTOR,
+            [0x04, 0xe4, 0x5a, 0xaf]
+        );
+    }
+}
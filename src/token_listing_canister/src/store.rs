@@ -19,16 +19,24 @@ use std::{borrow::Cow, cell::RefCell, collections::HashMap, str::FromStr};
 
 use crate::{
     ecdsa::{derive_public_key, ecdsa_public_key, sign_with_ecdsa},
-    evm::{EvmClient, encode_erc20_transfer},
+    evm::{
+        EvmClient, EvmTxKind, NONCE_SYNC_INTERVAL_MS, NonceManager, eip1559_fees,
+        encode_erc20_transfer, priority_fee_from_history,
+    },
     helper::format_error,
     icp,
     outcall::DefaultHttpOutcall,
     schnorr::{derive_schnorr_public_key, schnorr_public_key, sign_with_schnorr},
     svm::{
-        Message, Pubkey, SvmClient, Transaction, create_associated_token_account_idempotent,
-        get_associated_token_address, instruction, transfer_checked_instruction,
+        Instruction, MAX_COMPUTE_UNIT_LIMIT, Pubkey, SvmClient, VersionedTransaction,
+        compile_message, compute_unit_limit, compute_unit_price,
+        create_associated_token_account_idempotent, get_associated_token_address, instruction,
+        signed_transaction, transfer_checked_instruction, unsigned_transaction,
+        with_compute_budget, writable_accounts,
+    },
+    types::{
+        AuctionId, AuctionInfo, Chain, EvmFeeConfig, PublicKeyOutput, SolFeeConfig, StateInfo,
     },
-    types::{AuctionId, AuctionInfo, Chain, PublicKeyOutput, StateInfo},
 };
 
 type Memory = VirtualMemory<DefaultMemoryImpl>;
@@ -49,8 +57,17 @@ pub struct State {
     pub storages: Vec<Principal>,
     pub daos: Vec<Principal>,
     pub pending_deposits: HashMap<Principal, u64>,
-    // (gas_updated_at, gas_price, max_priority_fee_per_gas)
-    pub evm_latest_gas: (u64, u128, u128),
+    // chain_id -> (gas_updated_at, base_fee_per_gas, priority_fee_per_gas), replaces the
+    // single chain evm_latest_gas of earlier versions, which is ignored when decoding
+    #[serde(default)]
+    pub evm_latest_gas_by_chain: HashMap<u64, (u64, u128, u128)>,
+    #[serde(default)]
+    pub evm_fee_config: EvmFeeConfig,
+    #[serde(default)]
+    pub sol_fee_config: SolFeeConfig,
+    // chain_id -> nonces of evm_address
+    #[serde(default)]
+    pub evm_nonces: HashMap<u64, NonceManager>,
 }
 
 impl From<&State> for StateInfo {
@@ -66,6 +83,8 @@ impl From<&State> for StateInfo {
             auctions: s.auctions.iter().map(|v| v.into()).collect(),
             storages: s.storages.clone(),
             daos: s.daos.clone(),
+            evm_fee_config: s.evm_fee_config.clone(),
+            sol_fee_config: s.sol_fee_config.clone(),
         }
     }
 }
@@ -87,7 +106,10 @@ impl State {
             storages: Vec::new(),
             daos: Vec::new(),
             pending_deposits: HashMap::new(),
-            evm_latest_gas: (0, 0, 0),
+            evm_latest_gas_by_chain: HashMap::new(),
+            evm_fee_config: EvmFeeConfig::default(),
+            sol_fee_config: SolFeeConfig::default(),
+            evm_nonces: HashMap::new(),
         }
     }
 }
@@ -523,6 +545,29 @@ pub mod state {
         Ok(())
     }
 
+    pub fn set_evm_nonce_timer() {
+        ic_cdk_timers::set_timer_interval_serial(
+            std::time::Duration::from_millis(NONCE_SYNC_INTERVAL_MS),
+            async || {
+                let now_ms = ic_cdk::api::time() / 1_000_000;
+                let chain_ids: Vec<u64> = STATE.with_borrow(|s| {
+                    s.evm_nonces
+                        .iter()
+                        .filter(|(_, m)| !m.is_idle())
+                        .map(|(chain_id, _)| *chain_id)
+                        .collect()
+                });
+                for chain_id in chain_ids {
+                    if let Err(err) = reconcile_evm_nonce(chain_id, now_ms).await {
+                        ic_cdk::api::debug_print(format!(
+                            "failed to reconcile EVM nonce on chain {chain_id}: {err}"
+                        ));
+                    }
+                }
+            },
+        );
+    }
+
     fn evm_client(chain: &Chain) -> Result<EvmClient<DefaultHttpOutcall>, String> {
         STATE.with_borrow(|s| {
             let chain = s
@@ -625,15 +670,65 @@ pub mod state {
         )
         .await?;
 
+        send_evm_tx(&client, signed_tx, now_ms).await
+    }
+
+    async fn send_evm_tx(
+        client: &EvmClient<DefaultHttpOutcall>,
+        signed_tx: Signed<TxEip1559>,
+        now_ms: u64,
+    ) -> Result<String, String> {
         let txid = signed_tx.hash().to_string();
+        let chain_id = signed_tx.tx().chain_id;
+        let nonce = signed_tx.tx().nonce;
         let data = signed_tx.encoded_2718();
-        let _ = client
+        if let Err(err) = client
             .send_raw_transaction(now_ms, Bytes::from(data).to_string())
             .await
-            .map_err(format_error)?;
+        {
+            STATE.with_borrow_mut(|s| {
+                if let Some(m) = s.evm_nonces.get_mut(&chain_id) {
+                    m.release(nonce);
+                }
+            });
+            return Err(format_error(err));
+        }
         Ok(txid)
     }
 
+    /// Syncs the local nonces with the chain, and fills the gaps left by dropped
+    /// transactions with zero-value self transfers so later transactions can be mined.
+    async fn reconcile_evm_nonce(chain_id: u64, now_ms: u64) -> Result<(), String> {
+        let chain = Chain::Evm(chain_id);
+        let evm_address = STATE.with_borrow(|s| s.evm_address);
+        if evm_address == Address::ZERO {
+            return Ok(());
+        }
+
+        let client = evm_client(&chain)?;
+        let chain_nonce = client.get_transaction_count(now_ms, &evm_address).await?;
+        let gaps = STATE.with_borrow_mut(|s| {
+            let m = s.evm_nonces.entry(chain_id).or_default();
+            m.reconcile(chain_nonce, now_ms);
+            m.take_gaps()
+        });
+
+        for nonce in gaps {
+            let (client, signed_tx) = build_evm_tx(
+                &chain,
+                EvmTxKind::Transfer,
+                evm_address,
+                Bytes::new(),
+                chain_id,
+                Some(nonce),
+                now_ms,
+            )
+            .await?;
+            send_evm_tx(&client, signed_tx, now_ms).await?;
+        }
+        Ok(())
+    }
+
     async fn spl_balance_of(
         chain: &Chain,
         addr: &Pubkey,
@@ -662,17 +757,6 @@ pub mod state {
         Ok(account_data.lamports as u128)
     }
 
-    async fn erc20_balance_of(
-        chain: &Chain,
-        addr: &Address,
-        token: &Address,
-        now_ms: u64,
-    ) -> Result<u128, String> {
-        let client = evm_client(chain)?;
-        let balance = client.erc20_balance(now_ms, token, addr).await?;
-        Ok(balance)
-    }
-
     async fn build_spl_transfer_tx(
         chain: &Chain,
         to_addr: &Pubkey,
@@ -681,8 +765,8 @@ pub mod state {
         decimals: u8,
         amount: u128,
         now_ms: u64,
-    ) -> Result<(SvmClient<DefaultHttpOutcall>, Transaction), String> {
-        let (key_name, from, from_addr, ixs) = STATE.with_borrow(|s| {
+    ) -> Result<(SvmClient<DefaultHttpOutcall>, VersionedTransaction), String> {
+        let (from_addr, ixs) = STATE.with_borrow(|s| {
             let amount: u64 = amount
                 .try_into()
                 .map_err(|_| format!("amount is too large: {}", amount))?;
@@ -716,25 +800,10 @@ pub mod state {
                 decimals,
             );
 
-            Ok::<_, String>((s.key_name.clone(), s.icp_address, from_addr, vec![ix0, ix]))
+            Ok::<_, String>((from_addr, vec![ix0, ix]))
         })?;
 
-        let client = sol_client(chain)?;
-        let block = client
-            .get_latest_blockhash(now_ms)
-            .await
-            .map_err(|err| format!("SOL: failed to get latest blockhash, error: {}", err))?;
-
-        let message = Message::new_with_blockhash(&ixs, Some(&from_addr), &block);
-        let msg = bincode::serialize(&message).map_err(|err| format!("SOL: {err}"))?;
-        let sig = sign_with_schnorr(key_name, vec![from.as_slice().to_vec()], msg, None).await?;
-        let signature: [u8; 64] = sig.try_into().map_err(|_| "invalid signature length")?;
-        let transaction = Transaction {
-            message,
-            signatures: vec![signature.into()],
-        };
-
-        Ok((client, transaction))
+        build_sol_tx(chain, &from_addr, ixs, now_ms).await
     }
 
     async fn build_sol_transfer_tx(
@@ -742,8 +811,8 @@ pub mod state {
         to_addr: &Pubkey,
         amount: u64,
         now_ms: u64,
-    ) -> Result<(SvmClient<DefaultHttpOutcall>, Transaction), String> {
-        let (key_name, from, from_addr, ixs) = STATE.with_borrow(|s| {
+    ) -> Result<(SvmClient<DefaultHttpOutcall>, VersionedTransaction), String> {
+        let (from_addr, ixs) = STATE.with_borrow(|s| {
             let from_pk = derive_schnorr_public_key(
                 &s.ed25519_public_key,
                 vec![s.icp_address.as_slice().to_vec()],
@@ -756,23 +825,80 @@ pub mod state {
             }
 
             let ix = instruction::transfer(&from_addr, to_addr, amount);
-            Ok::<_, String>((s.key_name.clone(), s.icp_address, from_addr, vec![ix]))
+            Ok::<_, String>((from_addr, vec![ix]))
         })?;
 
+        build_sol_tx(chain, &from_addr, ixs, now_ms).await
+    }
+
+    // Simulates the transaction to size its compute unit limit, prices it from the recent
+    // prioritization fees, then signs it with the canister's Solana address.
+    async fn build_sol_tx(
+        chain: &Chain,
+        payer: &Pubkey,
+        ixs: Vec<Instruction>,
+        now_ms: u64,
+    ) -> Result<(SvmClient<DefaultHttpOutcall>, VersionedTransaction), String> {
+        let (key_name, from, cfg) =
+            STATE.with_borrow(|s| (s.key_name.clone(), s.icp_address, s.sol_fee_config.clone()));
+
         let client = sol_client(chain)?;
-        let block = client
-            .get_latest_blockhash(now_ms)
+        let (block, fees) = futures::future::try_join(
+            async {
+                client
+                    .get_latest_blockhash(now_ms)
+                    .await
+                    .map_err(|err| format!("SOL: failed to get latest blockhash, error: {}", err))
+            },
+            client.get_recent_prioritization_fees(now_ms, writable_accounts(&ixs)),
+        )
+        .await?;
+        let unit_price = compute_unit_price(
+            &fees,
+            cfg.priority_fee_percentile,
+            cfg.min_compute_unit_price,
+            cfg.max_compute_unit_price,
+        );
+
+        let message = compile_message(
+            payer,
+            &with_compute_budget(&ixs, MAX_COMPUTE_UNIT_LIMIT, unit_price),
+            &[],
+            &block,
+        )?;
+        let data = bincode::serialize(&unsigned_transaction(message))
+            .map_err(|err| format!("SOL: {err}"))?;
+        let simulated = client
+            .simulate_transaction(now_ms, data.into(), false)
             .await
-            .map_err(|err| format!("failed to get latest blockhash, error: {}", err))?;
+            .map_err(|err| format!("SOL: failed to simulate transaction, error: {err}"))?;
+        if let Some(err) = simulated.err {
+            return Err(format!(
+                "SOL: transaction simulation failed, error: {err}, logs: {:?}",
+                simulated.logs.unwrap_or_default()
+            ));
+        }
+        let unit_limit = compute_unit_limit(
+            simulated
+                .units_consumed
+                .unwrap_or(MAX_COMPUTE_UNIT_LIMIT as u64),
+        );
 
-        let message = Message::new_with_blockhash(&ixs, Some(&from_addr), &block);
-        let msg = bincode::serialize(&message).map_err(|err| format!("SOL: {err}"))?;
-        let sig = sign_with_schnorr(key_name, vec![from.as_slice().to_vec()], msg, None).await?;
+        let message = compile_message(
+            payer,
+            &with_compute_budget(&ixs, unit_limit, unit_price),
+            &[],
+            &block,
+        )?;
+        let sig = sign_with_schnorr(
+            key_name,
+            vec![from.as_slice().to_vec()],
+            message.serialize(),
+            None,
+        )
+        .await?;
         let signature: [u8; 64] = sig.try_into().map_err(|_| "invalid signature length")?;
-        let transaction = Transaction {
-            message,
-            signatures: vec![signature.into()],
-        };
+        let transaction = signed_transaction(message, vec![signature])?;
 
         Ok((client, transaction))
     }
@@ -785,59 +911,150 @@ pub mod state {
         amount: u128,
         now_ms: u64,
     ) -> Result<(EvmClient<DefaultHttpOutcall>, Signed<TxEip1559>), String> {
-        let (key_name, from, from_pk, mut tx, gas_updated_at) = STATE.with_borrow(|s| {
+        if &STATE.with_borrow(|s| s.evm_address) == to_addr {
+            return Err("from and to cannot be the same".to_string());
+        }
+
+        let input = encode_erc20_transfer(to_addr, amount);
+        build_evm_tx(
+            chain,
+            EvmTxKind::Erc20Transfer,
+            token,
+            input.into(),
+            chain_id,
+            None,
+            now_ms,
+        )
+        .await
+    }
+
+    // Allocates a new nonce when `nonce` is None, it is released if signing fails.
+    async fn build_evm_tx(
+        chain: &Chain,
+        kind: EvmTxKind,
+        to: Address,
+        input: Bytes,
+        chain_id: u64,
+        nonce: Option<u64>,
+        now_ms: u64,
+    ) -> Result<(EvmClient<DefaultHttpOutcall>, Signed<TxEip1559>), String> {
+        let (key_name, from, from_pk, cfg, latest_gas, nonce_synced) = STATE.with_borrow(|s| {
             let from_pk =
                 derive_public_key(&s.ecdsa_public_key, vec![s.icp_address.as_slice().to_vec()])
                     .map_err(|_e| "derive_public_key failed".to_string())?;
-
-            let input = encode_erc20_transfer(to_addr, amount);
-            let (gas_updated_at, gas_price, max_priority_fee_per_gas) = s.evm_latest_gas;
-            let max_priority_fee_per_gas = max_priority_fee_per_gas + max_priority_fee_per_gas / 5;
+            let nonce_synced = nonce.is_some()
+                || s.evm_nonces
+                    .get(&chain_id)
+                    .is_some_and(|m| !m.needs_sync(now_ms));
             Ok::<_, String>((
                 s.key_name.clone(),
                 s.icp_address,
                 from_pk,
-                TxEip1559 {
-                    chain_id,
-                    nonce: 0u64,
-                    gas_limit: 84_000u64, // sample: ~53,696
-                    max_fee_per_gas: gas_price * 2 + max_priority_fee_per_gas,
-                    max_priority_fee_per_gas,
-                    to: token.into(),
-                    input: input.into(),
-                    ..Default::default()
-                },
-                gas_updated_at,
+                s.evm_fee_config.clone(),
+                s.evm_latest_gas_by_chain
+                    .get(&chain_id)
+                    .copied()
+                    .unwrap_or_default(),
+                nonce_synced,
             ))
         })?;
 
         let from_addr = from_pk.to_evm_adress()?;
-        if &from_addr == to_addr {
-            return Err("from and to cannot be the same".to_string());
-        }
-
         let client = evm_client(chain)?;
-        if gas_updated_at + 120_000 >= now_ms {
-            let nonce = client.get_transaction_count(now_ms, &from_addr).await?;
-            tx.nonce = nonce;
-        } else {
-            let (nonce, gas_price, max_priority_fee_per_gas) = futures::future::try_join3(
-                client.get_transaction_count(now_ms, &from_addr),
-                client.gas_price(now_ms),
-                client.max_priority_fee_per_gas(now_ms),
-            )
-            .await?;
-            tx.nonce = nonce;
-            tx.max_priority_fee_per_gas = max_priority_fee_per_gas + max_priority_fee_per_gas / 5;
-            tx.max_fee_per_gas = gas_price * 2 + tx.max_priority_fee_per_gas;
+        let (gas_updated_at, mut base_fee, mut priority_fee) = latest_gas;
+        let (chain_nonce, fee_history, estimated_gas) = futures::future::try_join3(
+            async {
+                if nonce_synced {
+                    Ok(None)
+                } else {
+                    client
+                        .get_transaction_count(now_ms, &from_addr)
+                        .await
+                        .map(Some)
+                }
+            },
+            async {
+                if gas_updated_at + 120_000 >= now_ms {
+                    Ok(None)
+                } else {
+                    client
+                        .fee_history(now_ms, cfg.fee_history_blocks, cfg.reward_percentile)
+                        .await
+                        .map(Some)
+                }
+            },
+            async {
+                if kind == EvmTxKind::Transfer {
+                    Ok(None)
+                } else {
+                    let res = client
+                        .estimate_gas(now_ms, &from_addr, &to, input.to_string())
+                        .await;
+                    match res {
+                        Ok(gas) => Ok(Some(gas)),
+                        Err(err) if kind.requires_estimation() => {
+                            Err(format!("gas estimation failed: {}", err))
+                        }
+                        // the kind's default gas limit is used when the estimation fails
+                        Err(_) => Ok(None),
+                    }
+                }
+            },
+        )
+        .await?;
+
+        if let Some(history) = fee_history {
+            base_fee = history
+                .next_block_base_fee()
+                .ok_or_else(|| "missing base fee in fee history".to_string())?;
+            priority_fee = match priority_fee_from_history(&history) {
+                Some(fee) => fee,
+                None => client.max_priority_fee_per_gas(now_ms).await?,
+            };
             STATE.with_borrow_mut(|s| {
-                s.evm_latest_gas = (now_ms, gas_price, max_priority_fee_per_gas);
-            })
+                s.evm_latest_gas_by_chain
+                    .insert(chain_id, (now_ms, base_fee, priority_fee));
+            });
         }
 
+        let (max_fee_per_gas, max_priority_fee_per_gas) = eip1559_fees(
+            base_fee,
+            priority_fee,
+            cfg.max_priority_fee_per_gas,
+            cfg.max_fee_per_gas,
+        )?;
+        let mut tx = TxEip1559 {
+            chain_id,
+            nonce: 0u64,
+            gas_limit: kind.gas_limit(estimated_gas),
+            max_fee_per_gas,
+            max_priority_fee_per_gas,
+            to: to.into(),
+            input,
+            ..Default::default()
+        };
+
+        tx.nonce = match nonce {
+            Some(nonce) => nonce,
+            None => STATE.with_borrow_mut(|s| {
+                let m = s.evm_nonces.entry(chain_id).or_default();
+                if let Some(chain_nonce) = chain_nonce {
+                    m.reconcile(chain_nonce, now_ms);
+                }
+                m.allocate()
+            }),
+        };
+
         let msg_hash = tx.signature_hash();
-        let sig =
-            sign_with_ecdsa(key_name, vec![from.as_slice().to_vec()], msg_hash.to_vec()).await?;
+        let sig = sign_with_ecdsa(key_name, vec![from.as_slice().to_vec()], msg_hash.to_vec())
+            .await
+            .inspect_err(|_| {
+                STATE.with_borrow_mut(|s| {
+                    if let Some(m) = s.evm_nonces.get_mut(&chain_id) {
+                        m.release(tx.nonce);
+                    }
+                });
+            })?;
         let signature = Signature::new(
             U256::from_be_slice(&sig[0..32]),  // r
             U256::from_be_slice(&sig[32..64]), // s
@@ -871,3 +1088,63 @@ fn y_parity(prehash: &[u8], sig: &[u8], pubkey: &[u8]) -> Result<bool, String> {
         hex::encode(pubkey)
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // State as encoded by the versions before the per-chain EVM gas cache
+    #[derive(Serialize)]
+    struct LegacyState {
+        key_name: String,
+        icp_address: Principal,
+        evm_address: Address,
+        sol_address: Pubkey,
+        chain_providers: HashMap<Chain, Vec<String>>,
+        paying_public_keys: Vec<ByteArrayB64<32>>,
+        ecdsa_public_key: PublicKeyOutput,
+        ed25519_public_key: PublicKeyOutput,
+        nonce_iv: ByteArrayB64<32>,
+        governance_canister: Option<Principal>,
+        auctions: Vec<AuctionAddress>,
+        storages: Vec<Principal>,
+        daos: Vec<Principal>,
+        pending_deposits: HashMap<Principal, u64>,
+        evm_latest_gas: (u64, u128, u128),
+    }
+
+    #[test]
+    fn test_decode_legacy_state() {
+        let legacy = LegacyState {
+            key_name: "key_1".to_string(),
+            icp_address: Principal::management_canister(),
+            evm_address: Address::default(),
+            sol_address: Pubkey::default(),
+            chain_providers: HashMap::from([(
+                Chain::Evm(8453),
+                vec!["https://mainnet.base.org".to_string()],
+            )]),
+            paying_public_keys: vec![ByteArrayB64::default()],
+            ecdsa_public_key: PublicKeyOutput::default(),
+            ed25519_public_key: PublicKeyOutput::default(),
+            nonce_iv: ByteArrayB64::default(),
+            governance_canister: None,
+            auctions: vec![AuctionAddress::Icp(Principal::management_canister())],
+            storages: vec![],
+            daos: vec![],
+            pending_deposits: HashMap::new(),
+            evm_latest_gas: (1_000, 100, 10),
+        };
+        let mut buf = vec![];
+        into_writer(&legacy, &mut buf).unwrap();
+
+        let s: State = from_reader(&buf[..]).unwrap();
+        assert_eq!(s.key_name, "key_1");
+        assert_eq!(s.chain_providers.len(), 1);
+        assert_eq!(s.auctions.len(), 1);
+        assert!(s.evm_latest_gas_by_chain.is_empty());
+        assert!(s.evm_nonces.is_empty());
+        assert_eq!(s.evm_fee_config, EvmFeeConfig::default());
+        assert_eq!(s.sol_fee_config, SolFeeConfig::default());
+    }
+}
@@ -1,11 +1,15 @@
+mod compute_budget;
 mod rpc;
 mod spl;
 mod types;
+mod versioned;
 
 pub mod constants;
 
+pub use compute_budget::*;
 pub use rpc::*;
 pub use spl::*;
 pub use types::*;
+pub use versioned::*;
 
 pub use solana_system_interface::instruction;
@@ -0,0 +1,135 @@
+use solana_instruction::{Instruction, account_meta::AccountMeta};
+use std::collections::BTreeSet;
+
+use super::{constants, types::PrioritizationFee};
+
+/// The max compute units a transaction can request.
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+// Compute units consumed by the compute budget instructions themselves
+const COMPUTE_BUDGET_UNITS: u64 = 300;
+
+pub fn set_compute_unit_limit_instruction(units: u32) -> Instruction {
+    let mut data = Vec::with_capacity(5);
+    // ComputeBudget program "SetComputeUnitLimit" instruction
+    data.push(2);
+    data.extend_from_slice(&units.to_le_bytes());
+    Instruction {
+        program_id: constants::compute_budget::ID,
+        accounts: vec![],
+        data,
+    }
+}
+
+pub fn set_compute_unit_price_instruction(micro_lamports: u64) -> Instruction {
+    let mut data = Vec::with_capacity(9);
+    // ComputeBudget program "SetComputeUnitPrice" instruction
+    data.push(3);
+    data.extend_from_slice(&micro_lamports.to_le_bytes());
+    Instruction {
+        program_id: constants::compute_budget::ID,
+        accounts: vec![],
+        data,
+    }
+}
+
+/// Prepends the compute budget instructions to `ixs`.
+pub fn with_compute_budget(
+    ixs: &[Instruction],
+    unit_limit: u32,
+    unit_price: u64,
+) -> Vec<Instruction> {
+    let mut rt = Vec::with_capacity(ixs.len() + 2);
+    rt.push(set_compute_unit_limit_instruction(unit_limit));
+    rt.push(set_compute_unit_price_instruction(unit_price));
+    rt.extend_from_slice(ixs);
+    rt
+}
+
+/// Returns the writable accounts of `ixs`, they decide the local fee market of the transaction.
+pub fn writable_accounts(ixs: &[Instruction]) -> Vec<String> {
+    let accounts: BTreeSet<String> = ixs
+        .iter()
+        .flat_map(|ix| ix.accounts.iter())
+        .filter(|meta: &&AccountMeta| meta.is_writable)
+        .map(|meta| meta.pubkey.to_string())
+        .collect();
+    accounts.into_iter().collect()
+}
+
+/// Compute unit limit with a 10% margin over the simulated units.
+pub fn compute_unit_limit(units_consumed: u64) -> u32 {
+    let units = units_consumed + units_consumed / 10 + COMPUTE_BUDGET_UNITS;
+    units.min(MAX_COMPUTE_UNIT_LIMIT as u64) as u32
+}
+
+/// Returns the `percentile` of the recent prioritization fees, bounded by `min` and `max`.
+pub fn compute_unit_price(fees: &[PrioritizationFee], percentile: u8, min: u64, max: u64) -> u64 {
+    let mut fees: Vec<u64> = fees.iter().map(|f| f.prioritization_fee).collect();
+    let price = if fees.is_empty() {
+        0
+    } else {
+        fees.sort_unstable();
+        fees[(fees.len() - 1) * percentile.min(100) as usize / 100]
+    };
+    price.min(max).max(min)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::svm::Pubkey;
+
+    fn fees(values: &[u64]) -> Vec<PrioritizationFee> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| PrioritizationFee {
+                slot: i as u64,
+                prioritization_fee: *v,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_compute_budget_instructions() {
+        let ix = set_compute_unit_limit_instruction(200_000);
+        assert_eq!(ix.program_id, constants::compute_budget::ID);
+        assert_eq!(ix.data, vec![2, 0x40, 0x0d, 0x03, 0x00]);
+
+        let ix = set_compute_unit_price_instruction(1_000);
+        assert_eq!(ix.data, vec![3, 0xe8, 0x03, 0, 0, 0, 0, 0, 0]);
+
+        let payer = Pubkey::new_unique();
+        let to = Pubkey::new_unique();
+        let transfer = solana_system_interface::instruction::transfer(&payer, &to, 1);
+        let ixs = with_compute_budget(std::slice::from_ref(&transfer), 1_000, 10);
+        assert_eq!(ixs.len(), 3);
+        assert_eq!(ixs[2], transfer);
+
+        let mut accounts = vec![payer.to_string(), to.to_string()];
+        accounts.sort();
+        assert_eq!(writable_accounts(&ixs), accounts);
+    }
+
+    #[test]
+    fn test_compute_unit_limit() {
+        assert_eq!(compute_unit_limit(0), 300);
+        assert_eq!(compute_unit_limit(50_000), 55_300);
+        assert_eq!(compute_unit_limit(2_000_000), MAX_COMPUTE_UNIT_LIMIT);
+    }
+
+    #[test]
+    fn test_compute_unit_price() {
+        assert_eq!(compute_unit_price(&[], 75, 0, 1_000), 0);
+        assert_eq!(compute_unit_price(&[], 75, 10, 1_000), 10);
+
+        let fees = fees(&[0, 500, 100, 2_000, 300]);
+        assert_eq!(compute_unit_price(&fees, 0, 0, 10_000), 0);
+        assert_eq!(compute_unit_price(&fees, 50, 0, 10_000), 300);
+        assert_eq!(compute_unit_price(&fees, 75, 0, 10_000), 500);
+        assert_eq!(compute_unit_price(&fees, 100, 0, 10_000), 2_000);
+        // bounded
+        assert_eq!(compute_unit_price(&fees, 100, 0, 1_000), 1_000);
+        assert_eq!(compute_unit_price(&fees, 0, 50, 1_000), 50);
+    }
+}
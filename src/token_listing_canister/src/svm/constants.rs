@@ -9,3 +9,7 @@ pub mod spl_associated_token_account {
 pub mod system_program {
     solana_program::declare_id!("11111111111111111111111111111111");
 }
+
+pub mod compute_budget {
+    solana_program::declare_id!("ComputeBudget111111111111111111111111111111");
+}
@@ -158,20 +158,23 @@ impl<H: HttpOutcall> SvmClient<H> {
         .await
     }
 
-    #[allow(dead_code)]
     pub async fn simulate_transaction(
         &self,
         now_ms: u64,
-        transaction: String,
+        transaction: ByteBufB64,
         sig_verify: bool,
-    ) -> Result<Value, String> {
+    ) -> Result<SimulatedTransaction, String> {
         let mut config = Map::new();
+        config.insert("encoding".to_string(), "base64".into());
         self.insert_commitment(&mut config);
         config.insert("sigVerify".to_string(), Value::Bool(sig_verify));
 
-        let params = vec![Value::String(transaction), Value::Object(config)];
+        let params = vec![
+            Value::String(transaction.to_base64()),
+            Value::Object(config),
+        ];
 
-        let res: RpcContextValue<Value> = self
+        let res: RpcContextValue<SimulatedTransaction> = self
             .call(
                 format!("simulateTransaction-{now_ms}"),
                 "simulateTransaction",
@@ -182,6 +185,55 @@ impl<H: HttpOutcall> SvmClient<H> {
         Ok(res.value)
     }
 
+    /// Returns the prioritization fees paid in recent slots by transactions that
+    /// write to all of the `accounts`.
+    pub async fn get_recent_prioritization_fees(
+        &self,
+        now_ms: u64,
+        accounts: Vec<String>,
+    ) -> Result<Vec<PrioritizationFee>, String> {
+        let params = if accounts.is_empty() {
+            vec![]
+        } else {
+            vec![Value::Array(
+                accounts.into_iter().map(Value::String).collect(),
+            )]
+        };
+
+        self.call(
+            format!("getRecentPrioritizationFees-{now_ms}"),
+            "getRecentPrioritizationFees",
+            params.as_slice(),
+        )
+        .await
+    }
+
+    /// Returns up to `limit` signatures involving `address`, newest first, starting
+    /// after the `before` signature and stopping before the `until` signature.
+    pub async fn get_signatures_for_address(
+        &self,
+        now_ms: u64,
+        address: String,
+        before: Option<String>,
+        until: Option<String>,
+        limit: usize,
+    ) -> Result<Vec<ConfirmedSignature>, String> {
+        let mut config = Map::new();
+        self.insert_commitment(&mut config);
+        config.insert("limit".to_string(), Value::Number(limit.into()));
+        if let Some(before) = before {
+            config.insert("before".to_string(), Value::String(before));
+        }
+        if let Some(until) = until {
+            config.insert("until".to_string(), Value::String(until));
+        }
+
+        let id = format!("getSignaturesForAddress-{now_ms}-{address}");
+        let params = vec![Value::String(address), Value::Object(config)];
+        self.call(id, "getSignaturesForAddress", params.as_slice())
+            .await
+    }
+
     pub async fn get_account_info(
         &self,
         now_ms: u64,
@@ -204,6 +256,18 @@ impl<H: HttpOutcall> SvmClient<H> {
         Ok(res.value)
     }
 
+    pub async fn get_address_lookup_table(
+        &self,
+        now_ms: u64,
+        key: &Pubkey,
+    ) -> Result<AddressLookupTableAccount, String> {
+        let account = self
+            .get_account_info(now_ms, key.to_string())
+            .await?
+            .ok_or_else(|| format!("lookup table {key} not found"))?;
+        get_lookup_table_account(*key, account)
+    }
+
     pub async fn get_token_account_balance(
         &self,
         now_ms: u64,
@@ -443,6 +507,142 @@ mod tests {
         assert!(tx.is_none());
     }
 
+    #[test]
+    fn test_get_signatures_for_address() {
+        let mock = MockHttpOutcall::new(vec![success_response(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": [
+                {
+                    "signature": "5h6xBEauJ3PK6SWCZ1PGjBvj8vDdWG3KpwATGy1ARAXFSDwt8GFXM7W5Ncn16wmqokgpiKRLuS83KUxyZyv2sUYv",
+                    "slot": 114,
+                    "err": null,
+                    "memo": null,
+                    "blockTime": null,
+                    "confirmationStatus": "finalized"
+                }
+            ]
+        }))]);
+        let client = SvmClient::new(vec!["https://sol".to_string()], None, None, mock);
+
+        let sigs = futures::executor::block_on(client.get_signatures_for_address(
+            1_000,
+            "Vote111111111111111111111111111111111111111".to_string(),
+            None,
+            Some("4kEd3ciJBUvcwXq6uu9i7YGCcwLUp9t2rSKS3Hzs8wDH".to_string()),
+            10,
+        ))
+        .unwrap();
+        assert_eq!(sigs.len(), 1);
+        assert_eq!(sigs[0].slot, 114);
+        assert!(sigs[0].err.is_none());
+    }
+
+    #[test]
+    fn test_get_recent_prioritization_fees() {
+        let mock = MockHttpOutcall::new(vec![success_response(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": [
+                { "slot": 348125, "prioritizationFee": 0 },
+                { "slot": 348126, "prioritizationFee": 1000 },
+                { "slot": 348127, "prioritizationFee": 500 }
+            ]
+        }))]);
+        let client = SvmClient::new(vec!["https://sol".to_string()], None, None, mock);
+
+        let fees = futures::executor::block_on(client.get_recent_prioritization_fees(
+            1_000,
+            vec!["CxELquR1gPP8wHe33gZ4QxqGB3sZ9RSwsJ2KshVewkFY".to_string()],
+        ))
+        .unwrap();
+        assert_eq!(fees.len(), 3);
+        assert_eq!(
+            fees[1],
+            PrioritizationFee {
+                slot: 348126,
+                prioritization_fee: 1000
+            }
+        );
+    }
+
+    #[test]
+    fn test_simulate_transaction() {
+        let mock = MockHttpOutcall::new(vec![success_response(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": {
+                "context": { "slot": 218 },
+                "value": {
+                    "err": null,
+                    "accounts": null,
+                    "logs": ["Program 11111111111111111111111111111111 success"],
+                    "returnData": null,
+                    "unitsConsumed": 2366
+                }
+            }
+        }))]);
+        let client = SvmClient::new(vec!["https://sol".to_string()], None, None, mock);
+
+        let res = futures::executor::block_on(client.simulate_transaction(
+            1_000,
+            vec![1u8, 2, 3].into(),
+            false,
+        ))
+        .unwrap();
+        assert!(res.err.is_none());
+        assert_eq!(res.units_consumed, Some(2366));
+        assert_eq!(res.logs.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_get_address_lookup_table() {
+        let mock = MockHttpOutcall::new(vec![success_response(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": {
+                "context": { "slot": 1 },
+                "value": {
+                    "data": {
+                        "parsed": {
+                            "info": {
+                                "addresses": [
+                                    "So11111111111111111111111111111111111111112",
+                                    "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"
+                                ],
+                                "authority": "4Nd1mBQtrMJVYVfKf2PJy9NZUZdTAsp7D4xWLs4gDB4T",
+                                "deactivationSlot": "18446744073709551615",
+                                "lastExtendedSlot": "256",
+                                "lastExtendedSlotStartIndex": 0
+                            },
+                            "type": "lookupTable"
+                        },
+                        "program": "address-lookup-table",
+                        "space": 120
+                    },
+                    "executable": false,
+                    "lamports": 1726080,
+                    "owner": "AddressLookupTab1e1111111111111111111111111",
+                    "rentEpoch": 18446744073709551615u64,
+                    "space": 120
+                }
+            }
+        }))]);
+        let client = SvmClient::new(vec!["https://sol".to_string()], None, None, mock);
+
+        let key = Pubkey::new_unique();
+        let table =
+            futures::executor::block_on(client.get_address_lookup_table(1_000, &key)).unwrap();
+        assert_eq!(table.key, key);
+        assert_eq!(
+            table.addresses,
+            vec![
+                Pubkey::from_str_const("So11111111111111111111111111111111111111112"),
+                Pubkey::from_str_const("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"),
+            ]
+        );
+    }
+
     #[test]
     fn test_get_token_account_balance() {
         let mock = MockHttpOutcall::new(vec![success_response(json!({
@@ -6,8 +6,10 @@ pub use solana_account_decoder_client_types::{
     UiAccount, UiAccountData,
     token::{TokenAccountType, UiTokenAmount},
 };
+pub use solana_instruction::Instruction;
+pub use solana_message::{AddressLookupTableAccount, VersionedMessage};
 pub use solana_program::{hash::Hash, pubkey::Pubkey};
-pub use solana_transaction::{Message, Transaction};
+pub use solana_transaction::{Message, Signature, versioned::VersionedTransaction};
 pub use solana_transaction_status_client_types::EncodedTransactionWithStatusMeta;
 
 use crate::types::TransferChecked;
@@ -25,6 +27,31 @@ impl LatestBlockhash {
     }
 }
 
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PrioritizationFee {
+    pub slot: u64,
+    // micro-lamports per compute unit
+    pub prioritization_fee: u64,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulatedTransaction {
+    pub err: Option<Value>,
+    pub logs: Option<Vec<String>>,
+    pub units_consumed: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfirmedSignature {
+    pub signature: String,
+    pub slot: u64,
+    pub err: Option<Value>,
+    pub block_time: Option<i64>,
+}
+
 #[derive(Debug, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct SignatureStatus {
@@ -62,6 +89,31 @@ pub fn get_token_account(val: UiAccount) -> Result<TokenAccountType, String> {
     }
 }
 
+pub fn get_lookup_table_account(
+    key: Pubkey,
+    val: UiAccount,
+) -> Result<AddressLookupTableAccount, String> {
+    match val.data {
+        UiAccountData::Json(parsed_account) => {
+            let addresses = parsed_account.parsed["info"]["addresses"]
+                .as_array()
+                .ok_or("No addresses found in the lookup table".to_string())?
+                .iter()
+                .map(|v| {
+                    v.as_str()
+                        .and_then(|v| Pubkey::from_str(v).ok())
+                        .ok_or(format!("Invalid address in the lookup table: {}", v))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(AddressLookupTableAccount { key, addresses })
+        }
+        _ => Err("UiAccount data is not in JSON format".to_string()),
+    }
+}
+
+/// Returns the transfer of `token` in the transaction from the token balance changes.
+/// The received amount is net of any Token-2022 transfer fee, which is withheld in
+/// the recipient's token account and not part of its balance.
 pub fn get_transfer_checked(
     data: EncodedTransactionWithStatusMeta,
     token: &str,
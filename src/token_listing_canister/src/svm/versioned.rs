@@ -0,0 +1,124 @@
+use solana_message::v0;
+
+use super::types::{
+    AddressLookupTableAccount, Hash, Instruction, Message, Pubkey, Signature, VersionedMessage,
+    VersionedTransaction,
+};
+
+/// Compiles a v0 message when address lookup tables are given, or a legacy message otherwise.
+pub fn compile_message(
+    payer: &Pubkey,
+    ixs: &[Instruction],
+    lookup_tables: &[AddressLookupTableAccount],
+    blockhash: &Hash,
+) -> Result<VersionedMessage, String> {
+    if lookup_tables.is_empty() {
+        return Ok(VersionedMessage::Legacy(Message::new_with_blockhash(
+            ixs,
+            Some(payer),
+            blockhash,
+        )));
+    }
+
+    let message = v0::Message::try_compile(payer, ixs, lookup_tables, *blockhash)
+        .map_err(|err| format!("failed to compile v0 message: {err}"))?;
+    Ok(VersionedMessage::V0(message))
+}
+
+/// Returns a transaction with empty signatures, it can only be simulated without `sigVerify`.
+pub fn unsigned_transaction(message: VersionedMessage) -> VersionedTransaction {
+    let num_signatures = message.header().num_required_signatures as usize;
+    VersionedTransaction {
+        signatures: vec![Signature::default(); num_signatures],
+        message,
+    }
+}
+
+/// Assembles the transaction from the signatures of the required signers in order,
+/// each signature is over `message.serialize()`.
+pub fn signed_transaction(
+    message: VersionedMessage,
+    signatures: Vec<[u8; 64]>,
+) -> Result<VersionedTransaction, String> {
+    if signatures.len() != message.header().num_required_signatures as usize {
+        return Err(format!(
+            "expected {} signatures, got {}",
+            message.header().num_required_signatures,
+            signatures.len()
+        ));
+    }
+
+    Ok(VersionedTransaction {
+        signatures: signatures.into_iter().map(Signature::from).collect(),
+        message,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_instruction::AccountMeta;
+
+    #[test]
+    fn test_compile_message() {
+        let payer = Pubkey::new_unique();
+        let program_id = Pubkey::new_unique();
+        let accounts: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        let ix = Instruction {
+            program_id,
+            accounts: accounts
+                .iter()
+                .map(|key| AccountMeta::new(*key, false))
+                .collect(),
+            data: vec![1, 2, 3],
+        };
+        let blockhash = Hash::new_unique();
+
+        let legacy = compile_message(&payer, std::slice::from_ref(&ix), &[], &blockhash).unwrap();
+        assert!(matches!(legacy, VersionedMessage::Legacy(_)));
+        assert_eq!(legacy.static_account_keys().len(), 6);
+        assert_eq!(legacy.recent_blockhash(), &blockhash);
+
+        let table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: accounts.clone(),
+        };
+        let message = compile_message(
+            &payer,
+            std::slice::from_ref(&ix),
+            std::slice::from_ref(&table),
+            &blockhash,
+        )
+        .unwrap();
+        let VersionedMessage::V0(ref v0_message) = message else {
+            panic!("expected a v0 message");
+        };
+        assert_eq!(message.static_account_keys(), &[payer, program_id]);
+        assert_eq!(v0_message.address_table_lookups.len(), 1);
+        assert_eq!(
+            v0_message.address_table_lookups[0].writable_indexes,
+            vec![0, 1, 2, 3]
+        );
+        // the version prefix
+        assert_eq!(message.serialize()[0], 0x80);
+        assert!(message.serialize().len() < legacy.serialize().len());
+    }
+
+    #[test]
+    fn test_signed_transaction() {
+        let payer = Pubkey::new_unique();
+        let ix = solana_system_interface::instruction::transfer(&payer, &Pubkey::new_unique(), 1);
+        let message = compile_message(&payer, &[ix], &[], &Hash::new_unique()).unwrap();
+
+        let tx = unsigned_transaction(message.clone());
+        assert_eq!(tx.signatures, vec![Signature::default()]);
+
+        assert!(signed_transaction(message.clone(), vec![]).is_err());
+        let tx = signed_transaction(message, vec![[7u8; 64]]).unwrap();
+        assert_eq!(tx.signatures, vec![Signature::from([7u8; 64])]);
+
+        let data = bincode::serialize(&tx).unwrap();
+        let decoded: VersionedTransaction = bincode::deserialize(&data).unwrap();
+        assert_eq!(decoded, tx);
+    }
+}
@@ -54,6 +54,78 @@ pub struct StateInfo {
     pub auctions: Vec<AuctionId>,
     pub storages: Vec<Principal>,
     pub daos: Vec<Principal>,
+    pub evm_fee_config: EvmFeeConfig,
+    pub sol_fee_config: SolFeeConfig,
+}
+
+#[derive(CandidType, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EvmFeeConfig {
+    // Number of latest blocks sampled by eth_feeHistory
+    pub fee_history_blocks: u64,
+    // Percentile of the priority fees paid in each sampled block, 1 ~ 99
+    pub reward_percentile: u8,
+    // Caps in wei, the canister never pays more than them
+    pub max_priority_fee_per_gas: Option<u128>,
+    pub max_fee_per_gas: Option<u128>,
+}
+
+impl Default for EvmFeeConfig {
+    fn default() -> Self {
+        Self {
+            fee_history_blocks: 10,
+            reward_percentile: 50,
+            max_priority_fee_per_gas: None,
+            max_fee_per_gas: None,
+        }
+    }
+}
+
+impl EvmFeeConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.fee_history_blocks == 0 || self.fee_history_blocks > 1024 {
+            return Err("fee_history_blocks must be in 1 ~ 1024".to_string());
+        }
+        if self.reward_percentile == 0 || self.reward_percentile > 99 {
+            return Err("reward_percentile must be in 1 ~ 99".to_string());
+        }
+        if let (Some(priority), Some(max)) = (self.max_priority_fee_per_gas, self.max_fee_per_gas)
+            && priority > max
+        {
+            return Err("max_priority_fee_per_gas cannot exceed max_fee_per_gas".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(CandidType, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SolFeeConfig {
+    // Percentile of the recent prioritization fees paid for the written accounts, 0 ~ 100
+    pub priority_fee_percentile: u8,
+    // Bounds of the compute unit price in micro-lamports
+    pub min_compute_unit_price: u64,
+    pub max_compute_unit_price: u64,
+}
+
+impl Default for SolFeeConfig {
+    fn default() -> Self {
+        Self {
+            priority_fee_percentile: 75,
+            min_compute_unit_price: 0,
+            max_compute_unit_price: 1_000_000,
+        }
+    }
+}
+
+impl SolFeeConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.priority_fee_percentile > 100 {
+            return Err("priority_fee_percentile must be in 0 ~ 100".to_string());
+        }
+        if self.min_compute_unit_price > self.max_compute_unit_price {
+            return Err("min_compute_unit_price cannot exceed max_compute_unit_price".to_string());
+        }
+        Ok(())
+    }
 }
 
 #[derive(CandidType, Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -1,10 +1,17 @@
 use candid::Principal;
+use icrc_ledger_types::icrc1::account::Account;
 use std::collections::BTreeSet;
 
-use crate::{helper, store, types, x402};
+use crate::{api_admin, helper, ledger, store, types, x402};
 
 static X402_NETWORK: &str = "solana:5eykt4UsFv8P8NJdTREpY1vzqKqZKvdp";
 static X402_ASSET: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+static ICP_NETWORK: &str = "icp:1";
+const MAX_METERED_UNITS: u64 = 100;
+// the accepted payments of this action set the minimum top-up of prepaid credits
+static TOP_UP_ACTION: &str = "top_up_credits";
+// metered per started KiB of the stored image
+static STORE_IMAGE_ACTION: &str = "store_image";
 
 #[ic_cdk::query]
 fn info() -> Result<types::StateInfo, String> {
@@ -17,6 +24,39 @@ fn my_txs() -> Result<Vec<String>, String> {
     Ok(store::state::list_txs(caller))
 }
 
+#[ic_cdk::query]
+fn my_x402_receipts() -> Result<Vec<types::X402Receipt>, String> {
+    let caller = helper::msg_caller()?;
+    Ok(store::state::list_x402_receipts(caller))
+}
+
+#[ic_cdk::query]
+fn my_credits() -> Result<types::Credits, String> {
+    let caller = helper::msg_caller()?;
+    Ok(store::state::get_credits(caller))
+}
+
+#[ic_cdk::query]
+fn list_revenue(
+    start_date: Option<String>,
+    end_date: Option<String>,
+) -> Result<Vec<types::RevenueEntry>, String> {
+    let now_ms = ic_cdk::api::time() / 1_000_000;
+    let (start, end) = helper::revenue_days(start_date.as_deref(), end_date.as_deref(), now_ms)?;
+    Ok(store::state::list_revenue(start, end))
+}
+
+/// The receipt of a settled transaction, for the user who paid it and for controllers.
+#[ic_cdk::query]
+fn get_x402_receipt(txid: String) -> Result<types::X402Receipt, String> {
+    let caller = ic_cdk::api::msg_caller();
+    let receipt = store::state::get_x402_receipt(&txid)?;
+    if receipt.user != caller && api_admin::is_controller().is_err() {
+        return Err(format!("receipt of transaction {} not found", txid));
+    }
+    Ok(receipt)
+}
+
 #[ic_cdk::query]
 fn query_token(q: String) -> Vec<(u64, types::TokenMetadata)> {
     if q.len() < 3 {
@@ -35,24 +75,26 @@ fn list_tokens(take: usize, prev_id: Option<u64>) -> Vec<(u64, types::TokenMetad
     store::state::list_tokens(take.min(1000), prev_id)
 }
 
+/// The x402 payment of an action, discounted for the token the action is on.
 #[ic_cdk::query]
-fn x402_payment(action: String) -> Result<x402::X402PaymentOutput, String> {
+fn x402_payment(action: String, token_id: Option<u64>) -> Result<x402::X402PaymentOutput, String> {
     let caller = helper::msg_caller()?;
     let timestamp = ic_cdk::api::time() / 1_000_000;
-    store::state::with(|s| {
-        let amount = s.x402_prices.get(&action).cloned().ok_or_else(|| {
-            format!(
-                "No price set for action: {}, please contact the administrator",
-                action
-            )
-        })?;
+    let token_ids: Vec<u64> = token_id.into_iter().collect();
+    x402_payment_output(caller, &action, &token_ids, timestamp)
+}
 
-        s.x402.get_x402_exact_payment(
+fn x402_payment_output(
+    caller: Principal,
+    action: &str,
+    token_ids: &[u64],
+    timestamp: u64,
+) -> Result<x402::X402PaymentOutput, String> {
+    let discount = store::state::x402_discount(token_ids, timestamp);
+    store::state::with(|s| {
+        s.x402.get_x402_payment(
             &caller,
-            X402_NETWORK.to_string(),
-            X402_ASSET.to_string(),
-            amount.into(),
-            s.x402_pay_to.clone(),
+            x402_discounted(x402_accepts(s, action, timestamp)?, discount)?,
             timestamp,
             x402::ResourceInfo {
                 url: "https://tokenlist.ing".to_string(),
@@ -64,6 +106,183 @@ fn x402_payment(action: String) -> Result<x402::X402PaymentOutput, String> {
     })
 }
 
+#[ic_cdk::query]
+fn x402_metered_payment(
+    action: String,
+    max_units: u64,
+    token_ids: Option<Vec<u64>>,
+) -> Result<x402::X402PaymentOutput, String> {
+    let caller = helper::msg_caller()?;
+    let timestamp = ic_cdk::api::time() / 1_000_000;
+    x402_metered_payment_output(
+        caller,
+        &action,
+        &token_ids.unwrap_or_default(),
+        max_units,
+        timestamp,
+    )
+}
+
+fn x402_metered_payment_output(
+    caller: Principal,
+    action: &str,
+    token_ids: &[u64],
+    max_units: u64,
+    timestamp: u64,
+) -> Result<x402::X402PaymentOutput, String> {
+    if max_units == 0 || max_units > MAX_METERED_UNITS {
+        return Err(format!(
+            "max units must be between 1 and {}",
+            MAX_METERED_UNITS
+        ));
+    }
+    let discount = store::state::x402_discount(token_ids, timestamp);
+    store::state::with(|s| {
+        let accepts = x402_discounted(x402_accepts(s, action, timestamp)?, discount)?;
+        s.x402.get_x402_payment(
+            &caller,
+            x402_upto_accepts(accepts, max_units)?,
+            timestamp,
+            x402::ResourceInfo {
+                url: "https://tokenlist.ing".to_string(),
+                description: Some(format!(
+                    "Payment for up to {} units of action: {}",
+                    max_units, action
+                )),
+                mime_type: None,
+            },
+            Some(format!("Payment required for action: {}", action)),
+        )
+    })
+}
+
+#[ic_cdk::query]
+fn x402_top_up_payment(amount: u64) -> Result<x402::X402PaymentOutput, String> {
+    let caller = helper::msg_caller()?;
+    let timestamp = ic_cdk::api::time() / 1_000_000;
+    x402_top_up_payment_output(caller, amount, timestamp)
+}
+
+fn x402_top_up_payment_output(
+    caller: Principal,
+    amount: u64,
+    timestamp: u64,
+) -> Result<x402::X402PaymentOutput, String> {
+    store::state::with(|s| {
+        s.x402.get_x402_payment(
+            &caller,
+            x402_top_up_accepts(s, amount as u128, timestamp)?,
+            timestamp,
+            x402::ResourceInfo {
+                url: "https://tokenlist.ing".to_string(),
+                description: Some(format!("Top up {} prepaid credits", amount)),
+                mime_type: None,
+            },
+            Some("Payment required for topping up prepaid credits".to_string()),
+        )
+    })
+}
+
+// A top-up pays `amount` with any payment accepted for topping up, at least its price
+fn x402_top_up_accepts(
+    s: &store::State,
+    amount: u128,
+    timestamp: u64,
+) -> Result<Vec<x402::PaymentRequirements>, String> {
+    x402_accepts(s, TOP_UP_ACTION, timestamp)?
+        .into_iter()
+        .map(|pr| {
+            let min_amount = pr.amount()?;
+            if amount < min_amount {
+                return Err(format!("top-up amount must be at least {}", min_amount));
+            }
+            Ok(x402::PaymentRequirements::exact(
+                pr.network, pr.asset, amount, pr.pay_to, pr.extra,
+            ))
+        })
+        .collect()
+}
+
+// The payment requirements accepted for an action by payments issued at `timestamp`
+fn x402_accepts(
+    s: &store::State,
+    action: &str,
+    timestamp: u64,
+) -> Result<Vec<x402::PaymentRequirements>, String> {
+    let scheduled = s
+        .x402_price_schedule
+        .iter()
+        .rev()
+        .find(|c| c.action == action && c.effective_at <= timestamp)
+        .map(|c| &c.options);
+    if let Some(options) = scheduled.or_else(|| s.x402_accepts.get(action)) {
+        return options
+            .iter()
+            .map(|o| {
+                Ok(x402::PaymentRequirements::exact(
+                    o.network.clone(),
+                    o.asset.clone(),
+                    o.amount.into(),
+                    o.pay_to.clone(),
+                    o.extra()?
+                        .or_else(|| s.x402.payment_requirements_extra.clone()),
+                ))
+            })
+            .collect();
+    }
+
+    let amount = s.x402_prices.get(action).cloned().ok_or_else(|| {
+        format!(
+            "No price set for action: {}, please contact the administrator",
+            action
+        )
+    })?;
+    Ok(vec![x402::PaymentRequirements::exact(
+        X402_NETWORK.to_string(),
+        X402_ASSET.to_string(),
+        amount.into(),
+        s.x402_pay_to.clone(),
+        s.x402.payment_requirements_extra.clone(),
+    )])
+}
+
+// Applies a discount in percent to the amounts of the payment requirements
+fn x402_discounted(
+    accepts: Vec<x402::PaymentRequirements>,
+    discount: u8,
+) -> Result<Vec<x402::PaymentRequirements>, String> {
+    if discount == 0 {
+        return Ok(accepts);
+    }
+    accepts
+        .into_iter()
+        .map(|mut pr| {
+            let amount = pr.amount()? * (100 - discount.min(100) as u128) / 100;
+            pr.amount = amount.to_string();
+            Ok(pr)
+        })
+        .collect()
+}
+
+// Metered actions pay the action's price per unit, up to the maximum units of the payment
+fn x402_upto_accepts(
+    accepts: Vec<x402::PaymentRequirements>,
+    max_units: u64,
+) -> Result<Vec<x402::PaymentRequirements>, String> {
+    accepts
+        .into_iter()
+        .map(|pr| {
+            let max_amount = pr
+                .amount()?
+                .checked_mul(max_units as u128)
+                .ok_or("amount overflow")?;
+            Ok(x402::PaymentRequirements::upto(
+                pr.network, pr.asset, max_amount, pr.pay_to, pr.extra,
+            ))
+        })
+        .collect()
+}
+
 #[ic_cdk::query]
 fn check_permission(token_id: u64, user: Principal) -> Result<Vec<String>, String> {
     store::state::check_permission(token_id, user)
@@ -72,21 +291,149 @@ fn check_permission(token_id: u64, user: Principal) -> Result<Vec<String>, Strin
 #[ic_cdk::update]
 fn register_token(
     input: types::TokenMetadata,
-    payment: x402::PayingResultInput,
+    payment: Option<x402::PayingResultInput>,
 ) -> Result<u64, String> {
     let caller = helper::msg_caller()?;
     let now_ms = ic_cdk::api::time() / 1_000_000;
-    x402_settle(caller, "register_token", payment, now_ms)?;
+    register_token_with(caller, input, payment, now_ms)
+}
+
+fn register_token_with(
+    caller: Principal,
+    input: types::TokenMetadata,
+    payment: Option<x402::PayingResultInput>,
+    now_ms: u64,
+) -> Result<u64, String> {
     input.validate()?;
-    store::state::register_token(caller, input, now_ms)
+    store::state::check_token_locations(&input, None)?;
+
+    let paid = match payment {
+        Some(payment) => x402_settle(caller, "register_token", &[], payment, now_ms)?,
+        None => x402_redeem(caller, "register_token", &[], 1, now_ms)?,
+    };
+    x402_complete(
+        caller,
+        paid,
+        store::state::register_token(caller, input, now_ms),
+        now_ms,
+    )
 }
 
+/// Updates the metadata of a token, metered as one unit of the batch update.
 #[ic_cdk::update]
-fn update_token_metadata(token_id: u64, input: types::TokenMetadata) -> Result<(), String> {
+fn update_token_metadata(
+    token_id: u64,
+    input: types::TokenMetadata,
+    payment: Option<x402::PayingResultInput>,
+) -> Result<(), String> {
     let caller = helper::msg_caller()?;
     let now_ms = ic_cdk::api::time() / 1_000_000;
-    input.validate()?;
-    store::state::update_token_metadata(token_id, caller, input, now_ms)
+    batch_update_token_metadata_with(caller, vec![(token_id, input)], payment, now_ms)
+}
+
+#[ic_cdk::update]
+fn batch_update_token_metadata(
+    updates: Vec<(u64, types::TokenMetadata)>,
+    payment: Option<x402::PayingResultInput>,
+) -> Result<(), String> {
+    let caller = helper::msg_caller()?;
+    let now_ms = ic_cdk::api::time() / 1_000_000;
+    batch_update_token_metadata_with(caller, updates, payment, now_ms)
+}
+
+fn batch_update_token_metadata_with(
+    caller: Principal,
+    updates: Vec<(u64, types::TokenMetadata)>,
+    payment: Option<x402::PayingResultInput>,
+    now_ms: u64,
+) -> Result<(), String> {
+    if updates.is_empty() || updates.len() as u64 > MAX_METERED_UNITS {
+        return Err(format!(
+            "updates must be between 1 and {}",
+            MAX_METERED_UNITS
+        ));
+    }
+    let mut ids = BTreeSet::new();
+    for (id, input) in &updates {
+        if !ids.insert(*id) {
+            return Err(format!("duplicate token id {}", id));
+        }
+        input.validate()?;
+    }
+    store::state::check_batch_update(caller, &updates)?;
+
+    let token_ids: Vec<u64> = ids.into_iter().collect();
+    let units = token_ids.len() as u64;
+    let paid = match payment {
+        Some(payment) => x402_settle_metered(
+            caller,
+            "update_token_metadata",
+            &token_ids,
+            payment,
+            units,
+            now_ms,
+        )?,
+        None => x402_redeem(caller, "update_token_metadata", &token_ids, units, now_ms)?,
+    };
+    x402_complete(
+        caller,
+        paid,
+        store::state::batch_update_token_metadata(caller, updates, now_ms),
+        now_ms,
+    )
+}
+
+/// Checks that `user` may store an image of `size` bytes for the token and charges the
+/// storage, metered per started KiB. Called by the images canister, the user pays with
+/// `x402_metered_payment("store_image", units, ..)` or prepaid credits.
+#[ic_cdk::update]
+fn charge_image_storage(
+    token_id: u64,
+    user: Principal,
+    size: u64,
+    payment: Option<x402::PayingResultInput>,
+) -> Result<Vec<String>, String> {
+    let caller = ic_cdk::api::msg_caller();
+    let now_ms = ic_cdk::api::time() / 1_000_000;
+    charge_image_storage_with(caller, token_id, user, size, payment, now_ms)
+}
+
+fn charge_image_storage_with(
+    caller: Principal,
+    token_id: u64,
+    user: Principal,
+    size: u64,
+    payment: Option<x402::PayingResultInput>,
+    now_ms: u64,
+) -> Result<Vec<String>, String> {
+    if store::state::with(|s| s.images_canister) != Some(caller) {
+        return Err("caller is not the images canister".to_string());
+    }
+    helper::check_auth(&user)?;
+    let units = size.div_ceil(1024);
+    if units == 0 || units > MAX_METERED_UNITS {
+        return Err(format!(
+            "image size must be between 1 byte and {} KiB",
+            MAX_METERED_UNITS
+        ));
+    }
+    let locations = store::state::check_permission(token_id, user)?;
+    if locations.is_empty() {
+        return Err("No location on token for storing images".to_string());
+    }
+
+    let paid = match payment {
+        Some(payment) => x402_settle_metered(
+            user,
+            STORE_IMAGE_ACTION,
+            &[token_id],
+            payment,
+            units,
+            now_ms,
+        )?,
+        None => x402_redeem(user, STORE_IMAGE_ACTION, &[token_id], units, now_ms)?,
+    };
+    x402_complete(user, paid, Ok(locations), now_ms)
 }
 
 #[ic_cdk::update]
@@ -100,40 +447,1248 @@ fn update_token_controllers(token_id: u64, input: BTreeSet<Principal>) -> Result
 fn set_announcement(
     token_id: u64,
     input: types::Announcement,
-    payment: x402::PayingResultInput,
+    payment: Option<x402::PayingResultInput>,
+) -> Result<(), String> {
+    let caller = helper::msg_caller()?;
+    let now_ms = ic_cdk::api::time() / 1_000_000;
+    set_announcement_with(caller, token_id, input, payment, now_ms)
+}
+
+fn set_announcement_with(
+    caller: Principal,
+    token_id: u64,
+    input: types::Announcement,
+    payment: Option<x402::PayingResultInput>,
+    now_ms: u64,
 ) -> Result<(), String> {
+    input.validate()?;
+    store::state::check_announcement(token_id, caller, &input)?;
+
+    let paid = match payment {
+        Some(payment) => x402_settle(caller, "set_announcements", &[token_id], payment, now_ms)?,
+        None => x402_redeem(caller, "set_announcements", &[token_id], 1, now_ms)?,
+    };
+    x402_complete(
+        caller,
+        paid,
+        store::state::set_announcement(token_id, caller, input, now_ms),
+        now_ms,
+    )
+}
+
+#[ic_cdk::update]
+fn top_up_credits(payment: x402::PayingResultInput) -> Result<u128, String> {
+    let caller = helper::msg_caller()?;
+    let now_ms = ic_cdk::api::time() / 1_000_000;
+    top_up_credits_with(caller, payment, now_ms)
+}
+
+fn top_up_credits_with(
+    caller: Principal,
+    payment: x402::PayingResultInput,
+    now_ms: u64,
+) -> Result<u128, String> {
+    let (paid, credits) = store::state::with_mut(|s| {
+        s.promote_x402_prices(now_ms);
+        let amount = payment.payment_requirements()?.amount()?;
+        let accepts = x402_top_up_accepts(s, amount, payment.timestamp)?;
+        let timestamp = payment.timestamp;
+        let rt = s.x402.settle_response(payment, caller, &accepts, now_ms)?;
+        let amount = rt.settled_amount()?;
+        let credits = x402_top_up_credits(
+            s,
+            &rt.payment_requirements.network,
+            &rt.payment_requirements.asset,
+            amount,
+            timestamp,
+        )?;
+        let paid = x402_record(s, caller, TOP_UP_ACTION, 1, rt, amount, now_ms)?;
+        Ok::<_, String>((paid, credits))
+    })?;
+    let X402Paid::Settled(txid) = paid else {
+        return Err("unexpected payment".to_string());
+    };
+    store::state::top_up_credits(
+        caller,
+        types::CreditLogKind::X402TopUp(txid),
+        credits,
+        now_ms,
+    )
+}
+
+/// Tops up prepaid credits from the caller's ICRC-2 approval on the credits ledger.
+#[ic_cdk::update]
+async fn top_up_credits_icrc2(amount: u64) -> Result<u128, String> {
     let caller = helper::msg_caller()?;
+    let credits_ledger = store::state::with(|s| s.credits_ledger)
+        .ok_or_else(|| "credits ledger is not set".to_string())?;
+    if amount == 0 {
+        return Err("top-up amount must be greater than zero".to_string());
+    }
+    let now_ms = ic_cdk::api::time() / 1_000_000;
+    let credits = store::state::with_mut(|s| {
+        s.promote_x402_prices(now_ms);
+        x402_top_up_credits(
+            s,
+            ICP_NETWORK,
+            &credits_ledger.to_text(),
+            amount as u128,
+            now_ms,
+        )
+    })?;
+
+    let block_index = ledger::transfer_from(
+        credits_ledger,
+        Account {
+            owner: caller,
+            subaccount: None,
+        },
+        Account {
+            owner: ic_cdk::api::canister_self(),
+            subaccount: None,
+        },
+        amount.into(),
+    )
+    .await?;
     let now_ms = ic_cdk::api::time() / 1_000_000;
-    x402_settle(caller, "set_announcements", payment, now_ms)?;
-    store::state::set_announcement(token_id, caller, input, now_ms)
+    store::state::record_revenue(
+        &store::Revenue {
+            action: TOP_UP_ACTION,
+            network: ICP_NETWORK,
+            asset: &credits_ledger.to_text(),
+            pay_to: &ic_cdk::api::canister_self().to_text(),
+            amount: amount as u128,
+        },
+        now_ms,
+    );
+    store::state::with_mut(|s| s.total_incoming += amount as u128);
+    store::state::top_up_credits(
+        caller,
+        types::CreditLogKind::Icrc2TopUp(block_index),
+        credits,
+        now_ms,
+    )
 }
 
+// A payment for an action: a new settlement, the credited receipt of a failed action,
+// or prepaid credits that are debited when the action succeeds
+#[derive(Debug)]
+enum X402Paid {
+    Settled(String),
+    Credited(String),
+    Prepaid {
+        action: String,
+        units: u64,
+        cost: u128,
+    },
+}
+
+// Settles an action on the tokens, priced when the payment was issued
 fn x402_settle(
     caller: Principal,
     action: &str,
+    token_ids: &[u64],
     payment: x402::PayingResultInput,
     now_ms: u64,
-) -> Result<(), String> {
+) -> Result<X402Paid, String> {
+    let discount = store::state::x402_discount(token_ids, payment.timestamp);
+    store::state::with_mut(|s| {
+        s.promote_x402_prices(now_ms);
+        let accepts = x402_discounted(x402_accepts(s, action, payment.timestamp)?, discount)?;
+        let rt = s.x402.settle_response(payment, caller, &accepts, now_ms)?;
+        match rt.settled_amount() {
+            Ok(amount) => x402_record(s, caller, action, 1, rt, amount, now_ms),
+            Err(err) => {
+                // the funds have moved, the failed receipt keeps the amount when it is readable
+                let amount = rt
+                    .settle_response
+                    .amount
+                    .as_deref()
+                    .and_then(|v| v.parse::<u128>().ok())
+                    .unwrap_or(0);
+                let paid = x402_record(s, caller, action, 1, rt, amount, now_ms)?;
+                x402_complete(caller, paid, Err(err), now_ms)
+            }
+        }
+    })
+}
+
+// Settles a metered action of `units`, the payer settles exactly the price of the units
+fn x402_settle_metered(
+    caller: Principal,
+    action: &str,
+    token_ids: &[u64],
+    payment: x402::PayingResultInput,
+    units: u64,
+    now_ms: u64,
+) -> Result<X402Paid, String> {
+    let discount = store::state::x402_discount(token_ids, payment.timestamp);
     store::state::with_mut(|s| {
-        let amount = s.x402_prices.get(action).cloned().ok_or_else(|| {
+        s.promote_x402_prices(now_ms);
+        let pr = payment.payment_requirements()?;
+        let accepts = x402_discounted(x402_accepts(s, action, payment.timestamp)?, discount)?;
+        let price = accepts
+            .iter()
+            .find(|a| a.network == pr.network && a.asset == pr.asset && a.pay_to == pr.pay_to)
+            .ok_or("payment requirements mismatch")?
+            .amount()?;
+        let max_amount = pr.amount()?;
+        if price == 0 || max_amount % price != 0 {
+            return Err("payment requirements mismatch".to_string());
+        }
+        let max_units = (max_amount / price) as u64;
+        if units > max_units {
+            return Err(format!(
+                "{} units exceed the paid maximum of {} units",
+                units, max_units
+            ));
+        }
+
+        let accepts = x402_upto_accepts(accepts, max_units)?;
+        let rt = s.x402.settle_response(payment, caller, &accepts, now_ms)?;
+        let amount = rt.settled_amount()?;
+        let cost = price * units as u128;
+        if amount != cost {
+            // the funds have moved, the failed receipt keeps the units they pay for
+            let err = format!(
+                "settled amount {} does not match the cost {} of {} units",
+                amount, cost, units
+            );
+            let paid_units = (amount / price) as u64;
+            let paid = x402_record(s, caller, action, paid_units, rt, amount, now_ms)?;
+            return x402_complete(caller, paid, Err(err), now_ms);
+        }
+        x402_record(s, caller, action, units, rt, amount, now_ms)
+    })
+}
+
+fn x402_record(
+    s: &mut store::State,
+    caller: Principal,
+    action: &str,
+    units: u64,
+    rt: x402::PaymentSettleResult,
+    amount: u128,
+    now_ms: u64,
+) -> Result<X402Paid, String> {
+    let txid = rt.settle_response.transaction;
+    let receipt = types::X402Receipt {
+        txid: txid.clone(),
+        user: caller,
+        payer: rt.settle_response.payer,
+        action: action.to_string(),
+        units,
+        network: rt.settle_response.network,
+        asset: rt.payment_requirements.asset,
+        pay_to: rt.payment_requirements.pay_to,
+        amount,
+        settled_at: now_ms,
+        outcome: types::X402ReceiptOutcome::Succeeded,
+        updated_at: now_ms,
+    };
+    store::state::record_x402_receipt(rt.nonce, receipt.clone())?;
+    store::state::record_revenue(
+        &store::Revenue {
+            action,
+            network: &receipt.network,
+            asset: &receipt.asset,
+            pay_to: &receipt.pay_to,
+            amount,
+        },
+        now_ms,
+    );
+    s.total_incoming += amount;
+
+    store::state::add_tx(caller, txid.clone());
+    Ok(X402Paid::Settled(txid))
+}
+
+// Pays for an action without a new payment: with the user's credited payment of a failed
+// action, or with prepaid credits
+fn x402_redeem(
+    caller: Principal,
+    action: &str,
+    token_ids: &[u64],
+    units: u64,
+    now_ms: u64,
+) -> Result<X402Paid, String> {
+    if let Some(txid) = store::state::find_x402_credit(caller, action, units) {
+        return Ok(X402Paid::Credited(txid));
+    }
+
+    let discount = store::state::x402_discount(token_ids, now_ms);
+    let cost = store::state::with(|s| x402_credit_cost(s, action, discount, units, now_ms))?;
+    if store::state::credit_balance(caller) < cost {
+        return Err(format!(
+            "Payment or {} prepaid credits required for action: {}",
+            cost, action
+        ));
+    }
+    Ok(X402Paid::Prepaid {
+        action: action.to_string(),
+        units,
+        cost,
+    })
+}
+
+// Prepaid credits cost the action's price in the credit asset per unit
+fn x402_credit_cost(
+    s: &store::State,
+    action: &str,
+    discount: u8,
+    units: u64,
+    now_ms: u64,
+) -> Result<u128, String> {
+    let accepts = x402_discounted(x402_accepts(s, action, now_ms)?, discount)?;
+    x402_credit_price(&accepts, action)?
+        .checked_mul(units as u128)
+        .ok_or_else(|| "amount overflow".to_string())
+}
+
+// Credits are counted in atomic units of the default payment asset, USDC on Solana
+fn x402_credit_price(accepts: &[x402::PaymentRequirements], action: &str) -> Result<u128, String> {
+    accepts
+        .iter()
+        .find(|pr| pr.network == X402_NETWORK && pr.asset == X402_ASSET)
+        .ok_or_else(|| format!("No credit price set for action: {}", action))?
+        .amount()
+}
+
+// Converts a top-up of `amount` of an asset into credits, at the ratio of the minimum
+// top-ups in the credit asset and in the asset. Assets without a top-up price are rejected.
+fn x402_top_up_credits(
+    s: &store::State,
+    network: &str,
+    asset: &str,
+    amount: u128,
+    timestamp: u64,
+) -> Result<u128, String> {
+    let accepts = x402_accepts(s, TOP_UP_ACTION, timestamp)?;
+    let credit_price = x402_credit_price(&accepts, TOP_UP_ACTION)?;
+    let price = accepts
+        .iter()
+        .find(|pr| pr.network == network && pr.asset == asset)
+        .ok_or_else(|| format!("No top-up price set for asset {} on {}", asset, network))?
+        .amount()?;
+    if price == 0 {
+        return Err(format!(
+            "No top-up price set for asset {} on {}",
+            asset, network
+        ));
+    }
+    let credits = amount
+        .checked_mul(credit_price)
+        .ok_or_else(|| "amount overflow".to_string())?
+        / price;
+    Ok(credits)
+}
+
+// Records the outcome of a paid action. A failed retry keeps the credit, and prepaid
+// credits are only debited when the action succeeds.
+fn x402_complete<T>(
+    caller: Principal,
+    paid: X402Paid,
+    rt: Result<T, String>,
+    now_ms: u64,
+) -> Result<T, String> {
+    match (paid, &rt) {
+        (X402Paid::Settled(txid), Err(err)) => {
+            store::state::set_x402_receipt_outcome(
+                &txid,
+                types::X402ReceiptOutcome::Failed(err.clone()),
+                now_ms,
+            )?;
+        }
+        (X402Paid::Credited(txid), Ok(_)) => {
+            store::state::set_x402_receipt_outcome(
+                &txid,
+                types::X402ReceiptOutcome::Succeeded,
+                now_ms,
+            )?;
+        }
+        (
+            X402Paid::Prepaid {
+                action,
+                units,
+                cost,
+            },
+            Ok(_),
+        ) => {
+            store::state::debit_credits(caller, &action, units, cost, now_ms)?;
+        }
+        _ => {}
+    }
+    rt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use x402_facilitator::Facilitator;
+
+    fn setup(facilitator: &Facilitator) {
+        store::state::with_mut(|s| {
+            s.x402.paying_public_keys = vec![facilitator.public_key()];
+            s.x402_prices = [
+                ("register_token".to_string(), 1_000_000),
+                ("set_announcements".to_string(), 100_000),
+            ]
+            .into();
+            s.x402_pay_to = "9zf1nS8wWLcB6s1exaTH4dpRP8eXz3GpaW8KwhCNEhVs".to_string();
+            s.x402_accepts.insert(
+                "register_token".to_string(),
+                vec![
+                    types::X402PriceOption {
+                        network: X402_NETWORK.to_string(),
+                        asset: X402_ASSET.to_string(),
+                        amount: 1_000_000,
+                        pay_to: s.x402_pay_to.clone(),
+                        extra: None,
+                    },
+                    types::X402PriceOption {
+                        network: "eip155:8453".to_string(),
+                        asset: "0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913".to_string(),
+                        amount: 1_000_000,
+                        pay_to: "0x0000000000000000000000000000000000000001".to_string(),
+                        extra: Some(r#"{"name":"USD Coin","version":"2"}"#.to_string()),
+                    },
+                ],
+            );
+        });
+    }
+
+    fn token(symbol: &str) -> types::TokenMetadata {
+        types::TokenMetadata {
+            name: "ICPanda".to_string(),
+            symbol: symbol.to_string(),
+            decimals: 8,
+            image: "https://panda.fans/_assets/logo.svg".to_string(),
+            description: "ICPanda token".to_string(),
+            external_url: "https://panda.fans".to_string(),
+            links: vec![],
+            locations: vec![format!("icp:1/token:{}", symbol.to_ascii_lowercase())],
+        }
+    }
+
+    fn listing_announcement(symbol: &str) -> types::Announcement {
+        types::Announcement {
+            id: 0,
+            r#type: "listing".to_string(),
+            title: "Listed".to_string(),
+            content: format!("{} is listed", symbol),
+            url: None,
+            published_at: 0,
+        }
+    }
+
+    // Registers a token paid with a new settlement
+    fn register(facilitator: &Facilitator, user: Principal, symbol: &str, now_ms: u64) -> u64 {
+        let output = x402_payment_output(user, "register_token", &[], now_ms).unwrap();
+        let txid = format!("tx-{}", symbol.to_ascii_lowercase());
+        let payment = facilitator.settle(&output, 0, "payer", &txid).unwrap();
+        register_token_with(user, token(symbol), Some(payment), now_ms).unwrap()
+    }
+
+    #[test]
+    fn test_x402_register_token_and_set_announcement() {
+        let facilitator = Facilitator::from_seed(b"facilitator");
+        setup(&facilitator);
+        let user = Principal::from_slice(&[1]);
+        let now_ms = 1_000_000;
+
+        // pays for registration with USDC on Base
+        let output = x402_payment_output(user, "register_token", &[], now_ms).unwrap();
+        let pr = x402_facilitator::payment_required(&output).unwrap();
+        assert_eq!(pr.accepts.len(), 2);
+        assert_eq!(pr.accepts[1].network, "eip155:8453");
+        let payment = facilitator.settle(&output, 1, "0xpayer", "0x01").unwrap();
+        let id = register_token_with(user, token("PANDA"), Some(payment.clone()), now_ms).unwrap();
+
+        // the same result can not be used again
+        assert!(register_token_with(user, token("TOKEN"), Some(payment), now_ms + 1).is_err());
+        // nor the same transaction in a new result
+        let output = x402_payment_output(user, "register_token", &[], now_ms + 1).unwrap();
+        let payment = facilitator.settle(&output, 0, "payer", "0x01").unwrap();
+        assert_eq!(
+            register_token_with(user, token("TOKEN"), Some(payment), now_ms + 1).unwrap_err(),
+            "transaction 0x01 has already been used"
+        );
+
+        // pays for an announcement with USDC on Solana
+        let output = x402_payment_output(user, "set_announcements", &[id], now_ms + 2).unwrap();
+        let payment = facilitator.settle(&output, 0, "payer", "tx2").unwrap();
+        // a result paid for another action is rejected
+        assert!(
+            register_token_with(user, token("TOKEN"), Some(payment.clone()), now_ms + 2).is_err()
+        );
+        set_announcement_with(
+            user,
+            id,
+            listing_announcement("PANDA"),
+            Some(payment),
+            now_ms + 2,
+        )
+        .unwrap();
+        assert_eq!(
+            store::state::get_token_profile(id)
+                .unwrap()
+                .announcements
+                .len(),
+            1
+        );
+
+        assert_eq!(store::state::list_txs(user), vec!["0x01", "tx2"]);
+        assert_eq!(store::state::info().total_incoming, 1_100_000);
+    }
+
+    #[test]
+    fn test_x402_reused_transaction() {
+        let facilitator = Facilitator::from_seed(b"facilitator");
+        setup(&facilitator);
+        let user = Principal::from_slice(&[1]);
+        let now_ms = 1_000_000;
+
+        let output = x402_payment_output(user, "register_token", &[], now_ms).unwrap();
+        let payment = facilitator.settle(&output, 0, "payer", "tx1").unwrap();
+        let id = register_token_with(user, token("PANDA"), Some(payment), now_ms).unwrap();
+
+        // a new result for the settled transaction is rejected, for another action and
+        // after the first result expired
+        let later = now_ms + x402::TIME_EXPIRY_MS + 1;
+        let output = x402_payment_output(user, "set_announcements", &[id], later).unwrap();
+        let payment = facilitator.settle(&output, 0, "payer", "tx1").unwrap();
+        let announcement = listing_announcement("PANDA");
+        assert_eq!(
+            set_announcement_with(user, id, announcement, Some(payment), later).unwrap_err(),
+            "transaction tx1 has already been used"
+        );
+        assert_eq!(store::state::list_txs(user), vec!["tx1"]);
+        assert_eq!(store::state::info().total_incoming, 1_000_000);
+    }
+
+    #[test]
+    fn test_x402_batch_update_token_metadata() {
+        let facilitator = Facilitator::from_seed(b"facilitator");
+        setup(&facilitator);
+        store::state::with_mut(|s| {
+            s.x402_prices
+                .insert("update_token_metadata".to_string(), 10_000);
+        });
+        let user = Principal::from_slice(&[1]);
+        let now_ms = 1_000_000;
+        let id1 = register(&facilitator, user, "PANDA", now_ms);
+        let id2 = register(&facilitator, user, "TOKEN", now_ms);
+
+        // pays up to 5 updates, settles 2
+        let output =
+            x402_metered_payment_output(user, "update_token_metadata", &[], 5, now_ms).unwrap();
+        let pr = x402_facilitator::payment_required(&output).unwrap();
+        assert_eq!(pr.accepts[0].scheme, "upto");
+        assert_eq!(pr.accepts[0].amount, "50000");
+
+        let mut panda = token("PANDA");
+        panda.description = "Updated".to_string();
+        let updates = vec![(id1, panda), (id2, token("TOKEN"))];
+
+        // settles more than the usage
+        let payment = facilitator
+            .settle_upto(&output, 0, "payer", "tx1", 30_000)
+            .unwrap();
+        assert_eq!(
+            batch_update_token_metadata_with(user, updates.clone(), Some(payment), now_ms)
+                .unwrap_err(),
+            "settled amount 30000 does not match the cost 20000 of 2 units"
+        );
+        // the payment is kept as a failed receipt of the units it pays for
+        let receipt = store::state::get_x402_receipt("tx1").unwrap();
+        assert_eq!(receipt.units, 3);
+        assert_eq!(
+            receipt.outcome,
+            types::X402ReceiptOutcome::Failed(
+                "settled amount 30000 does not match the cost 20000 of 2 units".to_string()
+            )
+        );
+
+        let output =
+            x402_metered_payment_output(user, "update_token_metadata", &[], 5, now_ms + 1).unwrap();
+        let payment = facilitator
+            .settle_upto(&output, 0, "payer", "tx2", 20_000)
+            .unwrap();
+        // not a controller
+        let other = Principal::from_slice(&[2]);
+        assert!(
+            batch_update_token_metadata_with(
+                other,
+                updates.clone(),
+                Some(payment.clone()),
+                now_ms + 1
+            )
+            .is_err()
+        );
+        batch_update_token_metadata_with(user, updates.clone(), Some(payment), now_ms + 1).unwrap();
+        assert_eq!(
+            store::state::get_token_profile(id1)
+                .unwrap()
+                .metadata
+                .description,
+            "Updated"
+        );
+        assert_eq!(
+            store::state::list_txs(user),
+            vec!["tx-panda", "tx-token", "tx1", "tx2"]
+        );
+        assert_eq!(store::state::info().total_incoming, 2_050_000);
+
+        // more updates than paid for
+        let output =
+            x402_metered_payment_output(user, "update_token_metadata", &[], 1, now_ms + 2).unwrap();
+        let payment = facilitator
+            .settle_upto(&output, 0, "payer", "tx3", 20_000)
+            .unwrap();
+        assert_eq!(
+            batch_update_token_metadata_with(user, updates, Some(payment), now_ms + 2).unwrap_err(),
+            "2 units exceed the paid maximum of 1 units"
+        );
+
+        assert!(
+            x402_metered_payment_output(user, "update_token_metadata", &[], 0, now_ms).is_err()
+        );
+        assert!(
+            x402_metered_payment_output(user, "update_token_metadata", &[], 101, now_ms).is_err()
+        );
+    }
+
+    #[test]
+    fn test_x402_image_storage() {
+        let facilitator = Facilitator::from_seed(b"facilitator");
+        setup(&facilitator);
+        let images = Principal::from_slice(&[9]);
+        store::state::with_mut(|s| {
+            s.x402_prices.insert(STORE_IMAGE_ACTION.to_string(), 1_000);
+            s.images_canister = Some(images);
+        });
+        let user = Principal::from_slice(&[1]);
+        let now_ms = 1_000_000;
+        let id = register(&facilitator, user, "PANDA", now_ms);
+
+        // 1500 bytes are 2 units
+        let output =
+            x402_metered_payment_output(user, STORE_IMAGE_ACTION, &[id], 2, now_ms).unwrap();
+        let payment = facilitator
+            .settle_upto(&output, 0, "payer", "tx1", 2_000)
+            .unwrap();
+        assert_eq!(
+            charge_image_storage_with(user, id, user, 1_500, Some(payment.clone()), now_ms)
+                .unwrap_err(),
+            "caller is not the images canister"
+        );
+        assert_eq!(
+            charge_image_storage_with(images, id, user, 100 * 1024 + 1, None, now_ms).unwrap_err(),
+            "image size must be between 1 byte and 100 KiB"
+        );
+        let other = Principal::from_slice(&[2]);
+        assert!(
+            charge_image_storage_with(images, id, other, 1_500, Some(payment.clone()), now_ms)
+                .is_err()
+        );
+        let locations =
+            charge_image_storage_with(images, id, user, 1_500, Some(payment), now_ms).unwrap();
+        assert_eq!(locations, vec!["icp:1/token:panda", "panda"]);
+
+        let receipt = store::state::get_x402_receipt("tx1").unwrap();
+        assert_eq!(receipt.action, STORE_IMAGE_ACTION);
+        assert_eq!((receipt.units, receipt.amount), (2, 2_000));
+        // the next image is paid again
+        assert_eq!(
+            charge_image_storage_with(images, id, user, 1_024, None, now_ms + 1).unwrap_err(),
+            "Payment or 1000 prepaid credits required for action: store_image"
+        );
+    }
+
+    #[test]
+    fn test_x402_settle_rejections() {
+        let facilitator = Facilitator::from_seed(b"facilitator");
+        setup(&facilitator);
+        let user = Principal::from_slice(&[1]);
+        let now_ms = 1_000_000;
+        let output = x402_payment_output(user, "register_token", &[], now_ms).unwrap();
+
+        // failed settlement
+        let payment = facilitator
+            .settle_failed(&output, 0, "insufficient funds")
+            .unwrap();
+        assert_eq!(
+            register_token_with(user, token("PANDA"), Some(payment), now_ms).unwrap_err(),
+            "payment settlement failed: insufficient funds"
+        );
+
+        // unknown facilitator
+        let payment = Facilitator::from_seed(b"other")
+            .settle(&output, 0, "payer", "tx1")
+            .unwrap();
+        assert_eq!(
+            register_token_with(user, token("PANDA"), Some(payment), now_ms).unwrap_err(),
+            "signature verification failed"
+        );
+
+        // paid by another user
+        let payment = facilitator.settle(&output, 0, "payer", "tx1").unwrap();
+        let other = Principal::from_slice(&[2]);
+        assert!(register_token_with(other, token("PANDA"), Some(payment.clone()), now_ms).is_err());
+
+        // expired
+        assert!(
+            register_token_with(
+                user,
+                token("PANDA"),
+                Some(payment.clone()),
+                now_ms + x402::TIME_EXPIRY_MS + 1
+            )
+            .is_err()
+        );
+
+        // no price for the action
+        assert!(x402_payment_output(user, "unknown", &[], now_ms).is_err());
+
+        register_token_with(user, token("PANDA"), Some(payment), now_ms).unwrap();
+        assert_eq!(store::state::list_txs(user), vec!["tx1"]);
+    }
+
+    #[test]
+    fn test_x402_unreadable_settled_amount() {
+        let facilitator = Facilitator::from_seed(b"facilitator");
+        setup(&facilitator);
+        let user = Principal::from_slice(&[1]);
+        let now_ms = 1_000_000;
+
+        // a settlement whose amount does not parse
+        let output = x402_payment_output(user, "register_token", &[], now_ms).unwrap();
+        let payment_requirements = x402_facilitator::payment_required(&output)
+            .unwrap()
+            .accepts
+            .swap_remove(0);
+        let result = x402::PaymentSettleResult {
+            settle_response: x402::SettleResponse {
+                success: true,
+                error_reason: None,
+                transaction: "tx1".to_string(),
+                network: payment_requirements.network.clone(),
+                payer: Some("payer".to_string()),
+                amount: Some("1,000,000".to_string()),
+            },
+            payment_requirements,
+            nonce: output.nonce.clone(),
+        };
+        let payment = facilitator.sign(&result, output.timestamp).unwrap();
+        assert_eq!(
+            register_token_with(user, token("PANDA"), Some(payment), now_ms).unwrap_err(),
+            "invalid amount in settlement response"
+        );
+
+        // the payment is kept as a failed receipt of the action
+        let receipt = store::state::get_x402_receipt("tx1").unwrap();
+        assert_eq!(receipt.units, 1);
+        assert_eq!(receipt.amount, 0);
+        assert_eq!(
+            receipt.outcome,
+            types::X402ReceiptOutcome::Failed("invalid amount in settlement response".to_string())
+        );
+        assert_eq!(store::state::list_txs(user), vec!["tx1"]);
+        assert!(store::state::get_token_profile(1).is_err());
+    }
+
+    #[test]
+    fn test_x402_receipts_and_credits() {
+        let facilitator = Facilitator::from_seed(b"facilitator");
+        setup(&facilitator);
+        store::state::with_mut(|s| {
+            s.x402_prices
+                .insert("update_token_metadata".to_string(), 10_000);
+        });
+        let user = Principal::from_slice(&[1]);
+        let now_ms = 1_000_000;
+
+        // an invalid token is rejected before paying
+        let output = x402_payment_output(user, "register_token", &[], now_ms).unwrap();
+        let payment = facilitator.settle(&output, 0, "payer", "tx1").unwrap();
+        assert_eq!(
+            register_token_with(user, token(" PANDA"), Some(payment.clone()), now_ms).unwrap_err(),
+            "Symbol contains leading or trailing whitespace"
+        );
+        let id1 = register_token_with(user, token("PANDA"), Some(payment), now_ms).unwrap();
+        // so is a registered location
+        let output = x402_payment_output(user, "register_token", &[], now_ms + 1).unwrap();
+        let payment = facilitator.settle(&output, 0, "payer", "tx2").unwrap();
+        assert_eq!(
+            register_token_with(user, token("PANDA"), Some(payment.clone()), now_ms + 1)
+                .unwrap_err(),
+            "token location icp:1/token:panda already registered"
+        );
+        let id2 = register_token_with(user, token("TOKEN"), Some(payment), now_ms + 1).unwrap();
+        // and an announcement of another user's token
+        let output = x402_payment_output(user, "set_announcements", &[], now_ms + 2).unwrap();
+        let payment = facilitator.settle(&output, 0, "payer", "tx3").unwrap();
+        let announcement = listing_announcement("PANDA");
+        let other = Principal::from_slice(&[2]);
+        assert!(set_announcement_with(other, id1, announcement.clone(), None, now_ms + 2).is_err());
+        assert!(set_announcement_with(user, id1, announcement.clone(), None, now_ms + 2).is_err());
+        set_announcement_with(user, id1, announcement, Some(payment), now_ms + 2).unwrap();
+
+        let receipts = store::state::list_x402_receipts(user);
+        assert_eq!(receipts.len(), 3);
+        assert_eq!(receipts[0].txid, "tx1");
+        assert_eq!(receipts[0].action, "register_token");
+        assert_eq!(receipts[0].payer.as_deref(), Some("payer"));
+        assert_eq!(receipts[0].amount, 1_000_000);
+        assert_eq!(receipts[2].action, "set_announcements");
+        assert!(
+            receipts
+                .iter()
+                .all(|r| r.outcome == types::X402ReceiptOutcome::Succeeded)
+        );
+
+        // both updates claim the same location, rejected before paying
+        let mut panda = token("PANDA");
+        panda.locations.push("icp:1/token:new".to_string());
+        let mut token2 = token("TOKEN");
+        token2.locations.push("icp:1/token:new".to_string());
+        let updates = vec![(id1, panda), (id2, token2)];
+        let output =
+            x402_metered_payment_output(user, "update_token_metadata", &[], 2, now_ms + 3).unwrap();
+        let payment = facilitator
+            .settle_upto(&output, 0, "payer", "tx4", 20_000)
+            .unwrap();
+        assert_eq!(
+            batch_update_token_metadata_with(user, updates, Some(payment), now_ms + 3).unwrap_err(),
             format!(
-                "No price set for action: {}, please contact the administrator",
-                action
+                "token location icp:1/token:new claimed by tokens {} and {}",
+                id1, id2
             )
-        })?;
-        let rt = s.x402.settle_response(
-            payment,
-            caller,
-            X402_ASSET,
-            amount.into(),
-            &s.x402_pay_to,
-            now_ms,
-        )?;
+        );
+        assert!(store::state::get_x402_receipt("tx4").is_err());
+        // and none of them is applied
+        let mut probe = token("NEW");
+        probe.locations = vec!["icp:1/token:new".to_string()];
+        store::state::check_token_locations(&probe, None).unwrap();
 
-        s.total_incoming += amount as u128;
+        // settles less than the cost, the failed receipt pays for 1 unit
+        let updates = vec![(id1, token("PANDA")), (id2, token("TOKEN"))];
+        let payment = facilitator
+            .settle_upto(&output, 0, "payer", "tx4", 10_000)
+            .unwrap();
+        assert!(
+            batch_update_token_metadata_with(user, updates.clone(), Some(payment), now_ms + 3)
+                .is_err()
+        );
+        let receipt = store::state::get_x402_receipt("tx4").unwrap();
+        assert_eq!(receipt.units, 1);
+        assert!(matches!(
+            receipt.outcome,
+            types::X402ReceiptOutcome::Failed(_)
+        ));
 
-        store::state::add_tx(caller, rt.settle_response.transaction);
-        Ok::<_, String>(())
-    })?;
-    Ok(())
+        // no credit yet
+        let single = vec![(id1, token("PANDA"))];
+        assert_eq!(
+            batch_update_token_metadata_with(user, single.clone(), None, now_ms + 4).unwrap_err(),
+            "Payment or 10000 prepaid credits required for action: update_token_metadata"
+        );
+        assert!(store::state::admin_credit_x402_receipt("tx1", now_ms + 4).is_err());
+        store::state::admin_credit_x402_receipt("tx4", now_ms + 4).unwrap();
+        // the credit pays for exactly 1 unit of the action
+        assert!(
+            set_announcement_with(user, id2, listing_announcement("TOKEN"), None, now_ms + 4)
+                .is_err()
+        );
+        assert!(batch_update_token_metadata_with(user, updates, None, now_ms + 4).is_err());
+        batch_update_token_metadata_with(user, single.clone(), None, now_ms + 5).unwrap();
+        let receipt = store::state::get_x402_receipt("tx4").unwrap();
+        assert_eq!(receipt.outcome, types::X402ReceiptOutcome::Succeeded);
+        assert_eq!(receipt.updated_at, now_ms + 5);
+        // the credit is used
+        assert!(batch_update_token_metadata_with(user, single, None, now_ms + 6).is_err());
+        assert!(
+            store::state::admin_refund_x402_receipt("tx4", "refund".to_string(), now_ms + 6)
+                .is_err()
+        );
+
+        // a failed registration is refunded
+        store::state::set_x402_receipt_outcome(
+            "tx2",
+            types::X402ReceiptOutcome::Failed("failed".to_string()),
+            now_ms + 6,
+        )
+        .unwrap();
+        store::state::admin_refund_x402_receipt("tx2", "refund".to_string(), now_ms + 6).unwrap();
+        assert_eq!(
+            store::state::get_x402_receipt("tx2").unwrap().outcome,
+            types::X402ReceiptOutcome::Refunded("refund".to_string())
+        );
+        assert!(store::state::admin_credit_x402_receipt("tx2", now_ms + 7).is_err());
+        assert!(store::state::get_x402_receipt("tx0").is_err());
+    }
+
+    #[test]
+    fn test_prepaid_credits() {
+        let facilitator = Facilitator::from_seed(b"facilitator");
+        setup(&facilitator);
+        store::state::with_mut(|s| {
+            s.x402_prices.insert(TOP_UP_ACTION.to_string(), 1_000_000);
+        });
+        let user = Principal::from_slice(&[1]);
+        let now_ms = 1_000_000;
+
+        assert_eq!(
+            x402_top_up_payment_output(user, 999_999, now_ms).unwrap_err(),
+            "top-up amount must be at least 1000000"
+        );
+        assert_eq!(
+            register_token_with(user, token("PANDA"), None, now_ms).unwrap_err(),
+            "Payment or 1000000 prepaid credits required for action: register_token"
+        );
+
+        let output = x402_top_up_payment_output(user, 1_150_000, now_ms).unwrap();
+        let pr = x402_facilitator::payment_required(&output).unwrap();
+        assert_eq!(pr.accepts[0].amount, "1150000");
+        let payment = facilitator.settle(&output, 0, "payer", "tx1").unwrap();
+        // a top-up payment does not pay for an action
+        assert!(register_token_with(user, token("PANDA"), Some(payment.clone()), now_ms).is_err());
+        assert_eq!(
+            top_up_credits_with(user, payment.clone(), now_ms).unwrap(),
+            1_150_000
+        );
+        assert!(top_up_credits_with(user, payment, now_ms).is_err());
+
+        // actions debit the credits when they succeed
+        let id = register_token_with(user, token("PANDA"), None, now_ms + 1).unwrap();
+        assert!(register_token_with(user, token("PANDA"), None, now_ms + 1).is_err());
+        let announcement = listing_announcement("PANDA");
+        set_announcement_with(user, id, announcement, None, now_ms + 2).unwrap();
+        assert_eq!(
+            register_token_with(user, token("TOKEN"), None, now_ms + 3).unwrap_err(),
+            "Payment or 1000000 prepaid credits required for action: register_token"
+        );
+
+        let credits = store::state::get_credits(user);
+        assert_eq!(credits.balance, 50_000);
+        assert_eq!(
+            credits.history,
+            vec![
+                types::CreditLog {
+                    kind: types::CreditLogKind::X402TopUp("tx1".to_string()),
+                    amount: 1_150_000,
+                    balance: 1_150_000,
+                    created_at: now_ms,
+                },
+                types::CreditLog {
+                    kind: types::CreditLogKind::Debit {
+                        action: "register_token".to_string(),
+                        units: 1,
+                    },
+                    amount: 1_000_000,
+                    balance: 150_000,
+                    created_at: now_ms + 1,
+                },
+                types::CreditLog {
+                    kind: types::CreditLogKind::Debit {
+                        action: "set_announcements".to_string(),
+                        units: 1,
+                    },
+                    amount: 100_000,
+                    balance: 50_000,
+                    created_at: now_ms + 2,
+                },
+            ]
+        );
+        let info = store::state::info();
+        assert_eq!(info.total_credits, 50_000);
+        assert_eq!(info.total_incoming, 1_150_000);
+        assert_eq!(
+            store::state::list_x402_receipts(user)[0].action,
+            TOP_UP_ACTION
+        );
+        assert_eq!(
+            store::state::get_credits(Principal::from_slice(&[2])).balance,
+            0
+        );
+    }
+
+    #[test]
+    fn test_credit_top_up_assets() {
+        let facilitator = Facilitator::from_seed(b"facilitator");
+        setup(&facilitator);
+        let base_usdc = "0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913";
+        let ckusdc = "xevnm-gaaaa-aaaar-qafnq-cai";
+        store::state::with_mut(|s| {
+            s.x402_accepts.insert(
+                TOP_UP_ACTION.to_string(),
+                vec![
+                    types::X402PriceOption {
+                        network: X402_NETWORK.to_string(),
+                        asset: X402_ASSET.to_string(),
+                        amount: 1_000_000,
+                        pay_to: s.x402_pay_to.clone(),
+                        extra: None,
+                    },
+                    // an asset with 8 decimals
+                    types::X402PriceOption {
+                        network: "eip155:8453".to_string(),
+                        asset: base_usdc.to_string(),
+                        amount: 100_000_000,
+                        pay_to: "0x0000000000000000000000000000000000000001".to_string(),
+                        extra: None,
+                    },
+                ],
+            );
+        });
+        let user = Principal::from_slice(&[1]);
+        let now_ms = 1_000_000;
+
+        // the top-up is converted into credits of the credit asset
+        let output = x402_top_up_payment_output(user, 150_000_000, now_ms).unwrap();
+        let pr = x402_facilitator::payment_required(&output).unwrap();
+        assert_eq!(pr.accepts[0].amount, "150000000");
+        let payment = facilitator.settle(&output, 1, "0xpayer", "0x01").unwrap();
+        assert_eq!(
+            top_up_credits_with(user, payment, now_ms).unwrap(),
+            1_500_000
+        );
+        assert_eq!(store::state::info().total_incoming, 150_000_000);
+
+        // an asset without a top-up price is rejected
+        store::state::with(|s| {
+            assert_eq!(
+                x402_top_up_credits(s, ICP_NETWORK, ckusdc, 1_000_000, now_ms).unwrap_err(),
+                format!(
+                    "No top-up price set for asset {} on {}",
+                    ckusdc, ICP_NETWORK
+                )
+            );
+        });
+
+        // the history keeps the latest changes
+        for i in 0..100 {
+            store::state::top_up_credits(
+                user,
+                types::CreditLogKind::Icrc2TopUp(i.to_string()),
+                1,
+                now_ms + i,
+            )
+            .unwrap();
+        }
+        let credits = store::state::get_credits(user);
+        assert_eq!(credits.balance, 1_500_100);
+        assert_eq!(credits.history.len(), 100);
+        assert_eq!(
+            credits.history[0].kind,
+            types::CreditLogKind::Icrc2TopUp("0".to_string())
+        );
+    }
+
+    #[test]
+    fn test_revenue_ledger() {
+        let facilitator = Facilitator::from_seed(b"facilitator");
+        setup(&facilitator);
+        let user = Principal::from_slice(&[1]);
+        // 2026-01-31T12:00:00Z
+        let now_ms = 1_769_860_800_000;
+        let day_ms = 24 * 3600 * 1000;
+
+        let output = x402_payment_output(user, "register_token", &[], now_ms).unwrap();
+        let payment = facilitator.settle(&output, 0, "payer", "tx1").unwrap();
+        register_token_with(user, token("PANDA"), Some(payment), now_ms).unwrap();
+        let output = x402_payment_output(user, "register_token", &[], now_ms + 1).unwrap();
+        let payment = facilitator.settle(&output, 1, "0xpayer", "0x01").unwrap();
+        register_token_with(user, token("TOKEN"), Some(payment), now_ms + 1).unwrap();
+        let output = x402_payment_output(user, "register_token", &[], now_ms + day_ms).unwrap();
+        let payment = facilitator.settle(&output, 0, "payer", "tx2").unwrap();
+        register_token_with(user, token("TOKEN2"), Some(payment), now_ms + day_ms).unwrap();
+        store::state::set_x402_receipt_outcome(
+            "tx1",
+            types::X402ReceiptOutcome::Failed("failed".to_string()),
+            now_ms + day_ms,
+        )
+        .unwrap();
+        store::state::admin_refund_x402_receipt("tx1", "refund".to_string(), now_ms + day_ms)
+            .unwrap();
+
+        let (start, end) = helper::revenue_days(None, None, now_ms + day_ms).unwrap();
+        assert_eq!(end - start, 29);
+        let rows = store::state::list_revenue(start, end);
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].date, "2026-01-31");
+        assert_eq!(rows[0].network, "eip155:8453");
+        assert_eq!(rows[0].pay_to, "0x0000000000000000000000000000000000000001");
+        assert_eq!((rows[0].count, rows[0].amount), (1, 1_000_000));
+        assert_eq!(rows[1].date, "2026-01-31");
+        assert_eq!(rows[1].network, X402_NETWORK);
+        assert_eq!(rows[1].asset, X402_ASSET);
+        assert_eq!((rows[1].count, rows[1].amount), (1, 1_000_000));
+        assert_eq!(rows[1].refunded_count, 0);
+        // the refund is accounted on the day it was made
+        assert_eq!(rows[2].date, "2026-02-01");
+        assert_eq!(rows[2].action, "register_token");
+        assert_eq!((rows[2].count, rows[2].amount), (1, 1_000_000));
+        assert_eq!(
+            (rows[2].refunded_count, rows[2].refunded_amount),
+            (1, 1_000_000)
+        );
+
+        let (start, end) =
+            helper::revenue_days(Some("2026-02-01"), Some("2026-02-01"), now_ms).unwrap();
+        assert_eq!(store::state::list_revenue(start, end), rows[2..].to_vec());
+        assert!(helper::revenue_days(Some("2026-02-02"), Some("2026-02-01"), now_ms).is_err());
+        assert!(helper::revenue_days(Some("2025-01-01"), Some("2026-02-01"), now_ms).is_err());
+        assert!(helper::revenue_days(Some("2026/02/01"), None, now_ms).is_err());
+    }
+
+    #[test]
+    fn test_x402_price_schedule_and_discounts() {
+        let facilitator = Facilitator::from_seed(b"facilitator");
+        setup(&facilitator);
+        let user = Principal::from_slice(&[1]);
+        let now_ms = 1_000_000;
+        let effective_at = now_ms + 1_000;
+        store::state::with_mut(|s| {
+            s.x402_accepts.remove("register_token");
+            s.x402_price_schedule.push(types::X402PriceChange {
+                action: "register_token".to_string(),
+                effective_at,
+                options: vec![types::X402PriceOption {
+                    network: X402_NETWORK.to_string(),
+                    asset: X402_ASSET.to_string(),
+                    amount: 2_000_000,
+                    pay_to: s.x402_pay_to.clone(),
+                    extra: None,
+                }],
+            });
+        });
+
+        // a payment issued before the change keeps its price
+        let output = x402_payment_output(user, "register_token", &[], now_ms).unwrap();
+        let pr = x402_facilitator::payment_required(&output).unwrap();
+        assert_eq!(pr.accepts[0].amount, "1000000");
+        let payment = facilitator.settle(&output, 0, "payer", "tx1").unwrap();
+        let id1 =
+            register_token_with(user, token("PANDA"), Some(payment), effective_at + 1).unwrap();
+
+        let output = x402_payment_output(user, "register_token", &[], effective_at).unwrap();
+        let pr = x402_facilitator::payment_required(&output).unwrap();
+        assert_eq!(pr.accepts[0].amount, "2000000");
+        let payment = facilitator.settle(&output, 0, "payer", "tx2").unwrap();
+        assert_eq!(store::state::info().x402_price_schedule.len(), 1);
+        // the change is applied once every payment issued before it has expired
+        let later = effective_at + x402::TIME_EXPIRY_MS + 1;
+        assert!(register_token_with(user, token("TOKEN"), Some(payment), later).is_err());
+        let info = store::state::info();
+        assert!(info.x402_price_schedule.is_empty());
+        assert_eq!(info.x402_accepts["register_token"][0].amount, 2_000_000);
+        let output = x402_payment_output(user, "register_token", &[], later).unwrap();
+        let payment = facilitator.settle(&output, 0, "payer", "tx3").unwrap();
+        let id2 = register_token_with(user, token("TOKEN"), Some(payment), later).unwrap();
+
+        // discounts for verified and tagged tokens
+        store::state::with_mut(|s| {
+            s.x402_prices
+                .insert("update_token_metadata".to_string(), 10_000);
+            s.x402_discounts = vec![
+                types::X402Discount {
+                    target: types::X402DiscountTarget::Verified,
+                    percent: 50,
+                },
+                types::X402Discount {
+                    target: types::X402DiscountTarget::Tag("DeFi".to_string()),
+                    percent: 20,
+                },
+            ];
+        });
+        store::state::admin_update_token_verification_badge(
+            id1,
+            types::VerificationBadge {
+                is_verified: true,
+                methods: vec!["dao_vote".to_string()],
+                verified_at: later,
+            },
+            later,
+        )
+        .unwrap();
+        store::state::admin_update_token_tags(id2, vec!["DeFi".to_string()], later).unwrap();
+        assert_eq!(store::state::x402_discount(&[id1], later), 50);
+        assert_eq!(store::state::x402_discount(&[id1, id2], later), 20);
+        assert_eq!(store::state::x402_discount(&[id1, 1], later), 0);
+
+        let output = x402_payment_output(user, "set_announcements", &[id1], later).unwrap();
+        let pr = x402_facilitator::payment_required(&output).unwrap();
+        assert_eq!(pr.accepts[0].amount, "50000");
+        let announcement = listing_announcement("PANDA");
+        // the discount belongs to the token
+        let payment = facilitator.settle(&output, 0, "payer", "tx4").unwrap();
+        assert!(
+            set_announcement_with(
+                user,
+                id2,
+                announcement.clone(),
+                Some(payment.clone()),
+                later
+            )
+            .is_err()
+        );
+        set_announcement_with(user, id1, announcement, Some(payment), later).unwrap();
+
+        let output =
+            x402_metered_payment_output(user, "update_token_metadata", &[id1, id2], 2, later)
+                .unwrap();
+        let pr = x402_facilitator::payment_required(&output).unwrap();
+        assert_eq!(pr.accepts[0].amount, "16000");
+        let payment = facilitator
+            .settle_upto(&output, 0, "payer", "tx5", 16_000)
+            .unwrap();
+        let updates = vec![(id1, token("PANDA")), (id2, token("TOKEN"))];
+        batch_update_token_metadata_with(user, updates, Some(payment), later).unwrap();
+        assert_eq!(store::state::info().total_incoming, 3_066_000);
+
+        // a discount change mid-flow keeps the discount of issued payments
+        let output = x402_payment_output(user, "set_announcements", &[id1], later).unwrap();
+        store::state::with_mut(|s| {
+            s.x402_discount_schedule.push(types::X402DiscountChange {
+                effective_at: later + 1,
+                discounts: vec![types::X402Discount {
+                    target: types::X402DiscountTarget::Verified,
+                    percent: 10,
+                }],
+            });
+        });
+        let payment = facilitator.settle(&output, 0, "payer", "tx6").unwrap();
+        set_announcement_with(
+            user,
+            id1,
+            listing_announcement("PANDA"),
+            Some(payment),
+            later + 2,
+        )
+        .unwrap();
+        assert_eq!(
+            store::state::get_x402_receipt("tx6").unwrap().amount,
+            50_000
+        );
+        // payments issued from the change get its discount
+        let output = x402_payment_output(user, "set_announcements", &[id1], later + 1).unwrap();
+        let pr = x402_facilitator::payment_required(&output).unwrap();
+        assert_eq!(pr.accepts[0].amount, "90000");
+        let payment = facilitator.settle(&output, 0, "payer", "tx7").unwrap();
+        let expired = later + 2 + x402::TIME_EXPIRY_MS;
+        set_announcement_with(
+            user,
+            id1,
+            listing_announcement("PANDA"),
+            Some(payment),
+            expired - 1,
+        )
+        .unwrap();
+        // and the change is applied once the earlier payments expired
+        store::state::with_mut(|s| s.promote_x402_prices(expired));
+        let info = store::state::info();
+        assert!(info.x402_discount_schedule.is_empty());
+        assert_eq!(info.x402_discounts[0].percent, 10);
+    }
 }
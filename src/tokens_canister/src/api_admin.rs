@@ -1,18 +1,14 @@
-use ic_auth_types::ByteArrayB64;
+use candid::Principal;
 use serde_json::{Map, Value};
-use std::str::FromStr;
 
 use crate::{
     helper::{format_error, pretty_format},
-    store, types,
+    store, types, x402,
 };
 
 #[ic_cdk::update(guard = "is_controller")]
 fn admin_set_paying_public_keys(public_keys: Vec<String>) -> Result<(), String> {
-    let mut paying_public_keys = Vec::new();
-    for key in public_keys {
-        paying_public_keys.push(ByteArrayB64::from_str(&key).map_err(format_error)?)
-    }
+    let paying_public_keys = x402::parse_public_keys(public_keys)?;
 
     store::state::with_mut(|s| {
         s.x402.paying_public_keys = paying_public_keys;
@@ -22,9 +18,7 @@ fn admin_set_paying_public_keys(public_keys: Vec<String>) -> Result<(), String>
 
 #[ic_cdk::update]
 fn validate_admin_set_paying_public_keys(public_keys: Vec<String>) -> Result<String, String> {
-    for key in &public_keys {
-        ByteArrayB64::<32>::from_str(key).map_err(format_error)?;
-    }
+    x402::parse_public_keys(public_keys.clone())?;
     pretty_format(&(public_keys,))
 }
 
@@ -41,6 +35,156 @@ fn validate_admin_set_x402_prices(prices: Vec<(String, u64)>) -> Result<String,
     pretty_format(&(prices,))
 }
 
+#[ic_cdk::update(guard = "is_controller")]
+fn admin_set_x402_accepts(
+    action: String,
+    options: Vec<types::X402PriceOption>,
+) -> Result<(), String> {
+    for option in &options {
+        option.validate()?;
+    }
+    store::state::with_mut(|s| {
+        if options.is_empty() {
+            s.x402_accepts.remove(&action);
+        } else {
+            s.x402_accepts.insert(action, options);
+        }
+        Ok(())
+    })
+}
+
+#[ic_cdk::update]
+fn validate_admin_set_x402_accepts(
+    action: String,
+    options: Vec<types::X402PriceOption>,
+) -> Result<String, String> {
+    for option in &options {
+        option.validate()?;
+    }
+    pretty_format(&(action, options))
+}
+
+#[ic_cdk::update(guard = "is_controller")]
+fn admin_schedule_x402_price(change: types::X402PriceChange) -> Result<(), String> {
+    let now_ms = ic_cdk::api::time() / 1_000_000;
+    change.validate(now_ms)?;
+    store::state::with_mut(|s| {
+        s.promote_x402_prices(now_ms);
+        s.x402_price_schedule
+            .retain(|c| c.action != change.action || c.effective_at != change.effective_at);
+        let i = s
+            .x402_price_schedule
+            .partition_point(|c| c.effective_at <= change.effective_at);
+        s.x402_price_schedule.insert(i, change);
+        Ok(())
+    })
+}
+
+#[ic_cdk::update]
+fn validate_admin_schedule_x402_price(change: types::X402PriceChange) -> Result<String, String> {
+    let now_ms = ic_cdk::api::time() / 1_000_000;
+    change.validate(now_ms)?;
+    pretty_format(&(change,))
+}
+
+#[ic_cdk::update(guard = "is_controller")]
+fn admin_cancel_x402_price(action: String, effective_at: u64) -> Result<(), String> {
+    let now_ms = ic_cdk::api::time() / 1_000_000;
+    check_cancel_x402_price(&action, effective_at, now_ms)?;
+    store::state::with_mut(|s| {
+        s.x402_price_schedule
+            .retain(|c| c.action != action || c.effective_at != effective_at);
+        Ok(())
+    })
+}
+
+#[ic_cdk::update]
+fn validate_admin_cancel_x402_price(action: String, effective_at: u64) -> Result<String, String> {
+    let now_ms = ic_cdk::api::time() / 1_000_000;
+    check_cancel_x402_price(&action, effective_at, now_ms)?;
+    pretty_format(&(action, effective_at))
+}
+
+// Only price changes that have not taken effect can be cancelled
+fn check_cancel_x402_price(action: &str, effective_at: u64, now_ms: u64) -> Result<(), String> {
+    if effective_at <= now_ms {
+        return Err("price change has already taken effect".to_string());
+    }
+    store::state::with(|s| {
+        if !s
+            .x402_price_schedule
+            .iter()
+            .any(|c| c.action == action && c.effective_at == effective_at)
+        {
+            return Err(format!(
+                "no price change of action {} at {}",
+                action, effective_at
+            ));
+        }
+        Ok(())
+    })
+}
+
+#[ic_cdk::update(guard = "is_controller")]
+fn admin_schedule_x402_discounts(change: types::X402DiscountChange) -> Result<(), String> {
+    let now_ms = ic_cdk::api::time() / 1_000_000;
+    change.validate(now_ms)?;
+    store::state::with_mut(|s| {
+        s.promote_x402_prices(now_ms);
+        s.x402_discount_schedule
+            .retain(|c| c.effective_at != change.effective_at);
+        let i = s
+            .x402_discount_schedule
+            .partition_point(|c| c.effective_at <= change.effective_at);
+        s.x402_discount_schedule.insert(i, change);
+        Ok(())
+    })
+}
+
+#[ic_cdk::update]
+fn validate_admin_schedule_x402_discounts(
+    change: types::X402DiscountChange,
+) -> Result<String, String> {
+    let now_ms = ic_cdk::api::time() / 1_000_000;
+    change.validate(now_ms)?;
+    pretty_format(&(change,))
+}
+
+#[ic_cdk::update(guard = "is_controller")]
+fn admin_cancel_x402_discounts(effective_at: u64) -> Result<(), String> {
+    let now_ms = ic_cdk::api::time() / 1_000_000;
+    check_cancel_x402_discounts(effective_at, now_ms)?;
+    store::state::with_mut(|s| {
+        s.x402_discount_schedule
+            .retain(|c| c.effective_at != effective_at);
+        Ok(())
+    })
+}
+
+#[ic_cdk::update]
+fn validate_admin_cancel_x402_discounts(effective_at: u64) -> Result<String, String> {
+    let now_ms = ic_cdk::api::time() / 1_000_000;
+    check_cancel_x402_discounts(effective_at, now_ms)?;
+    pretty_format(&(effective_at,))
+}
+
+// Only discount changes that have not taken effect can be cancelled
+fn check_cancel_x402_discounts(effective_at: u64, now_ms: u64) -> Result<(), String> {
+    if effective_at <= now_ms {
+        return Err("discount change has already taken effect".to_string());
+    }
+    store::state::with(|s| {
+        if !s
+            .x402_discount_schedule
+            .iter()
+            .any(|c| c.effective_at == effective_at)
+        {
+            return Err(format!("no discount change at {}", effective_at));
+        }
+        Ok(())
+    })
+}
+
 #[ic_cdk::update(guard = "is_controller")]
 fn admin_set_x402_pay_to(x402_pay_to: String) -> Result<(), String> {
     store::state::with_mut(|s| {
@@ -54,6 +198,34 @@ fn validate_admin_set_x402_pay_to(x402_pay_to: String) -> Result<String, String>
     pretty_format(&(x402_pay_to,))
 }
 
+#[ic_cdk::update(guard = "is_controller")]
+fn admin_set_credits_ledger(credits_ledger: Option<Principal>) -> Result<(), String> {
+    store::state::with_mut(|s| {
+        s.credits_ledger = credits_ledger;
+        Ok(())
+    })
+}
+
+#[ic_cdk::update]
+fn validate_admin_set_credits_ledger(credits_ledger: Option<Principal>) -> Result<String, String> {
+    pretty_format(&(credits_ledger,))
+}
+
+#[ic_cdk::update(guard = "is_controller")]
+fn admin_set_images_canister(images_canister: Option<Principal>) -> Result<(), String> {
+    store::state::with_mut(|s| {
+        s.images_canister = images_canister;
+        Ok(())
+    })
+}
+
+#[ic_cdk::update]
+fn validate_admin_set_images_canister(
+    images_canister: Option<Principal>,
+) -> Result<String, String> {
+    pretty_format(&(images_canister,))
+}
+
 #[ic_cdk::update(guard = "is_controller")]
 fn admin_set_payment_requirements_extra(payment_requirements_extra: String) -> Result<(), String> {
     let val: Map<String, Value> =
@@ -73,6 +245,48 @@ fn validate_admin_set_payment_requirements_extra(
     pretty_format(&(payment_requirements_extra,))
 }
 
+#[ic_cdk::update(guard = "is_controller")]
+fn admin_credit_x402_receipt(txid: String) -> Result<(), String> {
+    let now_ms = ic_cdk::api::time() / 1_000_000;
+    store::state::admin_credit_x402_receipt(&txid, now_ms)
+}
+
+#[ic_cdk::update]
+fn validate_admin_credit_x402_receipt(txid: String) -> Result<String, String> {
+    let receipt = store::state::get_x402_receipt(&txid)?;
+    if !matches!(receipt.outcome, types::X402ReceiptOutcome::Failed(_)) {
+        return Err(format!(
+            "only failed receipts can be credited, receipt is {:?}",
+            receipt.outcome
+        ));
+    }
+    pretty_format(&(receipt,))
+}
+
+#[ic_cdk::update(guard = "is_controller")]
+fn admin_refund_x402_receipt(txid: String, refund_tx: String) -> Result<(), String> {
+    let now_ms = ic_cdk::api::time() / 1_000_000;
+    store::state::admin_refund_x402_receipt(&txid, refund_tx, now_ms)
+}
+
+#[ic_cdk::update]
+fn validate_admin_refund_x402_receipt(txid: String, refund_tx: String) -> Result<String, String> {
+    if refund_tx.is_empty() {
+        return Err("missing refund transaction".to_string());
+    }
+    let receipt = store::state::get_x402_receipt(&txid)?;
+    if !matches!(
+        receipt.outcome,
+        types::X402ReceiptOutcome::Failed(_) | types::X402ReceiptOutcome::Credited
+    ) {
+        return Err(format!(
+            "only failed or credited receipts can be refunded, receipt is {:?}",
+            receipt.outcome
+        ));
+    }
+    pretty_format(&(receipt, refund_tx))
+}
+
 #[ic_cdk::update(guard = "is_controller")]
 fn admin_batch_register_tokens(tokens: Vec<types::TokenMetadata>) -> Result<Vec<u64>, String> {
     let now_ms = ic_cdk::api::time() / 1_000_000;
@@ -111,7 +325,7 @@ fn admin_update_token_verification_badge(
     store::state::admin_update_token_verification_badge(token_id, badge, now_ms)
 }
 
-fn is_controller() -> Result<(), String> {
+pub(crate) fn is_controller() -> Result<(), String> {
     let caller = ic_cdk::api::msg_caller();
     if ic_cdk::api::is_controller(&caller)
         || store::state::with(|s| s.governance_canister == Some(caller))
@@ -7,7 +7,7 @@ use serde::{Deserialize, Serialize};
 use serde_bytes::ByteBuf;
 use serde_json::json;
 
-use crate::store;
+use crate::{helper, store, types};
 
 #[derive(CandidType, Deserialize, Serialize, Clone, Default)]
 pub struct HttpResponse {
@@ -23,6 +23,7 @@ struct HttpError {
 }
 
 static JSON: &str = "application/json";
+static CSV: &str = "text/csv";
 static IC_CERTIFICATE_HEADER: &str = "ic-certificate";
 static IC_CERTIFICATE_EXPRESSION_HEADER: &str = "ic-certificateexpression";
 
@@ -69,9 +70,20 @@ async fn http_request(request: HttpRequest<'static>) -> HttpResponse {
         }
     };
 
+    let mut content_type = JSON;
     let rt = match (request.method().as_str(), req_path.as_str()) {
         ("HEAD", _) => Ok(Vec::new()),
         ("GET", "/") => get_info(),
+        ("GET", "/revenue.json") => get_revenue(request.url()).and_then(|rows| {
+            serde_json::to_vec(&rows).map_err(|err| HttpError {
+                status_code: 500,
+                message: format!("failed to serialize revenue, error: {err}"),
+            })
+        }),
+        ("GET", "/revenue.csv") => {
+            content_type = CSV;
+            get_revenue(request.url()).map(|rows| revenue_csv(&rows))
+        }
         ("GET", "/.well-known/ic-domains") => get_domains(),
         ("GET", "/uniswap/all.json") => get_tokens(),
         ("GET", path) if path.ends_with(".json") => get_token(path),
@@ -83,7 +95,7 @@ async fn http_request(request: HttpRequest<'static>) -> HttpResponse {
 
     match rt {
         Ok(body) => {
-            headers.push(("content-type".to_string(), JSON.to_string()));
+            headers.push(("content-type".to_string(), content_type.to_string()));
             headers.push(("content-length".to_string(), body.len().to_string()));
             HttpResponse {
                 status_code: 200,
@@ -149,3 +161,78 @@ fn get_token(path: &str) -> Result<Vec<u8>, HttpError> {
         message: format!("failed to serialize tokens, error: {err}"),
     })
 }
+
+// GET /revenue.json?start=2026-01-01&end=2026-01-31, the last 30 days by default
+fn get_revenue(url: &str) -> Result<Vec<types::RevenueEntry>, HttpError> {
+    let mut start = None;
+    let mut end = None;
+    if let Some((_, query)) = url.split_once('?') {
+        for pair in query.split('&') {
+            match pair.split_once('=') {
+                Some(("start", v)) => start = Some(v),
+                Some(("end", v)) => end = Some(v),
+                _ => {}
+            }
+        }
+    }
+    let now_ms = ic_cdk::api::time() / 1_000_000;
+    let (start, end) = helper::revenue_days(start, end, now_ms).map_err(|err| HttpError {
+        status_code: 400,
+        message: err,
+    })?;
+    Ok(store::state::list_revenue(start, end))
+}
+
+fn revenue_csv(rows: &[types::RevenueEntry]) -> Vec<u8> {
+    let mut csv = String::from(
+        "date,action,network,asset,pay_to,count,amount,refunded_count,refunded_amount\n",
+    );
+    for r in rows {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{}\n",
+            r.date,
+            csv_field(&r.action),
+            csv_field(&r.network),
+            csv_field(&r.asset),
+            csv_field(&r.pay_to),
+            r.count,
+            r.amount,
+            r.refunded_count,
+            r.refunded_amount
+        ));
+    }
+    csv.into_bytes()
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_revenue_csv() {
+        let rows = vec![types::RevenueEntry {
+            date: "2026-01-31".to_string(),
+            action: "register_token".to_string(),
+            network: "eip155:8453".to_string(),
+            asset: "0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913".to_string(),
+            pay_to: "a,\"b\"".to_string(),
+            count: 2,
+            amount: 2_000_000,
+            refunded_count: 1,
+            refunded_amount: 1_000_000,
+        }];
+        assert_eq!(
+            String::from_utf8(revenue_csv(&rows)).unwrap(),
+            "date,action,network,asset,pay_to,count,amount,refunded_count,refunded_amount\n\
+             2026-01-31,register_token,eip155:8453,0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913,\"a,\"\"b\"\"\",2,2000000,1,1000000\n"
+        );
+    }
+}
@@ -1,7 +1,11 @@
-use candid::{CandidType, IDLValue, Principal, pretty::candid::value::pp_value};
+use candid::{
+    CandidType, IDLValue, Principal, pretty::candid::value::pp_value, utils::ArgumentEncoder,
+};
+use chrono::{DateTime, NaiveDate, NaiveTime};
 use std::collections::BTreeSet;
 
 const ANONYMOUS: Principal = Principal::anonymous();
+const MAX_REVENUE_DAYS: u64 = 366;
 
 pub fn msg_caller() -> Result<Principal, String> {
     let caller = ic_cdk::api::msg_caller();
@@ -35,6 +39,47 @@ where
     format!("{:?}", err)
 }
 
+/// Formats days since epoch as an UTC date, e.g. "2026-01-31".
+pub fn format_day(day: u64) -> String {
+    DateTime::from_timestamp((day * 24 * 3600) as i64, 0)
+        .map(|dt| dt.date_naive().to_string())
+        .unwrap_or_default()
+}
+
+/// Parses an UTC date, e.g. "2026-01-31", to days since epoch.
+pub fn parse_day(date: &str) -> Result<u64, String> {
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|err| format!("invalid date {}, error: {}", date, err))?;
+    let days = date.and_time(NaiveTime::MIN).and_utc().timestamp() / (24 * 3600);
+    u64::try_from(days).map_err(|_| format!("invalid date {}", date))
+}
+
+/// The inclusive range of days of a revenue report, the last 30 days by default.
+pub fn revenue_days(
+    start: Option<&str>,
+    end: Option<&str>,
+    now_ms: u64,
+) -> Result<(u64, u64), String> {
+    let end = match end {
+        Some(end) => parse_day(end)?,
+        None => now_ms / (24 * 3600 * 1000),
+    };
+    let start = match start {
+        Some(start) => parse_day(start)?,
+        None => end.saturating_sub(29),
+    };
+    if start > end {
+        return Err("start date is after end date".to_string());
+    }
+    if end - start >= MAX_REVENUE_DAYS {
+        return Err(format!(
+            "date range exceeds the maximum of {} days",
+            MAX_REVENUE_DAYS
+        ));
+    }
+    Ok((start, end))
+}
+
 pub fn pretty_format<T>(data: &T) -> Result<String, String>
 where
     T: CandidType,
@@ -45,10 +90,25 @@ where
     Ok(format!("{}", doc.pretty(120)))
 }
 
-pub fn sha3_256(data: &[u8]) -> [u8; 32] {
-    use sha3::Digest;
-
-    let mut hasher = sha3::Sha3_256::new();
-    hasher.update(data);
-    hasher.finalize().into()
+pub async fn call<In, Out>(
+    id: Principal,
+    method: &str,
+    args: In,
+    cycles: u128,
+) -> Result<Out, String>
+where
+    In: ArgumentEncoder + Send,
+    Out: candid::CandidType + for<'a> candid::Deserialize<'a>,
+{
+    let res = ic_cdk::call::Call::bounded_wait(id, method)
+        .with_args(&args)
+        .with_cycles(cycles)
+        .await
+        .map_err(|err| format!("failed to call {} on {:?}, error: {:?}", method, &id, err))?;
+    res.candid().map_err(|err| {
+        format!(
+            "failed to decode response from {} on {:?}, error: {:?}",
+            method, &id, err
+        )
+    })
 }
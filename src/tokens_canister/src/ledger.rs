@@ -0,0 +1,33 @@
+use candid::{Nat, Principal};
+use icrc_ledger_types::{
+    icrc1::account::Account,
+    icrc2::transfer_from::{TransferFromArgs, TransferFromError},
+};
+
+use crate::helper::call;
+
+// return block index
+pub async fn transfer_from(
+    ledger: Principal,
+    from: Account,
+    to: Account,
+    amount: Nat,
+) -> Result<String, String> {
+    let res: Result<Nat, TransferFromError> = call(
+        ledger,
+        "icrc2_transfer_from",
+        (TransferFromArgs {
+            spender_subaccount: None,
+            from,
+            to,
+            amount,
+            fee: None,
+            memo: None,
+            created_at_time: None,
+        },),
+        0,
+    )
+    .await?;
+    let res = res.map_err(|err| format!("failed to transfer token from, error: {:?}", err))?;
+    Ok(res.0.to_string())
+}
@@ -3,6 +3,7 @@ mod api_admin;
 mod api_http;
 mod api_init;
 mod helper;
+mod ledger;
 mod store;
 mod types;
 mod x402;
@@ -17,9 +17,12 @@ use std::{
 };
 
 use crate::{
+    helper::format_day,
     types::{
-        Announcement, ChainLocation, LinkItem, StateInfo, TokenMetadata, TokenProfile, TokenStatus,
-        UniswapToken, VerificationBadge,
+        Announcement, ChainLocation, CreditLog, CreditLogKind, Credits, LinkItem, RevenueEntry,
+        StateInfo, TokenMetadata, TokenProfile, TokenStatus, UniswapToken, VerificationBadge,
+        X402Discount, X402DiscountChange, X402DiscountTarget, X402PriceChange, X402PriceOption,
+        X402Receipt, X402ReceiptOutcome,
     },
     x402::*,
 };
@@ -35,8 +38,57 @@ pub struct State {
     pub x402: X402State,
     pub x402_prices: BTreeMap<String, u64>,
     pub x402_pay_to: String,
+    // action -> accepted payments, the action's x402_prices in USDC on Solana otherwise
+    #[serde(default)]
+    pub x402_accepts: BTreeMap<String, Vec<X402PriceOption>>,
     pub total_incoming: u128,
     pub governance_canister: Option<Principal>,
+    // ICRC-2 ledger for credit top-ups, converted at its accepted top-up price on "icp:1"
+    #[serde(default)]
+    pub credits_ledger: Option<Principal>,
+    // sum of all users' credit balances
+    #[serde(default)]
+    pub total_credits: u128,
+    // price changes by effective_at, folded into x402_accepts once no issued payment needs
+    // the previous prices
+    #[serde(default)]
+    pub x402_price_schedule: Vec<X402PriceChange>,
+    #[serde(default)]
+    pub x402_discounts: Vec<X402Discount>,
+    // discount changes by effective_at, folded into x402_discounts like the price changes
+    #[serde(default)]
+    pub x402_discount_schedule: Vec<X402DiscountChange>,
+    // the images canister charges image storage through this canister
+    #[serde(default)]
+    pub images_canister: Option<Principal>,
+}
+
+impl State {
+    /// Applies the scheduled price and discount changes that took effect before any
+    /// unexpired payment.
+    pub fn promote_x402_prices(&mut self, now_ms: u64) {
+        while let Some(change) = self.x402_price_schedule.first()
+            && change.effective_at + TIME_EXPIRY_MS < now_ms
+        {
+            let change = self.x402_price_schedule.remove(0);
+            self.x402_accepts.insert(change.action, change.options);
+        }
+        while let Some(change) = self.x402_discount_schedule.first()
+            && change.effective_at + TIME_EXPIRY_MS < now_ms
+        {
+            let change = self.x402_discount_schedule.remove(0);
+            self.x402_discounts = change.discounts;
+        }
+    }
+
+    /// The discounts of payments issued at `timestamp`.
+    pub fn x402_discounts_at(&self, timestamp: u64) -> &[X402Discount] {
+        self.x402_discount_schedule
+            .iter()
+            .rev()
+            .find(|c| c.effective_at <= timestamp)
+            .map_or(&self.x402_discounts, |c| &c.discounts)
+    }
 }
 
 impl From<&State> for StateInfo {
@@ -46,29 +98,44 @@ impl From<&State> for StateInfo {
             x402_paying_public_keys: s.x402.paying_public_keys.clone(),
             x402_prices: s.x402_prices.clone(),
             x402_pay_to: s.x402_pay_to.clone(),
+            x402_accepts: s.x402_accepts.clone(),
             total_incoming: s.total_incoming,
             governance_canister: s.governance_canister,
+            credits_ledger: s.credits_ledger,
+            total_credits: s.total_credits,
+            x402_price_schedule: s.x402_price_schedule.clone(),
+            x402_discounts: s.x402_discounts.clone(),
+            x402_discount_schedule: s.x402_discount_schedule.clone(),
+            images_canister: s.images_canister,
         }
     }
 }
 
 impl State {
-    fn new() -> Self {
+    fn new(canister: Principal) -> Self {
         Self {
             tokens: BTreeMap::new(),
             location_index: BTreeMap::new(),
             inverted_index: BTreeMap::new(),
             next_id: 1000001,
             x402: X402State {
-                canister: ic_cdk::api::canister_self(),
+                canister,
                 user_nonce: HashMap::new(), // 付费 user 的 nonce 记录，数量有限
                 paying_public_keys: Vec::new(),
                 payment_requirements_extra: None,
+                replay_guard: ReplayGuard::default(),
             },
             x402_prices: BTreeMap::new(),
             x402_pay_to: String::new(),
+            x402_accepts: BTreeMap::new(),
             total_incoming: 0,
             governance_canister: None,
+            credits_ledger: None,
+            total_credits: 0,
+            x402_price_schedule: Vec::new(),
+            x402_discounts: Vec::new(),
+            x402_discount_schedule: Vec::new(),
+            images_canister: None,
         }
     }
 }
@@ -404,12 +471,181 @@ impl Storable for Txs {
     }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
+struct X402ReceiptState {
+    #[serde(rename = "u")]
+    pub user: Principal,
+    #[serde(rename = "n")]
+    pub nonce: String,
+    #[serde(rename = "t")]
+    pub settled_at: u64,
+    // fields below were added with the receipts, settlements before have defaults
+    #[serde(rename = "p", default)]
+    pub payer: Option<String>,
+    #[serde(rename = "a", default)]
+    pub action: String,
+    #[serde(rename = "un", default = "default_units")]
+    pub units: u64,
+    #[serde(rename = "w", default)]
+    pub network: String,
+    #[serde(rename = "as", default)]
+    pub asset: String,
+    #[serde(rename = "pt", default)]
+    pub pay_to: String,
+    #[serde(rename = "m", default)]
+    pub amount: u128,
+    #[serde(rename = "o", default)]
+    pub outcome: X402ReceiptOutcome,
+    #[serde(rename = "ua", default)]
+    pub updated_at: u64,
+}
+
+fn default_units() -> u64 {
+    1
+}
+
+impl X402ReceiptState {
+    fn to_receipt(&self, txid: String) -> X402Receipt {
+        X402Receipt {
+            txid,
+            user: self.user,
+            payer: self.payer.clone(),
+            action: self.action.clone(),
+            units: self.units,
+            network: self.network.clone(),
+            asset: self.asset.clone(),
+            pay_to: self.pay_to.clone(),
+            amount: self.amount,
+            settled_at: self.settled_at,
+            outcome: self.outcome.clone(),
+            updated_at: self.updated_at,
+        }
+    }
+}
+
+impl Storable for X402ReceiptState {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn into_bytes(self) -> Vec<u8> {
+        let mut buf = vec![];
+        into_writer(&self, &mut buf).expect("failed to encode X402ReceiptState data");
+        buf
+    }
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let mut buf = vec![];
+        into_writer(&self, &mut buf).expect("failed to encode X402ReceiptState data");
+        Cow::Owned(buf)
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        from_reader(&bytes[..]).expect("failed to decode X402ReceiptState data")
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct CreditsState {
+    #[serde(rename = "b")]
+    pub balance: u128,
+    // the latest MAX_CREDIT_HISTORY changes, receipts and the revenue ledger keep the rest
+    #[serde(rename = "h")]
+    pub history: Vec<CreditLog>,
+}
+
+impl Storable for CreditsState {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn into_bytes(self) -> Vec<u8> {
+        let mut buf = vec![];
+        into_writer(&self, &mut buf).expect("failed to encode CreditsState data");
+        buf
+    }
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let mut buf = vec![];
+        into_writer(&self, &mut buf).expect("failed to encode CreditsState data");
+        Cow::Owned(buf)
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        from_reader(&bytes[..]).expect("failed to decode CreditsState data")
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct RevenueState {
+    #[serde(rename = "a")]
+    pub action: String,
+    #[serde(rename = "w")]
+    pub network: String,
+    #[serde(rename = "as")]
+    pub asset: String,
+    #[serde(rename = "pt")]
+    pub pay_to: String,
+    #[serde(rename = "c")]
+    pub count: u64,
+    #[serde(rename = "m")]
+    pub amount: u128,
+    #[serde(rename = "rc")]
+    pub refunded_count: u64,
+    #[serde(rename = "rm")]
+    pub refunded_amount: u128,
+}
+
+impl Storable for RevenueState {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn into_bytes(self) -> Vec<u8> {
+        let mut buf = vec![];
+        into_writer(&self, &mut buf).expect("failed to encode RevenueState data");
+        buf
+    }
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let mut buf = vec![];
+        into_writer(&self, &mut buf).expect("failed to encode RevenueState data");
+        Cow::Owned(buf)
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        from_reader(&bytes[..]).expect("failed to decode RevenueState data")
+    }
+}
+
+/// A payment or refund to account in the revenue ledger.
+pub struct Revenue<'a> {
+    pub action: &'a str,
+    pub network: &'a str,
+    pub asset: &'a str,
+    pub pay_to: &'a str,
+    pub amount: u128,
+}
+
+impl Revenue<'_> {
+    // revenue key: zero padded UTC day, then the breakdown, so keys sort by day
+    fn key(&self, day: u64) -> String {
+        format!(
+            "{:06}/{}/{}/{}/{}",
+            day, self.action, self.network, self.asset, self.pay_to
+        )
+    }
+}
+
 const STATE_MEMORY_ID: MemoryId = MemoryId::new(0);
 const TOKENS_MEMORY_ID: MemoryId = MemoryId::new(1);
 const X402_TXS_MEMORY_ID: MemoryId = MemoryId::new(2);
+const X402_SETTLED_MEMORY_ID: MemoryId = MemoryId::new(3);
+const CREDITS_MEMORY_ID: MemoryId = MemoryId::new(4);
+const REVENUE_MEMORY_ID: MemoryId = MemoryId::new(5);
+const DAY_MS: u64 = 24 * 3600 * 1000;
+const MAX_CREDIT_HISTORY: usize = 100;
 
 thread_local! {
-    static STATE: RefCell<State> = RefCell::new(State::new());
+    #[cfg(not(test))]
+    static STATE: RefCell<State> = RefCell::new(State::new(ic_cdk::api::canister_self()));
+    // native tests can not call the system API
+    #[cfg(test)]
+    static STATE: RefCell<State> = RefCell::new(State::new(Principal::anonymous()));
     static HTTP_TREE: RefCell<HttpCertificationTree> = RefCell::new(HttpCertificationTree::default());
 
     static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
@@ -433,6 +669,26 @@ thread_local! {
             MEMORY_MANAGER.with_borrow(|m| m.get(X402_TXS_MEMORY_ID)),
         )
     );
+
+    // settled transaction id -> receipt, every settlement is accepted only once
+    static X402_SETTLED: RefCell<StableBTreeMap<String, X402ReceiptState, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with_borrow(|m| m.get(X402_SETTLED_MEMORY_ID)),
+        )
+    );
+
+    static CREDITS: RefCell<StableBTreeMap<Principal, CreditsState, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with_borrow(|m| m.get(CREDITS_MEMORY_ID)),
+        )
+    );
+
+    // day/action/network/asset/pay_to -> revenue
+    static REVENUE: RefCell<StableBTreeMap<String, RevenueState, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with_borrow(|m| m.get(REVENUE_MEMORY_ID)),
+        )
+    );
 }
 
 pub mod state {
@@ -471,6 +727,24 @@ pub mod state {
         Ok(keys)
     }
 
+    // Locations of a token must not be registered by another token
+    fn check_locations(s: &State, token: &TokenMetadata, id: Option<u64>) -> Result<(), String> {
+        for loc in &token.locations {
+            if let Some(_id) = s.location_index.get(&loc.to_ascii_lowercase())
+                && Some(*_id) != id
+            {
+                return Err(format!("token location {} already registered", loc));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the locations of a new token, or of token `id`, are not registered by
+    /// another token.
+    pub fn check_token_locations(token: &TokenMetadata, id: Option<u64>) -> Result<(), String> {
+        STATE.with_borrow(|s| check_locations(s, token, id))
+    }
+
     pub fn with<R>(f: impl FnOnce(&State) -> R) -> R {
         STATE.with_borrow(f)
     }
@@ -525,11 +799,7 @@ pub mod state {
         now_ms: u64,
     ) -> Result<u64, String> {
         STATE.with_borrow_mut(|s| {
-            for loc in &token.locations {
-                if s.location_index.contains_key(&loc.to_ascii_lowercase()) {
-                    return Err(format!("token location {} already registered", loc));
-                }
-            }
+            check_locations(s, &token, None)?;
 
             let id = s.next_id;
             s.next_id += 1;
@@ -600,13 +870,7 @@ pub mod state {
                 .get(&id)
                 .ok_or_else(|| format!("Token with id {} not found", id))?;
 
-            for loc in &token.locations {
-                if let Some(_id) = s.location_index.get(&loc.to_ascii_lowercase())
-                    && _id != &id
-                {
-                    return Err(format!("token location {} already registered", loc));
-                }
-            }
+            check_locations(s, &token, Some(id))?;
 
             let token_state = TokenMetadataState {
                 name: token.name,
@@ -703,6 +967,56 @@ pub mod state {
         })
     }
 
+    /// Checks a batch of metadata updates as a whole: the user controls every token, and
+    /// no location is registered by another token or claimed by two updates.
+    pub fn check_batch_update(
+        user: Principal,
+        updates: &[(u64, TokenMetadata)],
+    ) -> Result<(), String> {
+        let mut claimed: BTreeMap<String, u64> = BTreeMap::new();
+        for (id, token) in updates {
+            check_controller(*id, user)?;
+            check_token_locations(token, Some(*id))?;
+            for loc in &token.locations {
+                if let Some(other) = claimed.insert(loc.to_ascii_lowercase(), *id)
+                    && other != *id
+                {
+                    return Err(format!(
+                        "token location {} claimed by tokens {} and {}",
+                        loc, other, id
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies a batch of metadata updates, all of them or none.
+    pub fn batch_update_token_metadata(
+        user: Principal,
+        updates: Vec<(u64, TokenMetadata)>,
+        now_ms: u64,
+    ) -> Result<(), String> {
+        check_batch_update(user, &updates)?;
+        for (id, token) in updates {
+            update_token_metadata(id, user, token, now_ms)?;
+        }
+        Ok(())
+    }
+
+    pub fn check_controller(id: u64, user: Principal) -> Result<(), String> {
+        let governance_canister = STATE.with_borrow(|s| s.governance_canister);
+        TOKENS.with_borrow(|t| {
+            let profile = t
+                .get(&id)
+                .ok_or_else(|| format!("Token with id {} not found", id))?;
+            if !profile.controllers.contains(&user) && governance_canister != Some(user) {
+                return Err("user is not a controller of the token".to_string());
+            }
+            Ok(())
+        })
+    }
+
     pub fn update_token_controllers(
         id: u64,
         user: Principal,
@@ -758,6 +1072,26 @@ pub mod state {
         })
     }
 
+    /// Checks that the caller can set the announcement, before paying for it.
+    pub fn check_announcement(
+        id: u64,
+        caller: Principal,
+        input: &Announcement,
+    ) -> Result<(), String> {
+        TOKENS.with_borrow(|t| {
+            let token = t
+                .get(&id)
+                .ok_or_else(|| format!("Token with id {} not found", id))?;
+            if !token.controllers.contains(&caller) {
+                return Err("caller is not a controller of the token".to_string());
+            }
+            if input.id > 0 && token.announcements.last().map(|a| a.id) != Some(input.id) {
+                return Err("announcement id is invalid".to_string());
+            }
+            Ok(())
+        })
+    }
+
     pub fn set_announcement(
         id: u64,
         caller: Principal,
@@ -960,6 +1294,297 @@ pub mod state {
         });
     }
 
+    /// Records the receipt of a settled x402 payment, rejecting a transaction that was
+    /// already used.
+    pub fn record_x402_receipt(nonce: String, receipt: X402Receipt) -> Result<(), String> {
+        X402_SETTLED.with_borrow_mut(|m| {
+            check_settled_txid(&receipt.txid, |txid| m.contains_key(&txid.to_string()))?;
+            m.insert(
+                receipt.txid,
+                X402ReceiptState {
+                    user: receipt.user,
+                    nonce,
+                    settled_at: receipt.settled_at,
+                    payer: receipt.payer,
+                    action: receipt.action,
+                    units: receipt.units,
+                    network: receipt.network,
+                    asset: receipt.asset,
+                    pay_to: receipt.pay_to,
+                    amount: receipt.amount,
+                    outcome: receipt.outcome,
+                    updated_at: receipt.updated_at,
+                },
+            );
+            Ok(())
+        })
+    }
+
+    pub fn get_x402_receipt(txid: &str) -> Result<X402Receipt, String> {
+        X402_SETTLED.with_borrow(|m| {
+            m.get(&txid.to_string())
+                .map(|r| r.to_receipt(txid.to_string()))
+                .ok_or_else(|| format!("receipt of transaction {} not found", txid))
+        })
+    }
+
+    pub fn list_x402_receipts(user: Principal) -> Vec<X402Receipt> {
+        let txs = list_txs(user);
+        X402_SETTLED.with_borrow(|m| {
+            txs.into_iter()
+                .filter_map(|txid| m.get(&txid).map(|r| r.to_receipt(txid)))
+                .collect()
+        })
+    }
+
+    /// The first credited receipt of the user that paid for exactly `units` of the action.
+    pub fn find_x402_credit(user: Principal, action: &str, units: u64) -> Option<String> {
+        let txs = list_txs(user);
+        X402_SETTLED.with_borrow(|m| {
+            txs.into_iter().find(|txid| {
+                m.get(txid).is_some_and(|r| {
+                    r.outcome == X402ReceiptOutcome::Credited
+                        && r.action == action
+                        && r.units == units
+                })
+            })
+        })
+    }
+
+    pub fn set_x402_receipt_outcome(
+        txid: &str,
+        outcome: X402ReceiptOutcome,
+        now_ms: u64,
+    ) -> Result<(), String> {
+        update_x402_receipt(txid, |r| {
+            r.outcome = outcome;
+            r.updated_at = now_ms;
+            Ok(())
+        })
+    }
+
+    /// Credits the payment of a failed action, the user can retry the action once without
+    /// paying again.
+    pub fn admin_credit_x402_receipt(txid: &str, now_ms: u64) -> Result<(), String> {
+        update_x402_receipt(txid, |r| {
+            if !matches!(r.outcome, X402ReceiptOutcome::Failed(_)) {
+                return Err(format!(
+                    "only failed receipts can be credited, receipt is {:?}",
+                    r.outcome
+                ));
+            }
+            r.outcome = X402ReceiptOutcome::Credited;
+            r.updated_at = now_ms;
+            Ok(())
+        })
+    }
+
+    /// Records the refund of a failed or credited payment in `refund_tx`.
+    pub fn admin_refund_x402_receipt(
+        txid: &str,
+        refund_tx: String,
+        now_ms: u64,
+    ) -> Result<(), String> {
+        if refund_tx.is_empty() {
+            return Err("missing refund transaction".to_string());
+        }
+        update_x402_receipt(txid, |r| {
+            if !matches!(
+                r.outcome,
+                X402ReceiptOutcome::Failed(_) | X402ReceiptOutcome::Credited
+            ) {
+                return Err(format!(
+                    "only failed or credited receipts can be refunded, receipt is {:?}",
+                    r.outcome
+                ));
+            }
+            r.outcome = X402ReceiptOutcome::Refunded(refund_tx);
+            r.updated_at = now_ms;
+            record_refund(
+                &Revenue {
+                    action: &r.action,
+                    network: &r.network,
+                    asset: &r.asset,
+                    pay_to: &r.pay_to,
+                    amount: r.amount,
+                },
+                now_ms,
+            );
+            Ok(())
+        })
+    }
+
+    fn update_x402_receipt(
+        txid: &str,
+        f: impl FnOnce(&mut X402ReceiptState) -> Result<(), String>,
+    ) -> Result<(), String> {
+        X402_SETTLED.with_borrow_mut(|m| {
+            let txid = txid.to_string();
+            let mut receipt = m
+                .get(&txid)
+                .ok_or_else(|| format!("receipt of transaction {} not found", txid))?;
+            f(&mut receipt)?;
+            m.insert(txid, receipt);
+            Ok(())
+        })
+    }
+
+    /// The discount in percent on an action of the tokens, paid with a payment issued at
+    /// `timestamp`: the smallest of the tokens' best discounts, so a batch is discounted
+    /// only as much as all its tokens are.
+    pub fn x402_discount(token_ids: &[u64], timestamp: u64) -> u8 {
+        let discounts = STATE.with_borrow(|s| s.x402_discounts_at(timestamp).to_vec());
+        if discounts.is_empty() || token_ids.is_empty() {
+            return 0;
+        }
+        TOKENS.with_borrow(|t| {
+            token_ids
+                .iter()
+                .map(|id| {
+                    t.get(id)
+                        .and_then(|profile| {
+                            discounts
+                                .iter()
+                                .filter(|d| match &d.target {
+                                    X402DiscountTarget::Verified => {
+                                        profile.verification.is_verified
+                                    }
+                                    X402DiscountTarget::Tag(tag) => profile.tags.contains(tag),
+                                })
+                                .map(|d| d.percent)
+                                .max()
+                        })
+                        .unwrap_or(0)
+                })
+                .min()
+                .unwrap_or(0)
+        })
+    }
+
+    pub fn get_credits(user: Principal) -> Credits {
+        CREDITS.with_borrow(|c| {
+            let credits = c.get(&user).unwrap_or_default();
+            Credits {
+                balance: credits.balance,
+                history: credits.history,
+            }
+        })
+    }
+
+    pub fn credit_balance(user: Principal) -> u128 {
+        CREDITS.with_borrow(|c| c.get(&user).map(|v| v.balance).unwrap_or_default())
+    }
+
+    pub fn top_up_credits(
+        user: Principal,
+        kind: CreditLogKind,
+        amount: u128,
+        now_ms: u64,
+    ) -> Result<u128, String> {
+        if amount == 0 {
+            return Err("top-up amount must be greater than zero".to_string());
+        }
+        let balance = update_credits(user, kind, |b| b.checked_add(amount), amount, now_ms)?;
+        STATE.with_borrow_mut(|s| s.total_credits += amount);
+        Ok(balance)
+    }
+
+    pub fn debit_credits(
+        user: Principal,
+        action: &str,
+        units: u64,
+        amount: u128,
+        now_ms: u64,
+    ) -> Result<u128, String> {
+        let kind = CreditLogKind::Debit {
+            action: action.to_string(),
+            units,
+        };
+        let balance = update_credits(user, kind, |b| b.checked_sub(amount), amount, now_ms)
+            .map_err(|_| format!("insufficient credits, {} required", amount))?;
+        STATE.with_borrow_mut(|s| s.total_credits -= amount);
+        Ok(balance)
+    }
+
+    fn update_credits(
+        user: Principal,
+        kind: CreditLogKind,
+        f: impl FnOnce(u128) -> Option<u128>,
+        amount: u128,
+        now_ms: u64,
+    ) -> Result<u128, String> {
+        CREDITS.with_borrow_mut(|c| {
+            let mut credits = c.get(&user).unwrap_or_default();
+            credits.balance = f(credits.balance).ok_or("invalid credit balance")?;
+            credits.history.push(CreditLog {
+                kind,
+                amount,
+                balance: credits.balance,
+                created_at: now_ms,
+            });
+            if credits.history.len() > MAX_CREDIT_HISTORY {
+                let n = credits.history.len() - MAX_CREDIT_HISTORY;
+                credits.history.drain(..n);
+            }
+            let balance = credits.balance;
+            c.insert(user, credits);
+            Ok(balance)
+        })
+    }
+
+    pub fn record_revenue(revenue: &Revenue, now_ms: u64) {
+        update_revenue(revenue, now_ms, |r| {
+            r.count += 1;
+            r.amount += revenue.amount;
+        })
+    }
+
+    pub fn record_refund(revenue: &Revenue, now_ms: u64) {
+        update_revenue(revenue, now_ms, |r| {
+            r.refunded_count += 1;
+            r.refunded_amount += revenue.amount;
+        })
+    }
+
+    fn update_revenue(revenue: &Revenue, now_ms: u64, f: impl FnOnce(&mut RevenueState)) {
+        let key = revenue.key(now_ms / DAY_MS);
+        REVENUE.with_borrow_mut(|m| {
+            let mut r = m.get(&key).unwrap_or_else(|| RevenueState {
+                action: revenue.action.to_string(),
+                network: revenue.network.to_string(),
+                asset: revenue.asset.to_string(),
+                pay_to: revenue.pay_to.to_string(),
+                ..Default::default()
+            });
+            f(&mut r);
+            m.insert(key, r);
+        })
+    }
+
+    /// Revenue of the UTC days from `start_day` to `end_day` inclusive, in days since epoch.
+    pub fn list_revenue(start_day: u64, end_day: u64) -> Vec<RevenueEntry> {
+        let range = format!("{:06}", start_day)..format!("{:06}", end_day + 1);
+        REVENUE.with_borrow(|m| {
+            m.range(range)
+                .map(|entry| {
+                    let day = entry.key()[..6].parse::<u64>().unwrap_or_default();
+                    let r = entry.value();
+                    RevenueEntry {
+                        date: format_day(day),
+                        action: r.action,
+                        network: r.network,
+                        asset: r.asset,
+                        pay_to: r.pay_to,
+                        count: r.count,
+                        amount: r.amount,
+                        refunded_count: r.refunded_count,
+                        refunded_amount: r.refunded_amount,
+                    }
+                })
+                .collect()
+        })
+    }
+
     pub fn list_txs(user: Principal) -> Vec<String> {
         TXS.with_borrow(|t| t.get(&user).map(|txs| txs.txs.clone()).unwrap_or_default())
     }
@@ -1,6 +1,7 @@
 use candid::{CandidType, Principal};
 use ic_auth_types::ByteArrayB64;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use std::collections::BTreeMap;
 
 #[derive(CandidType, Serialize, Deserialize)]
@@ -9,8 +10,139 @@ pub struct StateInfo {
     pub x402_paying_public_keys: Vec<ByteArrayB64<32>>,
     pub x402_prices: BTreeMap<String, u64>,
     pub x402_pay_to: String,
+    pub x402_accepts: BTreeMap<String, Vec<X402PriceOption>>,
     pub total_incoming: u128,
     pub governance_canister: Option<Principal>,
+    pub credits_ledger: Option<Principal>,
+    pub total_credits: u128,
+    pub x402_price_schedule: Vec<X402PriceChange>,
+    pub x402_discounts: Vec<X402Discount>,
+    pub x402_discount_schedule: Vec<X402DiscountChange>,
+    pub images_canister: Option<Principal>,
+}
+
+/// An accepted way to pay for an action, e.g. USDC on Base or ckUSDC on ICP.
+#[derive(CandidType, Clone, Debug, Serialize, Deserialize)]
+pub struct X402PriceOption {
+    /// CAIP-2 network identifier, e.g. "eip155:8453"
+    pub network: String,
+    /// Token address on the network
+    pub asset: String,
+    /// Price in atomic token units
+    pub amount: u64,
+    /// Recipient address on the network
+    pub pay_to: String,
+    /// Scheme-specific extra information in JSON, e.g. the EIP-712 domain of the asset.
+    /// Falls back to the canister's payment requirements extra.
+    pub extra: Option<String>,
+}
+
+impl X402PriceOption {
+    pub fn validate(&self) -> Result<(), String> {
+        x402::parse_network(&self.network)?;
+        if self.asset.trim().is_empty() || self.asset.trim().len() != self.asset.len() {
+            return Err("Invalid asset".to_string());
+        }
+        if self.pay_to.trim().is_empty() || self.pay_to.trim().len() != self.pay_to.len() {
+            return Err("Invalid pay_to address".to_string());
+        }
+        if self.amount == 0 {
+            return Err("Amount must be greater than zero".to_string());
+        }
+        self.extra()?;
+        Ok(())
+    }
+
+    pub fn extra(&self) -> Result<Option<Map<String, Value>>, String> {
+        self.extra
+            .as_ref()
+            .map(|extra| {
+                serde_json::from_str(extra).map_err(|err| format!("Invalid extra: {:?}", err))
+            })
+            .transpose()
+    }
+}
+
+/// A scheduled change of the accepted payments of an action. Payments issued from
+/// `effective_at` (Unix timestamp in milliseconds) are priced by the change.
+#[derive(CandidType, Clone, Debug, Serialize, Deserialize)]
+pub struct X402PriceChange {
+    pub action: String,
+    pub effective_at: u64,
+    pub options: Vec<X402PriceOption>,
+}
+
+impl X402PriceChange {
+    pub fn validate(&self, now_ms: u64) -> Result<(), String> {
+        if self.action.is_empty() {
+            return Err("action cannot be empty".to_string());
+        }
+        if self.effective_at <= now_ms {
+            return Err("effective_at must be in the future".to_string());
+        }
+        if self.options.is_empty() {
+            return Err("options cannot be empty".to_string());
+        }
+        for option in &self.options {
+            option.validate()?;
+        }
+        Ok(())
+    }
+}
+
+/// A discount in percent on the actions of matching tokens.
+#[derive(CandidType, Clone, Debug, Serialize, Deserialize)]
+pub struct X402Discount {
+    pub target: X402DiscountTarget,
+    pub percent: u8,
+}
+
+#[derive(CandidType, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum X402DiscountTarget {
+    /// Verified tokens.
+    Verified,
+    /// Tokens with the tag.
+    Tag(String),
+}
+
+impl X402Discount {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.percent == 0 || self.percent > 100 {
+            return Err("discount percent must be between 1 and 100".to_string());
+        }
+        if let X402DiscountTarget::Tag(tag) = &self.target
+            && tag.is_empty()
+        {
+            return Err("discount tag cannot be empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A scheduled change of the discounts. Payments issued from `effective_at` (Unix
+/// timestamp in milliseconds) are discounted by the change.
+#[derive(CandidType, Clone, Debug, Serialize, Deserialize)]
+pub struct X402DiscountChange {
+    pub effective_at: u64,
+    pub discounts: Vec<X402Discount>,
+}
+
+impl X402DiscountChange {
+    pub fn validate(&self, now_ms: u64) -> Result<(), String> {
+        if self.effective_at <= now_ms {
+            return Err("effective_at must be in the future".to_string());
+        }
+        for (i, discount) in self.discounts.iter().enumerate() {
+            discount.validate()?;
+            if self.discounts[..i]
+                .iter()
+                .any(|d| d.target == discount.target)
+            {
+                return Err(format!("duplicate discount target {:?}", discount.target));
+            }
+        }
+        Ok(())
+    }
 }
 
 /// Token Metadata following CAIP-390 standard:
@@ -304,3 +436,75 @@ impl Announcement {
         Ok(())
     }
 }
+
+/// Receipt of an x402 payment, keyed by the settled transaction.
+#[derive(CandidType, Clone, Debug, Deserialize, Serialize)]
+pub struct X402Receipt {
+    pub txid: String,
+    pub user: Principal,
+    pub payer: Option<String>, // payer address on the network
+    pub action: String,
+    pub units: u64, // units paid for a metered action, 1 otherwise
+    pub network: String,
+    pub asset: String,
+    pub pay_to: String,
+    pub amount: u128, // settled amount in atomic token units
+    pub settled_at: u64,
+    pub outcome: X402ReceiptOutcome,
+    pub updated_at: u64,
+}
+
+/// What came of the action an x402 payment was made for.
+#[derive(CandidType, Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub enum X402ReceiptOutcome {
+    /// The action succeeded, directly or on a retry with the credited payment.
+    #[default]
+    Succeeded,
+    /// The action failed after the payment was settled.
+    Failed(String),
+    /// The failed action can be retried once without paying again.
+    Credited,
+    /// The payment was refunded in the given transaction.
+    Refunded(String),
+}
+
+/// Prepaid credits of a user, in atomic units of the x402 prices.
+#[derive(CandidType, Clone, Debug, Deserialize, Serialize)]
+pub struct Credits {
+    /// Balance in atomic units of USDC on Solana
+    pub balance: u128,
+    /// The latest 100 changes of the balance
+    pub history: Vec<CreditLog>,
+}
+
+#[derive(CandidType, Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct CreditLog {
+    pub kind: CreditLogKind,
+    pub amount: u128,
+    pub balance: u128, // balance after the change
+    pub created_at: u64,
+}
+
+#[derive(CandidType, Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum CreditLogKind {
+    /// Topped up with an x402 payment in the given transaction.
+    X402TopUp(String),
+    /// Topped up with an ICRC-2 transfer in the given block index.
+    Icrc2TopUp(String),
+    /// Debited for units of an action.
+    Debit { action: String, units: u64 },
+}
+
+/// Revenue of a day, by action and by where it was paid.
+#[derive(CandidType, Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct RevenueEntry {
+    pub date: String, // UTC day, e.g. "2026-01-31"
+    pub action: String,
+    pub network: String,
+    pub asset: String,
+    pub pay_to: String,
+    pub count: u64,
+    pub amount: u128,
+    pub refunded_count: u64,
+    pub refunded_amount: u128,
+}
@@ -0,0 +1,780 @@
+//! # x402
+//!
+//! Payment requirements and validation of signed facilitator results for x402-gated
+//! canister endpoints.
+//!
+//! A canister issues a [`X402PaymentOutput`] with a nonce bound to the caller, the
+//! requirements and the issuing time. The client pays through a facilitator, which signs
+//! the [`PaymentVerifyResult`] or [`PaymentSettleResult`] with one of the canister's
+//! paying public keys. The canister then checks the signature, the nonce and the result
+//! with [`PaymentVerifier`], and consumes the nonce with [`ReplayGuard`]. The nonces
+//! expire with the results, so settled transactions are checked with
+//! [`check_settled_txid`] against the canister's own records.
+
+use candid::Principal;
+use ic_auth_types::{ByteArrayB64, ByteBufB64, deterministic_cbor_into_vec};
+use ic_ed25519::PublicKey;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{collections::BTreeMap, str::FromStr};
+
+mod types;
+
+pub use types::*;
+
+/// Signed facilitator results are accepted for 10 minutes after the payment was issued.
+pub const TIME_EXPIRY_MS: u64 = 600 * 1000;
+/// Time for the client to complete a payment.
+pub const MAX_TIMEOUT_SECONDS: u64 = 120;
+
+impl PaymentRequirements {
+    /// Requirements of the "exact" scheme: pay exactly `amount` of `asset` to `pay_to`.
+    pub fn exact(
+        network: String,
+        asset: String,
+        amount: u128,
+        pay_to: String,
+        extra: Option<Map<String, Value>>,
+    ) -> Self {
+        Self {
+            scheme: "exact".to_string(),
+            network,
+            amount: amount.to_string(),
+            asset,
+            pay_to,
+            max_timeout_seconds: MAX_TIMEOUT_SECONDS,
+            extra,
+        }
+    }
+
+    /// Requirements of the "upto" scheme: pay at most `max_amount` of `asset` to `pay_to`,
+    /// the settled amount depends on the usage.
+    pub fn upto(
+        network: String,
+        asset: String,
+        max_amount: u128,
+        pay_to: String,
+        extra: Option<Map<String, Value>>,
+    ) -> Self {
+        Self {
+            scheme: "upto".to_string(),
+            ..Self::exact(network, asset, max_amount, pay_to, extra)
+        }
+    }
+
+    /// Returns the required amount.
+    pub fn amount(&self) -> Result<u128, String> {
+        self.amount
+            .parse()
+            .map_err(|_| "invalid amount in payment requirements".to_string())
+    }
+
+    /// Checks the requirements a result was issued for against the endpoint's own.
+    pub fn check(&self, asset: &str, amount: u128, pay_to: &str) -> Result<(), String> {
+        if self.asset != asset || self.amount != amount.to_string() || self.pay_to != pay_to {
+            return Err("payment requirements mismatch".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl PaymentSettleResult {
+    /// Returns the amount the payer was charged.
+    pub fn settled_amount(&self) -> Result<u128, String> {
+        let amount = self.payment_requirements.amount()?;
+        let settled = match self.settle_response.amount.as_deref() {
+            Some(settled) => settled
+                .parse::<u128>()
+                .map_err(|_| "invalid amount in settlement response".to_string())?,
+            None => amount,
+        };
+        match self.payment_requirements.scheme.as_str() {
+            "exact" if settled == amount => Ok(amount),
+            "exact" => Err("settled amount differs from the exact amount".to_string()),
+            "upto" if self.settle_response.amount.is_none() => {
+                Err("missing amount in settlement response".to_string())
+            }
+            "upto" if settled == 0 => Err("settled amount must be greater than zero".to_string()),
+            "upto" if settled > amount => {
+                Err("settled amount exceeds the maximum amount".to_string())
+            }
+            "upto" => Ok(settled),
+            scheme => Err(format!("unsupported payment scheme: {}", scheme)),
+        }
+    }
+}
+
+impl PayingResultInput {
+    /// Reads the payment requirements of the result before it is verified, e.g. to rebuild
+    /// the requirements it was issued with.
+    pub fn payment_requirements(&self) -> Result<PaymentRequirements, String> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Issued {
+            payment_requirements: PaymentRequirements,
+        }
+
+        let result: Issued = ciborium::from_reader(&self.result[..]).map_err(format_error)?;
+        Ok(result.payment_requirements)
+    }
+}
+
+/// Derives the nonce binding a payment to the issuer's `secret`, the paying `user`, the
+/// accepted requirements and the issuing `timestamp`. The secret also separates payments
+/// of the same user for different purposes.
+pub fn derive_nonce(
+    secret: &[u8],
+    user: &Principal,
+    accepts: &[PaymentRequirements],
+    timestamp: u64,
+) -> Result<String, String> {
+    let seed = deterministic_cbor_into_vec(&(secret, user.as_slice(), accepts, timestamp))?;
+    Ok(ByteBufB64::from(sha3_256(&seed)).to_string())
+}
+
+/// Builds the payment required response for `user`, with its nonce. The payer may pay
+/// with any of the `accepts` requirements.
+pub fn payment_output(
+    secret: &[u8],
+    user: &Principal,
+    accepts: Vec<PaymentRequirements>,
+    resource: ResourceInfo,
+    error: Option<String>,
+    timestamp: u64,
+) -> Result<X402PaymentOutput, String> {
+    if accepts.is_empty() {
+        return Err("no accepted payment requirements".to_string());
+    }
+    let nonce = derive_nonce(secret, user, &accepts, timestamp)?;
+    let x402 = PaymentRequired {
+        x402_version: 2,
+        error,
+        resource,
+        accepts,
+        extensions: None,
+    };
+
+    Ok(X402PaymentOutput {
+        x402: ByteBufB64::from(deterministic_cbor_into_vec(&x402)?),
+        nonce,
+        timestamp,
+    })
+}
+
+/// Splits a CAIP-2 network identifier, e.g. "eip155:8453", into its namespace and
+/// reference.
+pub fn parse_network(network: &str) -> Result<(&str, &str), String> {
+    let (namespace, reference) = network
+        .split_once(':')
+        .ok_or_else(|| format!("invalid CAIP-2 network: {}", network))?;
+    if !(3..=8).contains(&namespace.len())
+        || !namespace
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        || !(1..=32).contains(&reference.len())
+        || !reference
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!("invalid CAIP-2 network: {}", network));
+    }
+    Ok((namespace, reference))
+}
+
+/// Parses the paying public keys of facilitators, given as base64 raw Ed25519 keys.
+pub fn parse_public_keys(keys: Vec<String>) -> Result<Vec<ByteArrayB64<32>>, String> {
+    keys.into_iter()
+        .map(|key| {
+            let key = ByteArrayB64::<32>::from_str(&key).map_err(format_error)?;
+            PublicKey::deserialize_raw(&key.0).map_err(format_error)?;
+            Ok(key)
+        })
+        .collect()
+}
+
+/// Validates signed facilitator results of payments issued with `secret`.
+pub struct PaymentVerifier<'a> {
+    pub public_keys: &'a [ByteArrayB64<32>],
+    pub secret: &'a [u8],
+    /// The requirements the payment was issued with, the result must be paid with one of
+    /// them. When empty, the result's own requirements are taken as the only accepted
+    /// ones, for payments where the payer chooses the amount.
+    pub accepts: &'a [PaymentRequirements],
+}
+
+impl PaymentVerifier<'_> {
+    /// Returns the verification result of a valid payment by `user`.
+    pub fn verify(
+        &self,
+        input: &PayingResultInput,
+        user: &Principal,
+        now_ms: u64,
+    ) -> Result<PaymentVerifyResult, String> {
+        let pv: PaymentVerifyResult = self.decode(input)?;
+        if input.timestamp + TIME_EXPIRY_MS < now_ms {
+            return Err("payment verification result expired".to_string());
+        }
+        if !pv.verify_response.is_valid {
+            return Err(format!(
+                "payment verification failed: {}",
+                pv.verify_response
+                    .invalid_reason
+                    .as_deref()
+                    .unwrap_or("unknown reason")
+            ));
+        }
+        self.check_nonce(&pv.nonce, user, &pv.payment_requirements, input.timestamp)?;
+        Ok(pv)
+    }
+
+    /// Returns the settlement result of a successful payment by `user`.
+    pub fn settle(
+        &self,
+        input: &PayingResultInput,
+        user: &Principal,
+        now_ms: u64,
+    ) -> Result<PaymentSettleResult, String> {
+        let ps: PaymentSettleResult = self.decode(input)?;
+        if input.timestamp + TIME_EXPIRY_MS < now_ms {
+            return Err("payment settlement result expired".to_string());
+        }
+        if !ps.settle_response.success {
+            return Err(format!(
+                "payment settlement failed: {}",
+                ps.settle_response
+                    .error_reason
+                    .as_deref()
+                    .unwrap_or("unknown reason")
+            ));
+        }
+        self.check_nonce(&ps.nonce, user, &ps.payment_requirements, input.timestamp)?;
+        Ok(ps)
+    }
+
+    fn decode<T: for<'de> Deserialize<'de>>(&self, input: &PayingResultInput) -> Result<T, String> {
+        verify_signature(self.public_keys, &input.result, &input.signature)?;
+        ciborium::from_reader(&input.result[..]).map_err(format_error)
+    }
+
+    fn check_nonce(
+        &self,
+        nonce: &str,
+        user: &Principal,
+        requirements: &PaymentRequirements,
+        timestamp: u64,
+    ) -> Result<(), String> {
+        let accepts = if self.accepts.is_empty() {
+            std::slice::from_ref(requirements)
+        } else if self.accepts.contains(requirements) {
+            self.accepts
+        } else {
+            return Err("payment requirements mismatch".to_string());
+        };
+        if derive_nonce(self.secret, user, accepts, timestamp)? != nonce {
+            return Err("nonce mismatch".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Checks that one of the paying public keys signed `message`.
+pub fn verify_signature(
+    public_keys: &[ByteArrayB64<32>],
+    message: &[u8],
+    signature: &[u8],
+) -> Result<(), String> {
+    for key in public_keys {
+        let pk = PublicKey::deserialize_raw(&key.0).map_err(format_error)?;
+        if pk.verify_signature(message, signature).is_ok() {
+            return Ok(());
+        }
+    }
+    Err("signature verification failed".to_string())
+}
+
+/// Remembers the nonces of accepted results until they expire, so that a signed result
+/// is accepted only once.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ReplayGuard {
+    // nonce -> issuing timestamp
+    #[serde(rename = "n")]
+    nonces: BTreeMap<String, u64>,
+}
+
+impl ReplayGuard {
+    /// Consumes the nonce of a result issued at `timestamp`, failing if it was consumed.
+    pub fn consume(&mut self, nonce: &str, timestamp: u64, now_ms: u64) -> Result<(), String> {
+        self.nonces.retain(|_, ts| *ts + TIME_EXPIRY_MS >= now_ms);
+        if self.nonces.contains_key(nonce) {
+            return Err("payment result has already been used".to_string());
+        }
+        self.nonces.insert(nonce.to_string(), timestamp);
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.nonces.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nonces.is_empty()
+    }
+}
+
+/// Checks the transaction of a settlement against the ones already settled, so that a
+/// transaction pays for one action only, also after the guard's nonces expired.
+pub fn check_settled_txid(txid: &str, is_settled: impl FnOnce(&str) -> bool) -> Result<(), String> {
+    if txid.is_empty() {
+        return Err("missing transaction in settlement response".to_string());
+    }
+    if is_settled(txid) {
+        return Err(format!("transaction {} has already been used", txid));
+    }
+    Ok(())
+}
+
+pub fn sha3_256(data: &[u8]) -> [u8; 32] {
+    use sha3::Digest;
+
+    let mut hasher = sha3::Sha3_256::new();
+    hasher.update(data);
+    hasher.finalize().into()
+}
+
+fn format_error<T>(err: T) -> String
+where
+    T: std::fmt::Debug,
+{
+    format!("{:?}", err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_auth_types::cbor_into_vec;
+    use ic_ed25519::PrivateKey;
+
+    const SECRET: &[u8] = b"secret";
+
+    fn requirements() -> PaymentRequirements {
+        PaymentRequirements::exact(
+            "icp:1".to_string(),
+            "ryjl3-tyaaa-aaaaa-aaaba-cai".to_string(),
+            1_000,
+            "pay-to".to_string(),
+            None,
+        )
+    }
+
+    fn signed<T: Serialize>(sk: &PrivateKey, result: &T, timestamp: u64) -> PayingResultInput {
+        let result = cbor_into_vec(result).unwrap();
+        let signature = sk.sign_message(&result);
+        PayingResultInput {
+            result: result.into(),
+            signature: signature.to_vec().into(),
+            timestamp,
+        }
+    }
+
+    fn settle_result(user: &Principal, timestamp: u64, success: bool) -> PaymentSettleResult {
+        let pr = requirements();
+        PaymentSettleResult {
+            nonce: derive_nonce(SECRET, user, std::slice::from_ref(&pr), timestamp).unwrap(),
+            payment_requirements: pr,
+            settle_response: SettleResponse {
+                success,
+                error_reason: (!success).then(|| "insufficient funds".to_string()),
+                transaction: "tx1".to_string(),
+                network: "icp:1".to_string(),
+                payer: Some("payer".to_string()),
+                amount: None,
+            },
+        }
+    }
+
+    #[test]
+    fn test_payment_output() {
+        let user = Principal::anonymous();
+        let output = payment_output(
+            SECRET,
+            &user,
+            vec![requirements()],
+            ResourceInfo {
+                url: "https://tokenlist.ing".to_string(),
+                description: None,
+                mime_type: None,
+            },
+            Some("Payment required".to_string()),
+            1_000,
+        )
+        .unwrap();
+        assert_eq!(
+            output.nonce,
+            derive_nonce(SECRET, &user, &[requirements()], 1_000).unwrap()
+        );
+        assert_ne!(
+            output.nonce,
+            derive_nonce(b"other", &user, &[requirements()], 1_000).unwrap()
+        );
+
+        let pr: PaymentRequired = ciborium::from_reader(&output.x402[..]).unwrap();
+        assert_eq!(pr.x402_version, 2);
+        assert_eq!(pr.accepts, vec![requirements()]);
+        assert_eq!(pr.accepts[0].amount().unwrap(), 1_000);
+        let mut bad = requirements();
+        bad.amount = "1e3".to_string();
+        assert_eq!(
+            bad.amount().unwrap_err(),
+            "invalid amount in payment requirements"
+        );
+        assert!(
+            pr.accepts[0]
+                .check("ryjl3-tyaaa-aaaaa-aaaba-cai", 1_000, "pay-to")
+                .is_ok()
+        );
+        assert_eq!(
+            pr.accepts[0]
+                .check("ryjl3-tyaaa-aaaaa-aaaba-cai", 999, "pay-to")
+                .unwrap_err(),
+            "payment requirements mismatch"
+        );
+        assert!(pr.accepts[0].check("other", 1_000, "pay-to").is_err());
+        assert!(
+            pr.accepts[0]
+                .check("ryjl3-tyaaa-aaaaa-aaaba-cai", 1_000, "other")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_network() {
+        assert_eq!(parse_network("eip155:8453").unwrap(), ("eip155", "8453"));
+        assert_eq!(parse_network("icp:1").unwrap(), ("icp", "1"));
+        assert_eq!(
+            parse_network("solana:5eykt4UsFv8P8NJdTREpY1vzqKqZKvdp").unwrap(),
+            ("solana", "5eykt4UsFv8P8NJdTREpY1vzqKqZKvdp")
+        );
+        assert!(parse_network("eip155").is_err());
+        assert!(parse_network("eip155:").is_err());
+        assert!(parse_network("ab:1").is_err());
+        assert!(parse_network("EIP155:1").is_err());
+        assert!(parse_network("eip155:1:2").is_err());
+        assert!(parse_network("solana:5eykt4UsFv8P8NJdTREpY1vzqKqZKvdp0").is_err());
+    }
+
+    #[test]
+    fn test_parse_public_keys() {
+        let sk = PrivateKey::generate_from_seed(b"facilitator");
+        let pk = ByteArrayB64::from(sk.public_key().serialize_raw());
+        assert_eq!(parse_public_keys(vec![pk.to_string()]).unwrap(), vec![pk]);
+        assert!(parse_public_keys(vec!["invalid".to_string()]).is_err());
+        assert!(parse_public_keys(vec![]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_settle() {
+        let sk = PrivateKey::generate_from_seed(b"facilitator");
+        let keys = vec![ByteArrayB64::from(sk.public_key().serialize_raw())];
+        let verifier = PaymentVerifier {
+            public_keys: &keys,
+            secret: SECRET,
+            accepts: &[],
+        };
+        let user = Principal::from_slice(&[1]);
+
+        let input = signed(&sk, &settle_result(&user, 1_000, true), 1_000);
+        let ps = verifier.settle(&input, &user, 2_000).unwrap();
+        assert_eq!(ps.settle_response.transaction, "tx1");
+
+        // signed by an unknown key
+        let other = PrivateKey::generate_from_seed(b"other");
+        let bad = signed(&other, &settle_result(&user, 1_000, true), 1_000);
+        assert_eq!(
+            verifier.settle(&bad, &user, 2_000).unwrap_err(),
+            "signature verification failed"
+        );
+
+        // tampered result
+        let mut bad = input.clone();
+        bad.signature = sk.sign_message(b"other").to_vec().into();
+        assert!(verifier.settle(&bad, &user, 2_000).is_err());
+
+        // not a settlement result
+        let bad = signed(&sk, &"not a result", 1_000);
+        assert!(verifier.settle(&bad, &user, 2_000).is_err());
+
+        // expired
+        assert_eq!(
+            verifier
+                .settle(&input, &user, 1_001 + TIME_EXPIRY_MS)
+                .unwrap_err(),
+            "payment settlement result expired"
+        );
+
+        // failed settlement
+        let bad = signed(&sk, &settle_result(&user, 1_000, false), 1_000);
+        assert_eq!(
+            verifier.settle(&bad, &user, 2_000).unwrap_err(),
+            "payment settlement failed: insufficient funds"
+        );
+
+        // issued for another user, at another time or with another secret
+        let other_user = Principal::from_slice(&[2]);
+        assert_eq!(
+            verifier.settle(&input, &other_user, 2_000).unwrap_err(),
+            "nonce mismatch"
+        );
+        let mut bad = input.clone();
+        bad.timestamp = 1_001;
+        assert_eq!(
+            verifier.settle(&bad, &user, 2_000).unwrap_err(),
+            "nonce mismatch"
+        );
+        let other_verifier = PaymentVerifier {
+            public_keys: &keys,
+            secret: b"other",
+            accepts: &[],
+        };
+        assert!(other_verifier.settle(&input, &user, 2_000).is_err());
+
+        // no keys
+        let no_keys = PaymentVerifier {
+            public_keys: &[],
+            secret: SECRET,
+            accepts: &[],
+        };
+        assert!(no_keys.settle(&input, &user, 2_000).is_err());
+    }
+
+    #[test]
+    fn test_settle_accepts() {
+        let sk = PrivateKey::generate_from_seed(b"facilitator");
+        let keys = vec![ByteArrayB64::from(sk.public_key().serialize_raw())];
+        let user = Principal::from_slice(&[1]);
+        let usdc_base = PaymentRequirements::exact(
+            "eip155:8453".to_string(),
+            "0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913".to_string(),
+            1_000,
+            "0x0000000000000000000000000000000000000001".to_string(),
+            None,
+        );
+        let accepts = vec![requirements(), usdc_base.clone()];
+        let verifier = PaymentVerifier {
+            public_keys: &keys,
+            secret: SECRET,
+            accepts: &accepts,
+        };
+        let result = |pr: PaymentRequirements| PaymentSettleResult {
+            nonce: derive_nonce(SECRET, &user, &accepts, 1_000).unwrap(),
+            payment_requirements: pr,
+            settle_response: SettleResponse {
+                success: true,
+                error_reason: None,
+                transaction: "0x01".to_string(),
+                network: "eip155:8453".to_string(),
+                payer: Some("payer".to_string()),
+                amount: None,
+            },
+        };
+
+        // paid with any of the accepted requirements
+        let output = payment_output(
+            SECRET,
+            &user,
+            accepts.clone(),
+            ResourceInfo {
+                url: "https://tokenlist.ing".to_string(),
+                description: None,
+                mime_type: None,
+            },
+            None,
+            1_000,
+        )
+        .unwrap();
+        assert_eq!(output.nonce, result(usdc_base.clone()).nonce);
+        let input = signed(&sk, &result(usdc_base.clone()), 1_000);
+        let ps = verifier.settle(&input, &user, 2_000).unwrap();
+        assert_eq!(ps.payment_requirements, usdc_base);
+        let input = signed(&sk, &result(requirements()), 1_000);
+        assert!(verifier.settle(&input, &user, 2_000).is_ok());
+
+        // paid with requirements that were not offered
+        let mut cheaper = usdc_base.clone();
+        cheaper.amount = "1".to_string();
+        let input = signed(&sk, &result(cheaper), 1_000);
+        assert_eq!(
+            verifier.settle(&input, &user, 2_000).unwrap_err(),
+            "payment requirements mismatch"
+        );
+
+        // issued with other accepted requirements
+        let input = signed(&sk, &result(usdc_base.clone()), 1_000);
+        let verifier = PaymentVerifier {
+            public_keys: &keys,
+            secret: SECRET,
+            accepts: std::slice::from_ref(&usdc_base),
+        };
+        assert_eq!(
+            verifier.settle(&input, &user, 2_000).unwrap_err(),
+            "nonce mismatch"
+        );
+
+        assert!(
+            payment_output(
+                SECRET,
+                &user,
+                vec![],
+                ResourceInfo {
+                    url: "https://tokenlist.ing".to_string(),
+                    description: None,
+                    mime_type: None,
+                },
+                None,
+                1_000,
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_settled_amount() {
+        let user = Principal::from_slice(&[1]);
+        let mut ps = settle_result(&user, 1_000, true);
+        assert_eq!(ps.settled_amount().unwrap(), 1_000);
+        ps.settle_response.amount = Some("1000".to_string());
+        assert_eq!(ps.settled_amount().unwrap(), 1_000);
+        ps.settle_response.amount = Some("999".to_string());
+        assert_eq!(
+            ps.settled_amount().unwrap_err(),
+            "settled amount differs from the exact amount"
+        );
+
+        ps.payment_requirements = PaymentRequirements::upto(
+            "icp:1".to_string(),
+            "ryjl3-tyaaa-aaaaa-aaaba-cai".to_string(),
+            1_000,
+            "pay-to".to_string(),
+            None,
+        );
+        assert_eq!(ps.payment_requirements.scheme, "upto");
+        assert_eq!(ps.payment_requirements.amount().unwrap(), 1_000);
+        assert_eq!(ps.settled_amount().unwrap(), 999);
+        ps.settle_response.amount = Some("1000".to_string());
+        assert_eq!(ps.settled_amount().unwrap(), 1_000);
+        ps.settle_response.amount = Some("1001".to_string());
+        assert_eq!(
+            ps.settled_amount().unwrap_err(),
+            "settled amount exceeds the maximum amount"
+        );
+        ps.settle_response.amount = Some("0".to_string());
+        assert_eq!(
+            ps.settled_amount().unwrap_err(),
+            "settled amount must be greater than zero"
+        );
+        ps.settle_response.amount = Some("-1".to_string());
+        assert_eq!(
+            ps.settled_amount().unwrap_err(),
+            "invalid amount in settlement response"
+        );
+        ps.settle_response.amount = None;
+        assert_eq!(
+            ps.settled_amount().unwrap_err(),
+            "missing amount in settlement response"
+        );
+
+        ps.payment_requirements.scheme = "stream".to_string();
+        assert_eq!(
+            ps.settled_amount().unwrap_err(),
+            "unsupported payment scheme: stream"
+        );
+    }
+
+    #[test]
+    fn test_paying_result_requirements() {
+        let sk = PrivateKey::generate_from_seed(b"facilitator");
+        let user = Principal::from_slice(&[1]);
+        let input = signed(&sk, &settle_result(&user, 1_000, true), 1_000);
+        assert_eq!(input.payment_requirements().unwrap(), requirements());
+        let bad = signed(&sk, &"not a result", 1_000);
+        assert!(bad.payment_requirements().is_err());
+    }
+
+    #[test]
+    fn test_verify() {
+        let sk = PrivateKey::generate_from_seed(b"facilitator");
+        let keys = vec![ByteArrayB64::from(sk.public_key().serialize_raw())];
+        let verifier = PaymentVerifier {
+            public_keys: &keys,
+            secret: SECRET,
+            accepts: &[],
+        };
+        let user = Principal::from_slice(&[1]);
+        let pr = requirements();
+        let result = |is_valid: bool| PaymentVerifyResult {
+            nonce: derive_nonce(SECRET, &user, std::slice::from_ref(&pr), 1_000).unwrap(),
+            payment_requirements: pr.clone(),
+            verify_response: VerifyResponse {
+                is_valid,
+                payer: Some("payer".to_string()),
+                invalid_reason: None,
+            },
+        };
+
+        let input = signed(&sk, &result(true), 1_000);
+        let pv = verifier.verify(&input, &user, 2_000).unwrap();
+        assert_eq!(pv.verify_response.payer.as_deref(), Some("payer"));
+
+        let bad = signed(&sk, &result(false), 1_000);
+        assert_eq!(
+            verifier.verify(&bad, &user, 2_000).unwrap_err(),
+            "payment verification failed: unknown reason"
+        );
+        assert_eq!(
+            verifier
+                .verify(&input, &user, 1_001 + TIME_EXPIRY_MS)
+                .unwrap_err(),
+            "payment verification result expired"
+        );
+        assert_eq!(
+            verifier
+                .verify(&input, &Principal::from_slice(&[2]), 2_000)
+                .unwrap_err(),
+            "nonce mismatch"
+        );
+
+        // a settlement result is not a verification result
+        let bad = signed(&sk, &settle_result(&user, 1_000, true), 1_000);
+        assert!(verifier.verify(&bad, &user, 2_000).is_err());
+    }
+
+    #[test]
+    fn test_replay_guard() {
+        let mut guard = ReplayGuard::default();
+        guard.consume("a", 1_000, 2_000).unwrap();
+        guard.consume("b", 1_500, 2_000).unwrap();
+        assert_eq!(
+            guard.consume("a", 1_000, 3_000).unwrap_err(),
+            "payment result has already been used"
+        );
+        assert_eq!(guard.len(), 2);
+
+        // expired nonces are dropped, their results are rejected as expired anyway
+        guard
+            .consume("c", 1_000 + TIME_EXPIRY_MS, 1_001 + TIME_EXPIRY_MS)
+            .unwrap();
+        assert_eq!(guard.len(), 2);
+    }
+
+    #[test]
+    fn test_check_settled_txid() {
+        check_settled_txid("tx1", |_| false).unwrap();
+        assert_eq!(
+            check_settled_txid("tx1", |txid| txid == "tx1").unwrap_err(),
+            "transaction tx1 has already been used"
+        );
+        assert_eq!(
+            check_settled_txid("", |_| false).unwrap_err(),
+            "missing transaction in settlement response"
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use candid::CandidType;
+use ic_auth_types::ByteBufB64;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+#[derive(CandidType, Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct X402PaymentOutput {
+    pub x402: ByteBufB64, // PaymentRequirementsResponse in CBOR
+    pub nonce: String,
+    pub timestamp: u64,
+}
+
+#[derive(CandidType, Clone, Debug, Serialize, Deserialize)]
+pub struct PayingResultInput {
+    pub result: ByteBufB64, // PaymentVerifyResult / PaymentSettleResult in CBOR,
+    pub signature: ByteBufB64, // Ed25519 signature over result by a paying public key
+    pub timestamp: u64,     // the timestamp from X402PaymentOutput
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentVerifyResult {
+    pub payment_requirements: PaymentRequirements,
+    pub verify_response: VerifyResponse,
+    pub nonce: String, // the nonce from X402PaymentOutput
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentSettleResult {
+    pub payment_requirements: PaymentRequirements,
+    pub settle_response: SettleResponse,
+    pub nonce: String, // the nonce from X402PaymentOutput
+}
+
+#[derive(CandidType, Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyResponse {
+    pub is_valid: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invalid_reason: Option<String>,
+}
+
+#[derive(CandidType, Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SettleResponse {
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_reason: Option<String>,
+    pub transaction: String,
+    pub network: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payer: Option<String>,
+    /// Settled amount in atomic token units, required by the "upto" scheme
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentRequired {
+    /// Protocol version identifier
+    pub x402_version: u8,
+    /// Human-readable error message explaining why payment is required
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// ResourceInfo object describing the protected resource
+    pub resource: ResourceInfo,
+    /// Array of payment requirement objects defining acceptable payment methods
+    pub accepts: Vec<PaymentRequirements>,
+    /// Protocol extensions data
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extensions: Option<Extensions>,
+}
+
+/// Payment requirements set by the payment-gated endpoint for an acceptable payment.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentRequirements {
+    /// Payment scheme identifier ("exact" or "upto")
+    pub scheme: String,
+    /// Blockchain network identifier (e.g., "icp")
+    pub network: String,
+    /// Required payment amount in atomic token units, the maximum for the "upto" scheme
+    pub amount: String,
+    /// Token ledger canister address
+    pub asset: String,
+    /// Recipient wallet address for the payment
+    pub pay_to: String,
+    /// Maximum time allowed for payment completion in seconds
+    pub max_timeout_seconds: u64,
+    /// Scheme-specific additional information.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extra: Option<Map<String, Value>>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceInfo {
+    /// the protected resource, e.g., URL of the resource endpoint
+    pub url: String,
+    /// Human-readable description of the resource
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// MIME type of the expected response
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+}
+
+/// Describes additional extension data for x402 payment.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Extensions {
+    /// Extension-specific data provided by the server
+    pub info: Map<String, Value>,
+    /// JSON Schema defining the expected structure of `info`
+    pub schema: Map<String, Value>,
+}
@@ -0,0 +1,170 @@
+//! # x402_facilitator
+//!
+//! A local stand-in for the x402 facilitator, for tests and local deployments.
+//!
+//! It does not verify or settle anything on chain. It accepts the payment requirements
+//! of a [`X402PaymentOutput`] as paid and signs the [`PaymentVerifyResult`] or
+//! [`PaymentSettleResult`] a canister expects, with an Ed25519 key that has to be listed
+//! in the canister's paying public keys.
+
+use ic_auth_types::{ByteArrayB64, cbor_into_vec};
+use ic_ed25519::PrivateKey;
+use serde::Serialize;
+use x402::{
+    PayingResultInput, PaymentRequired, PaymentRequirements, PaymentSettleResult,
+    PaymentVerifyResult, SettleResponse, VerifyResponse, X402PaymentOutput,
+};
+
+pub struct Facilitator {
+    key: PrivateKey,
+}
+
+impl Facilitator {
+    /// Derives the signing key from `seed`, the same seed always gives the same key.
+    pub fn from_seed(seed: &[u8]) -> Self {
+        Self {
+            key: PrivateKey::generate_from_seed(seed),
+        }
+    }
+
+    /// The key to add to the canister's paying public keys.
+    pub fn public_key(&self) -> ByteArrayB64<32> {
+        ByteArrayB64::from(self.key.public_key().serialize_raw())
+    }
+
+    /// Signs a valid verification result for the `accept`-th requirements of `output`.
+    pub fn verify(
+        &self,
+        output: &X402PaymentOutput,
+        accept: usize,
+        payer: &str,
+    ) -> Result<PayingResultInput, String> {
+        let result = PaymentVerifyResult {
+            payment_requirements: requirements(output, accept)?,
+            verify_response: VerifyResponse {
+                is_valid: true,
+                payer: Some(payer.to_string()),
+                invalid_reason: None,
+            },
+            nonce: output.nonce.clone(),
+        };
+        self.sign(&result, output.timestamp)
+    }
+
+    /// Signs a successful settlement result for the `accept`-th requirements of `output`,
+    /// paid by `payer` in `transaction`.
+    pub fn settle(
+        &self,
+        output: &X402PaymentOutput,
+        accept: usize,
+        payer: &str,
+        transaction: &str,
+    ) -> Result<PayingResultInput, String> {
+        self.settle_with(output, accept, payer, transaction, None)
+    }
+
+    /// Like [`Facilitator::settle`], settling `amount` of the maximum of "upto" requirements.
+    pub fn settle_upto(
+        &self,
+        output: &X402PaymentOutput,
+        accept: usize,
+        payer: &str,
+        transaction: &str,
+        amount: u128,
+    ) -> Result<PayingResultInput, String> {
+        self.settle_with(output, accept, payer, transaction, Some(amount))
+    }
+
+    fn settle_with(
+        &self,
+        output: &X402PaymentOutput,
+        accept: usize,
+        payer: &str,
+        transaction: &str,
+        amount: Option<u128>,
+    ) -> Result<PayingResultInput, String> {
+        let payment_requirements = requirements(output, accept)?;
+        let result = PaymentSettleResult {
+            settle_response: SettleResponse {
+                success: true,
+                error_reason: None,
+                transaction: transaction.to_string(),
+                network: payment_requirements.network.clone(),
+                payer: Some(payer.to_string()),
+                amount: amount.map(|v| v.to_string()),
+            },
+            payment_requirements,
+            nonce: output.nonce.clone(),
+        };
+        self.sign(&result, output.timestamp)
+    }
+
+    /// Signs a failed settlement result, e.g. to test how a canister rejects it.
+    pub fn settle_failed(
+        &self,
+        output: &X402PaymentOutput,
+        accept: usize,
+        reason: &str,
+    ) -> Result<PayingResultInput, String> {
+        let payment_requirements = requirements(output, accept)?;
+        let result = PaymentSettleResult {
+            settle_response: SettleResponse {
+                success: false,
+                error_reason: Some(reason.to_string()),
+                transaction: String::new(),
+                network: payment_requirements.network.clone(),
+                payer: None,
+                amount: None,
+            },
+            payment_requirements,
+            nonce: output.nonce.clone(),
+        };
+        self.sign(&result, output.timestamp)
+    }
+
+    /// Signs any result in CBOR, for payments issued at `timestamp`.
+    pub fn sign<T: Serialize>(
+        &self,
+        result: &T,
+        timestamp: u64,
+    ) -> Result<PayingResultInput, String> {
+        let result = cbor_into_vec(result)?;
+        let signature = self.key.sign_message(&result);
+        Ok(PayingResultInput {
+            result: result.into(),
+            signature: signature.to_vec().into(),
+            timestamp,
+        })
+    }
+}
+
+/// Decodes the payment required response of `output`.
+pub fn payment_required(output: &X402PaymentOutput) -> Result<PaymentRequired, String> {
+    ciborium::from_reader(&output.x402[..]).map_err(|err| format!("{:?}", err))
+}
+
+fn requirements(output: &X402PaymentOutput, accept: usize) -> Result<PaymentRequirements, String> {
+    let mut pr = payment_required(output)?;
+    if accept >= pr.accepts.len() {
+        return Err(format!(
+            "no accepted payment requirements at {}, {} available",
+            accept,
+            pr.accepts.len()
+        ));
+    }
+    Ok(pr.accepts.swap_remove(accept))
+}
+
+/// Formats a paying result as a candid argument, e.g. for `dfx canister call`.
+pub fn to_candid(input: &PayingResultInput) -> String {
+    format!(
+        "record {{ result = blob \"{}\"; signature = blob \"{}\"; timestamp = {} : nat64 }}",
+        blob_escape(&input.result),
+        blob_escape(&input.signature),
+        input.timestamp
+    )
+}
+
+fn blob_escape(data: &[u8]) -> String {
+    data.iter().map(|b| format!("\\{:02x}", b)).collect()
+}
@@ -0,0 +1,88 @@
+//! Signs x402 payment results for local canisters.
+//!
+//! ```sh
+//! x402-facilitator pubkey <seed>
+//! x402-facilitator verify <seed> <payer> [accept] < payment.json
+//! x402-facilitator settle <seed> <payer> <transaction> [accept] [amount] < payment.json
+//! ```
+//!
+//! `amount` is the settled amount of "upto" requirements.
+//!
+//! `payment.json` is the `X402PaymentOutput` of the canister's x402 payment query in JSON,
+//! with `x402` in base64url. The signed `PayingResultInput` is printed as JSON, or as a
+//! candid argument with `--candid`.
+
+use std::io::Read;
+use x402::X402PaymentOutput;
+use x402_facilitator::{Facilitator, to_candid};
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let candid = args.iter().any(|a| a == "--candid");
+    args.retain(|a| a != "--candid");
+    let args: Vec<&str> = args.iter().map(|a| a.as_str()).collect();
+
+    let input = match args.as_slice() {
+        ["pubkey", seed] => {
+            println!("{}", Facilitator::from_seed(seed.as_bytes()).public_key());
+            return Ok(());
+        }
+        ["verify", seed, payer, rest @ ..] if rest.len() <= 1 => {
+            let output = read_output()?;
+            Facilitator::from_seed(seed.as_bytes()).verify(&output, accept(rest)?, payer)?
+        }
+        ["settle", seed, payer, transaction, rest @ ..] if rest.len() <= 2 => {
+            let output = read_output()?;
+            let facilitator = Facilitator::from_seed(seed.as_bytes());
+            match rest.get(1) {
+                Some(amount) => facilitator.settle_upto(
+                    &output,
+                    accept(rest)?,
+                    payer,
+                    transaction,
+                    amount
+                        .parse()
+                        .map_err(|_| format!("invalid amount: {}", amount))?,
+                )?,
+                None => facilitator.settle(&output, accept(rest)?, payer, transaction)?,
+            }
+        }
+        _ => {
+            return Err("usage: x402-facilitator pubkey <seed> | verify <seed> <payer> [accept] | settle <seed> <payer> <transaction> [accept] [amount] [--candid]".to_string());
+        }
+    };
+
+    if candid {
+        println!("({})", to_candid(&input));
+    } else {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&input).map_err(|err| format!("{:?}", err))?
+        );
+    }
+    Ok(())
+}
+
+fn read_output() -> Result<X402PaymentOutput, String> {
+    let mut data = String::new();
+    std::io::stdin()
+        .read_to_string(&mut data)
+        .map_err(|err| format!("failed to read payment from stdin: {:?}", err))?;
+    serde_json::from_str(&data).map_err(|err| format!("invalid payment: {:?}", err))
+}
+
+fn accept(rest: &[&str]) -> Result<usize, String> {
+    rest.first()
+        .map(|v| {
+            v.parse()
+                .map_err(|_| format!("invalid accept index: {}", v))
+        })
+        .unwrap_or(Ok(0))
+}
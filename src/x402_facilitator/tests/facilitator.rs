@@ -0,0 +1,141 @@
+use candid::Principal;
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+use x402::{
+    PayingResultInput, PaymentRequirements, PaymentVerifier, ResourceInfo, X402PaymentOutput,
+    payment_output,
+};
+use x402_facilitator::{Facilitator, payment_required, to_candid};
+
+const SECRET: &[u8] = b"secret";
+
+fn output(user: &Principal) -> X402PaymentOutput {
+    payment_output(
+        SECRET,
+        user,
+        vec![
+            PaymentRequirements::exact(
+                "icp:1".to_string(),
+                "xevnm-gaaaa-aaaar-qafnq-cai".to_string(),
+                1_000_000,
+                "pay-to".to_string(),
+                None,
+            ),
+            PaymentRequirements::exact(
+                "eip155:8453".to_string(),
+                "0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913".to_string(),
+                1_000_000,
+                "0x0000000000000000000000000000000000000001".to_string(),
+                None,
+            ),
+        ],
+        ResourceInfo {
+            url: "https://tokenlist.ing".to_string(),
+            description: None,
+            mime_type: None,
+        },
+        None,
+        1_000,
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_results_are_accepted() {
+    let facilitator = Facilitator::from_seed(b"facilitator");
+    let keys = vec![facilitator.public_key()];
+    let user = Principal::from_slice(&[1]);
+    let output = output(&user);
+    let accepts = payment_required(&output).unwrap().accepts;
+    let verifier = PaymentVerifier {
+        public_keys: &keys,
+        secret: SECRET,
+        accepts: &accepts,
+    };
+
+    let input = facilitator.verify(&output, 0, "payer").unwrap();
+    let pv = verifier.verify(&input, &user, 2_000).unwrap();
+    assert_eq!(pv.verify_response.payer.as_deref(), Some("payer"));
+    assert_eq!(pv.payment_requirements, accepts[0]);
+
+    let input = facilitator.settle(&output, 1, "0xpayer", "0x01").unwrap();
+    let ps = verifier.settle(&input, &user, 2_000).unwrap();
+    assert_eq!(ps.settle_response.transaction, "0x01");
+    assert_eq!(ps.settle_response.network, "eip155:8453");
+    assert_eq!(ps.payment_requirements, accepts[1]);
+
+    let input = facilitator.settle_failed(&output, 0, "rejected").unwrap();
+    assert_eq!(
+        verifier.settle(&input, &user, 2_000).unwrap_err(),
+        "payment settlement failed: rejected"
+    );
+
+    assert!(facilitator.settle(&output, 2, "payer", "0x01").is_err());
+    assert_eq!(
+        Facilitator::from_seed(b"facilitator").public_key(),
+        facilitator.public_key()
+    );
+    assert_ne!(
+        Facilitator::from_seed(b"other").public_key(),
+        facilitator.public_key()
+    );
+}
+
+#[test]
+fn test_to_candid() {
+    let input = PayingResultInput {
+        result: vec![0x01, 0xab].into(),
+        signature: vec![0xff].into(),
+        timestamp: 42,
+    };
+    assert_eq!(
+        to_candid(&input),
+        r#"record { result = blob "\01\ab"; signature = blob "\ff"; timestamp = 42 : nat64 }"#
+    );
+}
+
+#[test]
+fn test_cli() {
+    let bin = env!("CARGO_BIN_EXE_x402-facilitator");
+    let user = Principal::from_slice(&[1]);
+    let output = output(&user);
+
+    let pubkey = Command::new(bin)
+        .args(["pubkey", "facilitator"])
+        .output()
+        .unwrap();
+    assert!(pubkey.status.success());
+    assert_eq!(
+        String::from_utf8(pubkey.stdout).unwrap().trim(),
+        Facilitator::from_seed(b"facilitator")
+            .public_key()
+            .to_string()
+    );
+
+    let mut child = Command::new(bin)
+        .args(["settle", "facilitator", "0xpayer", "0x01", "1"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(serde_json::to_string(&output).unwrap().as_bytes())
+        .unwrap();
+    let settled = child.wait_with_output().unwrap();
+    assert!(settled.status.success());
+    let input: PayingResultInput = serde_json::from_slice(&settled.stdout).unwrap();
+    let expected = Facilitator::from_seed(b"facilitator")
+        .settle(&output, 1, "0xpayer", "0x01")
+        .unwrap();
+    assert_eq!(input.result, expected.result);
+    assert_eq!(input.signature, expected.signature);
+    assert_eq!(input.timestamp, 1_000);
+
+    let usage = Command::new(bin).arg("unknown").output().unwrap();
+    assert!(!usage.status.success());
+}